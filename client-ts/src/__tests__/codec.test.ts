@@ -36,6 +36,172 @@ describe('MessageCodec', () => {
     }
   });
 
+  it('encodes subscribe replay options', () => {
+    const msg: ClientMessage = {
+      type: 'Subscribe',
+      subject: 'ticks',
+      id: 3,
+      replay: { type: 'Since', unixMs: 1700000000000 },
+    };
+
+    const decoded = decodeClientEnvelope(encodeClientMessage(msg));
+
+    expect(decoded.message.type).toBe('Subscribe');
+    if (decoded.message.type === 'Subscribe') {
+      expect(decoded.message.replay).toEqual({ type: 'Since', unix_ms: 1700000000000n });
+      expect(decoded.message.max_msgs).toBeNull();
+      expect(decoded.message.slow_consumer).toBeNull();
+    }
+  });
+
+  it('encodes subscribe slow-consumer policies', () => {
+    const decoded = decodeClientEnvelope(
+      encodeClientMessage({ type: 'Subscribe', subject: 'prices.*', id: 4, slowConsumer: 'Conflate' }),
+    );
+
+    expect(decoded.message.type).toBe('Subscribe');
+    if (decoded.message.type === 'Subscribe') {
+      expect(decoded.message.slow_consumer).toEqual({ type: 'Conflate' });
+    }
+  });
+
+  it('encodes durable consumer subscriptions and acks', () => {
+    const subscribe = decodeClientEnvelope(
+      encodeClientMessage({
+        type: 'SubscribeConsumer',
+        id: 4,
+        stream: 'ORDERS',
+        durable: 'ui',
+        filterSubject: 'orders.>',
+        deliver: { type: 'ByStartSequence', startSeq: 10 },
+      }),
+    );
+    expect(subscribe.message.type).toBe('SubscribeConsumer');
+    if (subscribe.message.type === 'SubscribeConsumer') {
+      expect(subscribe.message.filter_subject).toBe('orders.>');
+      expect(subscribe.message.deliver).toEqual({ type: 'ByStartSequence', start_seq: 10n });
+    }
+
+    const ack = decodeClientEnvelope(encodeClientMessage({ type: 'Ack', id: 4, streamSeq: 12, kind: 'Nak' }));
+    expect(ack.message).toEqual({ type: 'Ack', id: 4n, stream_seq: 12n, kind: { type: 'Nak' } });
+  });
+
+  it('maps durable publishes and their acks', () => {
+    const publish = decodeClientEnvelope(
+      encodeClientMessage({
+        type: 'JetStreamPublish',
+        requestId: 8,
+        subject: 'orders.created',
+        payload: new Uint8Array([1]),
+        msgId: 'order-1',
+      }),
+    );
+    expect(publish.message.type).toBe('JetStreamPublish');
+    if (publish.message.type === 'JetStreamPublish') {
+      expect(publish.message.msg_id).toBe('order-1');
+    }
+
+    const ack = decodeServerMessage(
+      encodeServerEnvelope({
+        message: { type: 'PubAck', request_id: 8n, stream: 'ORDERS', seq: 3n, duplicate: true },
+      }),
+    );
+    expect(ack).toEqual({ type: 'PubAck', requestId: 8, stream: 'ORDERS', seq: 3, duplicate: true });
+  });
+
+  it('maps key-value requests and replies', () => {
+    const put = decodeClientEnvelope(
+      encodeClientMessage({
+        type: 'KvPut',
+        requestId: 9,
+        bucket: 'settings',
+        key: 'users.alice.theme',
+        value: new Uint8Array([1, 2]),
+      }),
+    );
+    expect(put.message).toEqual({
+      type: 'KvPut',
+      request_id: 9n,
+      bucket: 'settings',
+      key: 'users.alice.theme',
+      value: [1, 2],
+    });
+
+    const missing = decodeServerMessage(
+      encodeServerEnvelope({
+        message: { type: 'KvEntry', request_id: 10n, key: 'users.bob.theme', value: null, revision: 0n },
+      }),
+    );
+    expect(missing).toEqual({
+      type: 'KvEntry',
+      requestId: 10,
+      key: 'users.bob.theme',
+      value: undefined,
+      revision: 0,
+    });
+
+    const update = decodeServerMessage(
+      encodeServerEnvelope({
+        message: {
+          type: 'KvUpdate',
+          id: 3n,
+          key: 'users.alice.theme',
+          value: [],
+          revision: 4n,
+          operation: { type: 'Delete' },
+        },
+      }),
+    );
+    expect(update).toEqual({
+      type: 'KvUpdate',
+      id: 3,
+      key: 'users.alice.theme',
+      value: new Uint8Array([]),
+      revision: 4,
+      operation: 'Delete',
+    });
+  });
+
+  it('maps object transfer messages', () => {
+    const start = decodeClientEnvelope(
+      encodeClientMessage({
+        type: 'ObjectUploadStart',
+        requestId: 11,
+        bucket: 'uploads',
+        name: 'alice.photo.jpg',
+        size: 3,
+        sha256: new Uint8Array([0xab, 0xcd]),
+      }),
+    );
+    expect(start.message).toEqual({
+      type: 'ObjectUploadStart',
+      request_id: 11n,
+      bucket: 'uploads',
+      name: 'alice.photo.jpg',
+      size: 3n,
+      sha256: [0xab, 0xcd],
+    });
+
+    const chunk = decodeServerMessage(
+      encodeServerEnvelope({
+        message: { type: 'ObjectChunk', request_id: 12n, offset: 65536n, data: [1, 2, 3] },
+      }),
+    );
+    expect(chunk).toEqual({
+      type: 'ObjectChunk',
+      requestId: 12,
+      offset: 65536,
+      data: new Uint8Array([1, 2, 3]),
+    });
+  });
+
+  it('maps backend status notifications', () => {
+    const status = decodeServerMessage(
+      encodeServerEnvelope({ message: { type: 'BackendStatus', available: false } }),
+    );
+    expect(status).toEqual({ type: 'BackendStatus', available: false });
+  });
+
   it('decodes server message payloads into Uint8Array', () => {
     const encoded = encodeServerEnvelope({
       message: {
@@ -43,6 +209,9 @@ describe('MessageCodec', () => {
         subscription_id: 42n,
         subject: 'messages',
         payload: [9, 8, 7],
+        seq: 7n,
+        dropped: 2,
+        origin: { type: 'History' },
       },
     });
 
@@ -51,6 +220,9 @@ describe('MessageCodec', () => {
     if (decoded.type === 'Message') {
       expect(decoded.subscriptionId).toBe(42);
       expect(decoded.payload).toEqual(new Uint8Array([9, 8, 7]));
+      expect(decoded.seq).toBe(7);
+      expect(decoded.dropped).toBe(2);
+      expect(decoded.origin).toBe('History');
     }
   });
 
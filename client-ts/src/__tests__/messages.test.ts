@@ -1,27 +1,15 @@
 import { describe, it, expect } from 'vitest';
-import { ErrorCodes } from '../protocol/messages';
+import { MottomeshError } from '../protocol/messages';
 import type { ClientMessage, ServerMessage } from '../protocol/messages';
 
 describe('Protocol Messages', () => {
-  describe('ErrorCodes', () => {
-    it('should have correct UNAUTHORIZED code', () => {
-      expect(ErrorCodes.UNAUTHORIZED).toBe(401);
-    });
-
-    it('should have correct FORBIDDEN code', () => {
-      expect(ErrorCodes.FORBIDDEN).toBe(403);
-    });
-
-    it('should have correct NOT_FOUND code', () => {
-      expect(ErrorCodes.NOT_FOUND).toBe(404);
-    });
-
-    it('should have correct INTERNAL_ERROR code', () => {
-      expect(ErrorCodes.INTERNAL_ERROR).toBe(500);
-    });
-
-    it('should have correct INVALID_MESSAGE code', () => {
-      expect(ErrorCodes.INVALID_MESSAGE).toBe(400);
+  describe('MottomeshError', () => {
+    it('should carry the typed error code', () => {
+      const error = new MottomeshError('PermissionDenied', 'Permission denied');
+      expect(error).toBeInstanceOf(Error);
+      expect(error.code).toBe('PermissionDenied');
+      expect(error.message).toBe('Permission denied');
+      expect(error.name).toBe('MottomeshError');
     });
   });
 
@@ -65,12 +53,12 @@ describe('Protocol Messages', () => {
 
   describe('ServerMessage types', () => {
     it('should allow AuthOk message type', () => {
-      const msg: ServerMessage = { type: 'AuthOk', sessionId: 'session-123' };
+      const msg: ServerMessage = { type: 'AuthOk', sessionId: 'session-123', resumed: false };
       expect(msg.type).toBe('AuthOk');
     });
 
     it('should allow AuthError message type', () => {
-      const msg: ServerMessage = { type: 'AuthError', reason: 'Invalid token' };
+      const msg: ServerMessage = { type: 'AuthError', code: 'Unauthorized', reason: 'Invalid token' };
       expect(msg.type).toBe('AuthError');
     });
 
@@ -80,7 +68,7 @@ describe('Protocol Messages', () => {
     });
 
     it('should allow SubscribeError message type', () => {
-      const msg: ServerMessage = { type: 'SubscribeError', id: 1, reason: 'Denied' };
+      const msg: ServerMessage = { type: 'SubscribeError', id: 1, code: 'PermissionDenied', reason: 'Denied' };
       expect(msg.type).toBe('SubscribeError');
     });
 
@@ -89,7 +77,10 @@ describe('Protocol Messages', () => {
         type: 'Message', 
         subscriptionId: 1,
         subject: 'test',
-        payload: new Uint8Array()
+        payload: new Uint8Array(),
+        seq: 1,
+        dropped: 0,
+        origin: 'Live'
       };
       expect(msg.type).toBe('Message');
     });
@@ -104,12 +95,12 @@ describe('Protocol Messages', () => {
     });
 
     it('should allow RequestError type', () => {
-      const msg: ServerMessage = { type: 'RequestError', requestId: 1, reason: 'Timeout' };
+      const msg: ServerMessage = { type: 'RequestError', requestId: 1, code: 'Timeout', reason: 'Timeout' };
       expect(msg.type).toBe('RequestError');
     });
 
     it('should allow Error type', () => {
-      const msg: ServerMessage = { type: 'Error', code: 500, message: 'Internal error' };
+      const msg: ServerMessage = { type: 'Error', code: 'Internal', message: 'Internal error' };
       expect(msg.type).toBe('Error');
     });
 
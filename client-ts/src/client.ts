@@ -3,7 +3,20 @@
  */
 
 import { Transport, TransportType, WebTransportTransport, WebSocketTransport } from './transport';
-import { encodeClientMessage, decodeServerMessage, ClientMessage, ServerMessage } from './protocol';
+import {
+  encodeClientMessage,
+  decodeServerMessage,
+  ClientMessage,
+  ServerMessage,
+  MottomeshError,
+  PresenceAction,
+  PresenceMember,
+  MessageOrigin,
+  ReplayFrom,
+  SlowConsumerPolicy,
+  DeliverPolicy,
+  KvOperation,
+} from './protocol';
 
 export interface ClientOptions {
   /** Gateway URL (e.g., "https://localhost:4433") */
@@ -20,6 +33,65 @@ export interface ClientOptions {
   maxReconnectAttempts?: number;
 }
 
+export interface SubscribeOptions {
+  /** End the subscription after this many messages */
+  maxMsgs?: number;
+  /**
+   * Replay recorded messages before live delivery starts. After a reconnect,
+   * such subscriptions catch up on what was published while disconnected.
+   */
+  replay?: ReplayFrom;
+  /**
+   * What the gateway does when messages arrive faster than this client reads
+   * them: 'Conflate' suits a feed where only the latest value per subject
+   * matters, 'Block' one that must not lose messages, where the gateway
+   * configures blocking for the subject (elsewhere the subscribe fails with
+   * `InvalidMessage`). Messages discarded show up in
+   * `dropped`. Defaults to the gateway's configuration.
+   */
+  slowConsumer?: SlowConsumerPolicy;
+}
+
+export interface ConsumeOptions {
+  /** Where a newly created consumer starts (default: all messages in the stream) */
+  deliver?: DeliverPolicy;
+}
+
+export interface PublishDurableOptions {
+  /** Id for server-side deduplication; a repeated id is stored only once */
+  msgId?: string;
+  /** How long to wait for the stream to store the message, in ms (default: 5000) */
+  timeout?: number;
+}
+
+export interface UploadOptions {
+  /** Bytes sent per chunk (default: 65536) */
+  chunkSize?: number;
+  /** Called as the gateway confirms each chunk */
+  onProgress?: (sent: number, total: number) => void;
+}
+
+export interface DownloadOptions {
+  /** Called as each chunk arrives */
+  onProgress?: (received: number, total: number) => void;
+}
+
+/** Where JetStream stored a durably published message */
+export interface PubAck {
+  stream: string;
+  seq: number;
+  /** The message id was seen before, so this publish was not stored again */
+  duplicate: boolean;
+}
+
+/** Current value of a key-value entry */
+export interface KvEntry {
+  key: string;
+  value: Uint8Array;
+  /** Revision of the change that set the value */
+  revision: number;
+}
+
 export interface Subscription {
   /** Subscription ID */
   id: number;
@@ -32,24 +104,107 @@ export interface Subscription {
 export type MessageCallback = (msg: {
   subject: string;
   payload: Uint8Array;
+  /** Position of the message within its subscription, starting at 1 */
+  seq: number;
+  /** Messages the gateway discarded since the previous delivery; a gap in seq */
+  dropped: number;
+  /** Published live, or replayed from the gateway's last-value cache or history */
+  origin: MessageOrigin;
+}) => void;
+
+export type ConsumerMessageCallback = (msg: {
+  subject: string;
+  payload: Uint8Array;
+  /** Position of the message in its stream */
+  streamSeq: number;
+  /** How many times the message has been delivered, this time included */
+  delivered: number;
+  /** Mark the message as processed */
+  ack(): void;
+  /** Ask for the message to be redelivered */
+  nak(): void;
+  /** Extend the time the server waits for an ack */
+  inProgress(): void;
+}) => void;
+
+export type KvWatchCallback = (update: {
+  key: string;
+  /** New value, empty for deletes and purges */
+  value: Uint8Array;
+  revision: number;
+  operation: KvOperation;
 }) => void;
 
-export type EventType = 'connect' | 'disconnect' | 'error' | 'auth';
+export type PresenceCallback = (event: { action: PresenceAction; member: PresenceMember }) => void;
+
+export type EventType =
+  | 'connect'
+  | 'disconnect'
+  | 'error'
+  | 'auth'
+  | 'goaway'
+  | 'subscriptionend'
+  | 'backend';
 
 type EventCallback = (data?: unknown) => void;
 
+/** An object upload or download in progress */
+interface ObjectTransfer {
+  /** Ask the gateway to carry on from where the transfer stopped */
+  resume(): void;
+  handle(msg: ObjectReply): void;
+  reject(error: Error): void;
+}
+
+/** Replies to object transfers */
+type ObjectReply = Extract<
+  ServerMessage,
+  { type: 'ObjectUploadProgress' | 'ObjectUploadOk' | 'ObjectMeta' | 'ObjectChunk' | 'ObjectDownloadEnd' }
+>;
+
+/** Replies to key-value requests */
+type KvReply = Extract<ServerMessage, { type: 'KvEntry' | 'KvPutOk' | 'KvDeleteOk' }>;
+
 export class MottomeshClient {
   private transport: Transport | null = null;
   private options: Required<ClientOptions>;
   private authenticated = false;
   private sessionId: string | null = null;
+  /** Lets the next connection take over this session's subscriptions */
+  private resumeToken: string | null = null;
+  /** Whether the last authentication resumed the previous session */
+  private resumed = false;
   private nextSubId = 1;
   private nextRequestId = 1;
-  private subscriptions = new Map<number, { subject: string; callback: MessageCallback }>();
+  private subscriptions = new Map<
+    number,
+    {
+      subject: string;
+      callback: MessageCallback;
+      remaining?: number;
+      catchUp: boolean;
+      slowConsumer?: SlowConsumerPolicy;
+    }
+  >();
+  private consumers = new Map<
+    number,
+    { stream: string; durable: string; filterSubject: string; deliver: DeliverPolicy; callback: ConsumerMessageCallback }
+  >();
+  private presenceWatches = new Map<number, { pattern: string; callback: PresenceCallback }>();
+  private kvWatches = new Map<number, { bucket: string; key: string; callback: KvWatchCallback }>();
   private pendingRequests = new Map<number, { resolve: (data: Uint8Array) => void; reject: (error: Error) => void }>();
+  private pendingPubAcks = new Map<number, { resolve: (ack: PubAck) => void; reject: (error: Error) => void }>();
+  private pendingKv = new Map<number, { resolve: (reply: KvReply) => void; reject: (error: Error) => void }>();
+  private transfers = new Map<number, ObjectTransfer>();
+  private pendingPresenceQueries = new Map<
+    number,
+    { resolve: (members: PresenceMember[]) => void; reject: (error: Error) => void }
+  >();
   private eventHandlers = new Map<EventType, Set<EventCallback>>();
   private reconnectAttempts = 0;
   private isReconnecting = false;
+  /** When the connection last dropped, for catching up on reconnect */
+  private disconnectedAt: number | null = null;
 
   constructor(options: ClientOptions) {
     this.options = {
@@ -92,18 +247,32 @@ export class MottomeshClient {
     }
     this.authenticated = false;
     this.sessionId = null;
+    this.resumeToken = null;
   }
 
   /**
    * Subscribe to a subject
    */
-  subscribe(subject: string, callback: MessageCallback): Subscription {
+  subscribe(subject: string, callback: MessageCallback, options: SubscribeOptions = {}): Subscription {
     const id = this.nextSubId++;
 
-    this.subscriptions.set(id, { subject, callback });
+    this.subscriptions.set(id, {
+      subject,
+      callback,
+      remaining: options.maxMsgs,
+      catchUp: options.replay !== undefined,
+      slowConsumer: options.slowConsumer,
+    });
 
     // Send subscribe message
-    this.sendMessage({ type: 'Subscribe', subject, id });
+    this.sendMessage({
+      type: 'Subscribe',
+      subject,
+      id,
+      maxMsgs: options.maxMsgs,
+      replay: options.replay,
+      slowConsumer: options.slowConsumer,
+    });
 
     return {
       id,
@@ -115,6 +284,84 @@ export class MottomeshClient {
     };
   }
 
+  /**
+   * Consume a JetStream stream through a durable consumer. Every message must
+   * be acked, or it is redelivered, even to a later session using the same
+   * durable name.
+   */
+  consume(
+    stream: string,
+    durable: string,
+    filterSubject: string,
+    callback: ConsumerMessageCallback,
+    options: ConsumeOptions = {},
+  ): Subscription {
+    const id = this.nextSubId++;
+    const deliver = options.deliver ?? { type: 'All' };
+
+    this.consumers.set(id, { stream, durable, filterSubject, deliver, callback });
+    this.sendMessage({ type: 'SubscribeConsumer', id, stream, durable, filterSubject, deliver });
+
+    return {
+      id,
+      subject: filterSubject,
+      unsubscribe: (): void => {
+        this.consumers.delete(id);
+        this.sendMessage({ type: 'Unsubscribe', id });
+      },
+    };
+  }
+
+  /**
+   * Watch sessions joining and leaving subjects that match a pattern
+   */
+  subscribePresence(pattern: string, callback: PresenceCallback): Subscription {
+    const id = this.nextSubId++;
+
+    this.presenceWatches.set(id, { pattern, callback });
+    this.sendMessage({ type: 'SubscribePresence', id, pattern });
+
+    return {
+      id,
+      subject: pattern,
+      unsubscribe: (): void => {
+        this.presenceWatches.delete(id);
+        this.sendMessage({ type: 'Unsubscribe', id });
+      },
+    };
+  }
+
+  /**
+   * List the sessions currently subscribed to subjects that match a pattern
+   */
+  async queryPresence(pattern: string, timeout = 5000): Promise<PresenceMember[]> {
+    if (!this.authenticated) {
+      throw new Error('Not authenticated');
+    }
+
+    const requestId = this.nextRequestId++;
+
+    return new Promise((resolve, reject) => {
+      const timer = setTimeout(() => {
+        this.pendingPresenceQueries.delete(requestId);
+        reject(new Error('Presence query timeout'));
+      }, timeout);
+
+      this.pendingPresenceQueries.set(requestId, {
+        resolve: (members): void => {
+          clearTimeout(timer);
+          resolve(members);
+        },
+        reject: (error): void => {
+          clearTimeout(timer);
+          reject(error);
+        },
+      });
+
+      this.sendMessage({ type: 'QueryPresence', requestId, pattern });
+    });
+  }
+
   /**
    * Publish a message to a subject
    */
@@ -125,6 +372,206 @@ export class MottomeshClient {
     this.sendMessage({ type: 'Publish', subject, payload });
   }
 
+  /**
+   * Publish a message to the JetStream stream capturing the subject and
+   * resolve once the stream has stored it
+   */
+  async publishDurable(subject: string, payload: Uint8Array, options: PublishDurableOptions = {}): Promise<PubAck> {
+    if (!this.authenticated) {
+      throw new Error('Not authenticated');
+    }
+
+    const requestId = this.nextRequestId++;
+    const timeout = options.timeout ?? 5000;
+
+    return new Promise((resolve, reject) => {
+      const timer = setTimeout(() => {
+        this.pendingPubAcks.delete(requestId);
+        reject(new Error('Publish timeout'));
+      }, timeout);
+
+      this.pendingPubAcks.set(requestId, {
+        resolve: (ack): void => {
+          clearTimeout(timer);
+          resolve(ack);
+        },
+        reject: (error): void => {
+          clearTimeout(timer);
+          reject(error);
+        },
+      });
+
+      this.sendMessage({ type: 'JetStreamPublish', requestId, subject, payload, msgId: options.msgId });
+    });
+  }
+
+  /**
+   * Read a key of a key-value bucket, resolving to null if it is not set
+   */
+  async kvGet(bucket: string, key: string, timeout = 5000): Promise<KvEntry | null> {
+    const reply = await this.kvRequest((requestId) => ({ type: 'KvGet', requestId, bucket, key }), timeout);
+    if (reply.type !== 'KvEntry' || reply.value === undefined) {
+      return null;
+    }
+    return { key: reply.key, value: reply.value, revision: reply.revision };
+  }
+
+  /**
+   * Set a key of a key-value bucket, resolving to the revision of the new value
+   */
+  async kvPut(bucket: string, key: string, value: Uint8Array, timeout = 5000): Promise<number> {
+    const reply = await this.kvRequest((requestId) => ({ type: 'KvPut', requestId, bucket, key, value }), timeout);
+    return reply.type === 'KvPutOk' ? reply.revision : 0;
+  }
+
+  /**
+   * Delete a key of a key-value bucket
+   */
+  async kvDelete(bucket: string, key: string, timeout = 5000): Promise<void> {
+    await this.kvRequest((requestId) => ({ type: 'KvDelete', requestId, bucket, key }), timeout);
+  }
+
+  /**
+   * Watch the keys of a bucket matching a pattern, which may use the `*` and
+   * `>` wildcards. The current value of each matching key is reported first,
+   * then every change.
+   */
+  kvWatch(bucket: string, key: string, callback: KvWatchCallback): Subscription {
+    const id = this.nextSubId++;
+
+    this.kvWatches.set(id, { bucket, key, callback });
+    this.sendMessage({ type: 'KvWatch', id, bucket, key });
+
+    return {
+      id,
+      subject: key,
+      unsubscribe: (): void => {
+        this.kvWatches.delete(id);
+        this.sendMessage({ type: 'Unsubscribe', id });
+      },
+    };
+  }
+
+  /**
+   * Store an object in an object store bucket, sending it in chunks. Resolves
+   * to the stored size once the gateway has checked the SHA-256 digest. An
+   * upload cut short by a reconnect carries on where it stopped.
+   */
+  async uploadObject(bucket: string, name: string, data: Uint8Array, options: UploadOptions = {}): Promise<number> {
+    if (!this.authenticated) {
+      throw new Error('Not authenticated');
+    }
+
+    const requestId = this.nextRequestId++;
+    const chunkSize = options.chunkSize ?? 64 * 1024;
+    const size = data.length;
+    const sha256 = await sha256Digest(data);
+    const start = (): void => {
+      this.sendMessage({ type: 'ObjectUploadStart', requestId, bucket, name, size, sha256 });
+    };
+
+    return new Promise((resolve, reject) => {
+      this.transfers.set(requestId, {
+        // The gateway recognises the upload and replies with its offset
+        resume: start,
+        handle: (msg): void => {
+          if (msg.type === 'ObjectUploadOk') {
+            this.transfers.delete(requestId);
+            resolve(msg.size);
+          } else if (msg.type === 'ObjectUploadProgress') {
+            options.onProgress?.(msg.offset, size);
+            const chunk = data.subarray(msg.offset, msg.offset + chunkSize);
+            this.sendMessage({ type: 'ObjectUploadChunk', requestId, offset: msg.offset, data: chunk });
+          }
+        },
+        reject,
+      });
+      start();
+    });
+  }
+
+  /**
+   * Read an object from an object store bucket in chunks, checking the
+   * SHA-256 digest the gateway reports at the end. A download cut short by a
+   * reconnect carries on from the last chunk received.
+   */
+  async downloadObject(bucket: string, name: string, options: DownloadOptions = {}): Promise<Uint8Array> {
+    if (!this.authenticated) {
+      throw new Error('Not authenticated');
+    }
+
+    const requestId = this.nextRequestId++;
+    let data: Uint8Array | null = null;
+    let received = 0;
+    const request = (): void => {
+      this.sendMessage({ type: 'ObjectDownload', requestId, bucket, name, offset: received });
+    };
+
+    return new Promise((resolve, reject) => {
+      this.transfers.set(requestId, {
+        resume: request,
+        handle: (msg): void => {
+          switch (msg.type) {
+            case 'ObjectMeta':
+              if (data !== null && data.length !== msg.size) {
+                this.transfers.delete(requestId);
+                reject(new Error(`Object ${name} changed during download`));
+              }
+              data ??= new Uint8Array(msg.size);
+              break;
+            case 'ObjectChunk':
+              data?.set(msg.data, msg.offset);
+              received = msg.offset + msg.data.length;
+              options.onProgress?.(received, data?.length ?? 0);
+              break;
+            case 'ObjectDownloadEnd': {
+              this.transfers.delete(requestId);
+              const object = data ?? new Uint8Array(0);
+              void sha256Digest(object).then((digest) => {
+                if (bytesEqual(digest, msg.sha256)) {
+                  resolve(object);
+                } else {
+                  reject(new Error(`SHA-256 of ${name} does not match`));
+                }
+              });
+              break;
+            }
+          }
+        },
+        reject,
+      });
+      request();
+    });
+  }
+
+  private async kvRequest(build: (requestId: number) => ClientMessage, timeout: number): Promise<KvReply> {
+    if (!this.authenticated) {
+      throw new Error('Not authenticated');
+    }
+
+    const requestId = this.nextRequestId++;
+
+    return new Promise((resolve, reject) => {
+      const timer = setTimeout(() => {
+        this.pendingKv.delete(requestId);
+        reject(new Error('Key-value request timeout'));
+      }, timeout);
+
+      this.pendingKv.set(requestId, {
+        resolve: (reply): void => {
+          clearTimeout(timer);
+          resolve(reply);
+        },
+        reject: (error): void => {
+          clearTimeout(timer);
+          reject(error);
+        },
+      });
+
+      this.sendMessage(build(requestId));
+    });
+  }
+
   /**
    * Request-reply pattern
    */
@@ -257,11 +704,13 @@ export class MottomeshClient {
           clearTimeout(timeout);
           this.authenticated = true;
           this.sessionId = msg.sessionId;
-          this.emit('auth', { sessionId: msg.sessionId });
+          this.resumeToken = msg.resumeToken ?? null;
+          this.resumed = msg.resumed;
+          this.emit('auth', { sessionId: msg.sessionId, resumed: msg.resumed });
           resolve();
         } else if (msg.type === 'AuthError') {
           clearTimeout(timeout);
-          reject(new Error(`Authentication failed: ${msg.reason}`));
+          reject(new MottomeshError(msg.code, `Authentication failed: ${msg.reason}`));
         } else {
           // Pass to normal handler
           originalHandler(data);
@@ -273,7 +722,7 @@ export class MottomeshClient {
       }
 
       // Send auth message
-      this.sendMessage({ type: 'Auth', token: this.options.token });
+      this.sendMessage({ type: 'Auth', token: this.options.token, resumeToken: this.resumeToken ?? undefined });
     });
   }
 
@@ -286,15 +735,37 @@ export class MottomeshClient {
       return;
     }
 
+    this.dispatchMessage(msg);
+  }
+
+  private dispatchMessage(msg: ServerMessage): void {
     switch (msg.type) {
       case 'Message': {
         const sub = this.subscriptions.get(msg.subscriptionId);
         if (sub) {
-          sub.callback({ subject: msg.subject, payload: msg.payload });
+          if (sub.remaining !== undefined) {
+            sub.remaining--;
+          }
+          sub.callback({ subject: msg.subject, payload: msg.payload, seq: msg.seq, dropped: msg.dropped, origin: msg.origin });
         }
         break;
       }
 
+      case 'ConsumerMessage': {
+        const id = msg.subscriptionId;
+        const streamSeq = msg.streamSeq;
+        this.consumers.get(id)?.callback({
+          subject: msg.subject,
+          payload: msg.payload,
+          streamSeq,
+          delivered: msg.delivered,
+          ack: (): void => { this.sendMessage({ type: 'Ack', id, streamSeq, kind: 'Ack' }); },
+          nak: (): void => { this.sendMessage({ type: 'Ack', id, streamSeq, kind: 'Nak' }); },
+          inProgress: (): void => { this.sendMessage({ type: 'Ack', id, streamSeq, kind: 'InProgress' }); },
+        });
+        break;
+      }
+
       case 'Response': {
         const pending = this.pendingRequests.get(msg.requestId);
         if (pending) {
@@ -305,10 +776,69 @@ export class MottomeshClient {
       }
 
       case 'RequestError': {
-        const pending = this.pendingRequests.get(msg.requestId);
+        const pending =
+          this.pendingRequests.get(msg.requestId) ??
+          this.pendingPresenceQueries.get(msg.requestId) ??
+          this.pendingPubAcks.get(msg.requestId) ??
+          this.pendingKv.get(msg.requestId) ??
+          this.transfers.get(msg.requestId);
         if (pending) {
           this.pendingRequests.delete(msg.requestId);
-          pending.reject(new Error(msg.reason));
+          this.pendingPresenceQueries.delete(msg.requestId);
+          this.pendingPubAcks.delete(msg.requestId);
+          this.pendingKv.delete(msg.requestId);
+          this.transfers.delete(msg.requestId);
+          pending.reject(new MottomeshError(msg.code, msg.reason));
+        }
+        break;
+      }
+
+      case 'PubAck': {
+        const pending = this.pendingPubAcks.get(msg.requestId);
+        if (pending) {
+          this.pendingPubAcks.delete(msg.requestId);
+          pending.resolve({ stream: msg.stream, seq: msg.seq, duplicate: msg.duplicate });
+        }
+        break;
+      }
+
+      case 'KvEntry':
+      case 'KvPutOk':
+      case 'KvDeleteOk': {
+        const pending = this.pendingKv.get(msg.requestId);
+        if (pending) {
+          this.pendingKv.delete(msg.requestId);
+          pending.resolve(msg);
+        }
+        break;
+      }
+
+      case 'ObjectUploadProgress':
+      case 'ObjectUploadOk':
+      case 'ObjectMeta':
+      case 'ObjectChunk':
+      case 'ObjectDownloadEnd':
+        this.transfers.get(msg.requestId)?.handle(msg);
+        break;
+
+      case 'KvUpdate':
+        this.kvWatches.get(msg.id)?.callback({
+          key: msg.key,
+          value: msg.value,
+          revision: msg.revision,
+          operation: msg.operation,
+        });
+        break;
+
+      case 'Presence':
+        this.presenceWatches.get(msg.id)?.callback({ action: msg.action, member: msg.member });
+        break;
+
+      case 'PresenceList': {
+        const pending = this.pendingPresenceQueries.get(msg.requestId);
+        if (pending) {
+          this.pendingPresenceQueries.delete(msg.requestId);
+          pending.resolve(msg.members);
         }
         break;
       }
@@ -318,21 +848,75 @@ export class MottomeshClient {
         break;
 
       case 'SubscribeError':
-        console.error(`Subscription error for id ${msg.id}: ${msg.reason}`);
+        console.error(`Subscription error for id ${msg.id} (${msg.code}): ${msg.reason}`);
         this.subscriptions.delete(msg.id);
+        this.consumers.delete(msg.id);
+        this.presenceWatches.delete(msg.id);
+        this.kvWatches.delete(msg.id);
         break;
 
+      case 'UnsubscribeOk':
+        // Unsubscribe confirmed
+        break;
+
+      case 'UnsubscribeError':
+        console.error(`Unsubscribe error for id ${msg.id} (${msg.code}): ${msg.reason}`);
+        break;
+
+      case 'SubscriptionEnded': {
+        const subject =
+          this.subscriptions.get(msg.id)?.subject ??
+          this.consumers.get(msg.id)?.filterSubject ??
+          this.kvWatches.get(msg.id)?.key;
+        this.subscriptions.delete(msg.id);
+        this.consumers.delete(msg.id);
+        this.kvWatches.delete(msg.id);
+        this.emit('subscriptionend', {
+          id: msg.id,
+          subject,
+          reason: msg.reason,
+          totalDropped: msg.totalDropped,
+        });
+        break;
+      }
+
       case 'Error':
-        this.emit('error', new Error(`Server error ${msg.code}: ${msg.message}`));
+        this.emit('error', new MottomeshError(msg.code, `Server error ${msg.code}: ${msg.message}`));
         break;
 
       case 'Pong':
         // Keepalive response
         break;
+
+      case 'Batch':
+        for (const inner of msg.messages) {
+          this.dispatchMessage(inner);
+        }
+        break;
+
+      case 'Compressed':
+        // Only sent after a Hello that offered compression, which this client never does
+        this.emit('error', new Error(`Unexpected ${msg.algorithm} compressed frame`));
+        break;
+
+      case 'GoAway':
+        // Gateway is draining; let the application decide where to reconnect
+        this.emit('goaway', {
+          reason: msg.reason,
+          reconnectAfterMs: msg.reconnectAfterMs,
+          alternateUrl: msg.alternateUrl,
+        });
+        break;
+
+      case 'BackendStatus':
+        // The gateway lost or regained NATS; subscriptions resume on their own
+        this.emit('backend', { available: msg.available });
+        break;
     }
   }
 
   private handleClose(reason?: string): void {
+    this.disconnectedAt ??= Date.now();
     this.authenticated = false;
     this.sessionId = null;
     this.emit('disconnect', reason);
@@ -359,9 +943,42 @@ export class MottomeshClient {
     try {
       await this.connect();
 
-      // Resubscribe to all subjects
-      for (const [id, { subject }] of this.subscriptions) {
-        this.sendMessage({ type: 'Subscribe', subject, id });
+      const since = this.disconnectedAt;
+      this.disconnectedAt = null;
+
+      // Transfers in flight stopped with the connection, resumed or not
+      for (const transfer of this.transfers.values()) {
+        transfer.resume();
+      }
+
+      // A resumed session kept its subscriptions and buffered what arrived
+      // in the meantime; there is nothing to restore
+      if (this.resumed) {
+        this.isReconnecting = false;
+        return;
+      }
+
+      // Resubscribe to all subjects, carrying over what is left of any limit
+      // and catching up on history where it was asked for
+      for (const [id, { subject, remaining, catchUp, slowConsumer }] of this.subscriptions) {
+        if (remaining === 0) {
+          this.subscriptions.delete(id);
+          continue;
+        }
+        const replay: ReplayFrom | undefined =
+          catchUp && since !== null ? { type: 'Since', unixMs: since } : undefined;
+        this.sendMessage({ type: 'Subscribe', subject, id, maxMsgs: remaining, replay, slowConsumer });
+      }
+      // Unacked consumer messages are redelivered to the new subscription
+      for (const [id, { stream, durable, filterSubject, deliver }] of this.consumers) {
+        this.sendMessage({ type: 'SubscribeConsumer', id, stream, durable, filterSubject, deliver });
+      }
+      for (const [id, { pattern }] of this.presenceWatches) {
+        this.sendMessage({ type: 'SubscribePresence', id, pattern });
+      }
+      // Watches start over with the current value of each key
+      for (const [id, { bucket, key }] of this.kvWatches) {
+        this.sendMessage({ type: 'KvWatch', id, bucket, key });
       }
 
       this.isReconnecting = false;
@@ -393,3 +1010,11 @@ export class MottomeshClient {
     }
   }
 }
+
+async function sha256Digest(data: Uint8Array): Promise<Uint8Array> {
+  return new Uint8Array(await crypto.subtle.digest('SHA-256', data));
+}
+
+function bytesEqual(a: Uint8Array, b: Uint8Array): boolean {
+  return a.length === b.length && a.every((byte, i) => byte === b[i]);
+}
@@ -29,9 +29,43 @@
  * ```
  */
 
-export { MottomeshClient, type ClientOptions, type Subscription, type MessageCallback, type EventType } from './client';
+export {
+  MottomeshClient,
+  type ClientOptions,
+  type SubscribeOptions,
+  type Subscription,
+  type MessageCallback,
+  type ConsumeOptions,
+  type PublishDurableOptions,
+  type PubAck,
+  type ConsumerMessageCallback,
+  type KvEntry,
+  type KvWatchCallback,
+  type UploadOptions,
+  type DownloadOptions,
+  type PresenceCallback,
+  type EventType,
+} from './client';
 export { Transport, TransportType, WebTransportTransport, WebSocketTransport } from './transport';
-export { ClientMessage, ServerMessage, ErrorCodes, encodeClientMessage, decodeServerMessage } from './protocol';
+export {
+  ClientMessage,
+  ServerMessage,
+  Capability,
+  CompressionAlgorithm,
+  SubscriptionEndReason,
+  PresenceAction,
+  PresenceMember,
+  MessageOrigin,
+  ReplayFrom,
+  SlowConsumerPolicy,
+  DeliverPolicy,
+  AckKind,
+  KvOperation,
+  ErrorCode,
+  MottomeshError,
+  encodeClientMessage,
+  decodeServerMessage,
+} from './protocol';
 
 // Backwards compatibility export (deprecated)
 // eslint-disable-next-line @typescript-eslint/no-deprecated
@@ -1,10 +1,21 @@
 import {
   decodeServerEnvelope,
   encodeClientEnvelope,
+  type Capability as SchemaCapability,
   type ClientMessage as SchemaClientMessage,
+  type DeliverPolicy as SchemaDeliverPolicy,
+  type PresenceMember as SchemaPresenceMember,
+  type ReplayFrom as SchemaReplayFrom,
   type ServerMessage as SchemaServerMessage,
 } from '@motto/schema';
-import type { ClientMessage, ServerMessage } from './messages';
+import type {
+  Capability,
+  ClientMessage,
+  DeliverPolicy,
+  PresenceMember,
+  ReplayFrom,
+  ServerMessage,
+} from './messages';
 
 function toBigIntId(value: number): bigint {
   return BigInt(value);
@@ -18,12 +29,50 @@ function toNumberId(value: bigint): number {
   return num;
 }
 
+function toSchemaCapabilities(capabilities: Capability[]): SchemaCapability[] {
+  return capabilities.map((type) => ({ type }));
+}
+
+function toPublicCapabilities(capabilities: SchemaCapability[]): Capability[] {
+  return capabilities.map((capability) => capability.type);
+}
+
+function toPublicPresenceMember(member: SchemaPresenceMember): PresenceMember {
+  return { sessionId: member.session_id, userId: member.user_id, subject: member.subject };
+}
+
+function toSchemaReplayFrom(replay: ReplayFrom): SchemaReplayFrom {
+  switch (replay.type) {
+    case 'Last':
+      return { type: 'Last', count: replay.count };
+    case 'Since':
+      return { type: 'Since', unix_ms: BigInt(replay.unixMs) };
+  }
+}
+
+function toSchemaDeliverPolicy(deliver: DeliverPolicy): SchemaDeliverPolicy {
+  switch (deliver.type) {
+    case 'ByStartSequence':
+      return { type: 'ByStartSequence', start_seq: BigInt(deliver.startSeq) };
+    default:
+      return { type: deliver.type };
+  }
+}
+
 function toSchemaClientMessage(msg: ClientMessage): SchemaClientMessage {
   switch (msg.type) {
     case 'Auth':
-      return { type: 'Auth', token: msg.token };
+      return { type: 'Auth', token: msg.token, resume_token: msg.resumeToken ?? null };
     case 'Subscribe':
-      return { type: 'Subscribe', subject: msg.subject, id: toBigIntId(msg.id) };
+      return {
+        type: 'Subscribe',
+        subject: msg.subject,
+        id: toBigIntId(msg.id),
+        credits: msg.credits ?? null,
+        max_msgs: msg.maxMsgs ?? null,
+        replay: msg.replay ? toSchemaReplayFrom(msg.replay) : null,
+        slow_consumer: msg.slowConsumer ? { type: msg.slowConsumer } : null,
+      };
     case 'Unsubscribe':
       return { type: 'Unsubscribe', id: toBigIntId(msg.id) };
     case 'Publish':
@@ -35,28 +84,143 @@ function toSchemaClientMessage(msg: ClientMessage): SchemaClientMessage {
         payload: Array.from(msg.payload),
         timeout_ms: msg.timeoutMs,
         request_id: toBigIntId(msg.requestId),
+        stream: msg.stream ?? false,
       };
     case 'Ping':
       return { type: 'Ping' };
+    case 'CancelRequest':
+      return { type: 'CancelRequest', request_id: toBigIntId(msg.requestId) };
+    case 'Hello':
+      return {
+        type: 'Hello',
+        client_name: msg.clientName,
+        client_version: msg.clientVersion,
+        schema_fingerprint: msg.schemaFingerprint,
+        capabilities: toSchemaCapabilities(msg.capabilities),
+        compression: (msg.compression ?? []).map((type) => ({ type })),
+      };
+    case 'Batch':
+      // Each message travels as a frame of its own
+      return {
+        type: 'Batch',
+        frames: msg.messages.map((inner) => Array.from(encodeClientMessage(inner))),
+      };
+    case 'GrantCredit':
+      return { type: 'GrantCredit', id: toBigIntId(msg.id), credits: msg.credits };
+    case 'Compressed':
+      return {
+        type: 'Compressed',
+        algorithm: { type: msg.algorithm },
+        frame: Array.from(msg.frame),
+      };
+    case 'SubscribePresence':
+      return { type: 'SubscribePresence', id: toBigIntId(msg.id), pattern: msg.pattern };
+    case 'QueryPresence':
+      return { type: 'QueryPresence', request_id: toBigIntId(msg.requestId), pattern: msg.pattern };
+    case 'SubscribeConsumer':
+      return {
+        type: 'SubscribeConsumer',
+        id: toBigIntId(msg.id),
+        stream: msg.stream,
+        durable: msg.durable,
+        filter_subject: msg.filterSubject,
+        deliver: toSchemaDeliverPolicy(msg.deliver),
+      };
+    case 'Ack':
+      return {
+        type: 'Ack',
+        id: toBigIntId(msg.id),
+        stream_seq: BigInt(msg.streamSeq),
+        kind: { type: msg.kind },
+      };
+    case 'JetStreamPublish':
+      return {
+        type: 'JetStreamPublish',
+        request_id: toBigIntId(msg.requestId),
+        subject: msg.subject,
+        payload: Array.from(msg.payload),
+        msg_id: msg.msgId ?? null,
+      };
+    case 'KvGet':
+      return {
+        type: 'KvGet',
+        request_id: toBigIntId(msg.requestId),
+        bucket: msg.bucket,
+        key: msg.key,
+      };
+    case 'KvPut':
+      return {
+        type: 'KvPut',
+        request_id: toBigIntId(msg.requestId),
+        bucket: msg.bucket,
+        key: msg.key,
+        value: Array.from(msg.value),
+      };
+    case 'KvDelete':
+      return {
+        type: 'KvDelete',
+        request_id: toBigIntId(msg.requestId),
+        bucket: msg.bucket,
+        key: msg.key,
+      };
+    case 'KvWatch':
+      return { type: 'KvWatch', id: toBigIntId(msg.id), bucket: msg.bucket, key: msg.key };
+    case 'ObjectUploadStart':
+      return {
+        type: 'ObjectUploadStart',
+        request_id: toBigIntId(msg.requestId),
+        bucket: msg.bucket,
+        name: msg.name,
+        size: toBigIntId(msg.size),
+        sha256: Array.from(msg.sha256),
+      };
+    case 'ObjectUploadChunk':
+      return {
+        type: 'ObjectUploadChunk',
+        request_id: toBigIntId(msg.requestId),
+        offset: toBigIntId(msg.offset),
+        data: Array.from(msg.data),
+      };
+    case 'ObjectDownload':
+      return {
+        type: 'ObjectDownload',
+        request_id: toBigIntId(msg.requestId),
+        bucket: msg.bucket,
+        name: msg.name,
+        offset: toBigIntId(msg.offset),
+      };
   }
 }
 
 function toPublicServerMessage(msg: SchemaServerMessage): ServerMessage {
   switch (msg.type) {
     case 'AuthOk':
-      return { type: 'AuthOk', sessionId: msg.session_id };
+      return {
+        type: 'AuthOk',
+        sessionId: msg.session_id,
+        resumeToken: msg.resume_token ?? undefined,
+        resumed: msg.resumed,
+      };
     case 'AuthError':
-      return { type: 'AuthError', reason: msg.reason };
+      return { type: 'AuthError', code: msg.code.type, reason: msg.reason };
     case 'SubscribeOk':
       return { type: 'SubscribeOk', id: toNumberId(msg.id) };
     case 'SubscribeError':
-      return { type: 'SubscribeError', id: toNumberId(msg.id), reason: msg.reason };
+      return {
+        type: 'SubscribeError',
+        id: toNumberId(msg.id),
+        code: msg.code.type,
+        reason: msg.reason,
+      };
     case 'Message':
       return {
         type: 'Message',
         subscriptionId: toNumberId(msg.subscription_id),
         subject: msg.subject,
         payload: new Uint8Array(msg.payload),
+        seq: toNumberId(msg.seq),
+        dropped: msg.dropped,
+        origin: msg.origin.type,
       };
     case 'Response':
       return {
@@ -68,12 +232,156 @@ function toPublicServerMessage(msg: SchemaServerMessage): ServerMessage {
       return {
         type: 'RequestError',
         requestId: toNumberId(msg.request_id),
+        code: msg.code.type,
         reason: msg.reason,
       };
     case 'Error':
-      return { type: 'Error', code: msg.code, message: msg.message };
+      return { type: 'Error', code: msg.code.type, message: msg.message };
     case 'Pong':
       return { type: 'Pong' };
+    case 'ResponseChunk':
+      return {
+        type: 'ResponseChunk',
+        requestId: toNumberId(msg.request_id),
+        seq: msg.seq,
+        payload: new Uint8Array(msg.payload),
+      };
+    case 'ResponseEnd':
+      return { type: 'ResponseEnd', requestId: toNumberId(msg.request_id) };
+    case 'Welcome':
+      return {
+        type: 'Welcome',
+        gatewayVersion: msg.gateway_version,
+        schemaFingerprint: msg.schema_fingerprint,
+        capabilities: toPublicCapabilities(msg.capabilities),
+        maxPayloadBytes: msg.max_payload_bytes,
+        compression: msg.compression.type,
+      };
+    case 'HelloError':
+      return { type: 'HelloError', code: msg.code.type, message: msg.message };
+    case 'GoAway':
+      return {
+        type: 'GoAway',
+        reason: msg.reason,
+        reconnectAfterMs: msg.reconnect_after_ms,
+        alternateUrl: msg.alternate_url ?? undefined,
+      };
+    case 'Batch':
+      return {
+        type: 'Batch',
+        messages: msg.frames.map((frame) => decodeServerMessage(new Uint8Array(frame))),
+      };
+    case 'Compressed':
+      return {
+        type: 'Compressed',
+        algorithm: msg.algorithm.type,
+        frame: new Uint8Array(msg.frame),
+      };
+    case 'UnsubscribeOk':
+      return {
+        type: 'UnsubscribeOk',
+        id: toNumberId(msg.id),
+        totalDropped: toNumberId(msg.total_dropped),
+      };
+    case 'UnsubscribeError':
+      return {
+        type: 'UnsubscribeError',
+        id: toNumberId(msg.id),
+        code: msg.code.type,
+        reason: msg.reason,
+      };
+    case 'SubscriptionEnded':
+      return {
+        type: 'SubscriptionEnded',
+        id: toNumberId(msg.id),
+        reason: msg.reason.type,
+        totalDropped: toNumberId(msg.total_dropped),
+      };
+    case 'Presence':
+      return {
+        type: 'Presence',
+        id: toNumberId(msg.id),
+        action: msg.action.type,
+        member: toPublicPresenceMember(msg.member),
+      };
+    case 'PresenceList':
+      return {
+        type: 'PresenceList',
+        requestId: toNumberId(msg.request_id),
+        members: msg.members.map(toPublicPresenceMember),
+      };
+    case 'ConsumerMessage':
+      return {
+        type: 'ConsumerMessage',
+        subscriptionId: toNumberId(msg.subscription_id),
+        subject: msg.subject,
+        payload: new Uint8Array(msg.payload),
+        streamSeq: toNumberId(msg.stream_seq),
+        delivered: msg.delivered,
+      };
+    case 'PubAck':
+      return {
+        type: 'PubAck',
+        requestId: toNumberId(msg.request_id),
+        stream: msg.stream,
+        seq: toNumberId(msg.seq),
+        duplicate: msg.duplicate,
+      };
+    case 'KvEntry':
+      return {
+        type: 'KvEntry',
+        requestId: toNumberId(msg.request_id),
+        key: msg.key,
+        value: msg.value === null ? undefined : new Uint8Array(msg.value),
+        revision: toNumberId(msg.revision),
+      };
+    case 'KvPutOk':
+      return {
+        type: 'KvPutOk',
+        requestId: toNumberId(msg.request_id),
+        revision: toNumberId(msg.revision),
+      };
+    case 'KvDeleteOk':
+      return { type: 'KvDeleteOk', requestId: toNumberId(msg.request_id) };
+    case 'KvUpdate':
+      return {
+        type: 'KvUpdate',
+        id: toNumberId(msg.id),
+        key: msg.key,
+        value: new Uint8Array(msg.value),
+        revision: toNumberId(msg.revision),
+        operation: msg.operation.type,
+      };
+    case 'ObjectUploadProgress':
+      return {
+        type: 'ObjectUploadProgress',
+        requestId: toNumberId(msg.request_id),
+        offset: toNumberId(msg.offset),
+      };
+    case 'ObjectUploadOk':
+      return { type: 'ObjectUploadOk', requestId: toNumberId(msg.request_id), size: toNumberId(msg.size) };
+    case 'ObjectMeta':
+      return {
+        type: 'ObjectMeta',
+        requestId: toNumberId(msg.request_id),
+        name: msg.name,
+        size: toNumberId(msg.size),
+      };
+    case 'ObjectChunk':
+      return {
+        type: 'ObjectChunk',
+        requestId: toNumberId(msg.request_id),
+        offset: toNumberId(msg.offset),
+        data: new Uint8Array(msg.data),
+      };
+    case 'ObjectDownloadEnd':
+      return {
+        type: 'ObjectDownloadEnd',
+        requestId: toNumberId(msg.request_id),
+        sha256: new Uint8Array(msg.sha256),
+      };
+    case 'BackendStatus':
+      return { type: 'BackendStatus', available: msg.available };
   }
 }
 
@@ -3,32 +3,177 @@
  * These map to the shared Motto schema contract.
  */
 
+// Optional protocol features negotiated during the Hello/Welcome handshake
+export type Capability = 'Compression' | 'Headers' | 'Batching';
+
+// Frame compression algorithms, negotiated alongside the Compression capability
+export type CompressionAlgorithm = 'None' | 'Gzip' | 'Zstd';
+
+// Why the gateway ended a subscription on its own
+export type SubscriptionEndReason = 'LimitReached' | 'PermissionRevoked' | 'BackendClosed';
+
+// Whether a session started or stopped subscribing to a subject
+export type PresenceAction = 'Join' | 'Leave';
+
+// Where a delivered message came from: published live, or replayed on subscribe
+export type MessageOrigin = 'Live' | 'Retained' | 'History';
+
+// Which recorded messages to replay when subscribing
+export type ReplayFrom = { type: 'Last'; count: number } | { type: 'Since'; unixMs: number };
+
+// What the gateway does when a subscription's messages pile up faster than they are read
+export type SlowConsumerPolicy = 'Block' | 'DropOldest' | 'DropNewest' | 'Conflate' | 'Disconnect';
+
+// Where a durable consumer starts delivering when it is first created
+export type DeliverPolicy =
+  | { type: 'All' }
+  | { type: 'New' }
+  | { type: 'Last' }
+  | { type: 'LastPerSubject' }
+  | { type: 'ByStartSequence'; startSeq: number };
+
+// How a durable consumer delivery is settled
+export type AckKind = 'Ack' | 'Nak' | 'InProgress';
+
+// What changed a watched key-value entry
+export type KvOperation = 'Put' | 'Delete' | 'Purge';
+
+// A session subscribed to a subject, as reported by presence
+export interface PresenceMember {
+  sessionId: string;
+  userId: string;
+  subject: string;
+}
+
+// Error codes shared by every error-bearing server message
+export type ErrorCode =
+  | 'Unauthorized'
+  | 'PermissionDenied'
+  | 'InvalidMessage'
+  | 'InvalidSubject'
+  | 'PayloadTooLarge'
+  | 'NotFound'
+  | 'AlreadyExists'
+  | 'QuotaExceeded'
+  | 'NoResponders'
+  | 'Timeout'
+  | 'BackendUnavailable'
+  | 'VersionMismatch'
+  | 'SchemaMismatch'
+  | 'Internal'
+  | 'Draining';
+
 // Client -> Server messages
 export type ClientMessage =
-  | { type: 'Auth'; token: string }
-  | { type: 'Subscribe'; subject: string; id: number }
+  | { type: 'Auth'; token: string; resumeToken?: string }
+  | { type: 'Subscribe'; subject: string; id: number; credits?: number; maxMsgs?: number; replay?: ReplayFrom; slowConsumer?: SlowConsumerPolicy }
   | { type: 'Unsubscribe'; id: number }
   | { type: 'Publish'; subject: string; payload: Uint8Array }
-  | { type: 'Request'; subject: string; payload: Uint8Array; timeoutMs: number; requestId: number }
-  | { type: 'Ping' };
+  | { type: 'Request'; subject: string; payload: Uint8Array; timeoutMs: number; requestId: number; stream?: boolean }
+  | { type: 'Ping' }
+  | { type: 'CancelRequest'; requestId: number }
+  | {
+      type: 'Hello';
+      clientName: string;
+      clientVersion: string;
+      schemaFingerprint: string;
+      capabilities: Capability[];
+      compression?: CompressionAlgorithm[];
+    }
+  | { type: 'Batch'; messages: ClientMessage[] }
+  | { type: 'GrantCredit'; id: number; credits: number }
+  | { type: 'Compressed'; algorithm: CompressionAlgorithm; frame: Uint8Array }
+  | { type: 'SubscribePresence'; id: number; pattern: string }
+  | { type: 'QueryPresence'; requestId: number; pattern: string }
+  | {
+      type: 'SubscribeConsumer';
+      id: number;
+      stream: string;
+      durable: string;
+      filterSubject: string;
+      deliver: DeliverPolicy;
+    }
+  | { type: 'Ack'; id: number; streamSeq: number; kind: AckKind }
+  | { type: 'JetStreamPublish'; requestId: number; subject: string; payload: Uint8Array; msgId?: string }
+  | { type: 'KvGet'; requestId: number; bucket: string; key: string }
+  | { type: 'KvPut'; requestId: number; bucket: string; key: string; value: Uint8Array }
+  | { type: 'KvDelete'; requestId: number; bucket: string; key: string }
+  | { type: 'KvWatch'; id: number; bucket: string; key: string }
+  | {
+      type: 'ObjectUploadStart';
+      requestId: number;
+      bucket: string;
+      name: string;
+      size: number;
+      sha256: Uint8Array;
+    }
+  | { type: 'ObjectUploadChunk'; requestId: number; offset: number; data: Uint8Array }
+  | { type: 'ObjectDownload'; requestId: number; bucket: string; name: string; offset: number };
 
 // Server -> Client messages
 export type ServerMessage =
-  | { type: 'AuthOk'; sessionId: string }
-  | { type: 'AuthError'; reason: string }
+  | { type: 'AuthOk'; sessionId: string; resumeToken?: string; resumed: boolean }
+  | { type: 'AuthError'; code: ErrorCode; reason: string }
   | { type: 'SubscribeOk'; id: number }
-  | { type: 'SubscribeError'; id: number; reason: string }
-  | { type: 'Message'; subscriptionId: number; subject: string; payload: Uint8Array }
+  | { type: 'SubscribeError'; id: number; code: ErrorCode; reason: string }
+  | { type: 'Message'; subscriptionId: number; subject: string; payload: Uint8Array; seq: number; dropped: number; origin: MessageOrigin }
   | { type: 'Response'; requestId: number; payload: Uint8Array }
-  | { type: 'RequestError'; requestId: number; reason: string }
-  | { type: 'Error'; code: number; message: string }
-  | { type: 'Pong' };
-
-// Error codes (matching Rust definitions)
-export const ErrorCodes = {
-  UNAUTHORIZED: 401,
-  FORBIDDEN: 403,
-  NOT_FOUND: 404,
-  INTERNAL_ERROR: 500,
-  INVALID_MESSAGE: 400,
-} as const;
+  | { type: 'RequestError'; requestId: number; code: ErrorCode; reason: string }
+  | { type: 'Error'; code: ErrorCode; message: string }
+  | { type: 'Pong' }
+  | { type: 'ResponseChunk'; requestId: number; seq: number; payload: Uint8Array }
+  | { type: 'ResponseEnd'; requestId: number }
+  | {
+      type: 'Welcome';
+      gatewayVersion: string;
+      schemaFingerprint: string;
+      capabilities: Capability[];
+      maxPayloadBytes: number;
+      compression: CompressionAlgorithm;
+    }
+  | { type: 'HelloError'; code: ErrorCode; message: string }
+  | { type: 'GoAway'; reason: string; reconnectAfterMs: number; alternateUrl?: string }
+  | { type: 'Batch'; messages: ServerMessage[] }
+  | { type: 'Compressed'; algorithm: CompressionAlgorithm; frame: Uint8Array }
+  | { type: 'UnsubscribeOk'; id: number; totalDropped: number }
+  | { type: 'UnsubscribeError'; id: number; code: ErrorCode; reason: string }
+  | { type: 'SubscriptionEnded'; id: number; reason: SubscriptionEndReason; totalDropped: number }
+  | { type: 'Presence'; id: number; action: PresenceAction; member: PresenceMember }
+  | { type: 'PresenceList'; requestId: number; members: PresenceMember[] }
+  | {
+      type: 'ConsumerMessage';
+      subscriptionId: number;
+      subject: string;
+      payload: Uint8Array;
+      streamSeq: number;
+      delivered: number;
+    }
+  | { type: 'PubAck'; requestId: number; stream: string; seq: number; duplicate: boolean }
+  | { type: 'KvEntry'; requestId: number; key: string; value?: Uint8Array; revision: number }
+  | { type: 'KvPutOk'; requestId: number; revision: number }
+  | { type: 'KvDeleteOk'; requestId: number }
+  | {
+      type: 'KvUpdate';
+      id: number;
+      key: string;
+      value: Uint8Array;
+      revision: number;
+      operation: KvOperation;
+    }
+  | { type: 'ObjectUploadProgress'; requestId: number; offset: number }
+  | { type: 'ObjectUploadOk'; requestId: number; size: number }
+  | { type: 'ObjectMeta'; requestId: number; name: string; size: number }
+  | { type: 'ObjectChunk'; requestId: number; offset: number; data: Uint8Array }
+  | { type: 'ObjectDownloadEnd'; requestId: number; sha256: Uint8Array }
+  | { type: 'BackendStatus'; available: boolean };
+
+/** Error raised by the client for a gateway error, carrying its typed code */
+export class MottomeshError extends Error {
+  constructor(
+    public readonly code: ErrorCode,
+    message: string,
+  ) {
+    super(message);
+    this.name = 'MottomeshError';
+  }
+}
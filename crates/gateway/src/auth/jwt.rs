@@ -11,7 +11,8 @@ pub struct Claims {
     pub exp: usize,
     /// Issued at (Unix timestamp)
     pub iat: usize,
-    /// Permissions: ["publish", "subscribe", "request"]
+    /// Permissions: ["publish", "subscribe", "request", "kv_read", "kv_write",
+    /// "object_read", "object_write"]
     #[serde(default)]
     pub permissions: Vec<String>,
     /// Allowed subject patterns (supports NATS wildcards * and >)
@@ -20,6 +21,21 @@ pub struct Claims {
     /// Denied subject patterns (takes precedence over allowed)
     #[serde(default)]
     pub deny_subjects: Vec<String>,
+    /// Allowed key-value patterns over `bucket.key` (supports wildcards * and >)
+    #[serde(default)]
+    pub kv_allowed: Vec<String>,
+    /// Denied key-value patterns (takes precedence over allowed)
+    #[serde(default)]
+    pub kv_deny: Vec<String>,
+    /// Allowed object patterns over `bucket.name` (supports wildcards * and >)
+    #[serde(default)]
+    pub object_allowed: Vec<String>,
+    /// Denied object patterns (takes precedence over allowed)
+    #[serde(default)]
+    pub object_deny: Vec<String>,
+    /// Tenant the user belongs to, passed on to backend services
+    #[serde(default)]
+    pub tenant: Option<String>,
 }
 
 pub struct JwtValidator {
@@ -78,6 +94,11 @@ mod tests {
             permissions: vec!["publish".to_string(), "subscribe".to_string()],
             allowed_subjects: vec!["messages.*".to_string()],
             deny_subjects: vec![],
+            kv_allowed: vec![],
+            kv_deny: vec![],
+            object_allowed: vec![],
+            object_deny: vec![],
+            tenant: None,
         }
     }
 
@@ -107,6 +128,11 @@ mod tests {
             permissions: vec![],
             allowed_subjects: vec![],
             deny_subjects: vec![],
+            kv_allowed: vec![],
+            kv_deny: vec![],
+            object_allowed: vec![],
+            object_deny: vec![],
+            tenant: None,
         };
 
         let token = create_test_token(secret, &claims);
@@ -167,6 +193,11 @@ mod tests {
             permissions: vec![],
             allowed_subjects: vec![],
             deny_subjects: vec![],
+            kv_allowed: vec![],
+            kv_deny: vec![],
+            object_allowed: vec![],
+            object_deny: vec![],
+            tenant: None,
         };
 
         let token = create_test_token(secret, &claims);
@@ -194,6 +225,11 @@ mod tests {
             ],
             allowed_subjects: vec![">".to_string()], // Full access
             deny_subjects: vec!["admin.>".to_string()], // Except admin topics
+            kv_allowed: vec![],
+            kv_deny: vec![],
+            object_allowed: vec![],
+            object_deny: vec![],
+            tenant: None,
         };
 
         let token = create_test_token(secret, &claims);
@@ -226,6 +262,11 @@ mod tests {
             permissions: vec![],
             allowed_subjects: vec![],
             deny_subjects: vec![],
+            kv_allowed: vec![],
+            kv_deny: vec![],
+            object_allowed: vec![],
+            object_deny: vec![],
+            tenant: None,
         };
         let token = create_test_token(&long_secret, &claims);
         assert!(validator.validate(&token).is_ok());
@@ -1,4 +1,5 @@
 use super::jwt::Claims;
+use crate::bridge::INTERNAL_SUBJECT_PREFIX;
 
 /// Permission types
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -6,6 +7,14 @@ pub enum Permission {
     Publish,
     Subscribe,
     Request,
+    /// Read and watch key-value entries
+    KvRead,
+    /// Put and delete key-value entries
+    KvWrite,
+    /// Download objects
+    ObjectRead,
+    /// Upload objects
+    ObjectWrite,
 }
 
 impl Permission {
@@ -16,6 +25,10 @@ impl Permission {
             "publish" => Some(Permission::Publish),
             "subscribe" => Some(Permission::Subscribe),
             "request" => Some(Permission::Request),
+            "kv_read" => Some(Permission::KvRead),
+            "kv_write" => Some(Permission::KvWrite),
+            "object_read" => Some(Permission::ObjectRead),
+            "object_write" => Some(Permission::ObjectWrite),
             _ => None,
         }
     }
@@ -31,6 +44,10 @@ impl PermissionChecker {
             Permission::Publish => "publish",
             Permission::Subscribe => "subscribe",
             Permission::Request => "request",
+            Permission::KvRead => "kv_read",
+            Permission::KvWrite => "kv_write",
+            Permission::ObjectRead => "object_read",
+            Permission::ObjectWrite => "object_write",
         };
         claims
             .permissions
@@ -43,6 +60,11 @@ impl PermissionChecker {
     /// - `*` matches a single token
     /// - `>` matches one or more tokens (must be at the end)
     pub fn is_subject_allowed(claims: &Claims, subject: &str) -> bool {
+        // Gateway-internal subjects are off limits to every client
+        if subject.starts_with(INTERNAL_SUBJECT_PREFIX) {
+            return false;
+        }
+
         // First check deny patterns (they take precedence)
         for pattern in &claims.deny_subjects {
             if Self::matches_pattern(pattern, subject) {
@@ -65,8 +87,37 @@ impl PermissionChecker {
         false
     }
 
+    /// Check if a key of a key-value bucket is accessible. Patterns are
+    /// matched against `bucket.key` with the same wildcards and precedence
+    /// as subject patterns.
+    pub fn is_kv_allowed(claims: &Claims, bucket: &str, key: &str) -> bool {
+        Self::is_path_allowed(&claims.kv_allowed, &claims.kv_deny, bucket, key)
+    }
+
+    /// Check if an object is accessible, matching patterns against
+    /// `bucket.name` like [`Self::is_kv_allowed`]
+    pub fn is_object_allowed(claims: &Claims, bucket: &str, name: &str) -> bool {
+        Self::is_path_allowed(&claims.object_allowed, &claims.object_deny, bucket, name)
+    }
+
+    fn is_path_allowed(allowed: &[String], deny: &[String], bucket: &str, name: &str) -> bool {
+        let path = format!("{}.{}", bucket, name);
+
+        if deny
+            .iter()
+            .any(|pattern| Self::matches_pattern(pattern, &path))
+        {
+            return false;
+        }
+
+        allowed.is_empty()
+            || allowed
+                .iter()
+                .any(|pattern| Self::matches_pattern(pattern, &path))
+    }
+
     /// Check if a subject matches a NATS-style pattern
-    fn matches_pattern(pattern: &str, subject: &str) -> bool {
+    pub fn matches_pattern(pattern: &str, subject: &str) -> bool {
         let pattern_parts: Vec<&str> = pattern.split('.').collect();
         let subject_parts: Vec<&str> = subject.split('.').collect();
 
@@ -100,6 +151,21 @@ impl PermissionChecker {
     pub fn can_perform(claims: &Claims, permission: Permission, subject: &str) -> bool {
         Self::has_permission(claims, permission) && Self::is_subject_allowed(claims, subject)
     }
+
+    /// Combined check for permission and key-value key
+    pub fn can_access_kv(claims: &Claims, permission: Permission, bucket: &str, key: &str) -> bool {
+        Self::has_permission(claims, permission) && Self::is_kv_allowed(claims, bucket, key)
+    }
+
+    /// Combined check for permission and object
+    pub fn can_access_object(
+        claims: &Claims,
+        permission: Permission,
+        bucket: &str,
+        name: &str,
+    ) -> bool {
+        Self::has_permission(claims, permission) && Self::is_object_allowed(claims, bucket, name)
+    }
 }
 
 #[cfg(test)]
@@ -114,9 +180,100 @@ mod tests {
             permissions: permissions.into_iter().map(String::from).collect(),
             allowed_subjects: allowed.into_iter().map(String::from).collect(),
             deny_subjects: denied.into_iter().map(String::from).collect(),
+            kv_allowed: vec![],
+            kv_deny: vec![],
+            object_allowed: vec![],
+            object_deny: vec![],
+            tenant: None,
         }
     }
 
+    // ============ matches_pattern Tests ============
+
+    #[test]
+    fn test_exact_pattern_match() {
+        assert!(PermissionChecker::matches_pattern(
+            "foo.bar.baz",
+            "foo.bar.baz"
+        ));
+        assert!(!PermissionChecker::matches_pattern(
+            "foo.bar.baz",
+            "foo.bar.qux"
+        ));
+        assert!(!PermissionChecker::matches_pattern(
+            "foo.bar",
+            "foo.bar.baz"
+        ));
+    }
+
+    #[test]
+    fn test_single_wildcard() {
+        assert!(PermissionChecker::matches_pattern(
+            "foo.*.baz",
+            "foo.bar.baz"
+        ));
+        assert!(PermissionChecker::matches_pattern(
+            "foo.*.baz",
+            "foo.qux.baz"
+        ));
+        assert!(!PermissionChecker::matches_pattern(
+            "foo.*.baz",
+            "foo.bar.qux"
+        ));
+        assert!(!PermissionChecker::matches_pattern(
+            "foo.*.baz",
+            "foo.bar.baz.extra"
+        ));
+    }
+
+    #[test]
+    fn test_multi_wildcard() {
+        assert!(PermissionChecker::matches_pattern("foo.>", "foo.bar"));
+        assert!(PermissionChecker::matches_pattern("foo.>", "foo.bar.baz"));
+        assert!(PermissionChecker::matches_pattern(
+            "foo.>",
+            "foo.bar.baz.qux"
+        ));
+        assert!(!PermissionChecker::matches_pattern("foo.>", "bar.baz"));
+    }
+
+    #[test]
+    fn test_full_wildcard() {
+        assert!(PermissionChecker::matches_pattern(">", "foo"));
+        assert!(PermissionChecker::matches_pattern(">", "foo.bar"));
+        assert!(PermissionChecker::matches_pattern(">", "foo.bar.baz"));
+    }
+
+    #[test]
+    fn test_mixed_wildcards() {
+        assert!(PermissionChecker::matches_pattern("*.bar.>", "foo.bar.baz"));
+        assert!(PermissionChecker::matches_pattern(
+            "*.bar.>",
+            "qux.bar.baz.extra"
+        ));
+        assert!(!PermissionChecker::matches_pattern(
+            "*.bar.>",
+            "foo.qux.baz"
+        ));
+    }
+
+    #[test]
+    fn test_empty_patterns() {
+        assert!(PermissionChecker::matches_pattern("", ""));
+        assert!(!PermissionChecker::matches_pattern("foo", ""));
+        assert!(!PermissionChecker::matches_pattern("", "foo"));
+    }
+
+    #[test]
+    fn test_single_token() {
+        assert!(PermissionChecker::matches_pattern("foo", "foo"));
+        assert!(!PermissionChecker::matches_pattern("foo", "bar"));
+        assert!(PermissionChecker::matches_pattern("*", "foo"));
+        assert!(PermissionChecker::matches_pattern(">", "foo"));
+    }
+
+    // ============ PermissionChecker Tests ============
+
     #[test]
     fn test_exact_match() {
         let claims = create_claims(vec!["subscribe"], vec!["messages"], vec![]);
@@ -177,6 +334,15 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_internal_subjects_never_allowed() {
+        let claims = create_claims(vec!["subscribe"], vec![], vec![]);
+        assert!(!PermissionChecker::is_subject_allowed(
+            &claims,
+            "_MOTTOMESH.presence"
+        ));
+    }
+
     #[test]
     fn test_has_permission() {
         let claims = create_claims(vec!["publish", "subscribe"], vec![], vec![]);
@@ -193,4 +359,70 @@ mod tests {
             Permission::Request
         ));
     }
+
+    #[test]
+    fn test_kv_patterns() {
+        let mut claims = create_claims(vec!["kv_read"], vec![], vec![]);
+        assert!(PermissionChecker::is_kv_allowed(&claims, "config", "theme"));
+
+        claims.kv_allowed = vec!["config.>".to_string(), "users.alice.*".to_string()];
+        claims.kv_deny = vec!["config.secrets.>".to_string()];
+        assert!(PermissionChecker::is_kv_allowed(&claims, "config", "theme"));
+        assert!(PermissionChecker::is_kv_allowed(
+            &claims,
+            "users",
+            "alice.prefs"
+        ));
+        assert!(!PermissionChecker::is_kv_allowed(
+            &claims,
+            "users",
+            "bob.prefs"
+        ));
+        assert!(!PermissionChecker::is_kv_allowed(
+            &claims,
+            "config",
+            "secrets.db"
+        ));
+
+        assert!(PermissionChecker::can_access_kv(
+            &claims,
+            Permission::KvRead,
+            "config",
+            "theme"
+        ));
+        assert!(!PermissionChecker::can_access_kv(
+            &claims,
+            Permission::KvWrite,
+            "config",
+            "theme"
+        ));
+    }
+
+    #[test]
+    fn test_object_patterns() {
+        let mut claims = create_claims(vec!["object_read"], vec![], vec![]);
+        claims.object_allowed = vec!["uploads.alice.>".to_string()];
+        assert!(PermissionChecker::is_object_allowed(
+            &claims,
+            "uploads",
+            "alice.photo.jpg"
+        ));
+        assert!(!PermissionChecker::is_object_allowed(
+            &claims,
+            "uploads",
+            "bob.photo.jpg"
+        ));
+        // Key-value patterns do not apply to objects
+        assert!(PermissionChecker::is_kv_allowed(
+            &claims,
+            "uploads",
+            "bob.photo.jpg"
+        ));
+        assert!(!PermissionChecker::can_access_object(
+            &claims,
+            Permission::ObjectWrite,
+            "uploads",
+            "alice.photo.jpg"
+        ));
+    }
 }
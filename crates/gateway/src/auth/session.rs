@@ -93,6 +93,11 @@ mod tests {
             permissions: vec!["publish".to_string(), "subscribe".to_string()],
             allowed_subjects: vec!["messages.*".to_string()],
             deny_subjects: vec![],
+            kv_allowed: vec![],
+            kv_deny: vec![],
+            object_allowed: vec![],
+            object_deny: vec![],
+            tenant: None,
         }
     }
 
@@ -271,6 +276,11 @@ mod tests {
             ],
             allowed_subjects: vec![">".to_string()],
             deny_subjects: vec!["admin.>".to_string()],
+            kv_allowed: vec![],
+            kv_deny: vec![],
+            object_allowed: vec![],
+            object_deny: vec![],
+            tenant: None,
         };
 
         let session = Session::new(claims);
@@ -0,0 +1,215 @@
+//! The message broker behind the gateway.
+//!
+//! Connection handlers reach their backend only through [`Broker`].
+//! [`NatsBridge`](super::NatsBridge) is the full implementation, with
+//! JetStream, key-value buckets, object stores and cross-instance presence.
+//! [`MemoryBroker`](super::MemoryBroker) keeps publish, subscribe and request
+//! within the process, for single-node deployments and tests. Features a
+//! broker does not provide fail with [`BridgeError::Unsupported`].
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::sync::{mpsc, watch};
+
+use super::consumer::AckHandle;
+use super::identity::Headers;
+use super::kv::KvValue;
+use super::mailbox::MailboxSender;
+use super::nats::{
+    BridgeError, Connectivity, NatsMessage, ResponseStream, StreamAck, SubscriptionEvent,
+};
+use super::objects::{ObjectDownload, ObjectUpload};
+use super::presence::Presence;
+use crate::protocol::{AckKind, DeliverPolicy, ReplayFrom};
+
+#[async_trait]
+pub trait Broker: Send + Sync {
+    /// Whether the backend is currently reachable
+    fn is_connected(&self) -> bool;
+
+    /// Follow the state of the connection to the backend
+    fn watch_connectivity(&self) -> watch::Receiver<Connectivity>;
+
+    /// Presence registry of the deployment
+    fn presence(&self) -> &Arc<Presence>;
+
+    /// Retained messages on subjects matching `subject`, tagged with
+    /// `subscription_id`
+    fn retained(&self, _subject: &str, _subscription_id: u64) -> Vec<NatsMessage> {
+        Vec::new()
+    }
+
+    /// Recorded messages on subjects matching `subject`, oldest first,
+    /// tagged with `subscription_id`
+    fn history(
+        &self,
+        _subject: &str,
+        _subscription_id: u64,
+        _from: &ReplayFrom,
+    ) -> Vec<NatsMessage> {
+        Vec::new()
+    }
+
+    /// Publish a message to a subject
+    async fn publish(
+        &self,
+        subject: &str,
+        headers: &Headers,
+        payload: Vec<u8>,
+    ) -> Result<(), BridgeError>;
+
+    /// Subscribe to a subject, which may contain wildcards, and queue
+    /// messages, tagged with the mailbox's subscription id, in the mailbox.
+    /// If the backend ends the subscription for good the mailbox is closed.
+    async fn subscribe(
+        &self,
+        subject: String,
+        mailbox: MailboxSender,
+    ) -> Result<SubscriptionHandle, BridgeError>;
+
+    /// Request-reply pattern
+    async fn request(
+        &self,
+        subject: &str,
+        headers: &Headers,
+        payload: Vec<u8>,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, BridgeError>;
+
+    /// Request with a streamed (multi-part) reply
+    async fn request_stream(
+        &self,
+        subject: &str,
+        headers: &Headers,
+        payload: Vec<u8>,
+    ) -> Result<ResponseStream, BridgeError>;
+
+    /// Publish a message to the durable stream capturing `subject` and wait
+    /// for it to be stored. A `msg_id` seen before within the stream's
+    /// duplicate window is not stored again; the ack then reports the
+    /// original sequence and sets `duplicate`.
+    async fn publish_durable(
+        &self,
+        _subject: &str,
+        _headers: &Headers,
+        _payload: Vec<u8>,
+        _msg_id: Option<String>,
+    ) -> Result<StreamAck, BridgeError> {
+        Err(BridgeError::Unsupported("Durable publish"))
+    }
+
+    /// Consume `stream` through the durable consumer `durable` of `user_id`,
+    /// creating it on first use, and forward its messages, tagged with
+    /// `subscription_id`, to the sender. Every delivery must be acknowledged
+    /// with [`Self::ack`] or it is redelivered.
+    #[allow(clippy::too_many_arguments)]
+    async fn subscribe_consumer(
+        &self,
+        _stream: &str,
+        _user_id: &str,
+        _durable: &str,
+        _filter_subject: String,
+        _deliver: &DeliverPolicy,
+        _subscription_id: u64,
+        _sender: mpsc::Sender<SubscriptionEvent>,
+    ) -> Result<SubscriptionHandle, BridgeError> {
+        Err(BridgeError::Unsupported("Durable consumers"))
+    }
+
+    /// Acknowledge, nak or mark in progress a consumer delivery
+    async fn ack(&self, _handle: &AckHandle, _kind: &AckKind) -> Result<(), BridgeError> {
+        Err(BridgeError::Unsupported("Durable consumers"))
+    }
+
+    /// Terminate a consumer delivery, so the server stops redelivering it
+    async fn term(&self, _handle: &AckHandle) -> Result<(), BridgeError> {
+        Err(BridgeError::Unsupported("Durable consumers"))
+    }
+
+    /// Read the current value of `key` in `bucket`
+    async fn kv_get(&self, _bucket: &str, _key: &str) -> Result<KvValue, BridgeError> {
+        Err(BridgeError::Unsupported("Key-value buckets"))
+    }
+
+    /// Set `key` in `bucket`, returning the revision of the new value
+    async fn kv_put(&self, _bucket: &str, _key: &str, _value: Vec<u8>) -> Result<u64, BridgeError> {
+        Err(BridgeError::Unsupported("Key-value buckets"))
+    }
+
+    /// Delete `key` from `bucket`, keeping its history
+    async fn kv_delete(&self, _bucket: &str, _key: &str) -> Result<(), BridgeError> {
+        Err(BridgeError::Unsupported("Key-value buckets"))
+    }
+
+    /// Watch the keys of `bucket` matching `key`, which may contain
+    /// wildcards. The current value of every matching key is sent first,
+    /// then each change as it happens, tagged with `subscription_id`.
+    async fn kv_watch(
+        &self,
+        _bucket: &str,
+        _key: &str,
+        _subscription_id: u64,
+        _sender: mpsc::Sender<SubscriptionEvent>,
+    ) -> Result<SubscriptionHandle, BridgeError> {
+        Err(BridgeError::Unsupported("Key-value buckets"))
+    }
+
+    /// Start uploading `size` bytes to the object `name` in `bucket`. The
+    /// object is only kept if the bytes hash to `sha256`.
+    async fn upload_object(
+        &self,
+        _bucket: &str,
+        _name: &str,
+        _size: u64,
+        _sha256: Vec<u8>,
+    ) -> Result<ObjectUpload, BridgeError> {
+        Err(BridgeError::Unsupported("Object stores"))
+    }
+
+    /// Start reading the object `name` in `bucket`
+    async fn download_object(
+        &self,
+        _bucket: &str,
+        _name: &str,
+    ) -> Result<ObjectDownload, BridgeError> {
+        Err(BridgeError::Unsupported("Object stores"))
+    }
+}
+
+/// Handle to cancel a subscription. Dropping it cancels the subscription too.
+pub struct SubscriptionHandle {
+    cancel: Cancel,
+}
+
+enum Cancel {
+    /// Stops a forwarding task of the subscription's own
+    Task(mpsc::Sender<()>),
+    /// Detaches the subscription when dropped
+    Guard { _guard: Box<dyn Send + Sync> },
+}
+
+impl SubscriptionHandle {
+    /// A subscription served by a task that stops when signalled
+    pub(super) fn task(cancel_tx: mpsc::Sender<()>) -> Self {
+        Self {
+            cancel: Cancel::Task(cancel_tx),
+        }
+    }
+
+    /// A subscription that ends when `guard` is dropped
+    pub(super) fn guard(guard: impl Send + Sync + 'static) -> Self {
+        Self {
+            cancel: Cancel::Guard {
+                _guard: Box::new(guard),
+            },
+        }
+    }
+
+    pub async fn unsubscribe(self) {
+        if let Cancel::Task(cancel_tx) = self.cancel {
+            let _ = cancel_tx.send(()).await;
+        }
+    }
+}
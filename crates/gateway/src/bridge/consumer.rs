@@ -0,0 +1,338 @@
+//! Durable JetStream consumers for at-least-once delivery.
+//!
+//! A client subscribing to a consumer gets the messages of a stream through
+//! a durable pull consumer with explicit acknowledgement. Each delivery
+//! carries its stream sequence, and stays unacknowledged until the client
+//! acks, naks or marks it in progress; anything not acked in time is
+//! redelivered, even to a later connection using the same durable name.
+//!
+//! Durable names are per user: the consumer created on the server is named
+//! after a digest of the user id and the name the client asked for, so two
+//! users picking the same name never share progress. Each user holds a
+//! limited number of consumers per stream, and the server removes those
+//! left unread for the configured inactivity threshold.
+
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_nats::jetstream::{self, consumer::pull};
+use async_nats::subject::Subject;
+use futures::StreamExt;
+use ring::digest;
+use tokio::sync::{mpsc, watch};
+use tracing::{debug, info, warn};
+
+use super::nats::{
+    BridgeError, Connectivity, PayloadCompression, SubscriptionEvent, decode_payload, reconnected,
+};
+use crate::protocol::{AckKind, DeliverPolicy};
+
+/// A message delivered by a durable consumer
+#[derive(Debug, Clone)]
+pub struct ConsumerDelivery {
+    /// Gateway subscription the message was received on
+    pub subscription_id: u64,
+    pub subject: String,
+    pub payload: Vec<u8>,
+    /// Position of the message in its stream
+    pub stream_seq: u64,
+    /// How many times the server has delivered the message, this time included
+    pub delivered: u32,
+    /// Where to acknowledge the message
+    pub ack: AckHandle,
+}
+
+/// Acknowledgement subject of a single consumer delivery
+#[derive(Debug, Clone)]
+pub struct AckHandle {
+    reply: Subject,
+}
+
+/// Limits on the durable consumers clients create on the server
+#[derive(Debug, Clone, Copy)]
+pub(super) struct ConsumerLimits {
+    /// Idle time after which the server removes a consumer
+    pub(super) inactive_threshold: Duration,
+    /// Consumers a user may hold on one stream
+    pub(super) per_user: usize,
+}
+
+impl Default for ConsumerLimits {
+    fn default() -> Self {
+        Self {
+            inactive_threshold: Duration::from_secs(24 * 60 * 60),
+            per_user: 16,
+        }
+    }
+}
+
+/// The durable consumer a subscription reads from, kept so it can be
+/// opened again
+pub(super) struct ConsumerSource {
+    pub(super) context: jetstream::Context,
+    pub(super) stream: String,
+    /// Server-side consumer name, see [`consumer_name`]
+    pub(super) name: String,
+    /// Prefix the names of all consumers of the same user share
+    pub(super) user_prefix: String,
+    pub(super) filter_subject: String,
+    pub(super) deliver: DeliverPolicy,
+    pub(super) limits: ConsumerLimits,
+}
+
+impl ConsumerSource {
+    /// Create (or reuse) the durable consumer and start pulling its messages
+    pub(super) async fn open(&self) -> Result<pull::Stream, BridgeError> {
+        let stream = self
+            .context
+            .get_stream(&self.stream)
+            .await
+            .map_err(|e| match e.kind() {
+                jetstream::context::GetStreamErrorKind::JetStream(error) if error.code() == 404 => {
+                    BridgeError::NotFound(format!("No stream named {:?}", self.stream))
+                }
+                _ => BridgeError::SubscribeFailed(e.to_string()),
+            })?;
+        self.check_quota(&stream).await?;
+
+        let consumer = stream
+            .get_or_create_consumer(
+                &self.name,
+                pull::Config {
+                    durable_name: Some(self.name.clone()),
+                    filter_subject: self.filter_subject.clone(),
+                    deliver_policy: deliver_policy(&self.deliver),
+                    ack_policy: jetstream::consumer::AckPolicy::Explicit,
+                    inactive_threshold: self.limits.inactive_threshold,
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|e| BridgeError::SubscribeFailed(e.to_string()))?;
+
+        consumer
+            .messages()
+            .await
+            .map_err(|e| BridgeError::SubscribeFailed(e.to_string()))
+    }
+
+    /// Refuse to create a consumer beyond the user's limit on `stream`.
+    /// Reusing one the user already has is always allowed.
+    async fn check_quota(&self, stream: &jetstream::stream::Stream) -> Result<(), BridgeError> {
+        let mut names = stream.consumer_names();
+        let mut held = 0;
+        while let Some(name) = names.next().await {
+            let name = name.map_err(|e| BridgeError::SubscribeFailed(e.to_string()))?;
+            if name == self.name {
+                return Ok(());
+            }
+            if name.starts_with(&self.user_prefix) {
+                held += 1;
+            }
+        }
+        if held >= self.limits.per_user {
+            return Err(BridgeError::QuotaExceeded(format!(
+                "At most {} durable consumers per user on stream {:?}",
+                self.limits.per_user, self.stream
+            )));
+        }
+        Ok(())
+    }
+
+    /// Open the consumer again once NATS is reachable
+    async fn reopen(
+        &self,
+        connectivity: &mut watch::Receiver<Connectivity>,
+    ) -> Option<pull::Stream> {
+        if !reconnected(connectivity).await {
+            return None;
+        }
+        self.open()
+            .await
+            .inspect_err(|e| warn!("Failed to reopen consumer {}: {}", self.name, e))
+            .ok()
+    }
+}
+
+/// Forward consumer messages, tagged with `subscription_id`, to the sender
+/// until cancelled. If the server ends the consumer it is opened again once
+/// NATS is reachable; only if that fails is a final
+/// [`SubscriptionEvent::Closed`] sent. Unacked messages are redelivered to
+/// the reopened consumer.
+pub(super) async fn forward(
+    mut messages: pull::Stream,
+    source: ConsumerSource,
+    subscription_id: u64,
+    sender: mpsc::Sender<SubscriptionEvent>,
+    rules: Arc<PayloadCompression>,
+    mut connectivity: watch::Receiver<Connectivity>,
+    mut cancel_rx: mpsc::Receiver<()>,
+) {
+    let name = &source.name;
+    let mut reopened = false;
+    loop {
+        tokio::select! {
+            msg = messages.next() => {
+                let msg = match msg {
+                    Some(Ok(msg)) => msg,
+                    // Missed heartbeats and the like; the stream carries on
+                    Some(Err(e)) => {
+                        warn!("Consumer {} reported: {}", name, e);
+                        continue;
+                    }
+                    // Reopened once per message received, so a consumer
+                    // that keeps ending is given up on
+                    None if !reopened => {
+                        reopened = true;
+                        let renewed = tokio::select! {
+                            renewed = source.reopen(&mut connectivity) => renewed,
+                            _ = cancel_rx.recv() => {
+                                debug!("Consumer subscription cancelled for {}", name);
+                                break;
+                            }
+                        };
+                        match renewed {
+                            Some(renewed) => {
+                                info!("Reopened consumer {}", name);
+                                messages = renewed;
+                                continue;
+                            }
+                            None => {
+                                debug!("Consumer {} ended", name);
+                                let _ = sender.send(SubscriptionEvent::Closed { subscription_id }).await;
+                                break;
+                            }
+                        }
+                    }
+                    None => {
+                        debug!("Consumer {} ended", name);
+                        let _ = sender.send(SubscriptionEvent::Closed { subscription_id }).await;
+                        break;
+                    }
+                };
+                reopened = false;
+                let Some(delivery) = delivery(&rules, msg, subscription_id).await else {
+                    continue;
+                };
+                if sender.send(SubscriptionEvent::Consumer(delivery)).await.is_err() {
+                    debug!("Subscription channel closed for consumer {}", name);
+                    break;
+                }
+            }
+            _ = cancel_rx.recv() => {
+                debug!("Consumer subscription cancelled for {}", name);
+                break;
+            }
+        }
+    }
+}
+
+/// Convert a consumer message for delivery. Messages that cannot be
+/// delivered are terminated so the server stops redelivering them.
+async fn delivery(
+    rules: &PayloadCompression,
+    msg: jetstream::Message,
+    subscription_id: u64,
+) -> Option<ConsumerDelivery> {
+    let (stream_seq, delivered) = match msg.info() {
+        Ok(info) => (info.stream_sequence, info.delivered),
+        Err(e) => {
+            warn!("Dropping consumer message without metadata: {}", e);
+            return None;
+        }
+    };
+    let reply = msg.reply.clone()?;
+
+    match decode_payload(rules, &msg.subject, &msg.payload) {
+        Ok(payload) => Some(ConsumerDelivery {
+            subscription_id,
+            subject: msg.subject.to_string(),
+            payload,
+            stream_seq,
+            delivered: u32::try_from(delivered).unwrap_or(u32::MAX),
+            ack: AckHandle { reply },
+        }),
+        Err(e) => {
+            warn!(
+                "Terminating message {} on {}: {}",
+                stream_seq, msg.subject, e
+            );
+            let _ = msg.ack_with(jetstream::AckKind::Term).await;
+            None
+        }
+    }
+}
+
+/// Acknowledge a delivery as the client asked
+pub(super) async fn ack(
+    client: &async_nats::Client,
+    handle: &AckHandle,
+    kind: &AckKind,
+) -> Result<(), BridgeError> {
+    let kind = match kind {
+        AckKind::Ack => jetstream::AckKind::Ack,
+        AckKind::Nak => jetstream::AckKind::Nak(None),
+        AckKind::InProgress => jetstream::AckKind::Progress,
+    };
+    settle(client, handle, kind).await
+}
+
+/// Terminate a delivery the client is not to receive
+pub(super) async fn term(
+    client: &async_nats::Client,
+    handle: &AckHandle,
+) -> Result<(), BridgeError> {
+    settle(client, handle, jetstream::AckKind::Term).await
+}
+
+async fn settle(
+    client: &async_nats::Client,
+    handle: &AckHandle,
+    kind: jetstream::AckKind,
+) -> Result<(), BridgeError> {
+    client
+        .publish(handle.reply.clone(), kind.into())
+        .await
+        .map_err(|e| BridgeError::PublishFailed(e.to_string()))
+}
+
+/// Server-side name of the durable consumer `durable` of `user_id`
+pub(super) fn consumer_name(user_id: &str, durable: &str) -> String {
+    let hash = digest::digest(&digest::SHA256, user_id.as_bytes());
+    let mut name = String::with_capacity(17 + durable.len());
+    for b in &hash.as_ref()[..8] {
+        let _ = write!(name, "{:02x}", b);
+    }
+    name.push('-');
+    name.push_str(durable);
+    name
+}
+
+fn deliver_policy(deliver: &DeliverPolicy) -> jetstream::consumer::DeliverPolicy {
+    match deliver {
+        DeliverPolicy::All => jetstream::consumer::DeliverPolicy::All,
+        DeliverPolicy::New => jetstream::consumer::DeliverPolicy::New,
+        DeliverPolicy::Last => jetstream::consumer::DeliverPolicy::Last,
+        DeliverPolicy::LastPerSubject => jetstream::consumer::DeliverPolicy::LastPerSubject,
+        DeliverPolicy::ByStartSequence { start_seq } => {
+            jetstream::consumer::DeliverPolicy::ByStartSequence {
+                start_sequence: *start_seq,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consumer_names_are_per_user() {
+        let name = consumer_name("alice", "orders");
+        assert_eq!(name.len(), 16 + 1 + "orders".len());
+        assert!(name.ends_with("-orders"));
+        assert_eq!(name, consumer_name("alice", "orders"));
+        assert_ne!(name, consumer_name("bob", "orders"));
+    }
+}
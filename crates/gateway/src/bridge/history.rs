@@ -0,0 +1,251 @@
+//! Bounded message history for replay on subscribe.
+//!
+//! For each subject pattern with history, the gateway keeps its own NATS
+//! subscription and records the most recent messages of every concrete
+//! subject matching it, up to a fixed number per subject and a fixed number
+//! of subjects, dropping the least recently updated subject first. A subscription
+//! can ask for the last N recorded messages or for those recorded since a
+//! point in time, so a client that lost its connection for a moment can
+//! catch up on what it missed.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_nats::Client;
+use futures::StreamExt;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use super::nats::{BridgeError, INTERNAL_SUBJECT_PREFIX, PayloadCompression, decode_payload};
+use super::subjects::SubjectMap;
+use crate::auth::PermissionChecker;
+use crate::protocol::ReplayFrom;
+
+/// A recorded message
+#[derive(Debug, Clone)]
+struct Entry {
+    /// Arrival order across all subjects
+    seq: u64,
+    /// Arrival time, milliseconds since the Unix epoch
+    unix_ms: u64,
+    payload: Vec<u8>,
+}
+
+#[derive(Default)]
+struct State {
+    subjects: SubjectMap<VecDeque<Entry>>,
+    next_seq: u64,
+    /// Messages kept per subject
+    length: usize,
+}
+
+impl State {
+    fn record(&mut self, subject: String, unix_ms: u64, payload: Vec<u8>) {
+        self.next_seq += 1;
+        let entries = self.subjects.update(subject, VecDeque::new);
+        if entries.len() >= self.length {
+            entries.pop_front();
+        }
+        entries.push_back(Entry {
+            seq: self.next_seq,
+            unix_ms,
+            payload,
+        });
+    }
+
+    /// Recorded messages on subjects matching `pattern`, oldest first
+    fn replay(&self, pattern: &str, from: &ReplayFrom) -> Vec<(String, Vec<u8>)> {
+        let mut matching: Vec<(&String, &Entry)> = self
+            .subjects
+            .iter()
+            .filter(|(subject, _)| PermissionChecker::matches_pattern(pattern, subject))
+            .flat_map(|(subject, entries)| entries.iter().map(move |entry| (subject, entry)))
+            .filter(|(_, entry)| match from {
+                ReplayFrom::Last { .. } => true,
+                ReplayFrom::Since { unix_ms } => entry.unix_ms >= *unix_ms,
+            })
+            .collect();
+        matching.sort_by_key(|(_, entry)| entry.seq);
+
+        if let ReplayFrom::Last { count } = from {
+            let skip = matching.len().saturating_sub(*count as usize);
+            matching.drain(..skip);
+        }
+
+        matching
+            .into_iter()
+            .map(|(subject, entry)| (subject.clone(), entry.payload.clone()))
+            .collect()
+    }
+}
+
+/// Recent messages of subjects with history, decoded for delivery
+#[derive(Default)]
+pub(super) struct MessageHistory {
+    state: Arc<Mutex<State>>,
+    /// Subscription tasks feeding `state`, stopped when the history is dropped
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl MessageHistory {
+    /// Subscribe to every pattern with history and start recording the last
+    /// `length` messages of up to `max_subjects` subjects
+    pub(super) async fn start(
+        client: &Client,
+        patterns: Vec<String>,
+        length: usize,
+        max_subjects: usize,
+        rules: Arc<PayloadCompression>,
+    ) -> Result<Self, BridgeError> {
+        let state = Arc::new(Mutex::new(State {
+            subjects: SubjectMap::new(max_subjects),
+            length,
+            ..State::default()
+        }));
+        let mut tasks = Vec::with_capacity(patterns.len());
+
+        for (i, pattern) in patterns.iter().enumerate() {
+            let subscriber = client
+                .subscribe(pattern.clone())
+                .await
+                .map_err(|e| BridgeError::SubscribeFailed(e.to_string()))?;
+            // A subject matching several patterns is recorded once, by the first
+            let earlier = patterns[..i].to_vec();
+            tasks.push(tokio::spawn(record(
+                subscriber,
+                earlier,
+                state.clone(),
+                rules.clone(),
+            )));
+        }
+
+        Ok(Self { state, tasks })
+    }
+
+    /// Recorded messages on subjects matching `pattern`, oldest first
+    pub(super) fn replay(&self, pattern: &str, from: &ReplayFrom) -> Vec<(String, Vec<u8>)> {
+        self.state.lock().unwrap().replay(pattern, from)
+    }
+}
+
+impl Drop for MessageHistory {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+async fn record(
+    mut subscriber: async_nats::Subscriber,
+    earlier: Vec<String>,
+    state: Arc<Mutex<State>>,
+    rules: Arc<PayloadCompression>,
+) {
+    while let Some(msg) = subscriber.next().await {
+        if msg.subject.starts_with(INTERNAL_SUBJECT_PREFIX)
+            || earlier
+                .iter()
+                .any(|pattern| PermissionChecker::matches_pattern(pattern, &msg.subject))
+        {
+            continue;
+        }
+        let subject = msg.subject.to_string();
+        match decode_payload(&rules, &subject, &msg.payload) {
+            Ok(payload) => state
+                .lock()
+                .unwrap()
+                .record(subject, now_unix_ms(), payload),
+            Err(e) => warn!("Not recording message on {}: {}", subject, e),
+        }
+    }
+    debug!("History subscription ended");
+}
+
+fn now_unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(length: usize) -> State {
+        State {
+            subjects: SubjectMap::new(10),
+            length,
+            ..State::default()
+        }
+    }
+
+    fn payloads(replayed: Vec<(String, Vec<u8>)>) -> Vec<Vec<u8>> {
+        replayed.into_iter().map(|(_, payload)| payload).collect()
+    }
+
+    #[test]
+    fn test_keeps_last_messages_per_subject() {
+        let mut state = state(2);
+        for n in 1..=3 {
+            state.record("ticks.a".to_string(), n, vec![n as u8]);
+        }
+        state.record("ticks.b".to_string(), 4, vec![4]);
+
+        let replayed = state.replay("ticks.a", &ReplayFrom::Last { count: 10 });
+        assert_eq!(payloads(replayed), vec![vec![2], vec![3]]);
+    }
+
+    #[test]
+    fn test_replay_interleaves_subjects_in_arrival_order() {
+        let mut state = state(10);
+        state.record("ticks.a".to_string(), 1, vec![1]);
+        state.record("ticks.b".to_string(), 2, vec![2]);
+        state.record("ticks.a".to_string(), 3, vec![3]);
+
+        let replayed = state.replay("ticks.*", &ReplayFrom::Last { count: 2 });
+        assert_eq!(
+            replayed,
+            vec![
+                ("ticks.b".to_string(), vec![2]),
+                ("ticks.a".to_string(), vec![3]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replay_since_time() {
+        let mut state = state(10);
+        state.record("ticks.a".to_string(), 100, vec![1]);
+        state.record("ticks.a".to_string(), 200, vec![2]);
+        state.record("ticks.a".to_string(), 300, vec![3]);
+
+        let replayed = state.replay("ticks.a", &ReplayFrom::Since { unix_ms: 200 });
+        assert_eq!(payloads(replayed), vec![vec![2], vec![3]]);
+    }
+
+    #[test]
+    fn test_subjects_beyond_limit_evict_least_recent() {
+        let mut state = State {
+            subjects: SubjectMap::new(2),
+            length: 10,
+            ..State::default()
+        };
+        state.record("ticks.a".to_string(), 1, vec![1]);
+        state.record("ticks.b".to_string(), 2, vec![2]);
+        state.record("ticks.a".to_string(), 3, vec![3]);
+        state.record("ticks.c".to_string(), 4, vec![4]);
+
+        let replayed = state.replay("ticks.*", &ReplayFrom::Last { count: 10 });
+        assert_eq!(
+            replayed,
+            vec![
+                ("ticks.a".to_string(), vec![1]),
+                ("ticks.a".to_string(), vec![3]),
+                ("ticks.c".to_string(), vec![4]),
+            ]
+        );
+    }
+}
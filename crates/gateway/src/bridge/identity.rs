@@ -0,0 +1,225 @@
+//! Sender identity stamped on relayed messages.
+//!
+//! Every publish and request a client sends through the gateway reaches the
+//! broker with headers naming the user, session, tenant and client address
+//! it came from, and the gateway instance that relayed it. The gateway
+//! writes these headers itself: clients cannot set any header, so a value
+//! under one of these names always comes from a gateway.
+//!
+//! With a signing key configured, [`SIGNATURE_HEADER`] carries the hex
+//! HMAC-SHA256 of the subject, the other identity headers and the payload as
+//! the client sent it, so services sharing the key can verify the metadata
+//! was not forged by whoever else can publish to NATS. [`TIMESTAMP_HEADER`]
+//! lets them reject stale replays.
+
+use std::fmt::Write;
+use std::net::IpAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ring::hmac;
+
+/// User id (JWT subject) of the sender
+pub const USER_HEADER: &str = "Mottomesh-User";
+/// Gateway session of the sender
+pub const SESSION_HEADER: &str = "Mottomesh-Session";
+/// Tenant claimed in the sender's token, if any
+pub const TENANT_HEADER: &str = "Mottomesh-Tenant";
+/// Address the sender connected from
+pub const CLIENT_IP_HEADER: &str = "Mottomesh-Client-Ip";
+/// Gateway instance that relayed the message
+pub const GATEWAY_HEADER: &str = "Mottomesh-Gateway";
+/// When the gateway relayed the message, in Unix milliseconds
+pub const TIMESTAMP_HEADER: &str = "Mottomesh-Timestamp";
+/// HMAC-SHA256 over the other identity headers, subject and payload
+pub const SIGNATURE_HEADER: &str = "Mottomesh-Signature";
+
+/// Who a relayed message came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub user_id: String,
+    pub session_id: String,
+    pub tenant: Option<String>,
+    pub client_ip: Option<IpAddr>,
+    pub gateway: String,
+}
+
+impl Identity {
+    /// Headers for a message on `subject` carrying `payload`, signed if a
+    /// signer is given
+    pub fn headers(
+        &self,
+        subject: &str,
+        payload: &[u8],
+        signer: Option<&IdentitySigner>,
+    ) -> Headers {
+        let mut headers = Headers::default();
+        headers.insert(USER_HEADER, &self.user_id);
+        headers.insert(SESSION_HEADER, &self.session_id);
+        if let Some(tenant) = &self.tenant {
+            headers.insert(TENANT_HEADER, tenant);
+        }
+        if let Some(client_ip) = &self.client_ip {
+            headers.insert(CLIENT_IP_HEADER, &client_ip.to_string());
+        }
+        headers.insert(GATEWAY_HEADER, &self.gateway);
+        headers.insert(TIMESTAMP_HEADER, &now_unix_ms().to_string());
+
+        if let Some(signer) = signer {
+            let signature = signer.sign(subject, &headers, payload);
+            headers.insert(SIGNATURE_HEADER, &signature);
+        }
+        headers
+    }
+}
+
+/// Headers the gateway attaches to a message, in order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Headers(Vec<(&'static str, String)>);
+
+impl Headers {
+    /// Set `name`, replacing any earlier value. Header values are a single
+    /// line, so control characters are replaced.
+    pub fn insert(&mut self, name: &'static str, value: &str) {
+        let value: String = value
+            .chars()
+            .map(|c| if c.is_control() { '?' } else { c })
+            .collect();
+        match self.0.iter_mut().find(|(existing, _)| *existing == name) {
+            Some((_, existing)) => *existing = value,
+            None => self.0.push((name, value)),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(existing, _)| *existing == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, &str)> {
+        self.0.iter().map(|(name, value)| (*name, value.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Signs identity headers with the key shared with backend services
+pub struct IdentitySigner {
+    key: hmac::Key,
+}
+
+impl IdentitySigner {
+    pub fn new(key: &[u8]) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, key),
+        }
+    }
+
+    /// Hex HMAC-SHA256 of [`signing_input`]
+    pub fn sign(&self, subject: &str, headers: &Headers, payload: &[u8]) -> String {
+        let tag = hmac::sign(&self.key, &signing_input(subject, headers, payload));
+        tag.as_ref()
+            .iter()
+            .fold(String::with_capacity(64), |mut signature, b| {
+                let _ = write!(signature, "{:02x}", b);
+                signature
+            })
+    }
+}
+
+/// What the signature covers: the subject and the values of the user,
+/// session, tenant, client IP, gateway and timestamp headers, each followed
+/// by a newline (absent headers as empty lines), then the payload bytes
+pub fn signing_input(subject: &str, headers: &Headers, payload: &[u8]) -> Vec<u8> {
+    let mut input = Vec::with_capacity(256 + payload.len());
+    input.extend_from_slice(subject.as_bytes());
+    input.push(b'\n');
+    for name in [
+        USER_HEADER,
+        SESSION_HEADER,
+        TENANT_HEADER,
+        CLIENT_IP_HEADER,
+        GATEWAY_HEADER,
+        TIMESTAMP_HEADER,
+    ] {
+        input.extend_from_slice(headers.get(name).unwrap_or_default().as_bytes());
+        input.push(b'\n');
+    }
+    input.extend_from_slice(payload);
+    input
+}
+
+fn now_unix_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity() -> Identity {
+        Identity {
+            user_id: "user-1".to_string(),
+            session_id: "session-1".to_string(),
+            tenant: None,
+            client_ip: Some("203.0.113.7".parse().unwrap()),
+            gateway: "gateway-1".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_headers_name_the_sender() {
+        let headers = identity().headers("orders", b"{}", None);
+        assert_eq!(headers.get(USER_HEADER), Some("user-1"));
+        assert_eq!(headers.get(SESSION_HEADER), Some("session-1"));
+        assert_eq!(headers.get(TENANT_HEADER), None);
+        assert_eq!(headers.get(CLIENT_IP_HEADER), Some("203.0.113.7"));
+        assert_eq!(headers.get(GATEWAY_HEADER), Some("gateway-1"));
+        assert!(headers.get(TIMESTAMP_HEADER).is_some());
+        assert_eq!(headers.get(SIGNATURE_HEADER), None);
+    }
+
+    #[test]
+    fn test_insert_replaces_and_stays_on_one_line() {
+        let mut headers = Headers::default();
+        headers.insert(USER_HEADER, "mallory");
+        headers.insert(USER_HEADER, "alice\r\nMottomesh-Tenant: acme");
+        assert_eq!(headers.iter().count(), 1);
+        assert_eq!(
+            headers.get(USER_HEADER),
+            Some("alice??Mottomesh-Tenant: acme")
+        );
+    }
+
+    #[test]
+    fn test_signature_verifies_and_covers_the_message() {
+        let signer = IdentitySigner::new(b"shared-secret");
+        let mut identity = identity();
+        identity.tenant = Some("acme".to_string());
+        let headers = identity.headers("orders", b"{}", Some(&signer));
+        let signature = headers.get(SIGNATURE_HEADER).unwrap();
+        assert_eq!(signature.len(), 64);
+
+        // A service holding the key recomputes the same tag
+        let key = hmac::Key::new(hmac::HMAC_SHA256, b"shared-secret");
+        let tag: Vec<u8> = (0..signature.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&signature[i..i + 2], 16).unwrap())
+            .collect();
+        let input = signing_input("orders", &headers, b"{}");
+        assert!(hmac::verify(&key, &input, &tag).is_ok());
+
+        // Any change to the subject, payload or identity breaks it
+        assert!(hmac::verify(&key, &signing_input("payments", &headers, b"{}"), &tag).is_err());
+        assert!(hmac::verify(&key, &signing_input("orders", &headers, b"[]"), &tag).is_err());
+        let mut forged = headers.clone();
+        forged.insert(TENANT_HEADER, "globex");
+        assert!(hmac::verify(&key, &signing_input("orders", &forged, b"{}"), &tag).is_err());
+    }
+}
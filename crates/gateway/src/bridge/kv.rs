@@ -0,0 +1,190 @@
+//! JetStream key-value buckets.
+//!
+//! Clients read, write and watch keys of existing buckets; creating and
+//! configuring buckets is left to operators. Bucket handles are looked up
+//! once and cached, so each operation costs a single round trip.
+//!
+//! Values are stored as sent, without the per-subject compression applied
+//! to published messages.
+
+use async_nats::jetstream::{self, kv};
+use bytes::Bytes;
+use dashmap::DashMap;
+use futures::StreamExt;
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use super::nats::{BridgeError, SubscriptionEvent};
+use crate::protocol::KvOperation;
+
+/// Current value of a key
+#[derive(Debug, Clone, PartialEq)]
+pub struct KvValue {
+    /// `None` if the key was never set or has been deleted
+    pub value: Option<Vec<u8>>,
+    /// Revision of the last change, 0 if the key was never set
+    pub revision: u64,
+}
+
+/// A change to a watched key
+#[derive(Debug, Clone)]
+pub struct KvChange {
+    /// Gateway subscription the change was received on
+    pub subscription_id: u64,
+    pub bucket: String,
+    pub key: String,
+    /// New value, empty for deletes and purges
+    pub value: Vec<u8>,
+    pub revision: u64,
+    pub operation: KvOperation,
+}
+
+/// Cached handles of the buckets used so far
+pub(super) struct Buckets {
+    context: jetstream::Context,
+    stores: DashMap<String, kv::Store>,
+}
+
+impl Buckets {
+    pub(super) fn new(context: jetstream::Context) -> Self {
+        Self {
+            context,
+            stores: DashMap::new(),
+        }
+    }
+
+    async fn store(&self, bucket: &str) -> Result<kv::Store, BridgeError> {
+        if let Some(store) = self.stores.get(bucket) {
+            return Ok(store.clone());
+        }
+
+        let store = self
+            .context
+            .get_key_value(bucket)
+            .await
+            .map_err(|e| match e.kind() {
+                jetstream::context::KeyValueErrorKind::InvalidStoreName => {
+                    BridgeError::InvalidRequest(format!("Invalid bucket name {:?}", bucket))
+                }
+                jetstream::context::KeyValueErrorKind::GetBucket => {
+                    BridgeError::NotFound(format!("No bucket named {:?}", bucket))
+                }
+                _ => BridgeError::RequestFailed(e.to_string()),
+            })?;
+        self.stores.insert(bucket.to_string(), store.clone());
+        Ok(store)
+    }
+
+    pub(super) async fn get(&self, bucket: &str, key: &str) -> Result<KvValue, BridgeError> {
+        let entry = self
+            .store(bucket)
+            .await?
+            .entry(key)
+            .await
+            .map_err(|e| match e.kind() {
+                kv::EntryErrorKind::InvalidKey => invalid_key(key),
+                kv::EntryErrorKind::TimedOut => BridgeError::RequestTimeout,
+                _ => BridgeError::RequestFailed(e.to_string()),
+            })?;
+
+        Ok(match entry {
+            Some(entry) => KvValue {
+                value: (entry.operation == kv::Operation::Put).then(|| entry.value.to_vec()),
+                revision: entry.revision,
+            },
+            None => KvValue {
+                value: None,
+                revision: 0,
+            },
+        })
+    }
+
+    pub(super) async fn put(
+        &self,
+        bucket: &str,
+        key: &str,
+        value: Vec<u8>,
+    ) -> Result<u64, BridgeError> {
+        self.store(bucket)
+            .await?
+            .put(key, Bytes::from(value))
+            .await
+            .map_err(|e| match e.kind() {
+                kv::PutErrorKind::InvalidKey => invalid_key(key),
+                _ => BridgeError::PublishFailed(e.to_string()),
+            })
+    }
+
+    pub(super) async fn delete(&self, bucket: &str, key: &str) -> Result<(), BridgeError> {
+        self.store(bucket)
+            .await?
+            .delete(key)
+            .await
+            .map_err(|e| match e.kind() {
+                kv::DeleteErrorKind::InvalidKey => invalid_key(key),
+                kv::DeleteErrorKind::TimedOut => BridgeError::RequestTimeout,
+                _ => BridgeError::PublishFailed(e.to_string()),
+            })
+    }
+
+    pub(super) async fn watch(&self, bucket: &str, key: &str) -> Result<kv::Watch, BridgeError> {
+        self.store(bucket)
+            .await?
+            .watch_with_history(key)
+            .await
+            .map_err(|e| BridgeError::SubscribeFailed(e.to_string()))
+    }
+}
+
+/// Forward changes of a watch, tagged with `subscription_id`, to the sender
+/// until cancelled. If the server ends the watch a final
+/// [`SubscriptionEvent::Closed`] is sent.
+pub(super) async fn forward(
+    mut watch: kv::Watch,
+    subscription_id: u64,
+    sender: mpsc::Sender<SubscriptionEvent>,
+    mut cancel_rx: mpsc::Receiver<()>,
+) {
+    loop {
+        tokio::select! {
+            entry = watch.next() => {
+                let entry = match entry {
+                    Some(Ok(entry)) => entry,
+                    Some(Err(e)) => {
+                        warn!("Key-value watch {} reported: {}", subscription_id, e);
+                        continue;
+                    }
+                    None => {
+                        debug!("Key-value watch {} ended", subscription_id);
+                        let _ = sender.send(SubscriptionEvent::Closed { subscription_id }).await;
+                        break;
+                    }
+                };
+                let change = KvChange {
+                    subscription_id,
+                    bucket: entry.bucket,
+                    key: entry.key,
+                    value: entry.value.to_vec(),
+                    revision: entry.revision,
+                    operation: match entry.operation {
+                        kv::Operation::Put => KvOperation::Put,
+                        kv::Operation::Delete => KvOperation::Delete,
+                        kv::Operation::Purge => KvOperation::Purge,
+                    },
+                };
+                if sender.send(SubscriptionEvent::KvChange(change)).await.is_err() {
+                    debug!("Subscription channel closed for key-value watch {}", subscription_id);
+                    break;
+                }
+            }
+            _ = cancel_rx.recv() => {
+                debug!("Key-value watch {} cancelled", subscription_id);
+                break;
+            }
+        }
+    }
+}
+
+fn invalid_key(key: &str) -> BridgeError {
+    BridgeError::InvalidRequest(format!("Invalid key {:?}", key))
+}
@@ -0,0 +1,401 @@
+//! Per-subscription delivery queues.
+//!
+//! Messages for a core subscription wait in the subscription's own bounded
+//! mailbox until the connection handler takes them, so one subscription
+//! falling behind does not hold up the others. What happens to a message
+//! arriving at a full mailbox is the subscription's [`SlowConsumerPolicy`]:
+//!
+//! - `Block` waits for room, holding up the broker side. Nothing is lost;
+//!   the subscription's upstream is not shared, so only it waits.
+//! - `DropOldest` discards the oldest queued message.
+//! - `DropNewest` discards the message that just arrived.
+//! - `Conflate` keeps only the latest queued message per subject, replacing
+//!   an older one in place; if all subjects differ it drops the oldest.
+//! - `Disconnect` gives up on the client.
+//!
+//! Discarded messages are counted. Each message taken carries the number
+//! discarded since the one before, which the handler turns into a gap in
+//! the subscription's sequence numbers.
+//!
+//! The handler learns which mailboxes have something for it from a shared
+//! channel of subscription ids. A mailbox puts its id there when it stops
+//! being empty and again after each message taken while more are waiting,
+//! so busy subscriptions take turns.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{Notify, mpsc};
+use tracing::{debug, warn};
+
+use super::nats::NatsMessage;
+use crate::protocol::SlowConsumerPolicy;
+
+/// Create a mailbox for `subscription_id` holding up to `capacity`
+/// messages, announcing itself on `ready`
+pub fn mailbox(
+    subscription_id: u64,
+    capacity: usize,
+    policy: SlowConsumerPolicy,
+    ready: mpsc::UnboundedSender<u64>,
+) -> (MailboxSender, Mailbox) {
+    let shared = Arc::new(Shared {
+        subscription_id,
+        capacity: capacity.max(1),
+        policy,
+        state: Mutex::new(State::default()),
+        space: Notify::new(),
+        ready,
+    });
+    (MailboxSender(shared.clone()), Mailbox(shared))
+}
+
+/// What the handler takes out of a mailbox
+#[derive(Debug)]
+pub enum MailboxEvent {
+    /// The next message, with how many were discarded since the previous one
+    Message { message: NatsMessage, dropped: u64 },
+    /// The mailbox overflowed under the Disconnect policy
+    Overflowed,
+    /// The broker ended the subscription for good
+    Closed,
+}
+
+struct Shared {
+    subscription_id: u64,
+    capacity: usize,
+    policy: SlowConsumerPolicy,
+    state: Mutex<State>,
+    /// Signalled when a message is taken or the mailbox is dropped
+    space: Notify,
+    ready: mpsc::UnboundedSender<u64>,
+}
+
+#[derive(Default)]
+struct State {
+    queue: VecDeque<NatsMessage>,
+    /// Messages discarded since the last one taken
+    dropped: u64,
+    /// Messages discarded over the subscription's lifetime
+    total_dropped: u64,
+    overflowed: bool,
+    closed: bool,
+    /// The subscription id is waiting in the ready channel
+    signalled: bool,
+    /// The handler side is gone; anything sent is discarded
+    detached: bool,
+}
+
+impl State {
+    fn record_drop(&mut self) {
+        self.dropped += 1;
+        self.total_dropped += 1;
+    }
+
+    fn has_events(&self) -> bool {
+        !self.queue.is_empty() || self.overflowed || self.closed
+    }
+}
+
+impl Shared {
+    /// Announce the mailbox to the handler unless it already was
+    fn signal(&self, state: &mut State) {
+        if !state.signalled {
+            state.signalled = true;
+            let _ = self.ready.send(self.subscription_id);
+        }
+    }
+
+    /// Queue a message, making room as the policy says if full
+    fn enqueue(&self, state: &mut State, msg: NatsMessage) {
+        if state.detached || state.overflowed {
+            return;
+        }
+
+        if matches!(self.policy, SlowConsumerPolicy::Conflate)
+            && let Some(queued) = state.queue.iter_mut().find(|q| q.subject == msg.subject)
+        {
+            *queued = msg;
+            state.record_drop();
+            return;
+        }
+
+        if state.queue.len() >= self.capacity {
+            if state.total_dropped == 0 {
+                warn!(
+                    "Subscription {} is not keeping up ({:?})",
+                    self.subscription_id, self.policy
+                );
+            }
+            match self.policy {
+                SlowConsumerPolicy::DropOldest | SlowConsumerPolicy::Conflate => {
+                    state.queue.pop_front();
+                    state.record_drop();
+                }
+                SlowConsumerPolicy::DropNewest | SlowConsumerPolicy::Block => {
+                    state.record_drop();
+                    return;
+                }
+                SlowConsumerPolicy::Disconnect => {
+                    state.queue.clear();
+                    state.overflowed = true;
+                    self.signal(state);
+                    return;
+                }
+            }
+        }
+
+        state.queue.push_back(msg);
+        self.signal(state);
+    }
+}
+
+/// Broker side of a mailbox
+#[derive(Clone)]
+pub struct MailboxSender(Arc<Shared>);
+
+impl MailboxSender {
+    pub fn subscription_id(&self) -> u64 {
+        self.0.subscription_id
+    }
+
+    /// Whether the subscription waits for room rather than losing messages
+    pub fn blocks(&self) -> bool {
+        matches!(self.0.policy, SlowConsumerPolicy::Block)
+    }
+
+    /// Queue a message for the subscription. Under the Block policy this
+    /// waits while the mailbox is full.
+    pub async fn send(&self, msg: NatsMessage) {
+        loop {
+            let space = self.0.space.notified();
+            tokio::pin!(space);
+            // Register before looking, so a message taken in between wakes us
+            space.as_mut().enable();
+            {
+                let mut state = self.0.state.lock().unwrap();
+                let full = state.queue.len() >= self.0.capacity;
+                if !matches!(self.0.policy, SlowConsumerPolicy::Block) || !full || state.detached {
+                    self.0.enqueue(&mut state, msg);
+                    return;
+                }
+            }
+            space.await;
+        }
+    }
+
+    /// Queue a message without waiting. A full mailbox under the Block
+    /// policy discards it.
+    pub fn offer(&self, msg: NatsMessage) {
+        let mut state = self.0.state.lock().unwrap();
+        self.0.enqueue(&mut state, msg);
+    }
+
+    /// End the subscription once the queued messages are taken
+    pub fn close(&self) {
+        let mut state = self.0.state.lock().unwrap();
+        if !state.detached {
+            state.closed = true;
+            self.0.signal(&mut state);
+        }
+    }
+}
+
+/// Handler side of a mailbox. Dropping it discards anything still queued
+/// and releases a broker waiting for room.
+pub struct Mailbox(Arc<Shared>);
+
+impl Mailbox {
+    /// Take the next event, if any. While more are waiting the mailbox
+    /// announces itself again.
+    pub fn take(&self) -> Option<MailboxEvent> {
+        let mut state = self.0.state.lock().unwrap();
+        let event = if std::mem::take(&mut state.overflowed) {
+            Some(MailboxEvent::Overflowed)
+        } else if let Some(message) = state.queue.pop_front() {
+            let dropped = std::mem::take(&mut state.dropped);
+            Some(MailboxEvent::Message { message, dropped })
+        } else if std::mem::take(&mut state.closed) {
+            Some(MailboxEvent::Closed)
+        } else {
+            None
+        };
+
+        if state.has_events() {
+            let _ = self.0.ready.send(self.0.subscription_id);
+        } else {
+            state.signalled = false;
+        }
+        drop(state);
+
+        if matches!(event, Some(MailboxEvent::Message { .. })) {
+            self.0.space.notify_one();
+        }
+        event
+    }
+
+    /// Messages discarded over the subscription's lifetime
+    pub fn total_dropped(&self) -> u64 {
+        self.0.state.lock().unwrap().total_dropped
+    }
+}
+
+impl Drop for Mailbox {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+        state.detached = true;
+        state.queue.clear();
+        if state.total_dropped > 0 {
+            debug!(
+                "Subscription {} dropped {} messages",
+                self.0.subscription_id, state.total_dropped
+            );
+        }
+        drop(state);
+        self.0.space.notify_one();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::protocol::MessageOrigin;
+
+    fn message(subject: &str, payload: &[u8]) -> NatsMessage {
+        NatsMessage {
+            subscription_id: 1,
+            subject: subject.to_string(),
+            payload: payload.to_vec(),
+            origin: MessageOrigin::Live,
+        }
+    }
+
+    fn setup(
+        capacity: usize,
+        policy: SlowConsumerPolicy,
+    ) -> (MailboxSender, Mailbox, mpsc::UnboundedReceiver<u64>) {
+        let (ready_tx, ready_rx) = mpsc::unbounded_channel();
+        let (sender, mailbox) = mailbox(1, capacity, policy, ready_tx);
+        (sender, mailbox, ready_rx)
+    }
+
+    /// Payloads taken until the mailbox is empty, with the drop counts
+    fn drain(mailbox: &Mailbox) -> Vec<(Vec<u8>, u64)> {
+        let mut taken = Vec::new();
+        while let Some(event) = mailbox.take() {
+            match event {
+                MailboxEvent::Message { message, dropped } => {
+                    taken.push((message.payload, dropped))
+                }
+                other => panic!("Expected Message, got: {:?}", other),
+            }
+        }
+        taken
+    }
+
+    #[test]
+    fn test_drop_oldest_and_newest() {
+        let (sender, mailbox, _ready) = setup(2, SlowConsumerPolicy::DropOldest);
+        for payload in [b"1", b"2", b"3"] {
+            sender.offer(message("ticks", payload));
+        }
+        assert_eq!(
+            drain(&mailbox),
+            vec![(b"2".to_vec(), 1), (b"3".to_vec(), 0)]
+        );
+
+        let (sender, mailbox, _ready) = setup(2, SlowConsumerPolicy::DropNewest);
+        for payload in [b"1", b"2", b"3"] {
+            sender.offer(message("ticks", payload));
+        }
+        assert_eq!(
+            drain(&mailbox),
+            vec![(b"1".to_vec(), 1), (b"2".to_vec(), 0)]
+        );
+        assert_eq!(mailbox.total_dropped(), 1);
+    }
+
+    #[test]
+    fn test_conflate_keeps_latest_per_subject() {
+        let (sender, mailbox, _ready) = setup(8, SlowConsumerPolicy::Conflate);
+        sender.offer(message("prices.btc", b"100"));
+        sender.offer(message("prices.eth", b"10"));
+        sender.offer(message("prices.btc", b"101"));
+        sender.offer(message("prices.btc", b"102"));
+        assert_eq!(
+            drain(&mailbox),
+            vec![(b"102".to_vec(), 2), (b"10".to_vec(), 0)]
+        );
+        assert_eq!(mailbox.total_dropped(), 2);
+    }
+
+    #[test]
+    fn test_disconnect_reports_overflow_once() {
+        let (sender, mailbox, _ready) = setup(1, SlowConsumerPolicy::Disconnect);
+        sender.offer(message("chat", b"1"));
+        sender.offer(message("chat", b"2"));
+        sender.offer(message("chat", b"3"));
+        assert!(matches!(mailbox.take(), Some(MailboxEvent::Overflowed)));
+        assert!(mailbox.take().is_none());
+    }
+
+    #[test]
+    fn test_ready_announcements() {
+        let (sender, mailbox, mut ready) = setup(8, SlowConsumerPolicy::Block);
+        sender.offer(message("chat", b"1"));
+        sender.offer(message("chat", b"2"));
+        // Announced once while waiting to be taken
+        assert_eq!(ready.try_recv().ok(), Some(1));
+        assert!(ready.try_recv().is_err());
+
+        // And again after a take that leaves more behind
+        assert!(mailbox.take().is_some());
+        assert_eq!(ready.try_recv().ok(), Some(1));
+        assert!(mailbox.take().is_some());
+        assert!(ready.try_recv().is_err());
+
+        sender.close();
+        assert_eq!(ready.try_recv().ok(), Some(1));
+        assert!(matches!(mailbox.take(), Some(MailboxEvent::Closed)));
+        assert!(mailbox.take().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_block_waits_for_room() {
+        let (sender, mailbox, _ready) = setup(1, SlowConsumerPolicy::Block);
+        sender.send(message("chat", b"1")).await;
+
+        let blocked = tokio::spawn({
+            let sender = sender.clone();
+            async move { sender.send(message("chat", b"2")).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!blocked.is_finished());
+
+        assert_eq!(drain(&mailbox), vec![(b"1".to_vec(), 0)]);
+        tokio::time::timeout(Duration::from_secs(1), blocked)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(drain(&mailbox), vec![(b"2".to_vec(), 0)]);
+    }
+
+    #[tokio::test]
+    async fn test_dropping_mailbox_releases_blocked_sender() {
+        let (sender, mailbox, _ready) = setup(1, SlowConsumerPolicy::Block);
+        sender.send(message("chat", b"1")).await;
+
+        let blocked = tokio::spawn({
+            let sender = sender.clone();
+            async move { sender.send(message("chat", b"2")).await }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(mailbox);
+        tokio::time::timeout(Duration::from_secs(1), blocked)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+}
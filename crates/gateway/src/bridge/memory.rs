@@ -0,0 +1,460 @@
+//! In-process broker.
+//!
+//! Publish, subscribe and request without a server, for a gateway embedded
+//! in a single-node application and for tests. Subjects and wildcards follow
+//! NATS: `*` matches exactly one token and `>` one or more trailing tokens.
+//! Requests go to responders registered with [`MemoryBroker::serve`], not to
+//! subscribers, and carry the sender's identity headers. Subscribers get
+//! payloads alone.
+//!
+//! As with core NATS, delivery is at most once: a subscriber whose mailbox
+//! is full loses messages as its slow-consumer policy says. Publishing
+//! never waits, since a session publishing to its own full subscription
+//! would wait forever; Block subscriptions, which must never lose a
+//! message, are refused instead.
+//!
+//! Presence covers this instance only, and retained subjects, history,
+//! durable streams, key-value buckets and object stores are not provided.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::sync::{mpsc, watch};
+use tracing::debug;
+
+use super::broker::{Broker, SubscriptionHandle};
+use super::identity::Headers;
+use super::mailbox::MailboxSender;
+use super::nats::{BridgeError, Connectivity, NatsMessage, ResponseStream, StreamChunk};
+use super::presence::Presence;
+use crate::auth::PermissionChecker;
+use crate::protocol::MessageOrigin;
+
+/// Requests queued per responder
+const RESPONDER_BUFFER: usize = 64;
+
+/// Reply parts buffered per request
+const REPLY_BUFFER: usize = 16;
+
+/// Entries keyed by the subject pattern they were registered for
+struct Routes<T> {
+    by_key: HashMap<u64, (String, T)>,
+    next_key: u64,
+}
+
+impl<T> Default for Routes<T> {
+    fn default() -> Self {
+        Self {
+            by_key: HashMap::new(),
+            next_key: 0,
+        }
+    }
+}
+
+impl<T: Clone> Routes<T> {
+    fn insert(&mut self, pattern: String, value: T) -> u64 {
+        self.next_key += 1;
+        self.by_key.insert(self.next_key, (pattern, value));
+        self.next_key
+    }
+
+    /// Entries whose pattern matches `subject`, oldest first
+    fn matching(&self, subject: &str) -> Vec<T> {
+        let mut matching: Vec<_> = self
+            .by_key
+            .iter()
+            .filter(|(_, (pattern, _))| PermissionChecker::matches_pattern(pattern, subject))
+            .map(|(key, (_, value))| (*key, value.clone()))
+            .collect();
+        matching.sort_by_key(|(key, _)| *key);
+        matching.into_iter().map(|(_, value)| value).collect()
+    }
+}
+
+/// Removes its route when dropped
+struct RouteGuard<T> {
+    routes: Weak<Mutex<Routes<T>>>,
+    key: u64,
+}
+
+impl<T> Drop for RouteGuard<T> {
+    fn drop(&mut self) {
+        if let Some(routes) = self.routes.upgrade() {
+            routes.lock().unwrap().by_key.remove(&self.key);
+        }
+    }
+}
+
+/// Broker keeping all traffic within the process
+pub struct MemoryBroker {
+    subscriptions: Arc<Mutex<Routes<MailboxSender>>>,
+    responders: Arc<Mutex<Routes<mpsc::Sender<MemoryRequest>>>>,
+    /// Spreads requests over the responders of a subject
+    next_responder: AtomicUsize,
+    presence: Arc<Presence>,
+    /// Always connected; kept so watchers never see the state go away
+    connectivity: watch::Sender<Connectivity>,
+}
+
+impl Default for MemoryBroker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryBroker {
+    pub fn new() -> Self {
+        Self {
+            subscriptions: Arc::default(),
+            responders: Arc::default(),
+            next_responder: AtomicUsize::new(0),
+            presence: Presence::local(),
+            connectivity: watch::Sender::new(Connectivity::Connected),
+        }
+    }
+
+    /// Answer requests on subjects matching `pattern` until the returned
+    /// responder is dropped. Responders of the same subject take turns.
+    pub fn serve(&self, pattern: &str) -> Responder {
+        let (sender, requests) = mpsc::channel(RESPONDER_BUFFER);
+        let key = self
+            .responders
+            .lock()
+            .unwrap()
+            .insert(pattern.to_string(), sender);
+        Responder {
+            requests,
+            _route: RouteGuard {
+                routes: Arc::downgrade(&self.responders),
+                key,
+            },
+        }
+    }
+
+    /// Hand a request to the next responder of `subject`
+    async fn dispatch(
+        &self,
+        subject: &str,
+        headers: &Headers,
+        payload: Vec<u8>,
+    ) -> Result<mpsc::Receiver<StreamChunk>, BridgeError> {
+        let responders = self.responders.lock().unwrap().matching(subject);
+        if responders.is_empty() {
+            return Err(BridgeError::NoResponders);
+        }
+        let turn = self.next_responder.fetch_add(1, Ordering::Relaxed);
+        let responder = &responders[turn % responders.len()];
+
+        let (replies, reply_rx) = mpsc::channel(REPLY_BUFFER);
+        let request = MemoryRequest {
+            subject: subject.to_string(),
+            headers: headers.clone(),
+            payload,
+            replies,
+        };
+        responder
+            .send(request)
+            .await
+            .map_err(|_| BridgeError::RequestFailed("responder stopped".to_string()))?;
+        Ok(reply_rx)
+    }
+}
+
+#[async_trait]
+impl Broker for MemoryBroker {
+    fn is_connected(&self) -> bool {
+        true
+    }
+
+    fn watch_connectivity(&self) -> watch::Receiver<Connectivity> {
+        self.connectivity.subscribe()
+    }
+
+    fn presence(&self) -> &Arc<Presence> {
+        &self.presence
+    }
+
+    async fn publish(
+        &self,
+        subject: &str,
+        _headers: &Headers,
+        payload: Vec<u8>,
+    ) -> Result<(), BridgeError> {
+        let subscriptions = self.subscriptions.lock().unwrap().matching(subject);
+        for mailbox in subscriptions {
+            mailbox.offer(NatsMessage {
+                subscription_id: mailbox.subscription_id(),
+                subject: subject.to_string(),
+                payload: payload.clone(),
+                origin: MessageOrigin::Live,
+            });
+        }
+        Ok(())
+    }
+
+    async fn subscribe(
+        &self,
+        subject: String,
+        mailbox: MailboxSender,
+    ) -> Result<SubscriptionHandle, BridgeError> {
+        if mailbox.blocks() {
+            return Err(BridgeError::Unsupported("The Block slow-consumer policy"));
+        }
+        debug!("Subscribing to {} in memory", subject);
+        let key = self.subscriptions.lock().unwrap().insert(subject, mailbox);
+        Ok(SubscriptionHandle::guard(RouteGuard {
+            routes: Arc::downgrade(&self.subscriptions),
+            key,
+        }))
+    }
+
+    async fn request(
+        &self,
+        subject: &str,
+        headers: &Headers,
+        payload: Vec<u8>,
+        timeout: Duration,
+    ) -> Result<Vec<u8>, BridgeError> {
+        tokio::time::timeout(timeout, async {
+            let mut replies = self.dispatch(subject, headers, payload).await?;
+            match replies.recv().await {
+                Some(StreamChunk::Data(payload)) => Ok(payload),
+                Some(StreamChunk::End) => Ok(Vec::new()),
+                None => Err(BridgeError::RequestFailed(
+                    "responder dropped the request".to_string(),
+                )),
+            }
+        })
+        .await
+        .map_err(|_| BridgeError::RequestTimeout)?
+    }
+
+    async fn request_stream(
+        &self,
+        subject: &str,
+        headers: &Headers,
+        payload: Vec<u8>,
+    ) -> Result<ResponseStream, BridgeError> {
+        let replies = self.dispatch(subject, headers, payload).await?;
+        Ok(ResponseStream::local(replies))
+    }
+}
+
+/// Requests for an in-process service, see [`MemoryBroker::serve`]
+pub struct Responder {
+    requests: mpsc::Receiver<MemoryRequest>,
+    _route: RouteGuard<mpsc::Sender<MemoryRequest>>,
+}
+
+impl Responder {
+    /// Wait for the next request
+    pub async fn next(&mut self) -> Option<MemoryRequest> {
+        self.requests.recv().await
+    }
+}
+
+/// A request waiting for its reply
+pub struct MemoryRequest {
+    pub subject: String,
+    /// Identity of the sender, as stamped by the gateway
+    pub headers: Headers,
+    pub payload: Vec<u8>,
+    replies: mpsc::Sender<StreamChunk>,
+}
+
+impl MemoryRequest {
+    /// Reply with a single payload
+    pub async fn respond(self, payload: Vec<u8>) {
+        if self.send_chunk(payload).await {
+            self.end().await;
+        }
+    }
+
+    /// Send one part of a streamed reply. Returns false once the requester
+    /// has gone.
+    pub async fn send_chunk(&self, payload: Vec<u8>) -> bool {
+        self.replies.send(StreamChunk::Data(payload)).await.is_ok()
+    }
+
+    /// Mark a streamed reply as complete
+    pub async fn end(self) {
+        let _ = self.replies.send(StreamChunk::End).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bridge::mailbox::{Mailbox, MailboxEvent, mailbox};
+    use crate::protocol::SlowConsumerPolicy;
+
+    fn subscriber(id: u64, capacity: usize) -> (MailboxSender, Mailbox) {
+        let (ready, _) = mpsc::unbounded_channel();
+        mailbox(id, capacity, SlowConsumerPolicy::DropNewest, ready)
+    }
+
+    fn next_message(mailbox: &Mailbox) -> Option<NatsMessage> {
+        match mailbox.take() {
+            Some(MailboxEvent::Message { message, .. }) => Some(message),
+            None => None,
+            other => panic!("Expected Message, got: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wildcard_delivery() {
+        let broker = MemoryBroker::new();
+        let (exact_tx, exact) = subscriber(1, 16);
+        let (single_tx, single) = subscriber(2, 16);
+        let (tail_tx, tail) = subscriber(3, 16);
+        let _exact = broker
+            .subscribe("prices.btc".to_string(), exact_tx)
+            .await
+            .unwrap();
+        let _single = broker
+            .subscribe("prices.*".to_string(), single_tx)
+            .await
+            .unwrap();
+        let _tail = broker
+            .subscribe("prices.>".to_string(), tail_tx)
+            .await
+            .unwrap();
+
+        broker
+            .publish("prices.btc", &Headers::default(), b"1".to_vec())
+            .await
+            .unwrap();
+        for (id, mailbox) in [(1, &exact), (2, &single), (3, &tail)] {
+            assert_eq!(next_message(mailbox).unwrap().subscription_id, id);
+        }
+
+        // Only `>` spans several tokens, and neither wildcard matches none
+        broker
+            .publish("prices.btc.usd", &Headers::default(), b"2".to_vec())
+            .await
+            .unwrap();
+        broker
+            .publish("prices", &Headers::default(), b"3".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(next_message(&tail).unwrap().subject, "prices.btc.usd");
+        assert!(next_message(&exact).is_none());
+        assert!(next_message(&single).is_none());
+        assert!(next_message(&tail).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_dropping_handle_unsubscribes() {
+        let broker = MemoryBroker::new();
+        let (sender, mailbox) = subscriber(1, 16);
+        let handle = broker
+            .subscribe("events".to_string(), sender)
+            .await
+            .unwrap();
+        drop(handle);
+
+        broker
+            .publish("events", &Headers::default(), b"lost".to_vec())
+            .await
+            .unwrap();
+        assert!(next_message(&mailbox).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_full_subscriber_misses_messages() {
+        let broker = MemoryBroker::new();
+        let (sender, mailbox) = subscriber(1, 1);
+        let _handle = broker
+            .subscribe("events".to_string(), sender)
+            .await
+            .unwrap();
+
+        broker
+            .publish("events", &Headers::default(), b"first".to_vec())
+            .await
+            .unwrap();
+        broker
+            .publish("events", &Headers::default(), b"second".to_vec())
+            .await
+            .unwrap();
+        assert_eq!(next_message(&mailbox).unwrap().payload, b"first");
+        assert!(next_message(&mailbox).is_none());
+        assert_eq!(mailbox.total_dropped(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_request_reply() {
+        let broker = Arc::new(MemoryBroker::new());
+        let timeout = Duration::from_secs(1);
+        assert!(matches!(
+            broker
+                .request("math.double", &Headers::default(), vec![2], timeout)
+                .await,
+            Err(BridgeError::NoResponders)
+        ));
+
+        let mut responder = broker.serve("math.*");
+        tokio::spawn(async move {
+            while let Some(request) = responder.next().await {
+                let doubled = request.payload.iter().map(|b| b * 2).collect();
+                request.respond(doubled).await;
+            }
+        });
+        assert_eq!(
+            broker
+                .request("math.double", &Headers::default(), vec![2], timeout)
+                .await
+                .unwrap(),
+            vec![4]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_request_stream() {
+        let broker = MemoryBroker::new();
+        let mut responder = broker.serve("feed");
+        tokio::spawn(async move {
+            let request = responder.next().await.unwrap();
+            assert!(request.send_chunk(b"a".to_vec()).await);
+            assert!(request.send_chunk(b"b".to_vec()).await);
+            request.end().await;
+        });
+
+        let timeout = Duration::from_secs(1);
+        let mut stream = broker
+            .request_stream("feed", &Headers::default(), Vec::new())
+            .await
+            .unwrap();
+        assert_eq!(
+            stream.next_chunk(timeout).await.unwrap(),
+            StreamChunk::Data(b"a".to_vec())
+        );
+        assert_eq!(
+            stream.next_chunk(timeout).await.unwrap(),
+            StreamChunk::Data(b"b".to_vec())
+        );
+        assert_eq!(stream.next_chunk(timeout).await.unwrap(), StreamChunk::End);
+        assert_eq!(stream.next_chunk(timeout).await.unwrap(), StreamChunk::End);
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_features() {
+        let broker = MemoryBroker::new();
+        let error = broker.kv_get("bucket", "key").await.unwrap_err();
+        assert!(matches!(error, BridgeError::Unsupported(_)));
+        assert_eq!(error.code(), crate::protocol::ErrorCode::BackendUnavailable);
+
+        // Publishing cannot wait for a full subscription, so Block is refused
+        let (ready, _) = mpsc::unbounded_channel();
+        let (sender, _mailbox) = mailbox(1, 1, SlowConsumerPolicy::Block, ready);
+        let error = broker
+            .subscribe("events".to_string(), sender)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(error, BridgeError::Unsupported(_)));
+    }
+}
@@ -1,3 +1,30 @@
+mod broker;
+mod consumer;
+mod history;
+mod identity;
+mod kv;
+mod mailbox;
+mod memory;
 mod nats;
+mod objects;
+mod presence;
+mod retained;
+mod shared;
+mod subjects;
 
-pub use nats::{NatsBridge, NatsMessage, SubscriptionHandle};
+pub use broker::{Broker, SubscriptionHandle};
+pub use consumer::{AckHandle, ConsumerDelivery};
+pub use identity::{
+    CLIENT_IP_HEADER, GATEWAY_HEADER, Headers, Identity, IdentitySigner, SESSION_HEADER,
+    SIGNATURE_HEADER, TENANT_HEADER, TIMESTAMP_HEADER, USER_HEADER, signing_input,
+};
+pub use kv::{KvChange, KvValue};
+pub use mailbox::{Mailbox, MailboxEvent, MailboxSender, mailbox};
+pub use memory::{MemoryBroker, MemoryRequest, Responder};
+pub use nats::{
+    Connectivity, INTERNAL_SUBJECT_PREFIX, NatsBridge, NatsMessage, ResponseStream,
+    STREAM_END_HEADER, StreamAck, StreamChunk, SubscriptionEvent,
+};
+pub use objects::{ObjectDownload, ObjectUpload, STAGING_PREFIX};
+pub use presence::{Presence, PresenceChange, PresenceWatch};
+pub use shared::SubscriptionStats;
@@ -1,123 +1,741 @@
+use std::sync::Arc;
 use std::time::Duration;
 
-use async_nats::Client;
+use async_nats::client::{Request, RequestErrorKind};
+use async_nats::{Client, ConnectOptions, Event, ServerAddr, StatusCode, Subscriber, jetstream};
+use async_trait::async_trait;
 use bytes::Bytes;
 use futures::StreamExt;
-use tokio::sync::mpsc;
-use tracing::{debug, info};
+use tokio::sync::{mpsc, watch};
+use tracing::{debug, info, warn};
+
+use super::broker::{Broker, SubscriptionHandle};
+use super::consumer::{self, AckHandle, ConsumerDelivery, ConsumerLimits, ConsumerSource};
+use super::history::MessageHistory;
+use super::identity::Headers;
+use super::kv::{self, Buckets, KvChange, KvValue};
+use super::mailbox::MailboxSender;
+use super::objects::{ObjectDownload, ObjectStores, ObjectUpload};
+use super::presence::Presence;
+use super::retained::RetainedCache;
+use super::shared::{SharedSubscriptions, SubscriptionStats};
+use crate::auth::PermissionChecker;
+use crate::config::{NatsAuth, NatsConfig, SubjectCompression};
+use crate::protocol::compression;
+use crate::protocol::{
+    AckKind, CompressionAlgorithm, DeliverPolicy, ErrorCode, MessageOrigin, ReplayFrom,
+};
+
+/// State of the gateway's connection to NATS
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+    Connected,
+    /// Lost; the client is reconnecting
+    Disconnected,
+    /// Closed for good, e.g. after running out of reconnect attempts
+    Closed,
+}
 
 /// Bridge to NATS messaging system
 pub struct NatsBridge {
     client: Client,
+    /// Follows the connection state through connection events
+    connectivity: watch::Receiver<Connectivity>,
+    /// Core subscriptions, shared between sessions by subject
+    subscriptions: SharedSubscriptions,
+    jetstream: jetstream::Context,
+    /// Per-subject compression of payloads stored in NATS
+    compression: Arc<PayloadCompression>,
+    presence: Arc<Presence>,
+    /// Last message of each retained subject
+    retained: RetainedCache,
+    /// Recent messages of subjects with history
+    history: MessageHistory,
+    /// Key-value buckets
+    buckets: Buckets,
+    /// Object stores for chunked transfers
+    objects: ObjectStores,
+    /// Limits on the durable consumers clients create
+    consumer_limits: ConsumerLimits,
 }
 
 impl NatsBridge {
-    /// Connect to NATS server
-    pub async fn connect(url: &str) -> Result<Self, BridgeError> {
-        info!("Connecting to NATS at {}", url);
-        let client = async_nats::connect(url)
+    /// Connect to NATS through the first seed server that answers
+    pub async fn connect(config: &NatsConfig) -> Result<Self, BridgeError> {
+        let servers = config
+            .urls
+            .iter()
+            .map(|url| {
+                url.parse::<ServerAddr>().map_err(|e| {
+                    BridgeError::ConnectionFailed(format!("Invalid NATS URL {:?}: {}", url, e))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if servers.is_empty() {
+            return Err(BridgeError::ConnectionFailed(
+                "No NATS servers configured".to_string(),
+            ));
+        }
+
+        info!("Connecting to NATS at {}", config.urls.join(", "));
+        let (connectivity_tx, connectivity) = watch::channel(Connectivity::Connected);
+        let connectivity_tx = Arc::new(connectivity_tx);
+        let client = connect_options(config)
+            .await?
+            .event_callback(move |event| {
+                let connectivity_tx = connectivity_tx.clone();
+                async move { track_connectivity(&connectivity_tx, event) }
+            })
+            .connect(servers)
             .await
             .map_err(|e| BridgeError::ConnectionFailed(e.to_string()))?;
 
         info!("Connected to NATS");
-        Ok(Self { client })
+        let presence = Presence::start(client.clone()).await?;
+        let jetstream = jetstream::new(client.clone());
+        Ok(Self {
+            buckets: Buckets::new(jetstream.clone()),
+            objects: ObjectStores::new(jetstream.clone()),
+            subscriptions: SharedSubscriptions::new(client.clone(), connectivity.clone()),
+            jetstream,
+            client,
+            connectivity,
+            compression: Arc::default(),
+            presence,
+            retained: RetainedCache::default(),
+            history: MessageHistory::default(),
+            consumer_limits: ConsumerLimits::default(),
+        })
+    }
+
+    /// Fail fast while NATS is unreachable, instead of queueing work that
+    /// would only time out
+    fn ensure_connected(&self) -> Result<(), BridgeError> {
+        if self.is_connected() {
+            Ok(())
+        } else {
+            Err(BridgeError::Unavailable)
+        }
+    }
+
+    /// Compress and decompress payloads on matching subjects. Messages that
+    /// would decompress to more than `max_payload` bytes are dropped.
+    pub fn with_compression(mut self, rules: Vec<SubjectCompression>, max_payload: u32) -> Self {
+        self.compression = Arc::new(PayloadCompression {
+            rules,
+            max_payload: max_payload as usize,
+        });
+        self
+    }
+
+    /// Have the server remove durable consumers left unread for
+    /// `inactive_threshold`, and allow each user `per_user` of them per
+    /// stream
+    pub fn with_consumer_limits(mut self, inactive_threshold: Duration, per_user: usize) -> Self {
+        self.consumer_limits = ConsumerLimits {
+            inactive_threshold,
+            per_user,
+        };
+        self
+    }
+
+    /// Keep the last message of up to `max_subjects` subjects matching
+    /// `patterns` for replay to new subscribers. Call after
+    /// [`Self::with_compression`] so retained payloads are decoded with the
+    /// same rules.
+    pub async fn with_retained(
+        mut self,
+        patterns: Vec<String>,
+        max_subjects: usize,
+    ) -> Result<Self, BridgeError> {
+        if !patterns.is_empty() {
+            self.retained = RetainedCache::start(
+                &self.client,
+                patterns,
+                max_subjects,
+                self.compression.clone(),
+            )
+            .await?;
+        }
+        Ok(self)
+    }
+
+    /// Record the last `length` messages of up to `max_subjects` subjects
+    /// matching `patterns` for replay on subscribe. Like
+    /// [`Self::with_retained`], call after [`Self::with_compression`].
+    pub async fn with_history(
+        mut self,
+        patterns: Vec<String>,
+        length: usize,
+        max_subjects: usize,
+    ) -> Result<Self, BridgeError> {
+        if !patterns.is_empty() && length > 0 {
+            self.history = MessageHistory::start(
+                &self.client,
+                patterns,
+                length,
+                max_subjects,
+                self.compression.clone(),
+            )
+            .await?;
+        }
+        Ok(self)
+    }
+
+    /// Encode a client payload for storage on `subject`
+    fn encode_payload(&self, subject: &str, payload: Vec<u8>) -> Result<Vec<u8>, BridgeError> {
+        match algorithm_for(&self.compression, subject) {
+            Some(algorithm) => compression::compress(algorithm, &payload)
+                .map_err(|e| BridgeError::Compression(e.to_string())),
+            None => Ok(payload),
+        }
+    }
+
+    /// Upstream NATS subscriptions and the session subscriptions sharing them
+    pub fn subscription_stats(&self) -> SubscriptionStats {
+        self.subscriptions.stats()
+    }
+}
+
+#[async_trait]
+impl Broker for NatsBridge {
+    fn is_connected(&self) -> bool {
+        *self.connectivity.borrow() == Connectivity::Connected
     }
 
-    /// Subscribe to a subject and forward messages to the sender
-    pub async fn subscribe(
+    fn watch_connectivity(&self) -> watch::Receiver<Connectivity> {
+        self.connectivity.clone()
+    }
+
+    fn presence(&self) -> &Arc<Presence> {
+        &self.presence
+    }
+
+    fn retained(&self, subject: &str, subscription_id: u64) -> Vec<NatsMessage> {
+        self.retained
+            .matching(subject)
+            .into_iter()
+            .map(|(subject, payload)| NatsMessage {
+                subscription_id,
+                subject,
+                payload,
+                origin: MessageOrigin::Retained,
+            })
+            .collect()
+    }
+
+    fn history(&self, subject: &str, subscription_id: u64, from: &ReplayFrom) -> Vec<NatsMessage> {
+        self.history
+            .replay(subject, from)
+            .into_iter()
+            .map(|(subject, payload)| NatsMessage {
+                subscription_id,
+                subject,
+                payload,
+                origin: MessageOrigin::History,
+            })
+            .collect()
+    }
+
+    /// Subscriptions to the same subject share one NATS subscription. If
+    /// NATS ends it, it is taken up again once the server is reachable.
+    async fn subscribe(
         &self,
         subject: String,
-        sender: mpsc::Sender<NatsMessage>,
+        mailbox: MailboxSender,
     ) -> Result<SubscriptionHandle, BridgeError> {
-        let subscriber = self
-            .client
-            .subscribe(subject.clone())
-            .await
-            .map_err(|e| BridgeError::SubscribeFailed(e.to_string()))?;
-
-        let (cancel_tx, mut cancel_rx) = mpsc::channel::<()>(1);
-
-        let subject_clone = subject.clone();
-        tokio::spawn(async move {
-            let mut subscriber = subscriber;
-            loop {
-                tokio::select! {
-                    msg = subscriber.next() => {
-                        match msg {
-                            Some(msg) => {
-                                let nats_msg = NatsMessage {
-                                    subject: msg.subject.to_string(),
-                                    payload: msg.payload.to_vec(),
-                                };
-                                if sender.send(nats_msg).await.is_err() {
-                                    debug!("Subscription channel closed for {}", subject_clone);
-                                    break;
-                                }
-                            }
-                            None => {
-                                debug!("NATS subscription ended for {}", subject_clone);
-                                break;
-                            }
-                        }
-                    }
-                    _ = cancel_rx.recv() => {
-                        debug!("Subscription cancelled for {}", subject_clone);
-                        break;
-                    }
-                }
-            }
-        });
+        let lease = self
+            .subscriptions
+            .subscribe(subject, mailbox, self.compression.clone())
+            .await?;
+        Ok(SubscriptionHandle::guard(lease))
+    }
+
+    async fn subscribe_consumer(
+        &self,
+        stream: &str,
+        user_id: &str,
+        durable: &str,
+        filter_subject: String,
+        deliver: &DeliverPolicy,
+        subscription_id: u64,
+        sender: mpsc::Sender<SubscriptionEvent>,
+    ) -> Result<SubscriptionHandle, BridgeError> {
+        let source = ConsumerSource {
+            context: self.jetstream.clone(),
+            stream: stream.to_string(),
+            name: consumer::consumer_name(user_id, durable),
+            user_prefix: consumer::consumer_name(user_id, ""),
+            filter_subject,
+            deliver: deliver.clone(),
+            limits: self.consumer_limits,
+        };
+        let messages = source.open().await?;
+
+        let (cancel_tx, cancel_rx) = mpsc::channel::<()>(1);
+        tokio::spawn(consumer::forward(
+            messages,
+            source,
+            subscription_id,
+            sender,
+            self.compression.clone(),
+            self.connectivity.clone(),
+            cancel_rx,
+        ));
 
-        Ok(SubscriptionHandle { cancel_tx })
+        Ok(SubscriptionHandle::task(cancel_tx))
     }
 
-    /// Publish a message to a subject
-    pub async fn publish(&self, subject: &str, payload: Vec<u8>) -> Result<(), BridgeError> {
+    async fn ack(&self, handle: &AckHandle, kind: &AckKind) -> Result<(), BridgeError> {
+        consumer::ack(&self.client, handle, kind).await
+    }
+
+    async fn term(&self, handle: &AckHandle) -> Result<(), BridgeError> {
+        consumer::term(&self.client, handle).await
+    }
+
+    async fn kv_get(&self, bucket: &str, key: &str) -> Result<KvValue, BridgeError> {
+        self.buckets.get(bucket, key).await
+    }
+
+    async fn kv_put(&self, bucket: &str, key: &str, value: Vec<u8>) -> Result<u64, BridgeError> {
+        self.buckets.put(bucket, key, value).await
+    }
+
+    async fn kv_delete(&self, bucket: &str, key: &str) -> Result<(), BridgeError> {
+        self.buckets.delete(bucket, key).await
+    }
+
+    async fn kv_watch(
+        &self,
+        bucket: &str,
+        key: &str,
+        subscription_id: u64,
+        sender: mpsc::Sender<SubscriptionEvent>,
+    ) -> Result<SubscriptionHandle, BridgeError> {
+        let watch = self.buckets.watch(bucket, key).await?;
+
+        let (cancel_tx, cancel_rx) = mpsc::channel::<()>(1);
+        tokio::spawn(kv::forward(watch, subscription_id, sender, cancel_rx));
+
+        Ok(SubscriptionHandle::task(cancel_tx))
+    }
+
+    async fn upload_object(
+        &self,
+        bucket: &str,
+        name: &str,
+        size: u64,
+        sha256: Vec<u8>,
+    ) -> Result<ObjectUpload, BridgeError> {
+        self.objects.upload(bucket, name, size, sha256).await
+    }
+
+    async fn download_object(
+        &self,
+        bucket: &str,
+        name: &str,
+    ) -> Result<ObjectDownload, BridgeError> {
+        self.objects.download(bucket, name).await
+    }
+
+    async fn publish(
+        &self,
+        subject: &str,
+        headers: &Headers,
+        payload: Vec<u8>,
+    ) -> Result<(), BridgeError> {
+        self.ensure_connected()?;
+        let payload = self.encode_payload(subject, payload)?;
         self.client
-            .publish(subject.to_string(), Bytes::from(payload))
+            .publish_with_headers(
+                subject.to_string(),
+                header_map(headers),
+                Bytes::from(payload),
+            )
             .await
             .map_err(|e| BridgeError::PublishFailed(e.to_string()))?;
         Ok(())
     }
 
-    /// Request-reply pattern
-    pub async fn request(
+    async fn publish_durable(
         &self,
         subject: &str,
+        headers: &Headers,
+        payload: Vec<u8>,
+        msg_id: Option<String>,
+    ) -> Result<StreamAck, BridgeError> {
+        self.ensure_connected()?;
+        let payload = self.encode_payload(subject, payload)?;
+        let mut publish = jetstream::context::Publish::build().payload(Bytes::from(payload));
+        for (name, value) in headers.iter() {
+            publish = publish.header(name, value);
+        }
+        if let Some(msg_id) = msg_id {
+            publish = publish.message_id(msg_id);
+        }
+
+        let ack = self
+            .jetstream
+            .send_publish(subject.to_string(), publish)
+            .await
+            .map_err(publish_error)?
+            .await
+            .map_err(publish_error)?;
+
+        Ok(StreamAck {
+            stream: ack.stream,
+            seq: ack.sequence,
+            duplicate: ack.duplicate,
+        })
+    }
+
+    async fn request(
+        &self,
+        subject: &str,
+        headers: &Headers,
         payload: Vec<u8>,
         timeout: Duration,
     ) -> Result<Vec<u8>, BridgeError> {
-        let response = tokio::time::timeout(
-            timeout,
-            self.client
-                .request(subject.to_string(), Bytes::from(payload)),
-        )
-        .await
-        .map_err(|_| BridgeError::RequestTimeout)?
-        .map_err(|e| BridgeError::RequestFailed(e.to_string()))?;
+        self.ensure_connected()?;
+        let payload = self.encode_payload(subject, payload)?;
+        // The caller's timeout replaces the client's default request timeout
+        let request = Request::new()
+            .headers(header_map(headers))
+            .payload(Bytes::from(payload))
+            .timeout(Some(timeout));
+        let response = self
+            .client
+            .send_request(subject.to_string(), request)
+            .await
+            .map_err(|e| match e.kind() {
+                RequestErrorKind::TimedOut => BridgeError::RequestTimeout,
+                RequestErrorKind::NoResponders => BridgeError::NoResponders,
+                _ => BridgeError::RequestFailed(e.to_string()),
+            })?;
 
-        Ok(response.payload.to_vec())
+        decode_payload(&self.compression, subject, &response.payload)
+    }
+
+    /// The request is published with a temporary inbox as its reply subject.
+    /// The backend may publish any number of replies to that inbox and marks
+    /// the last one with the [`STREAM_END_HEADER`] header.
+    async fn request_stream(
+        &self,
+        subject: &str,
+        headers: &Headers,
+        payload: Vec<u8>,
+    ) -> Result<ResponseStream, BridgeError> {
+        self.ensure_connected()?;
+        let payload = self.encode_payload(subject, payload)?;
+        let inbox = self.client.new_inbox();
+        let subscriber = self
+            .client
+            .subscribe(inbox.clone())
+            .await
+            .map_err(|e| BridgeError::RequestFailed(e.to_string()))?;
+
+        self.client
+            .publish_with_reply_and_headers(
+                subject.to_string(),
+                inbox,
+                header_map(headers),
+                Bytes::from(payload),
+            )
+            .await
+            .map_err(|e| BridgeError::RequestFailed(e.to_string()))?;
+
+        Ok(ResponseStream {
+            replies: Replies::Nats {
+                subscriber,
+                compression: algorithm_for(&self.compression, subject).cloned(),
+                max_payload: self.compression.max_payload,
+            },
+            finished: false,
+        })
+    }
+}
+
+fn header_map(headers: &Headers) -> async_nats::HeaderMap {
+    let mut map = async_nats::HeaderMap::new();
+    for (name, value) in headers.iter() {
+        map.insert(name, value);
+    }
+    map
+}
+
+fn publish_error(e: jetstream::context::PublishError) -> BridgeError {
+    match e.kind() {
+        jetstream::context::PublishErrorKind::StreamNotFound => {
+            BridgeError::NotFound("No stream captures this subject".to_string())
+        }
+        jetstream::context::PublishErrorKind::TimedOut => BridgeError::RequestTimeout,
+        _ => BridgeError::PublishFailed(e.to_string()),
+    }
+}
+
+/// Per-subject compression rules and the size compressed payloads may
+/// expand to
+#[derive(Debug, Default)]
+pub(super) struct PayloadCompression {
+    rules: Vec<SubjectCompression>,
+    max_payload: usize,
+}
+
+/// Compression used for payloads on `subject`, if any rule matches
+fn algorithm_for<'a>(
+    compression: &'a PayloadCompression,
+    subject: &str,
+) -> Option<&'a CompressionAlgorithm> {
+    compression
+        .rules
+        .iter()
+        .find(|rule| PermissionChecker::matches_pattern(&rule.pattern, subject))
+        .map(|rule| &rule.algorithm)
+}
+
+/// Decode a payload received on `subject` for delivery to a client.
+///
+/// Replies are decoded with the rule of the subject the request was sent to.
+/// Payloads that expand past the configured maximum are rejected.
+pub(super) fn decode_payload(
+    compression: &PayloadCompression,
+    subject: &str,
+    payload: &[u8],
+) -> Result<Vec<u8>, BridgeError> {
+    match algorithm_for(compression, subject) {
+        Some(algorithm) => compression::decompress(algorithm, payload, compression.max_payload)
+            .map_err(|e| BridgeError::Compression(e.to_string())),
+        None => Ok(payload.to_vec()),
+    }
+}
+
+/// Header set by the backend on the final message of a streamed reply
+pub const STREAM_END_HEADER: &str = "Mottomesh-Stream-End";
+
+/// Prefix of the subjects gateway instances use among themselves
+pub const INTERNAL_SUBJECT_PREFIX: &str = "_MOTTOMESH.";
+
+/// Replies to a streamed request, read one chunk at a time
+pub struct ResponseStream {
+    replies: Replies,
+    finished: bool,
+}
+
+enum Replies {
+    /// Messages on the request's reply inbox
+    Nats {
+        subscriber: Subscriber,
+        /// Compression of the reply payloads, from the request subject
+        compression: Option<CompressionAlgorithm>,
+        /// Most bytes a reply payload may decompress to
+        max_payload: usize,
+    },
+    /// Chunks passed on by an in-process responder
+    Local(mpsc::Receiver<StreamChunk>),
+}
+
+/// A single part of a streamed reply
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamChunk {
+    /// Another part of the reply
+    Data(Vec<u8>),
+    /// The backend marked the reply as complete
+    End,
+}
+
+impl ResponseStream {
+    pub(super) fn local(replies: mpsc::Receiver<StreamChunk>) -> Self {
+        Self {
+            replies: Replies::Local(replies),
+            finished: false,
+        }
+    }
+
+    /// Wait for the next part of the reply.
+    ///
+    /// `timeout` bounds the wait for each individual chunk, not the whole
+    /// stream. A marker message carrying a payload yields that payload as a
+    /// final `Data` chunk before `End`.
+    pub async fn next_chunk(&mut self, timeout: Duration) -> Result<StreamChunk, BridgeError> {
+        if self.finished {
+            return Ok(StreamChunk::End);
+        }
+
+        let (subscriber, compression, max_payload) = match &mut self.replies {
+            Replies::Nats {
+                subscriber,
+                compression,
+                max_payload,
+            } => (subscriber, compression, *max_payload),
+            Replies::Local(replies) => {
+                let chunk = tokio::time::timeout(timeout, replies.recv())
+                    .await
+                    .map_err(|_| BridgeError::RequestTimeout)?
+                    .ok_or_else(|| {
+                        BridgeError::RequestFailed("responder dropped the request".to_string())
+                    })?;
+                self.finished = chunk == StreamChunk::End;
+                return Ok(chunk);
+            }
+        };
+
+        let msg = tokio::time::timeout(timeout, subscriber.next())
+            .await
+            .map_err(|_| BridgeError::RequestTimeout)?
+            .ok_or_else(|| BridgeError::RequestFailed("reply inbox closed".to_string()))?;
+
+        if msg.status == Some(StatusCode::NO_RESPONDERS) {
+            return Err(BridgeError::NoResponders);
+        }
+
+        let is_end = msg
+            .headers
+            .as_ref()
+            .is_some_and(|headers| headers.get(STREAM_END_HEADER).is_some());
+
+        if !is_end {
+            return decode_chunk(compression.as_ref(), &msg.payload, max_payload);
+        }
+
+        self.finished = true;
+        if msg.payload.is_empty() {
+            Ok(StreamChunk::End)
+        } else {
+            decode_chunk(compression.as_ref(), &msg.payload, max_payload)
+        }
+    }
+}
+
+/// Decode one part of a streamed reply
+fn decode_chunk(
+    algorithm: Option<&CompressionAlgorithm>,
+    payload: &[u8],
+    max_payload: usize,
+) -> Result<StreamChunk, BridgeError> {
+    match algorithm {
+        Some(algorithm) => compression::decompress(algorithm, payload, max_payload)
+            .map(StreamChunk::Data)
+            .map_err(|e| BridgeError::Compression(e.to_string())),
+        None => Ok(StreamChunk::Data(payload.to_vec())),
     }
 }
 
 /// Message received from NATS
 #[derive(Debug, Clone)]
 pub struct NatsMessage {
+    /// Gateway subscription the message was received on
+    pub subscription_id: u64,
     pub subject: String,
     pub payload: Vec<u8>,
+    /// Received live, or replayed from the last-value cache or history
+    pub origin: MessageOrigin,
 }
 
-/// Handle to cancel a subscription
-pub struct SubscriptionHandle {
-    cancel_tx: mpsc::Sender<()>,
+/// Where JetStream stored a published message
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamAck {
+    pub stream: String,
+    pub seq: u64,
+    /// The message id was already stored, so this publish was discarded
+    pub duplicate: bool,
 }
 
-impl SubscriptionHandle {
-    pub async fn unsubscribe(self) {
-        let _ = self.cancel_tx.send(()).await;
+/// Event from a NATS subscription task
+#[derive(Debug, Clone)]
+pub enum SubscriptionEvent {
+    /// A message was received
+    Message(NatsMessage),
+    /// A durable consumer delivered a message awaiting acknowledgement
+    Consumer(ConsumerDelivery),
+    /// A watched key changed
+    KvChange(KvChange),
+    /// NATS ended the subscription for good, e.g. because the connection
+    /// closed
+    Closed { subscription_id: u64 },
+}
+
+/// Record connection events that change whether NATS is reachable
+fn track_connectivity(connectivity: &watch::Sender<Connectivity>, event: Event) {
+    let state = match event {
+        Event::Connected => Connectivity::Connected,
+        Event::Disconnected => Connectivity::Disconnected,
+        Event::Closed => Connectivity::Closed,
+        other => {
+            debug!("NATS connection event: {}", other);
+            return;
+        }
+    };
+    let previous = connectivity.send_replace(state);
+    if previous != state {
+        match state {
+            Connectivity::Connected => info!("NATS connection restored"),
+            _ => warn!("NATS connection {:?}", state),
+        }
     }
 }
 
+/// Wait until NATS is reachable, then return whether the connection is
+/// usable or closed for good
+pub(super) async fn reconnected(connectivity: &mut watch::Receiver<Connectivity>) -> bool {
+    connectivity
+        .wait_for(|state| *state != Connectivity::Disconnected)
+        .await
+        .is_ok_and(|state| *state == Connectivity::Connected)
+}
+
+/// Translate the connection settings into client options
+async fn connect_options(config: &NatsConfig) -> Result<ConnectOptions, BridgeError> {
+    let options = match &config.auth {
+        NatsAuth::None => ConnectOptions::new(),
+        NatsAuth::UserPassword { user, password } => {
+            ConnectOptions::with_user_and_password(user.clone(), password.clone())
+        }
+        NatsAuth::Token(token) => ConnectOptions::with_token(token.clone()),
+        NatsAuth::NKey(seed) => ConnectOptions::with_nkey(seed.clone()),
+        NatsAuth::CredentialsFile(path) => ConnectOptions::with_credentials_file(path)
+            .await
+            .map_err(|e| {
+                BridgeError::ConnectionFailed(format!(
+                    "Failed to load NATS credentials from {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?,
+    };
+
+    let mut options = options
+        .require_tls(config.tls.required)
+        .max_reconnects(config.max_reconnects)
+        .connection_timeout(Duration::from_millis(config.connection_timeout_ms))
+        .ping_interval(Duration::from_millis(config.ping_interval_ms))
+        .reconnect_delay_callback({
+            let max = Duration::from_millis(config.reconnect_delay_max_ms);
+            move |attempts| reconnect_delay(attempts, max)
+        });
+    if let Some(name) = &config.name {
+        options = options.name(name);
+    }
+    if let Some(ca_path) = &config.tls.ca_path {
+        options = options.add_root_certificates(ca_path.clone());
+    }
+    if let Some((cert_path, key_path)) = &config.tls.client_cert {
+        options = options.add_client_certificate(cert_path.clone(), key_path.clone());
+    }
+
+    Ok(options)
+}
+
+/// Wait before a reconnect attempt: none for the first, then doubling from
+/// 2ms up to `max`, as async-nats does by default
+fn reconnect_delay(attempts: usize, max: Duration) -> Duration {
+    if attempts <= 1 {
+        return Duration::ZERO;
+    }
+    let exponent = u32::try_from(attempts - 1).unwrap_or(u32::MAX);
+    Duration::from_millis(2u64.saturating_pow(exponent)).min(max)
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum BridgeError {
     #[error("Failed to connect to NATS: {0}")]
@@ -130,4 +748,132 @@ pub enum BridgeError {
     RequestFailed(String),
     #[error("Request timed out")]
     RequestTimeout,
+    #[error("No responders are listening on the subject")]
+    NoResponders,
+    #[error("Payload compression failed: {0}")]
+    Compression(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    InvalidRequest(String),
+    #[error("{0}")]
+    QuotaExceeded(String),
+    #[error("NATS is unavailable")]
+    Unavailable,
+    #[error("{0} is not supported by this broker")]
+    Unsupported(&'static str),
+}
+
+impl BridgeError {
+    /// Protocol error code reported to clients for this failure
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::ConnectionFailed(_)
+            | Self::SubscribeFailed(_)
+            | Self::PublishFailed(_)
+            | Self::RequestFailed(_)
+            | Self::Unavailable
+            | Self::Unsupported(_) => ErrorCode::BackendUnavailable,
+            Self::RequestTimeout => ErrorCode::Timeout,
+            Self::NoResponders => ErrorCode::NoResponders,
+            Self::Compression(_) => ErrorCode::Internal,
+            Self::NotFound(_) => ErrorCode::NotFound,
+            Self::InvalidRequest(_) => ErrorCode::InvalidMessage,
+            Self::QuotaExceeded(_) => ErrorCode::QuotaExceeded,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconnect_delay_backs_off_up_to_max() {
+        let max = Duration::from_millis(500);
+        assert_eq!(reconnect_delay(1, max), Duration::ZERO);
+        assert_eq!(reconnect_delay(2, max), Duration::from_millis(2));
+        assert_eq!(reconnect_delay(5, max), Duration::from_millis(16));
+        assert_eq!(reconnect_delay(20, max), max);
+        assert_eq!(reconnect_delay(usize::MAX, max), max);
+    }
+
+    #[test]
+    fn test_decode_payload_limited_to_max_payload() {
+        let compression = PayloadCompression {
+            rules: SubjectCompression::parse_list("logs.>=zstd").unwrap(),
+            max_payload: 1024,
+        };
+        let fits = compression::compress(&CompressionAlgorithm::Zstd, &[7; 1024]).unwrap();
+        let bomb = compression::compress(&CompressionAlgorithm::Zstd, &[7; 1025]).unwrap();
+
+        assert_eq!(
+            decode_payload(&compression, "logs.app", &fits).unwrap(),
+            vec![7; 1024]
+        );
+        assert!(matches!(
+            decode_payload(&compression, "logs.app", &bomb),
+            Err(BridgeError::Compression(_))
+        ));
+        // Subjects without a rule pass through as stored
+        assert_eq!(
+            decode_payload(&compression, "metrics", &bomb).unwrap(),
+            bomb
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connectivity_follows_connection_events() {
+        let (tx, mut rx) = watch::channel(Connectivity::Connected);
+
+        track_connectivity(&tx, Event::Disconnected);
+        assert_eq!(*rx.borrow_and_update(), Connectivity::Disconnected);
+
+        // Other events leave the state alone
+        track_connectivity(&tx, Event::SlowConsumer(1));
+        assert!(!rx.has_changed().unwrap());
+
+        let waiter = tokio::spawn(async move { reconnected(&mut rx).await });
+        track_connectivity(&tx, Event::Connected);
+        assert!(waiter.await.unwrap());
+
+        track_connectivity(&tx, Event::Closed);
+        assert!(!reconnected(&mut tx.subscribe()).await);
+    }
+
+    #[tokio::test]
+    async fn test_credentials_file() {
+        let seed = nkeys::KeyPair::new_user().seed().unwrap();
+        let path = std::env::temp_dir().join(format!("mottomesh-{}.creds", std::process::id()));
+        std::fs::write(
+            &path,
+            format!(
+                "-----BEGIN NATS USER JWT-----\neyJ0eXAiOiJKV1QiLCJhbGciOiJlZDI1NTE5LW5rZXkifQ.e30.sig\n------END NATS USER JWT------\n\n\
+                 -----BEGIN USER NKEY SEED-----\n{}\n------END USER NKEY SEED------\n",
+                seed
+            ),
+        )
+        .unwrap();
+
+        let mut config = NatsConfig::new("localhost:4222");
+        config.auth = NatsAuth::CredentialsFile(path.clone());
+        let loaded = connect_options(&config).await;
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.is_ok());
+
+        // A missing file is reported before any connection attempt
+        let result = NatsBridge::connect(&config).await;
+        assert!(
+            matches!(result, Err(BridgeError::ConnectionFailed(reason)) if reason.contains("credentials"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_rejects_invalid_urls() {
+        let result = NatsBridge::connect(&NatsConfig::new("nats://bad host:4222")).await;
+        assert!(matches!(result, Err(BridgeError::ConnectionFailed(_))));
+
+        let result = NatsBridge::connect(&NatsConfig::new("")).await;
+        assert!(matches!(result, Err(BridgeError::ConnectionFailed(_))));
+    }
 }
@@ -0,0 +1,287 @@
+//! JetStream object store transfers.
+//!
+//! Payloads too large for a single message are moved in chunks. An upload
+//! streams the chunks a client sends into a staging object while hashing
+//! them. Only once the SHA-256 digest matches the one the client declared
+//! up front is the staged copy stored under the real name, replacing any
+//! earlier version; until then that version stays untouched. A download
+//! reads the object back in chunks from any offset, hashing the whole
+//! object on the way so the client can check what it assembled.
+//!
+//! Abandoned uploads leave their chunks in the bucket's stream until it is
+//! purged; neither the staging object nor the real one is written.
+
+use std::fmt::Write;
+
+use async_nats::jetstream::{self, object_store};
+use dashmap::DashMap;
+use ring::digest;
+use ring::rand::{SecureRandom, SystemRandom};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use super::nats::BridgeError;
+
+/// Bytes buffered between an upload and the object store
+const UPLOAD_BUFFER_BYTES: usize = 256 * 1024;
+
+/// Prefix of the objects uploads are staged in until their digest checks out
+pub const STAGING_PREFIX: &str = "_upload/";
+
+/// Cached handles of the object stores used so far
+pub(super) struct ObjectStores {
+    context: jetstream::Context,
+    stores: DashMap<String, object_store::ObjectStore>,
+}
+
+impl ObjectStores {
+    pub(super) fn new(context: jetstream::Context) -> Self {
+        Self {
+            context,
+            stores: DashMap::new(),
+        }
+    }
+
+    async fn store(&self, bucket: &str) -> Result<object_store::ObjectStore, BridgeError> {
+        if let Some(store) = self.stores.get(bucket) {
+            return Ok(store.clone());
+        }
+
+        let store = self
+            .context
+            .get_object_store(bucket)
+            .await
+            .map_err(|e| match e.kind() {
+                jetstream::context::ObjectStoreErrorKind::InvalidBucketName => {
+                    BridgeError::InvalidRequest(format!("Invalid bucket name {:?}", bucket))
+                }
+                jetstream::context::ObjectStoreErrorKind::GetStore => {
+                    BridgeError::NotFound(format!("No object store named {:?}", bucket))
+                }
+            })?;
+        self.stores.insert(bucket.to_string(), store.clone());
+        Ok(store)
+    }
+
+    pub(super) async fn upload(
+        &self,
+        bucket: &str,
+        name: &str,
+        size: u64,
+        sha256: Vec<u8>,
+    ) -> Result<ObjectUpload, BridgeError> {
+        let store = self.store(bucket).await?;
+
+        let staging = staging_name(name);
+        let (writer, mut reader) = tokio::io::duplex(UPLOAD_BUFFER_BYTES);
+        let task = tokio::spawn({
+            let store = store.clone();
+            let staging = staging.clone();
+            async move { store.put(staging.as_str(), &mut reader).await }
+        });
+
+        Ok(ObjectUpload {
+            store,
+            bucket: bucket.to_string(),
+            name: name.to_string(),
+            staging,
+            size,
+            sha256,
+            received: 0,
+            digest: digest::Context::new(&digest::SHA256),
+            writer: Some(writer),
+            task: Some(task),
+        })
+    }
+
+    pub(super) async fn download(
+        &self,
+        bucket: &str,
+        name: &str,
+    ) -> Result<ObjectDownload, BridgeError> {
+        let object = self
+            .store(bucket)
+            .await?
+            .get(name)
+            .await
+            .map_err(|e| match e.kind() {
+                object_store::GetErrorKind::InvalidName => {
+                    BridgeError::InvalidRequest(format!("Invalid object name {:?}", name))
+                }
+                object_store::GetErrorKind::NotFound => {
+                    BridgeError::NotFound(format!("No object named {:?}", name))
+                }
+                object_store::GetErrorKind::TimedOut => BridgeError::RequestTimeout,
+                _ => BridgeError::RequestFailed(e.to_string()),
+            })?;
+
+        Ok(ObjectDownload {
+            size: object.info.size as u64,
+            object,
+            digest: digest::Context::new(&digest::SHA256),
+        })
+    }
+}
+
+/// Unique name to stage an upload of `name` under
+fn staging_name(name: &str) -> String {
+    let mut bytes = [0u8; 8];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("system random number generator failed");
+    let mut staging = String::from(STAGING_PREFIX);
+    for b in bytes {
+        let _ = write!(staging, "{:02x}", b);
+    }
+    staging.push('/');
+    staging.push_str(name);
+    staging
+}
+
+/// An object being uploaded. Dropping it abandons the upload.
+pub struct ObjectUpload {
+    store: object_store::ObjectStore,
+    bucket: String,
+    name: String,
+    /// Where the chunks go until the digest is checked
+    staging: String,
+    size: u64,
+    sha256: Vec<u8>,
+    received: u64,
+    digest: digest::Context,
+    writer: Option<DuplexStream>,
+    task: Option<JoinHandle<Result<object_store::ObjectInfo, object_store::PutError>>>,
+}
+
+impl ObjectUpload {
+    /// Whether this upload stores the object the client describes
+    pub fn matches(&self, bucket: &str, name: &str, size: u64, sha256: &[u8]) -> bool {
+        self.bucket == bucket && self.name == name && self.size == size && self.sha256 == sha256
+    }
+
+    /// Bytes received so far, where the next chunk starts
+    pub fn offset(&self) -> u64 {
+        self.received
+    }
+
+    /// Whether every declared byte has been received
+    pub fn is_complete(&self) -> bool {
+        self.received == self.size
+    }
+
+    /// Pass the next chunk on to the object store
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), BridgeError> {
+        if self.received + data.len() as u64 > self.size {
+            return Err(BridgeError::InvalidRequest(format!(
+                "Upload of {:?} exceeds its declared size of {} bytes",
+                self.name, self.size
+            )));
+        }
+        let Some(writer) = self.writer.as_mut() else {
+            return Err(BridgeError::InvalidRequest(
+                "Upload already finished".into(),
+            ));
+        };
+
+        writer
+            .write_all(data)
+            .await
+            .map_err(|e| BridgeError::PublishFailed(e.to_string()))?;
+        self.digest.update(data);
+        self.received += data.len() as u64;
+        Ok(())
+    }
+
+    /// Wait for the object store to take the last chunk and check the
+    /// digest. A staged object that passes is stored under the real name,
+    /// one that fails is deleted; either way the staging copy goes away.
+    pub async fn finish(mut self) -> Result<u64, BridgeError> {
+        // Closing the pipe ends the staged object
+        drop(self.writer.take());
+        let Some(task) = self.task.take() else {
+            return Err(BridgeError::InvalidRequest(
+                "Upload already finished".into(),
+            ));
+        };
+        task.await
+            .map_err(|e| BridgeError::PublishFailed(e.to_string()))?
+            .map_err(|e| BridgeError::PublishFailed(e.to_string()))?;
+
+        let digest = self.digest.clone().finish();
+        let stored = if digest.as_ref() == self.sha256.as_slice() {
+            self.promote().await
+        } else {
+            Err(BridgeError::InvalidRequest(format!(
+                "SHA-256 of {:?} does not match the declared digest",
+                self.name
+            )))
+        };
+
+        if let Err(e) = self.store.delete(&self.staging).await {
+            warn!("Failed to delete staged upload {:?}: {}", self.staging, e);
+        }
+        stored
+    }
+
+    /// Copy the verified staging object to the real name. The object store
+    /// only drops the previous version once the new one is complete.
+    async fn promote(&self) -> Result<u64, BridgeError> {
+        let mut staged = self
+            .store
+            .get(&self.staging)
+            .await
+            .map_err(|e| BridgeError::PublishFailed(e.to_string()))?;
+        let info = self
+            .store
+            .put(self.name.as_str(), &mut staged)
+            .await
+            .map_err(|e| BridgeError::PublishFailed(e.to_string()))?;
+        Ok(info.size as u64)
+    }
+}
+
+impl Drop for ObjectUpload {
+    fn drop(&mut self) {
+        // Stop the store before the pipe closes, so a partial upload is
+        // never written as the staging object
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+    }
+}
+
+/// An object being read back
+pub struct ObjectDownload {
+    pub size: u64,
+    object: object_store::Object,
+    digest: digest::Context,
+}
+
+impl ObjectDownload {
+    /// Read up to `max` bytes. Returns an empty chunk at the end of the
+    /// object.
+    pub async fn read_chunk(&mut self, max: usize) -> Result<Vec<u8>, BridgeError> {
+        let mut chunk = vec![0; max];
+        let mut filled = 0;
+        while filled < max {
+            let n = self
+                .object
+                .read(&mut chunk[filled..])
+                .await
+                .map_err(|e| BridgeError::RequestFailed(e.to_string()))?;
+            if n == 0 {
+                break;
+            }
+            filled += n;
+        }
+        chunk.truncate(filled);
+        self.digest.update(&chunk);
+        Ok(chunk)
+    }
+
+    /// SHA-256 of everything read
+    pub fn sha256(&self) -> Vec<u8> {
+        self.digest.clone().finish().as_ref().to_vec()
+    }
+}
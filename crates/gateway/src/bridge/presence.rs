@@ -0,0 +1,654 @@
+//! Presence: which sessions are subscribed to which subjects.
+//!
+//! Every gateway instance tracks the subscriptions of its own sessions and
+//! gossips changes to the others over NATS, so each instance holds the
+//! member list of the whole deployment. Besides individual joins and leaves,
+//! instances periodically announce their full member list; this repairs
+//! missed gossip and lets an instance that stopped announcing (crashed or
+//! partitioned) be dropped from everyone's view.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+
+use async_nats::Client;
+use bytes::Bytes;
+use futures::StreamExt;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+use super::nats::{BridgeError, INTERNAL_SUBJECT_PREFIX};
+use crate::auth::{Claims, PermissionChecker, Session};
+use crate::protocol::{PresenceAction, PresenceMember};
+
+/// How often each instance announces its full member list
+const HEARTBEAT: Duration = Duration::from_secs(5);
+
+/// Instances that miss this many announcements are presumed gone
+const MISSED_HEARTBEATS: u32 = 3;
+
+fn gossip_subject() -> String {
+    format!("{}presence", INTERNAL_SUBJECT_PREFIX)
+}
+
+/// Random id for this gateway instance, so instances started at the same
+/// moment cannot collide
+fn instance_id() -> String {
+    let mut bytes = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("system random number generator failed");
+    bytes.iter().fold(String::with_capacity(32), |mut id, b| {
+        let _ = write!(id, "{:02x}", b);
+        id
+    })
+}
+
+/// A session subscribed to a subject
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+struct Member {
+    session_id: String,
+    user_id: String,
+    /// Tenant of the session; members are only visible within their tenant
+    #[serde(default)]
+    tenant: Option<String>,
+    subject: String,
+}
+
+impl Member {
+    fn new(session: &Session, subject: &str) -> Self {
+        Self {
+            session_id: session.id.clone(),
+            user_id: session.user_id.clone(),
+            tenant: session.claims.tenant.clone(),
+            subject: subject.to_string(),
+        }
+    }
+
+    /// Whether a client with these claims may see this member: same
+    /// tenant, and a subject the client is allowed to subscribe to
+    fn visible_to(&self, claims: &Claims) -> bool {
+        self.tenant == claims.tenant && PermissionChecker::is_subject_allowed(claims, &self.subject)
+    }
+}
+
+impl From<Member> for PresenceMember {
+    fn from(member: Member) -> Self {
+        Self {
+            session_id: member.session_id,
+            user_id: member.user_id,
+            subject: member.subject,
+        }
+    }
+}
+
+/// Presence updates exchanged between gateway instances
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Gossip {
+    Join {
+        instance: String,
+        member: Member,
+    },
+    Leave {
+        instance: String,
+        member: Member,
+    },
+    /// Full member list of an instance
+    Sync {
+        instance: String,
+        members: Vec<Member>,
+    },
+    /// A new instance asking the others to announce their members
+    Hello {
+        instance: String,
+    },
+}
+
+/// A presence change for one of a connection's presence subscriptions
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresenceChange {
+    /// Client-chosen id of the presence subscription
+    pub id: u64,
+    pub action: PresenceAction,
+    pub member: PresenceMember,
+}
+
+struct Watcher {
+    id: u64,
+    pattern: String,
+    /// Claims of the watching client, deciding which members it sees
+    claims: Claims,
+    sender: mpsc::Sender<PresenceChange>,
+}
+
+struct InstanceMembers {
+    members: HashSet<Member>,
+    last_seen: Instant,
+}
+
+#[derive(Default)]
+struct State {
+    /// Members by the gateway instance hosting their session, this one included
+    instances: HashMap<String, InstanceMembers>,
+    /// Subscriptions holding each of this instance's members
+    local_refs: HashMap<Member, usize>,
+    watchers: HashMap<u64, Watcher>,
+    next_watcher: u64,
+}
+
+impl State {
+    fn notify(&self, action: PresenceAction, member: &Member) {
+        for watcher in self.watchers.values() {
+            if !PermissionChecker::matches_pattern(&watcher.pattern, &member.subject)
+                || !member.visible_to(&watcher.claims)
+            {
+                continue;
+            }
+            let change = PresenceChange {
+                id: watcher.id,
+                action: action.clone(),
+                member: member.clone().into(),
+            };
+            // A watcher that is not keeping up loses the newest changes
+            // rather than buffering without bound; it can catch up with a
+            // presence query
+            if let Err(TrySendError::Full(_)) = watcher.sender.try_send(change) {
+                debug!(
+                    "Presence: watcher {} is full, dropping a change",
+                    watcher.id
+                );
+            }
+        }
+    }
+
+    fn insert(&mut self, instance: &str, member: Member) {
+        let entry = self
+            .instances
+            .entry(instance.to_string())
+            .or_insert_with(|| InstanceMembers {
+                members: HashSet::new(),
+                last_seen: Instant::now(),
+            });
+        entry.last_seen = Instant::now();
+        if entry.members.insert(member.clone()) {
+            self.notify(PresenceAction::Join, &member);
+        }
+    }
+
+    fn remove(&mut self, instance: &str, member: &Member) {
+        let removed = self
+            .instances
+            .get_mut(instance)
+            .is_some_and(|entry| entry.members.remove(member));
+        if removed {
+            self.notify(PresenceAction::Leave, member);
+        }
+    }
+
+    /// Replace an instance's members, reporting the difference
+    fn replace(&mut self, instance: &str, members: HashSet<Member>) {
+        let previous = self
+            .instances
+            .insert(
+                instance.to_string(),
+                InstanceMembers {
+                    members: members.clone(),
+                    last_seen: Instant::now(),
+                },
+            )
+            .map(|entry| entry.members)
+            .unwrap_or_default();
+
+        for member in previous.difference(&members) {
+            self.notify(PresenceAction::Leave, member);
+        }
+        for member in members.difference(&previous) {
+            self.notify(PresenceAction::Join, member);
+        }
+    }
+
+    /// Forget instances that stopped announcing themselves
+    fn expire(&mut self, local: &str, max_silence: Duration) {
+        let now = Instant::now();
+        let expired: Vec<String> = self
+            .instances
+            .iter()
+            .filter(|(instance, entry)| {
+                instance.as_str() != local && now.duration_since(entry.last_seen) > max_silence
+            })
+            .map(|(instance, _)| instance.clone())
+            .collect();
+
+        for instance in expired {
+            warn!(
+                "Presence: instance {} went silent, dropping its members",
+                instance
+            );
+            if let Some(entry) = self.instances.remove(&instance) {
+                for member in &entry.members {
+                    self.notify(PresenceAction::Leave, member);
+                }
+            }
+        }
+    }
+}
+
+/// Deployment-wide presence registry
+pub struct Presence {
+    instance_id: String,
+    state: Mutex<State>,
+    /// Gossip waiting to be published, in order
+    outgoing: mpsc::UnboundedSender<Gossip>,
+}
+
+impl Presence {
+    /// Join the presence gossip of the deployment
+    pub(super) async fn start(client: Client) -> Result<Arc<Self>, BridgeError> {
+        let subscriber = client
+            .subscribe(gossip_subject())
+            .await
+            .map_err(|e| BridgeError::SubscribeFailed(e.to_string()))?;
+
+        let (outgoing, outgoing_rx) = mpsc::unbounded_channel();
+        let presence = Arc::new(Self {
+            instance_id: instance_id(),
+            state: Mutex::new(State::default()),
+            outgoing,
+        });
+
+        presence.send(Gossip::Hello {
+            instance: presence.instance_id.clone(),
+        });
+
+        tokio::spawn(run_gossip(
+            Arc::downgrade(&presence),
+            client,
+            subscriber,
+            outgoing_rx,
+        ));
+
+        Ok(presence)
+    }
+
+    /// A registry of this instance's members alone, for brokers without
+    /// other instances to gossip with
+    pub(super) fn local() -> Arc<Self> {
+        // Gossip has nowhere to go; sending it fails harmlessly
+        let (outgoing, _) = mpsc::unbounded_channel();
+        Arc::new(Self {
+            instance_id: instance_id(),
+            state: Mutex::new(State::default()),
+            outgoing,
+        })
+    }
+
+    /// Identifies this gateway instance within the deployment
+    pub fn instance_id(&self) -> &str {
+        &self.instance_id
+    }
+
+    /// Record that a session subscribed to a subject
+    pub fn join(&self, session: &Session, subject: &str) {
+        let member = Member::new(session, subject);
+
+        let mut state = self.state.lock().unwrap();
+        let refs = state.local_refs.entry(member.clone()).or_insert(0);
+        *refs += 1;
+        if *refs == 1 {
+            state.insert(&self.instance_id, member.clone());
+            self.send(Gossip::Join {
+                instance: self.instance_id.clone(),
+                member,
+            });
+        }
+    }
+
+    /// Record that a session unsubscribed from a subject
+    pub fn leave(&self, session: &Session, subject: &str) {
+        let member = Member::new(session, subject);
+
+        let mut state = self.state.lock().unwrap();
+        let Some(refs) = state.local_refs.get_mut(&member) else {
+            return;
+        };
+        *refs -= 1;
+        if *refs == 0 {
+            state.local_refs.remove(&member);
+            state.remove(&self.instance_id, &member);
+            self.send(Gossip::Leave {
+                instance: self.instance_id.clone(),
+                member,
+            });
+        }
+    }
+
+    /// Members subscribed to subjects matching the pattern, on any
+    /// instance, that a client with these claims may see
+    pub fn members(&self, pattern: &str, claims: &Claims) -> Vec<PresenceMember> {
+        let state = self.state.lock().unwrap();
+        state
+            .instances
+            .values()
+            .flat_map(|entry| entry.members.iter())
+            .filter(|member| {
+                PermissionChecker::matches_pattern(pattern, &member.subject)
+                    && member.visible_to(claims)
+            })
+            .cloned()
+            .map(PresenceMember::from)
+            .collect()
+    }
+
+    /// Report joins and leaves on subjects matching the pattern, of the
+    /// members a client with these claims may see, to the sender, tagged
+    /// with `id`, until the returned handle is dropped
+    pub fn watch(
+        self: &Arc<Self>,
+        id: u64,
+        pattern: String,
+        claims: Claims,
+        sender: mpsc::Sender<PresenceChange>,
+    ) -> PresenceWatch {
+        let mut state = self.state.lock().unwrap();
+        let key = state.next_watcher;
+        state.next_watcher += 1;
+        state.watchers.insert(
+            key,
+            Watcher {
+                id,
+                pattern: pattern.clone(),
+                claims,
+                sender,
+            },
+        );
+
+        PresenceWatch {
+            presence: Arc::downgrade(self),
+            key,
+            pattern,
+        }
+    }
+
+    fn send(&self, gossip: Gossip) {
+        // Only fails once the gossip task has stopped, i.e. on shutdown
+        let _ = self.outgoing.send(gossip);
+    }
+
+    fn local_snapshot(&self) -> Gossip {
+        let state = self.state.lock().unwrap();
+        Gossip::Sync {
+            instance: self.instance_id.clone(),
+            members: state.local_refs.keys().cloned().collect(),
+        }
+    }
+
+    fn apply(&self, gossip: Gossip) {
+        let mut state = self.state.lock().unwrap();
+        match gossip {
+            Gossip::Join { instance, member } if instance != self.instance_id => {
+                state.insert(&instance, member);
+            }
+            Gossip::Leave { instance, member } if instance != self.instance_id => {
+                state.remove(&instance, &member);
+            }
+            Gossip::Sync { instance, members } if instance != self.instance_id => {
+                state.replace(&instance, members.into_iter().collect());
+            }
+            Gossip::Hello { instance } if instance != self.instance_id => {
+                debug!("Presence: instance {} joined", instance);
+                drop(state);
+                self.send(self.local_snapshot());
+            }
+            // Our own gossip, echoed back
+            _ => {}
+        }
+    }
+}
+
+/// A presence subscription; dropping it stops the notifications
+pub struct PresenceWatch {
+    presence: Weak<Presence>,
+    key: u64,
+    pattern: String,
+}
+
+impl PresenceWatch {
+    /// The subject pattern being watched
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+}
+
+impl Drop for PresenceWatch {
+    fn drop(&mut self) {
+        if let Some(presence) = self.presence.upgrade() {
+            presence.state.lock().unwrap().watchers.remove(&self.key);
+        }
+    }
+}
+
+/// Publish outgoing gossip, apply incoming gossip, and announce this
+/// instance's members on every heartbeat, for as long as the registry lives
+async fn run_gossip(
+    presence: Weak<Presence>,
+    client: Client,
+    mut subscriber: async_nats::Subscriber,
+    mut outgoing: mpsc::UnboundedReceiver<Gossip>,
+) {
+    let subject = gossip_subject();
+    let mut heartbeat = tokio::time::interval(HEARTBEAT);
+
+    loop {
+        tokio::select! {
+            Some(gossip) = outgoing.recv() => {
+                match serde_json::to_vec(&gossip) {
+                    Ok(payload) => {
+                        if let Err(e) = client.publish(subject.clone(), Bytes::from(payload)).await {
+                            warn!("Presence: failed to publish gossip: {}", e);
+                        }
+                    }
+                    Err(e) => warn!("Presence: failed to encode gossip: {}", e),
+                }
+            }
+            msg = subscriber.next() => {
+                let Some(msg) = msg else {
+                    warn!("Presence: gossip subscription ended");
+                    return;
+                };
+                let Some(presence) = presence.upgrade() else { return };
+                match serde_json::from_slice::<Gossip>(&msg.payload) {
+                    Ok(gossip) => presence.apply(gossip),
+                    Err(e) => warn!("Presence: ignoring malformed gossip: {}", e),
+                }
+            }
+            _ = heartbeat.tick() => {
+                let Some(presence) = presence.upgrade() else { return };
+                presence.send(presence.local_snapshot());
+                presence
+                    .state
+                    .lock()
+                    .unwrap()
+                    .expire(&presence.instance_id, HEARTBEAT * MISSED_HEARTBEATS);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn member(session_id: &str, subject: &str) -> Member {
+        Member {
+            session_id: session_id.to_string(),
+            user_id: "user".to_string(),
+            tenant: None,
+            subject: subject.to_string(),
+        }
+    }
+
+    fn claims(deny: &[&str], tenant: Option<&str>) -> Claims {
+        Claims {
+            sub: "watcher".to_string(),
+            exp: 9999999999,
+            iat: 0,
+            permissions: vec!["subscribe".to_string()],
+            allowed_subjects: vec![],
+            deny_subjects: deny.iter().map(|s| s.to_string()).collect(),
+            kv_allowed: vec![],
+            kv_deny: vec![],
+            object_allowed: vec![],
+            object_deny: vec![],
+            tenant: tenant.map(String::from),
+        }
+    }
+
+    fn watched_state(pattern: &str) -> (State, mpsc::Receiver<PresenceChange>) {
+        watched_state_as(pattern, claims(&[], None), 64)
+    }
+
+    fn watched_state_as(
+        pattern: &str,
+        claims: Claims,
+        capacity: usize,
+    ) -> (State, mpsc::Receiver<PresenceChange>) {
+        let (sender, receiver) = mpsc::channel(capacity);
+        let mut state = State::default();
+        state.watchers.insert(
+            0,
+            Watcher {
+                id: 7,
+                pattern: pattern.to_string(),
+                claims,
+                sender,
+            },
+        );
+        (state, receiver)
+    }
+
+    fn drain(receiver: &mut mpsc::Receiver<PresenceChange>) -> Vec<(PresenceAction, String)> {
+        let mut changes = Vec::new();
+        while let Ok(change) = receiver.try_recv() {
+            assert_eq!(change.id, 7);
+            changes.push((change.action, change.member.session_id));
+        }
+        changes
+    }
+
+    #[test]
+    fn test_notifies_matching_watchers_only() {
+        let (mut state, mut receiver) = watched_state("chat.*");
+
+        state.insert("a", member("s1", "chat.lobby"));
+        state.insert("a", member("s2", "news"));
+        // Already known: no second Join
+        state.insert("a", member("s1", "chat.lobby"));
+        state.remove("a", &member("s1", "chat.lobby"));
+
+        assert_eq!(
+            drain(&mut receiver),
+            vec![
+                (PresenceAction::Join, "s1".to_string()),
+                (PresenceAction::Leave, "s1".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_replace_reports_difference() {
+        let (mut state, mut receiver) = watched_state(">");
+
+        state.insert("a", member("s1", "chat"));
+        state.insert("a", member("s2", "chat"));
+        drain(&mut receiver);
+
+        state.replace(
+            "a",
+            [member("s2", "chat"), member("s3", "chat")]
+                .into_iter()
+                .collect(),
+        );
+
+        assert_eq!(
+            drain(&mut receiver),
+            vec![
+                (PresenceAction::Leave, "s1".to_string()),
+                (PresenceAction::Join, "s3".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expire_drops_silent_instances() {
+        let (mut state, mut receiver) = watched_state(">");
+
+        state.insert("local", member("s1", "chat"));
+        state.insert("remote", member("s2", "chat"));
+        drain(&mut receiver);
+
+        std::thread::sleep(Duration::from_millis(5));
+        state.expire("local", Duration::from_millis(1));
+
+        assert_eq!(
+            drain(&mut receiver),
+            vec![(PresenceAction::Leave, "s2".to_string())]
+        );
+        assert!(state.instances.contains_key("local"));
+        assert!(!state.instances.contains_key("remote"));
+    }
+
+    #[test]
+    fn test_watchers_see_only_permitted_members() {
+        let (mut state, mut receiver) =
+            watched_state_as("chat.*", claims(&["chat.secret"], None), 64);
+
+        state.insert("a", member("s1", "chat.lobby"));
+        state.insert("a", member("s2", "chat.secret"));
+
+        assert_eq!(
+            drain(&mut receiver),
+            vec![(PresenceAction::Join, "s1".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_watchers_see_only_their_tenant() {
+        let (mut state, mut receiver) = watched_state_as("chat.*", claims(&[], Some("acme")), 64);
+
+        let mut acme = member("s1", "chat.lobby");
+        acme.tenant = Some("acme".to_string());
+        let mut other = member("s2", "chat.lobby");
+        other.tenant = Some("globex".to_string());
+        state.insert("a", acme);
+        state.insert("a", other);
+        state.insert("a", member("s3", "chat.lobby"));
+
+        assert_eq!(
+            drain(&mut receiver),
+            vec![(PresenceAction::Join, "s1".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_full_watcher_drops_newest_changes() {
+        let (mut state, mut receiver) = watched_state_as(">", claims(&[], None), 2);
+
+        for session in ["s1", "s2", "s3"] {
+            state.insert("a", member(session, "chat"));
+        }
+
+        assert_eq!(
+            drain(&mut receiver),
+            vec![
+                (PresenceAction::Join, "s1".to_string()),
+                (PresenceAction::Join, "s2".to_string()),
+            ]
+        );
+    }
+}
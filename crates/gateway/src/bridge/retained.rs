@@ -0,0 +1,142 @@
+//! Last-value cache for retained subjects.
+//!
+//! For each subject pattern marked as retained, the gateway keeps its own
+//! NATS subscription and remembers the last payload published to every
+//! concrete subject matching it. Publishes go through NATS, so every gateway
+//! instance sees the same updates no matter which one the publisher used.
+//! An empty payload clears the retained value of its subject. The number of
+//! subjects is bounded; past it, the least recently updated subject is
+//! forgotten.
+
+use std::sync::{Arc, Mutex};
+
+use async_nats::Client;
+use futures::StreamExt;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use super::nats::{BridgeError, INTERNAL_SUBJECT_PREFIX, PayloadCompression, decode_payload};
+use super::subjects::SubjectMap;
+use crate::auth::PermissionChecker;
+
+/// Last payload of each retained subject, decoded for delivery
+#[derive(Default)]
+pub(super) struct RetainedCache {
+    entries: Arc<Mutex<SubjectMap<Vec<u8>>>>,
+    /// Subscription tasks feeding `entries`, stopped when the cache is dropped
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl RetainedCache {
+    /// Subscribe to every retained pattern and start recording up to
+    /// `max_subjects` subjects
+    pub(super) async fn start(
+        client: &Client,
+        patterns: Vec<String>,
+        max_subjects: usize,
+        rules: Arc<PayloadCompression>,
+    ) -> Result<Self, BridgeError> {
+        let entries = Arc::new(Mutex::new(SubjectMap::new(max_subjects)));
+        let mut tasks = Vec::with_capacity(patterns.len());
+
+        for pattern in patterns {
+            let subscriber = client
+                .subscribe(pattern)
+                .await
+                .map_err(|e| BridgeError::SubscribeFailed(e.to_string()))?;
+            tasks.push(tokio::spawn(record(
+                subscriber,
+                entries.clone(),
+                rules.clone(),
+            )));
+        }
+
+        Ok(Self { entries, tasks })
+    }
+
+    /// Retained subjects matching `pattern` with their last payloads, in
+    /// subject order
+    pub(super) fn matching(&self, pattern: &str) -> Vec<(String, Vec<u8>)> {
+        let entries = self.entries.lock().unwrap();
+        let mut matching: Vec<_> = entries
+            .iter()
+            .filter(|(subject, _)| PermissionChecker::matches_pattern(pattern, subject))
+            .map(|(subject, payload)| (subject.clone(), payload.clone()))
+            .collect();
+        matching.sort_by(|a, b| a.0.cmp(&b.0));
+        matching
+    }
+}
+
+impl Drop for RetainedCache {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+async fn record(
+    mut subscriber: async_nats::Subscriber,
+    entries: Arc<Mutex<SubjectMap<Vec<u8>>>>,
+    rules: Arc<PayloadCompression>,
+) {
+    while let Some(msg) = subscriber.next().await {
+        if msg.subject.starts_with(INTERNAL_SUBJECT_PREFIX) {
+            continue;
+        }
+        let subject = msg.subject.to_string();
+        if msg.payload.is_empty() {
+            entries.lock().unwrap().remove(&subject);
+            continue;
+        }
+        match decode_payload(&rules, &subject, &msg.payload) {
+            Ok(payload) => {
+                *entries.lock().unwrap().update(subject, Vec::new) = payload;
+            }
+            Err(e) => warn!("Not retaining message on {}: {}", subject, e),
+        }
+    }
+    debug!("Retained subscription ended");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(entries: &[(&str, &[u8])]) -> RetainedCache {
+        let mut subjects = SubjectMap::new(entries.len());
+        for (subject, payload) in entries {
+            *subjects.update(subject.to_string(), Vec::new) = payload.to_vec();
+        }
+        RetainedCache {
+            entries: Arc::new(Mutex::new(subjects)),
+            tasks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_matching_filters_by_pattern() {
+        let cache = cache(&[
+            ("prices.eur", b"1"),
+            ("prices.usd", b"2"),
+            ("status.db", b"up"),
+        ]);
+        let subjects: Vec<_> = cache
+            .matching("prices.*")
+            .into_iter()
+            .map(|(subject, _)| subject)
+            .collect();
+        assert_eq!(subjects, vec!["prices.eur", "prices.usd"]);
+    }
+
+    #[test]
+    fn test_matching_exact_subject() {
+        let cache = cache(&[("status.db", b"up")]);
+        assert_eq!(
+            cache.matching("status.db"),
+            vec![("status.db".to_string(), b"up".to_vec())]
+        );
+        assert!(cache.matching("status.cache").is_empty());
+    }
+}
@@ -0,0 +1,445 @@
+//! Shared upstream subscriptions.
+//!
+//! Sessions subscribing to the same subject share one NATS subscription.
+//! The registry counts the sessions attached to each subject: the first one
+//! subscribes upstream, the last one to leave unsubscribes, and in between a
+//! single task per subject decodes each message once and fans it out to
+//! every attached session under that session's own subscription id.
+//!
+//! Fan-out never waits: each message is offered to every mailbox and a full
+//! one loses messages as its policy says, so a session that stops reading
+//! does not hold up the others on its subject. A subscription under the
+//! Block policy gets an upstream subscription of its own instead, where
+//! waiting for room slows down only that subscription.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+
+use async_nats::{Client, Subscriber};
+use futures::StreamExt;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use super::mailbox::MailboxSender;
+use super::nats::{
+    BridgeError, Connectivity, INTERNAL_SUBJECT_PREFIX, NatsMessage, PayloadCompression,
+    decode_payload, reconnected,
+};
+use crate::protocol::MessageOrigin;
+
+/// Times a subscription NATS ended is taken up again before giving up, if
+/// no message arrives in between
+const MAX_RESUBSCRIBES: u32 = 3;
+
+/// Upstream subscriptions and the sessions attached to them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscriptionStats {
+    /// Distinct subjects subscribed to in NATS
+    pub upstream: usize,
+    /// Session subscriptions served from them
+    pub sessions: usize,
+}
+
+/// A session subscription attached to an upstream subscription
+#[derive(Clone)]
+struct Downstream {
+    mailbox: MailboxSender,
+}
+
+/// Sessions attached to one upstream subscription
+#[derive(Default)]
+struct Fanout {
+    downstreams: HashMap<u64, Downstream>,
+    /// Snapshot of `downstreams` to deliver to, rebuilt after changes so
+    /// attaching a session stays cheap
+    targets: Option<Arc<[Downstream]>>,
+}
+
+impl Fanout {
+    fn targets(&mut self) -> Arc<[Downstream]> {
+        self.targets
+            .get_or_insert_with(|| self.downstreams.values().cloned().collect())
+            .clone()
+    }
+
+    fn insert(&mut self, key: u64, downstream: Downstream) {
+        self.downstreams.insert(key, downstream);
+        self.targets = None;
+    }
+
+    fn remove(&mut self, key: u64) {
+        if self.downstreams.remove(&key).is_some() {
+            self.targets = None;
+        }
+    }
+}
+
+struct Upstream {
+    /// Tells this upstream apart from later ones on the same subject
+    generation: u64,
+    fanout: Arc<Mutex<Fanout>>,
+    task: JoinHandle<()>,
+}
+
+#[derive(Default)]
+struct Upstreams {
+    by_subject: HashMap<String, Upstream>,
+    /// Upstreams of Block subscriptions, one per session, by generation
+    dedicated: HashMap<u64, Upstream>,
+    /// Source of upstream generations and downstream keys
+    next_key: u64,
+}
+
+impl Upstreams {
+    fn next_key(&mut self) -> u64 {
+        self.next_key += 1;
+        self.next_key
+    }
+
+    fn all(&self) -> impl Iterator<Item = &Upstream> {
+        self.by_subject.values().chain(self.dedicated.values())
+    }
+
+    /// Attach a session to the upstream subscription of `subject`, if any
+    fn attach(
+        &mut self,
+        registry: &Arc<Mutex<Upstreams>>,
+        subject: &str,
+        downstream: &Downstream,
+    ) -> Option<SharedLease> {
+        let key = self.next_key();
+        let upstream = self.by_subject.get(subject)?;
+        upstream
+            .fanout
+            .lock()
+            .unwrap()
+            .insert(key, downstream.clone());
+        Some(SharedLease {
+            registry: Arc::downgrade(registry),
+            subject: subject.to_string(),
+            generation: upstream.generation,
+            key,
+        })
+    }
+
+    /// The upstream subscription a lease was given for, if still there
+    fn get(&self, subject: &str, generation: u64) -> Option<&Upstream> {
+        self.by_subject
+            .get(subject)
+            .filter(|upstream| upstream.generation == generation)
+            .or_else(|| self.dedicated.get(&generation))
+    }
+
+    /// Forget the upstream subscription of `subject` if it is still the
+    /// given generation
+    fn remove(&mut self, subject: &str, generation: u64) -> Option<Upstream> {
+        if let Some(upstream) = self.dedicated.remove(&generation) {
+            Some(upstream)
+        } else if self
+            .by_subject
+            .get(subject)
+            .is_some_and(|upstream| upstream.generation == generation)
+        {
+            self.by_subject.remove(subject)
+        } else {
+            None
+        }
+    }
+}
+
+/// Reference-counted NATS subscriptions, one per distinct subject
+pub(super) struct SharedSubscriptions {
+    client: Client,
+    connectivity: watch::Receiver<Connectivity>,
+    upstreams: Arc<Mutex<Upstreams>>,
+}
+
+impl SharedSubscriptions {
+    pub(super) fn new(client: Client, connectivity: watch::Receiver<Connectivity>) -> Self {
+        Self {
+            client,
+            connectivity,
+            upstreams: Arc::new(Mutex::new(Upstreams::default())),
+        }
+    }
+
+    /// Attach a session subscription to `subject`, subscribing upstream if
+    /// it is the first. A Block subscription always subscribes upstream on
+    /// its own. Payloads are decoded with `rules`.
+    pub(super) async fn subscribe(
+        &self,
+        subject: String,
+        mailbox: MailboxSender,
+        rules: Arc<PayloadCompression>,
+    ) -> Result<SharedLease, BridgeError> {
+        let dedicated = mailbox.blocks();
+        let downstream = Downstream { mailbox };
+        if !dedicated
+            && let Some(lease) =
+                self.upstreams
+                    .lock()
+                    .unwrap()
+                    .attach(&self.upstreams, &subject, &downstream)
+        {
+            return Ok(lease);
+        }
+
+        let subscriber = self
+            .client
+            .subscribe(subject.clone())
+            .await
+            .map_err(|e| BridgeError::SubscribeFailed(e.to_string()))?;
+
+        let mut upstreams = self.upstreams.lock().unwrap();
+        // Another session may have subscribed meanwhile; dropping ours
+        // unsubscribes it again
+        if !dedicated && let Some(lease) = upstreams.attach(&self.upstreams, &subject, &downstream)
+        {
+            return Ok(lease);
+        }
+
+        let generation = upstreams.next_key();
+        let key = upstreams.next_key();
+        let mut fanout = Fanout::default();
+        fanout.insert(key, downstream);
+        let fanout = Arc::new(Mutex::new(fanout));
+        let task = tokio::spawn(fan_out(
+            subscriber,
+            Source {
+                client: self.client.clone(),
+                connectivity: self.connectivity.clone(),
+                registry: Arc::downgrade(&self.upstreams),
+                subject: subject.clone(),
+                generation,
+            },
+            fanout.clone(),
+            rules,
+        ));
+        debug!("Subscribed to {} in NATS", subject);
+        let upstream = Upstream {
+            generation,
+            fanout,
+            task,
+        };
+        if dedicated {
+            upstreams.dedicated.insert(generation, upstream);
+        } else {
+            upstreams.by_subject.insert(subject.clone(), upstream);
+        }
+
+        Ok(SharedLease {
+            registry: Arc::downgrade(&self.upstreams),
+            subject,
+            generation,
+            key,
+        })
+    }
+
+    pub(super) fn stats(&self) -> SubscriptionStats {
+        let upstreams = self.upstreams.lock().unwrap();
+        SubscriptionStats {
+            upstream: upstreams.by_subject.len() + upstreams.dedicated.len(),
+            sessions: upstreams
+                .all()
+                .map(|upstream| upstream.fanout.lock().unwrap().downstreams.len())
+                .sum(),
+        }
+    }
+}
+
+impl Drop for SharedSubscriptions {
+    fn drop(&mut self) {
+        for upstream in self.upstreams.lock().unwrap().all() {
+            upstream.task.abort();
+        }
+    }
+}
+
+/// A session's place in an upstream subscription. Dropping it detaches the
+/// session, and unsubscribes upstream if it was the last one.
+pub(super) struct SharedLease {
+    registry: Weak<Mutex<Upstreams>>,
+    subject: String,
+    generation: u64,
+    key: u64,
+}
+
+impl Drop for SharedLease {
+    fn drop(&mut self) {
+        let Some(registry) = self.registry.upgrade() else {
+            return;
+        };
+        let mut upstreams = registry.lock().unwrap();
+        let Some(upstream) = upstreams.get(&self.subject, self.generation) else {
+            return;
+        };
+
+        let mut fanout = upstream.fanout.lock().unwrap();
+        fanout.remove(self.key);
+        if fanout.downstreams.is_empty() {
+            drop(fanout);
+            if let Some(upstream) = upstreams.remove(&self.subject, self.generation) {
+                // Dropping the subscriber unsubscribes in NATS
+                upstream.task.abort();
+                debug!("Unsubscribed from {} in NATS", self.subject);
+            }
+        }
+    }
+}
+
+/// Where an upstream subscription comes from, to take it up again
+struct Source {
+    client: Client,
+    connectivity: watch::Receiver<Connectivity>,
+    registry: Weak<Mutex<Upstreams>>,
+    subject: String,
+    generation: u64,
+}
+
+impl Source {
+    /// Subscribe again once NATS is reachable
+    async fn resubscribe(&mut self) -> Option<Subscriber> {
+        if !reconnected(&mut self.connectivity).await {
+            return None;
+        }
+        self.client
+            .subscribe(self.subject.clone())
+            .await
+            .inspect_err(|e| warn!("Failed to resubscribe to {}: {}", self.subject, e))
+            .ok()
+    }
+}
+
+/// Deliver the messages of an upstream subscription to every attached
+/// session until the last one leaves. If NATS ends the subscription it is
+/// taken up again once the server is reachable; only if that fails is each
+/// session's mailbox closed.
+async fn fan_out(
+    mut subscriber: Subscriber,
+    mut source: Source,
+    fanout: Arc<Mutex<Fanout>>,
+    rules: Arc<PayloadCompression>,
+) {
+    let mut resubscribes = 0;
+    loop {
+        match subscriber.next().await {
+            // Gateway-internal traffic never reaches clients
+            Some(msg) if msg.subject.starts_with(INTERNAL_SUBJECT_PREFIX) => {}
+            Some(msg) => {
+                let payload = match decode_payload(&rules, &msg.subject, &msg.payload) {
+                    Ok(payload) => payload,
+                    Err(e) => {
+                        warn!("Dropping message on {}: {}", msg.subject, e);
+                        continue;
+                    }
+                };
+                resubscribes = 0;
+                let targets = fanout.lock().unwrap().targets();
+                for target in targets.iter() {
+                    let nats_msg = NatsMessage {
+                        subscription_id: target.mailbox.subscription_id(),
+                        subject: msg.subject.to_string(),
+                        payload: payload.clone(),
+                        origin: MessageOrigin::Live,
+                    };
+                    // Only a dedicated upstream has a Block mailbox
+                    if target.mailbox.blocks() {
+                        target.mailbox.send(nats_msg).await;
+                    } else {
+                        target.mailbox.offer(nats_msg);
+                    }
+                }
+            }
+            None if resubscribes < MAX_RESUBSCRIBES => {
+                resubscribes += 1;
+                if let Some(renewed) = source.resubscribe().await {
+                    info!("Re-established NATS subscription to {}", source.subject);
+                    subscriber = renewed;
+                    continue;
+                }
+                break;
+            }
+            None => break,
+        }
+    }
+
+    debug!("NATS subscription ended for {}", source.subject);
+    // Sessions subscribing from now on start a new upstream subscription
+    if let Some(registry) = source.registry.upgrade() {
+        registry
+            .lock()
+            .unwrap()
+            .remove(&source.subject, source.generation);
+    }
+    let targets = fanout.lock().unwrap().targets();
+    for target in targets.iter() {
+        target.mailbox.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::bridge::mailbox::{Mailbox, mailbox};
+    use crate::protocol::SlowConsumerPolicy;
+
+    fn downstream(subscription_id: u64) -> (Downstream, Mailbox) {
+        let (ready, _) = mpsc::unbounded_channel();
+        let (mailbox, receiver) = mailbox(subscription_id, 1, SlowConsumerPolicy::Block, ready);
+        (Downstream { mailbox }, receiver)
+    }
+
+    #[test]
+    fn test_fanout_targets_follow_changes() {
+        let mut fanout = Fanout::default();
+        let (first, _first_rx) = downstream(1);
+        let (second, _second_rx) = downstream(7);
+
+        fanout.insert(1, first);
+        assert_eq!(fanout.targets().len(), 1);
+        // The snapshot is reused until the sessions change
+        assert!(Arc::ptr_eq(&fanout.targets(), &fanout.targets()));
+
+        fanout.insert(2, second);
+        let mut ids: Vec<u64> = fanout
+            .targets()
+            .iter()
+            .map(|t| t.mailbox.subscription_id())
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec![1, 7]);
+
+        fanout.remove(1);
+        fanout.remove(1);
+        let ids: Vec<u64> = fanout
+            .targets()
+            .iter()
+            .map(|t| t.mailbox.subscription_id())
+            .collect();
+        assert_eq!(ids, vec![7]);
+    }
+
+    #[tokio::test]
+    async fn test_dedicated_upstreams_are_not_shared() {
+        let registry = Arc::new(Mutex::new(Upstreams::default()));
+        let (blocking, _blocking_rx) = downstream(1);
+        let mut fanout = Fanout::default();
+        fanout.insert(1, blocking.clone());
+        let upstream = Upstream {
+            generation: 5,
+            fanout: Arc::new(Mutex::new(fanout)),
+            task: tokio::spawn(async {}),
+        };
+
+        let mut upstreams = registry.lock().unwrap();
+        upstreams.dedicated.insert(5, upstream);
+        // A session on the same subject cannot join it
+        assert!(upstreams.attach(&registry, "chat", &blocking).is_none());
+        assert!(upstreams.get("chat", 5).is_some());
+        assert!(upstreams.remove("chat", 5).is_some());
+        assert!(upstreams.get("chat", 5).is_none());
+    }
+}
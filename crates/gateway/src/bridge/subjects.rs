@@ -0,0 +1,116 @@
+//! Per-subject state with a bound on the number of subjects.
+//!
+//! Caches fed by wildcard subscriptions see every concrete subject that
+//! matches, and publishers choose those freely. Once the bound is reached,
+//! a new subject evicts the one updated least recently.
+
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug)]
+struct Slot<V> {
+    /// When the subject was last updated, in update order
+    touched: u64,
+    value: V,
+}
+
+/// Values by subject, keeping at most `capacity` subjects
+#[derive(Debug, Default)]
+pub(super) struct SubjectMap<V> {
+    slots: HashMap<String, Slot<V>>,
+    /// Subjects by the update that last touched them, least recent first
+    order: BTreeMap<u64, String>,
+    next_touch: u64,
+    capacity: usize,
+}
+
+impl<V> SubjectMap<V> {
+    pub(super) fn new(capacity: usize) -> Self {
+        Self {
+            slots: HashMap::new(),
+            order: BTreeMap::new(),
+            next_touch: 0,
+            capacity,
+        }
+    }
+
+    /// The value of `subject` for updating, created with `default` if the
+    /// subject is new. Marks the subject as the most recently updated.
+    pub(super) fn update(&mut self, subject: String, default: impl FnOnce() -> V) -> &mut V {
+        self.next_touch += 1;
+        let touched = self.next_touch;
+
+        if let Some(slot) = self.slots.get_mut(&subject) {
+            self.order.remove(&slot.touched);
+            slot.touched = touched;
+        } else {
+            while self.slots.len() >= self.capacity.max(1) {
+                let Some((_, evicted)) = self.order.pop_first() else {
+                    break;
+                };
+                self.slots.remove(&evicted);
+            }
+            self.slots.insert(
+                subject.clone(),
+                Slot {
+                    touched,
+                    value: default(),
+                },
+            );
+        }
+
+        self.order.insert(touched, subject.clone());
+        &mut self
+            .slots
+            .get_mut(&subject)
+            .expect("slot just updated")
+            .value
+    }
+
+    /// Forget `subject`
+    pub(super) fn remove(&mut self, subject: &str) {
+        if let Some(slot) = self.slots.remove(subject) {
+            self.order.remove(&slot.touched);
+        }
+    }
+
+    pub(super) fn iter(&self) -> impl Iterator<Item = (&String, &V)> {
+        self.slots
+            .iter()
+            .map(|(subject, slot)| (subject, &slot.value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subjects<V>(map: &SubjectMap<V>) -> Vec<String> {
+        let mut subjects: Vec<_> = map.iter().map(|(subject, _)| subject.clone()).collect();
+        subjects.sort();
+        subjects
+    }
+
+    #[test]
+    fn test_evicts_least_recently_updated() {
+        let mut map = SubjectMap::new(2);
+        *map.update("a".to_string(), || 0) = 1;
+        *map.update("b".to_string(), || 0) = 2;
+        // Updating a makes b the least recent
+        *map.update("a".to_string(), || 0) += 10;
+        *map.update("c".to_string(), || 0) = 3;
+
+        assert_eq!(subjects(&map), vec!["a", "c"]);
+        assert_eq!(*map.update("a".to_string(), || 0), 11);
+    }
+
+    #[test]
+    fn test_remove_frees_room() {
+        let mut map = SubjectMap::new(2);
+        map.update("a".to_string(), || ());
+        map.update("b".to_string(), || ());
+        map.remove("a");
+        map.update("c".to_string(), || ());
+
+        assert_eq!(subjects(&map), vec!["b", "c"]);
+    }
+}
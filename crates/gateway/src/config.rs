@@ -1,4 +1,67 @@
 use std::env;
+use std::path::PathBuf;
+
+use crate::protocol::compression;
+use crate::protocol::{CompressionAlgorithm, SlowConsumerPolicy};
+use crate::transport::SubjectMapping;
+
+/// Default payload limit (1 MiB), matching the NATS server default
+const DEFAULT_MAX_PAYLOAD_BYTES: u32 = 1024 * 1024;
+
+/// Default time connections get to finish up after a GoAway
+const DEFAULT_DRAIN_GRACE_MS: u64 = 5000;
+
+/// Default delay clients are asked to wait before reconnecting after a GoAway
+const DEFAULT_RECONNECT_AFTER_MS: u32 = 1000;
+
+/// Default number of deliveries buffered per subscription while out of credit
+const DEFAULT_CREDIT_BUFFER: usize = 1024;
+
+/// Default number of messages queued per subscription for a client that
+/// is not keeping up
+const DEFAULT_SUBSCRIPTION_BUFFER: usize = 256;
+
+/// Default size above which frames to a compressing client are compressed
+const DEFAULT_COMPRESSION_THRESHOLD_BYTES: u32 = 1024;
+
+/// Default size budget for a batched frame
+const DEFAULT_BATCH_MAX_BYTES: u32 = 64 * 1024;
+
+/// Default number of messages recorded per subject with history
+const DEFAULT_HISTORY_LENGTH: usize = 100;
+
+/// Default number of subjects whose history is recorded
+const DEFAULT_HISTORY_MAX_SUBJECTS: usize = 10_000;
+
+/// Default number of subjects whose last message is retained
+const DEFAULT_RETAINED_MAX_SUBJECTS: usize = 10_000;
+
+/// Default time a dropped connection's session is kept for resumption
+const DEFAULT_RESUME_GRACE_MS: u64 = 30_000;
+
+/// Default number of deliveries buffered for a detached session
+const DEFAULT_RESUME_BUFFER: usize = 1024;
+
+/// Default time a delivery may wait for others to share its batched frame
+const DEFAULT_BATCH_MAX_DELAY_MS: u64 = 5;
+
+/// Default name the gateway's NATS connection shows in server monitoring
+const DEFAULT_NATS_CONNECTION_NAME: &str = "mottomesh-gateway";
+
+/// Default longest wait between attempts to reconnect to NATS
+const DEFAULT_NATS_RECONNECT_DELAY_MAX_MS: u64 = 4000;
+
+/// Default time allowed for establishing a NATS connection
+const DEFAULT_NATS_CONNECTION_TIMEOUT_MS: u64 = 5000;
+
+/// Default idle time after which NATS removes a durable consumer (a day)
+const DEFAULT_CONSUMER_INACTIVE_THRESHOLD_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// Default number of durable consumers a user may hold per stream
+const DEFAULT_CONSUMERS_PER_USER: usize = 16;
+
+/// Default interval between pings that detect a dead NATS connection
+const DEFAULT_NATS_PING_INTERVAL_MS: u64 = 60_000;
 
 #[derive(Debug, Clone)]
 pub struct GatewayConfig {
@@ -6,10 +69,326 @@ pub struct GatewayConfig {
     pub host: String,
     /// WebSocket port
     pub ws_port: u16,
-    /// NATS server URL
-    pub nats_url: String,
+    /// Backend messages are relayed through
+    pub broker: BrokerKind,
+    /// How to connect to NATS
+    pub nats: NatsConfig,
     /// JWT secret for token validation
     pub jwt_secret: String,
+    /// Largest payload a client may publish or send as a request
+    pub max_payload_bytes: u32,
+    /// How long connections stay open after a GoAway before being closed
+    pub drain_grace_ms: u64,
+    /// Reconnect delay suggested to clients in GoAway
+    pub reconnect_after_ms: u32,
+    /// Another gateway instance clients may migrate to on GoAway
+    pub alternate_url: Option<String>,
+    /// Flush a batched frame once its messages reach this many bytes
+    pub batch_max_bytes: u32,
+    /// Flush a batched frame this long after its first message was queued
+    pub batch_max_delay_ms: u64,
+    /// Deliveries buffered per credit-limited subscription while out of credit
+    pub credit_buffer: usize,
+    /// What to do when a credit-limited subscription's buffer is full
+    pub overflow_policy: CreditOverflowPolicy,
+    /// Messages queued per subscription before its slow-consumer policy applies
+    pub subscription_buffer: usize,
+    /// Slow-consumer policy of subscriptions no rule or client choice covers
+    pub slow_consumer_policy: SlowConsumerPolicy,
+    /// Slow-consumer policies by subscription subject, first match wins
+    pub subject_slow_consumer: Vec<SubjectSlowConsumer>,
+    /// Frames to a client that negotiated compression are compressed from this size
+    pub compression_threshold_bytes: u32,
+    /// Compression of payloads stored in NATS, by subject
+    pub subject_compression: Vec<SubjectCompression>,
+    /// Rewrites between client-facing and NATS subjects, first match wins
+    pub subject_mappings: Vec<SubjectMapping>,
+    /// Subject patterns whose last message is replayed to new subscribers
+    pub retained_subjects: Vec<String>,
+    /// Most subjects retained; the least recently updated is dropped first
+    pub retained_max_subjects: usize,
+    /// Subject patterns whose recent messages can be replayed on subscribe
+    pub history_subjects: Vec<String>,
+    /// Messages recorded per subject with history
+    pub history_length: usize,
+    /// Most subjects with recorded history; the least recently updated is dropped first
+    pub history_max_subjects: usize,
+    /// How long a dropped connection's session is kept for resumption (0 disables it)
+    pub resume_grace_ms: u64,
+    /// Deliveries buffered for a detached session before it is given up
+    pub resume_buffer: usize,
+    /// Key signing the identity headers of relayed messages
+    pub identity_signing_key: Option<String>,
+    /// Idle time after which NATS removes a durable consumer
+    pub consumer_inactive_threshold_ms: u64,
+    /// Durable consumers a user may hold per stream
+    pub consumers_per_user: usize,
+}
+
+/// How the gateway connects to NATS
+#[derive(Debug, Clone, PartialEq)]
+pub struct NatsConfig {
+    /// Seed server URLs. Servers the cluster announces are added once
+    /// connected.
+    pub urls: Vec<String>,
+    /// Connection name shown in server monitoring
+    pub name: Option<String>,
+    pub auth: NatsAuth,
+    pub tls: NatsTls,
+    /// Reconnect attempts before giving up, unlimited if `None`
+    pub max_reconnects: Option<usize>,
+    /// Longest wait between reconnect attempts; the wait doubles up to it
+    pub reconnect_delay_max_ms: u64,
+    /// Time allowed for establishing a connection
+    pub connection_timeout_ms: u64,
+    /// Interval between pings that detect a dead connection
+    pub ping_interval_ms: u64,
+}
+
+/// Credentials presented to NATS
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum NatsAuth {
+    #[default]
+    None,
+    UserPassword {
+        user: String,
+        password: String,
+    },
+    Token(String),
+    /// NKey seed whose public key the server knows
+    NKey(String),
+    /// `.creds` file holding a user JWT and its NKey seed
+    CredentialsFile(PathBuf),
+}
+
+/// TLS settings for the NATS connection
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NatsTls {
+    /// Use TLS even if the server does not ask for it
+    pub required: bool,
+    /// CA certificate the servers are verified with, besides the system roots
+    pub ca_path: Option<PathBuf>,
+    /// Certificate and key presented to servers that verify clients
+    pub client_cert: Option<(PathBuf, PathBuf)>,
+}
+
+impl NatsConfig {
+    /// Connect to a comma-separated list of seed servers without
+    /// credentials or TLS
+    pub fn new(urls: &str) -> Self {
+        Self {
+            urls: parse_list(urls),
+            name: Some(DEFAULT_NATS_CONNECTION_NAME.to_string()),
+            auth: NatsAuth::None,
+            tls: NatsTls::default(),
+            max_reconnects: None,
+            reconnect_delay_max_ms: DEFAULT_NATS_RECONNECT_DELAY_MAX_MS,
+            connection_timeout_ms: DEFAULT_NATS_CONNECTION_TIMEOUT_MS,
+            ping_interval_ms: DEFAULT_NATS_PING_INTERVAL_MS,
+        }
+    }
+
+    fn from_env() -> Result<Self, ConfigError> {
+        let urls = env::var("NATS_URL").unwrap_or_else(|_| "localhost:4222".to_string());
+        let mut config = Self::new(&urls);
+        if config.urls.is_empty() {
+            return Err(ConfigError::InvalidValue("NATS_URL".to_string()));
+        }
+
+        if let Ok(name) = env::var("NATS_CONNECTION_NAME") {
+            config.name = Some(name).filter(|name| !name.is_empty());
+        }
+        config.auth = NatsAuth::from_env()?;
+        config.tls = NatsTls::from_env()?;
+        config.max_reconnects = match env::var("NATS_MAX_RECONNECTS") {
+            Ok(value) => Some(
+                value
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidValue("NATS_MAX_RECONNECTS".to_string()))?,
+            ),
+            Err(_) => None,
+        };
+        config.reconnect_delay_max_ms = env::var("NATS_RECONNECT_DELAY_MAX_MS")
+            .unwrap_or_else(|_| DEFAULT_NATS_RECONNECT_DELAY_MAX_MS.to_string())
+            .parse()
+            .map_err(|_| ConfigError::InvalidValue("NATS_RECONNECT_DELAY_MAX_MS".to_string()))?;
+        config.connection_timeout_ms = env::var("NATS_CONNECTION_TIMEOUT_MS")
+            .unwrap_or_else(|_| DEFAULT_NATS_CONNECTION_TIMEOUT_MS.to_string())
+            .parse()
+            .map_err(|_| ConfigError::InvalidValue("NATS_CONNECTION_TIMEOUT_MS".to_string()))?;
+        config.ping_interval_ms = env::var("NATS_PING_INTERVAL_MS")
+            .unwrap_or_else(|_| DEFAULT_NATS_PING_INTERVAL_MS.to_string())
+            .parse()
+            .map_err(|_| ConfigError::InvalidValue("NATS_PING_INTERVAL_MS".to_string()))?;
+
+        Ok(config)
+    }
+}
+
+impl NatsAuth {
+    /// Read credentials from the environment. At most one kind may be set.
+    fn from_env() -> Result<Self, ConfigError> {
+        let mut auth = Vec::new();
+
+        if let Ok(user) = env::var("NATS_USER") {
+            let password = env::var("NATS_PASSWORD")
+                .map_err(|_| ConfigError::MissingEnvVar("NATS_PASSWORD".to_string()))?;
+            auth.push(("NATS_USER", NatsAuth::UserPassword { user, password }));
+        }
+        if let Ok(token) = env::var("NATS_TOKEN") {
+            auth.push(("NATS_TOKEN", NatsAuth::Token(token)));
+        }
+        if let Ok(seed) = env::var("NATS_NKEY_SEED") {
+            auth.push(("NATS_NKEY_SEED", NatsAuth::NKey(seed)));
+        }
+        if let Ok(path) = env::var("NATS_CREDS_FILE") {
+            auth.push(("NATS_CREDS_FILE", NatsAuth::CredentialsFile(path.into())));
+        }
+
+        match auth.len() {
+            0 => Ok(NatsAuth::None),
+            1 => Ok(auth.remove(0).1),
+            _ => Err(ConfigError::ConflictingEnvVars(
+                auth[0].0.to_string(),
+                auth[1].0.to_string(),
+            )),
+        }
+    }
+}
+
+impl NatsTls {
+    fn from_env() -> Result<Self, ConfigError> {
+        let client_cert = match (env::var("NATS_TLS_CERT"), env::var("NATS_TLS_KEY")) {
+            (Ok(cert), Ok(key)) => Some((cert.into(), key.into())),
+            (Ok(_), Err(_)) => return Err(ConfigError::MissingEnvVar("NATS_TLS_KEY".to_string())),
+            (Err(_), Ok(_)) => return Err(ConfigError::MissingEnvVar("NATS_TLS_CERT".to_string())),
+            (Err(_), Err(_)) => None,
+        };
+
+        Ok(Self {
+            required: match env::var("NATS_TLS_REQUIRED") {
+                Ok(value) => value
+                    .parse()
+                    .map_err(|_| ConfigError::InvalidValue("NATS_TLS_REQUIRED".to_string()))?,
+                Err(_) => false,
+            },
+            ca_path: env::var("NATS_TLS_CA").ok().map(PathBuf::from),
+            client_cert,
+        })
+    }
+}
+
+/// Compression applied to payloads on the NATS side of matching subjects.
+///
+/// The gateway compresses what clients publish to these subjects and
+/// decompresses what it delivers from them, so clients always see plain
+/// payloads.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubjectCompression {
+    /// Subject pattern, with NATS-style wildcards
+    pub pattern: String,
+    pub algorithm: CompressionAlgorithm,
+}
+
+impl SubjectCompression {
+    /// Parse a comma-separated list of `pattern=algorithm` rules
+    pub fn parse_list(s: &str) -> Option<Vec<Self>> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+            .map(|rule| {
+                let (pattern, algorithm) = rule.split_once('=')?;
+                Some(Self {
+                    pattern: pattern.trim().to_string(),
+                    algorithm: compression::parse_algorithm(algorithm.trim())?,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Slow-consumer policy for subscriptions to matching subjects, unless the
+/// client chooses one
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubjectSlowConsumer {
+    /// Subject pattern, with NATS-style wildcards
+    pub pattern: String,
+    pub policy: SlowConsumerPolicy,
+}
+
+impl SubjectSlowConsumer {
+    /// Parse a comma-separated list of `pattern=policy` rules
+    pub fn parse_list(s: &str) -> Option<Vec<Self>> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+            .map(|rule| {
+                let (pattern, policy) = rule.split_once('=')?;
+                Some(Self {
+                    pattern: pattern.trim().to_string(),
+                    policy: parse_slow_consumer_policy(policy.trim())?,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Parse a slow-consumer policy from its `GATEWAY_SLOW_CONSUMER_POLICY`
+/// spelling. A free function, as the policy is a protocol type.
+pub fn parse_slow_consumer_policy(s: &str) -> Option<SlowConsumerPolicy> {
+    match s.to_lowercase().as_str() {
+        "block" => Some(SlowConsumerPolicy::Block),
+        "drop-oldest" => Some(SlowConsumerPolicy::DropOldest),
+        "drop-newest" => Some(SlowConsumerPolicy::DropNewest),
+        "conflate" => Some(SlowConsumerPolicy::Conflate),
+        "disconnect" => Some(SlowConsumerPolicy::Disconnect),
+        _ => None,
+    }
+}
+
+/// Backend the gateway relays messages through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrokerKind {
+    /// A NATS server or cluster
+    Nats,
+    /// Within the gateway process, for a single node
+    Memory,
+}
+
+impl BrokerKind {
+    /// Parse a broker kind from a string
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "nats" => Some(BrokerKind::Nats),
+            "memory" => Some(BrokerKind::Memory),
+            _ => None,
+        }
+    }
+}
+
+/// What happens to a delivery that arrives when a credit-limited
+/// subscription's buffer is full. Not to be confused with the
+/// [`SlowConsumerPolicy`] of the queue in front of that buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CreditOverflowPolicy {
+    /// Discard the oldest buffered delivery to make room
+    DropOldest,
+    /// Discard the delivery that just arrived
+    DropNewest,
+    /// Close the client connection
+    Disconnect,
+}
+
+impl CreditOverflowPolicy {
+    /// Parse a policy from its `GATEWAY_OVERFLOW_POLICY` spelling
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "drop-oldest" => Some(CreditOverflowPolicy::DropOldest),
+            "drop-newest" => Some(CreditOverflowPolicy::DropNewest),
+            "disconnect" => Some(CreditOverflowPolicy::Disconnect),
+            _ => None,
+        }
+    }
 }
 
 impl GatewayConfig {
@@ -23,8 +402,114 @@ impl GatewayConfig {
                 .unwrap_or_else(|_| "4434".to_string())
                 .parse()
                 .map_err(|_| ConfigError::InvalidPort)?,
-            nats_url: env::var("NATS_URL").unwrap_or_else(|_| "localhost:4222".to_string()),
+            broker: match env::var("GATEWAY_BROKER") {
+                Ok(value) => BrokerKind::parse(&value)
+                    .ok_or_else(|| ConfigError::InvalidValue("GATEWAY_BROKER".to_string()))?,
+                Err(_) => BrokerKind::Nats,
+            },
+            nats: NatsConfig::from_env()?,
             jwt_secret,
+            max_payload_bytes: env::var("GATEWAY_MAX_PAYLOAD_BYTES")
+                .unwrap_or_else(|_| DEFAULT_MAX_PAYLOAD_BYTES.to_string())
+                .parse()
+                .map_err(|_| ConfigError::InvalidValue("GATEWAY_MAX_PAYLOAD_BYTES".to_string()))?,
+            drain_grace_ms: env::var("GATEWAY_DRAIN_GRACE_MS")
+                .unwrap_or_else(|_| DEFAULT_DRAIN_GRACE_MS.to_string())
+                .parse()
+                .map_err(|_| ConfigError::InvalidValue("GATEWAY_DRAIN_GRACE_MS".to_string()))?,
+            reconnect_after_ms: env::var("GATEWAY_RECONNECT_AFTER_MS")
+                .unwrap_or_else(|_| DEFAULT_RECONNECT_AFTER_MS.to_string())
+                .parse()
+                .map_err(|_| ConfigError::InvalidValue("GATEWAY_RECONNECT_AFTER_MS".to_string()))?,
+            alternate_url: env::var("GATEWAY_ALTERNATE_URL").ok(),
+            batch_max_bytes: env::var("GATEWAY_BATCH_MAX_BYTES")
+                .unwrap_or_else(|_| DEFAULT_BATCH_MAX_BYTES.to_string())
+                .parse()
+                .map_err(|_| ConfigError::InvalidValue("GATEWAY_BATCH_MAX_BYTES".to_string()))?,
+            batch_max_delay_ms: env::var("GATEWAY_BATCH_MAX_DELAY_MS")
+                .unwrap_or_else(|_| DEFAULT_BATCH_MAX_DELAY_MS.to_string())
+                .parse()
+                .map_err(|_| ConfigError::InvalidValue("GATEWAY_BATCH_MAX_DELAY_MS".to_string()))?,
+            credit_buffer: env::var("GATEWAY_CREDIT_BUFFER")
+                .unwrap_or_else(|_| DEFAULT_CREDIT_BUFFER.to_string())
+                .parse()
+                .map_err(|_| ConfigError::InvalidValue("GATEWAY_CREDIT_BUFFER".to_string()))?,
+            overflow_policy: match env::var("GATEWAY_OVERFLOW_POLICY") {
+                Ok(value) => CreditOverflowPolicy::parse(&value).ok_or_else(|| {
+                    ConfigError::InvalidValue("GATEWAY_OVERFLOW_POLICY".to_string())
+                })?,
+                Err(_) => CreditOverflowPolicy::DropOldest,
+            },
+            subscription_buffer: env::var("GATEWAY_SUBSCRIPTION_BUFFER")
+                .unwrap_or_else(|_| DEFAULT_SUBSCRIPTION_BUFFER.to_string())
+                .parse()
+                .map_err(|_| {
+                    ConfigError::InvalidValue("GATEWAY_SUBSCRIPTION_BUFFER".to_string())
+                })?,
+            slow_consumer_policy: match env::var("GATEWAY_SLOW_CONSUMER_POLICY") {
+                Ok(value) => parse_slow_consumer_policy(&value).ok_or_else(|| {
+                    ConfigError::InvalidValue("GATEWAY_SLOW_CONSUMER_POLICY".to_string())
+                })?,
+                Err(_) => SlowConsumerPolicy::DropOldest,
+            },
+            subject_slow_consumer: match env::var("GATEWAY_SUBJECT_SLOW_CONSUMER") {
+                Ok(value) => SubjectSlowConsumer::parse_list(&value).ok_or_else(|| {
+                    ConfigError::InvalidValue("GATEWAY_SUBJECT_SLOW_CONSUMER".to_string())
+                })?,
+                Err(_) => Vec::new(),
+            },
+            compression_threshold_bytes: env::var("GATEWAY_COMPRESSION_THRESHOLD_BYTES")
+                .unwrap_or_else(|_| DEFAULT_COMPRESSION_THRESHOLD_BYTES.to_string())
+                .parse()
+                .map_err(|_| {
+                    ConfigError::InvalidValue("GATEWAY_COMPRESSION_THRESHOLD_BYTES".to_string())
+                })?,
+            subject_compression: match env::var("GATEWAY_SUBJECT_COMPRESSION") {
+                Ok(value) => SubjectCompression::parse_list(&value).ok_or_else(|| {
+                    ConfigError::InvalidValue("GATEWAY_SUBJECT_COMPRESSION".to_string())
+                })?,
+                Err(_) => Vec::new(),
+            },
+            subject_mappings: match env::var("GATEWAY_SUBJECT_MAPPINGS") {
+                Ok(value) => SubjectMapping::parse_list(&value).ok_or_else(|| {
+                    ConfigError::InvalidValue("GATEWAY_SUBJECT_MAPPINGS".to_string())
+                })?,
+                Err(_) => Vec::new(),
+            },
+            retained_subjects: env::var("GATEWAY_RETAINED_SUBJECTS")
+                .map(|value| parse_list(&value))
+                .unwrap_or_default(),
+            retained_max_subjects: parse_positive(
+                "GATEWAY_RETAINED_MAX_SUBJECTS",
+                DEFAULT_RETAINED_MAX_SUBJECTS,
+            )?,
+            history_subjects: env::var("GATEWAY_HISTORY_SUBJECTS")
+                .map(|value| parse_list(&value))
+                .unwrap_or_default(),
+            history_length: parse_positive("GATEWAY_HISTORY_LENGTH", DEFAULT_HISTORY_LENGTH)?,
+            history_max_subjects: parse_positive(
+                "GATEWAY_HISTORY_MAX_SUBJECTS",
+                DEFAULT_HISTORY_MAX_SUBJECTS,
+            )?,
+            resume_grace_ms: env::var("GATEWAY_RESUME_GRACE_MS")
+                .unwrap_or_else(|_| DEFAULT_RESUME_GRACE_MS.to_string())
+                .parse()
+                .map_err(|_| ConfigError::InvalidValue("GATEWAY_RESUME_GRACE_MS".to_string()))?,
+            resume_buffer: env::var("GATEWAY_RESUME_BUFFER")
+                .unwrap_or_else(|_| DEFAULT_RESUME_BUFFER.to_string())
+                .parse()
+                .map_err(|_| ConfigError::InvalidValue("GATEWAY_RESUME_BUFFER".to_string()))?,
+            identity_signing_key: env::var("GATEWAY_IDENTITY_SIGNING_KEY").ok(),
+            consumer_inactive_threshold_ms: env::var("GATEWAY_CONSUMER_INACTIVE_THRESHOLD_MS")
+                .unwrap_or_else(|_| DEFAULT_CONSUMER_INACTIVE_THRESHOLD_MS.to_string())
+                .parse()
+                .map_err(|_| {
+                    ConfigError::InvalidValue("GATEWAY_CONSUMER_INACTIVE_THRESHOLD_MS".to_string())
+                })?,
+            consumers_per_user: parse_positive(
+                "GATEWAY_CONSUMERS_PER_USER",
+                DEFAULT_CONSUMERS_PER_USER,
+            )?,
         })
     }
 
@@ -33,16 +518,66 @@ impl GatewayConfig {
         Self {
             host: "127.0.0.1".to_string(),
             ws_port,
-            nats_url: nats_url.to_string(),
+            broker: BrokerKind::Nats,
+            nats: NatsConfig::new(nats_url),
             jwt_secret: jwt_secret.to_string(),
+            max_payload_bytes: DEFAULT_MAX_PAYLOAD_BYTES,
+            drain_grace_ms: DEFAULT_DRAIN_GRACE_MS,
+            reconnect_after_ms: DEFAULT_RECONNECT_AFTER_MS,
+            alternate_url: None,
+            batch_max_bytes: DEFAULT_BATCH_MAX_BYTES,
+            batch_max_delay_ms: DEFAULT_BATCH_MAX_DELAY_MS,
+            credit_buffer: DEFAULT_CREDIT_BUFFER,
+            overflow_policy: CreditOverflowPolicy::DropOldest,
+            subscription_buffer: DEFAULT_SUBSCRIPTION_BUFFER,
+            slow_consumer_policy: SlowConsumerPolicy::DropOldest,
+            subject_slow_consumer: Vec::new(),
+            compression_threshold_bytes: DEFAULT_COMPRESSION_THRESHOLD_BYTES,
+            subject_compression: Vec::new(),
+            subject_mappings: Vec::new(),
+            retained_subjects: Vec::new(),
+            retained_max_subjects: DEFAULT_RETAINED_MAX_SUBJECTS,
+            history_subjects: Vec::new(),
+            history_length: DEFAULT_HISTORY_LENGTH,
+            history_max_subjects: DEFAULT_HISTORY_MAX_SUBJECTS,
+            resume_grace_ms: DEFAULT_RESUME_GRACE_MS,
+            resume_buffer: DEFAULT_RESUME_BUFFER,
+            identity_signing_key: None,
+            consumer_inactive_threshold_ms: DEFAULT_CONSUMER_INACTIVE_THRESHOLD_MS,
+            consumers_per_user: DEFAULT_CONSUMERS_PER_USER,
         }
     }
 }
 
+/// Read a count from `var` that must be at least 1, or `default` if unset
+fn parse_positive(var: &str, default: usize) -> Result<usize, ConfigError> {
+    match env::var(var) {
+        Ok(value) => value
+            .parse()
+            .ok()
+            .filter(|count| *count > 0)
+            .ok_or_else(|| ConfigError::InvalidValue(var.to_string())),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Parse a comma-separated list, such as subject patterns or server URLs
+fn parse_list(s: &str) -> Vec<String> {
+    s.split(',')
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .map(String::from)
+        .collect()
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigError {
     #[error("Missing required environment variable: {0}")]
     MissingEnvVar(String),
     #[error("Invalid port number")]
     InvalidPort,
+    #[error("Invalid value for environment variable: {0}")]
+    InvalidValue(String),
+    #[error("Conflicting environment variables: {0} and {1}")]
+    ConflictingEnvVars(String, String),
 }
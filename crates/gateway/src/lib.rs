@@ -5,17 +5,18 @@ pub mod protocol;
 pub mod transport;
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use auth::JwtValidator;
-use bridge::NatsBridge;
-pub use config::GatewayConfig;
-use tokio::sync::oneshot;
+use bridge::{Broker, MemoryBroker, NatsBridge};
+pub use config::{BrokerKind, CreditOverflowPolicy, GatewayConfig};
+use tokio::sync::{oneshot, watch};
 use tracing::{error, info};
 
 pub struct Gateway {
-    config: GatewayConfig,
+    config: Arc<GatewayConfig>,
     jwt_validator: Arc<JwtValidator>,
-    nats_bridge: Arc<NatsBridge>,
+    broker: Arc<dyn Broker>,
 }
 
 impl Gateway {
@@ -23,30 +24,60 @@ impl Gateway {
         config: GatewayConfig,
     ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         let jwt_validator = Arc::new(JwtValidator::new(&config.jwt_secret)?);
-        let nats_bridge = Arc::new(NatsBridge::connect(&config.nats_url).await?);
+        let broker: Arc<dyn Broker> = match config.broker {
+            BrokerKind::Nats => Arc::new(
+                NatsBridge::connect(&config.nats)
+                    .await?
+                    .with_compression(config.subject_compression.clone(), config.max_payload_bytes)
+                    .with_consumer_limits(
+                        Duration::from_millis(config.consumer_inactive_threshold_ms),
+                        config.consumers_per_user,
+                    )
+                    .with_retained(
+                        config.retained_subjects.clone(),
+                        config.retained_max_subjects,
+                    )
+                    .await?
+                    .with_history(
+                        config.history_subjects.clone(),
+                        config.history_length,
+                        config.history_max_subjects,
+                    )
+                    .await?,
+            ),
+            BrokerKind::Memory => {
+                info!("Using the in-memory broker; messages stay within this process");
+                Arc::new(MemoryBroker::new())
+            }
+        };
 
         Ok(Self {
-            config,
+            config: Arc::new(config),
             jwt_validator,
-            nats_bridge,
+            broker,
         })
     }
 
-    /// Create a gateway with pre-built components (for testing)
+    /// Create a gateway with pre-built components, e.g. a [`MemoryBroker`]
+    /// shared with in-process services
     pub fn with_components(
         config: GatewayConfig,
         jwt_validator: Arc<JwtValidator>,
-        nats_bridge: Arc<NatsBridge>,
+        broker: Arc<dyn Broker>,
     ) -> Self {
         Self {
-            config,
+            config: Arc::new(config),
             jwt_validator,
-            nats_bridge,
+            broker,
         }
     }
 
     /// Run the gateway until shutdown signal is received
     /// Returns the actual WebSocket port the server bound to
+    ///
+    /// On shutdown every live connection is sent a GoAway and given
+    /// `drain_grace_ms` to finish. This returns as soon as the server and all
+    /// connections have closed, or once the grace period is over.
     pub async fn run(
         self,
         shutdown: oneshot::Receiver<()>,
@@ -54,16 +85,13 @@ impl Gateway {
         info!("Starting gateway...");
 
         let ws_jwt = self.jwt_validator.clone();
-        let ws_nats = self.nats_bridge.clone();
+        let ws_broker = self.broker.clone();
+        let (drain_tx, drain_rx) = watch::channel(false);
 
         // Run WebSocket server with shutdown support
-        let (actual_port, server_handle) = transport::websocket::run_server(
-            self.config.host.clone(),
-            self.config.ws_port,
-            ws_jwt,
-            ws_nats,
-        )
-        .await?;
+        let (actual_port, mut server_handle) =
+            transport::websocket::run_server(self.config.clone(), ws_jwt, ws_broker, drain_rx)
+                .await?;
 
         info!("WebSocket server listening on port {}", actual_port);
 
@@ -72,11 +100,28 @@ impl Gateway {
             _ = shutdown => {
                 info!("Shutdown signal received");
             }
-            result = server_handle => {
+            result = &mut server_handle => {
                 if let Err(e) = result {
                     error!("Server error: {}", e);
                 }
+                return Ok(actual_port);
+            }
+        }
+
+        let grace = Duration::from_millis(self.config.drain_grace_ms);
+        info!("Draining connections for up to {:?}", grace);
+        drain_tx.send_replace(true);
+        let drained = tokio::time::timeout(grace, async {
+            if let Ok(Err(e)) = server_handle.await {
+                error!("Server error: {}", e);
             }
+            // Upgraded connections outlive the server; each holds a drain
+            // receiver until it closes
+            drain_tx.closed().await;
+        })
+        .await;
+        if drained.is_err() {
+            info!("Drain grace period over with connections still open");
         }
 
         Ok(actual_port)
@@ -91,15 +136,13 @@ impl Gateway {
         );
 
         let ws_jwt = self.jwt_validator.clone();
-        let ws_nats = self.nats_bridge.clone();
-
-        let (actual_port, server_handle) = transport::websocket::run_server(
-            self.config.host.clone(),
-            self.config.ws_port,
-            ws_jwt,
-            ws_nats,
-        )
-        .await?;
+        let ws_broker = self.broker.clone();
+        // Never signalled; held until the server exits so connections don't drain
+        let (_drain_tx, drain_rx) = watch::channel(false);
+
+        let (actual_port, server_handle) =
+            transport::websocket::run_server(self.config.clone(), ws_jwt, ws_broker, drain_rx)
+                .await?;
 
         info!("WebSocket server listening on port {}", actual_port);
 
@@ -0,0 +1,122 @@
+//! Frame and payload compression
+
+use std::io::Read;
+
+use flate2::Compression;
+use flate2::read::{GzDecoder, GzEncoder};
+
+use super::{CodecError, CompressionAlgorithm};
+
+/// zstd level used by the gateway; favours speed over ratio
+const ZSTD_LEVEL: i32 = 3;
+
+/// Parse an algorithm name as used in configuration
+pub fn parse_algorithm(s: &str) -> Option<CompressionAlgorithm> {
+    match s.to_lowercase().as_str() {
+        "none" => Some(CompressionAlgorithm::None),
+        "gzip" => Some(CompressionAlgorithm::Gzip),
+        "zstd" => Some(CompressionAlgorithm::Zstd),
+        _ => None,
+    }
+}
+
+/// Compress data with the given algorithm
+pub fn compress(algorithm: &CompressionAlgorithm, data: &[u8]) -> Result<Vec<u8>, CodecError> {
+    let mut out = Vec::new();
+    match algorithm {
+        CompressionAlgorithm::None => return Ok(data.to_vec()),
+        CompressionAlgorithm::Gzip => {
+            GzEncoder::new(data, Compression::fast()).read_to_end(&mut out)
+        }
+        CompressionAlgorithm::Zstd => zstd::stream::read::Encoder::new(data, ZSTD_LEVEL)
+            .and_then(|mut encoder| encoder.read_to_end(&mut out)),
+    }
+    .map_err(|e| CodecError::Compression(e.to_string()))?;
+    Ok(out)
+}
+
+/// Decompress data with the given algorithm, refusing to produce more than
+/// `limit` bytes
+pub fn decompress(
+    algorithm: &CompressionAlgorithm,
+    data: &[u8],
+    limit: usize,
+) -> Result<Vec<u8>, CodecError> {
+    // Read one byte past the limit to tell "exactly at" from "over"
+    let cap = (limit as u64).saturating_add(1);
+    let mut out = Vec::new();
+    match algorithm {
+        CompressionAlgorithm::None => out.extend_from_slice(data),
+        CompressionAlgorithm::Gzip => {
+            GzDecoder::new(data)
+                .take(cap)
+                .read_to_end(&mut out)
+                .map_err(|e| CodecError::Compression(e.to_string()))?;
+        }
+        CompressionAlgorithm::Zstd => {
+            zstd::stream::read::Decoder::new(data)
+                .and_then(|decoder| decoder.take(cap).read_to_end(&mut out))
+                .map_err(|e| CodecError::Compression(e.to_string()))?;
+        }
+    }
+
+    if out.len() > limit {
+        return Err(CodecError::DecompressedTooLarge { limit });
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<u8> {
+        b"mottomesh ".repeat(200)
+    }
+
+    #[test]
+    fn test_roundtrip_all_algorithms() {
+        let data = sample();
+        for algorithm in [
+            CompressionAlgorithm::None,
+            CompressionAlgorithm::Gzip,
+            CompressionAlgorithm::Zstd,
+        ] {
+            let compressed = compress(&algorithm, &data).unwrap();
+            if algorithm != CompressionAlgorithm::None {
+                assert!(
+                    compressed.len() < data.len(),
+                    "{:?} should shrink",
+                    algorithm
+                );
+            }
+            let decompressed = decompress(&algorithm, &compressed, data.len()).unwrap();
+            assert_eq!(decompressed, data);
+        }
+    }
+
+    #[test]
+    fn test_decompress_enforces_limit() {
+        let data = sample();
+        let compressed = compress(&CompressionAlgorithm::Zstd, &data).unwrap();
+        let result = decompress(&CompressionAlgorithm::Zstd, &compressed, data.len() - 1);
+        assert!(matches!(
+            result,
+            Err(CodecError::DecompressedTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn test_decompress_rejects_garbage() {
+        let result = decompress(&CompressionAlgorithm::Gzip, &[1, 2, 3], 1024);
+        assert!(matches!(result, Err(CodecError::Compression(_))));
+    }
+
+    #[test]
+    fn test_parse_algorithm() {
+        assert_eq!(parse_algorithm("zstd"), Some(CompressionAlgorithm::Zstd));
+        assert_eq!(parse_algorithm("GZIP"), Some(CompressionAlgorithm::Gzip));
+        assert_eq!(parse_algorithm("none"), Some(CompressionAlgorithm::None));
+        assert_eq!(parse_algorithm("brotli"), None);
+    }
+}
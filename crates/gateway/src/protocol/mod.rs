@@ -1,5 +1,12 @@
+pub mod compression;
+
 use schema_sdk::codec::{Decode, Encode};
-pub use schema_sdk::{ClientEnvelope, ClientMessage, ServerEnvelope, ServerMessage};
+pub use schema_sdk::{
+    AckKind, Capability, ClientEnvelope, ClientMessage, CompressionAlgorithm, DeliverPolicy,
+    ErrorCode, KvOperation, MessageOrigin, PROTOCOL_VERSION_BYTE, PresenceAction, PresenceMember,
+    ReplayFrom, SCHEMA_FINGERPRINT, ServerEnvelope, ServerMessage, SlowConsumerPolicy,
+    SubscriptionEndReason,
+};
 
 pub struct MessageCodec;
 
@@ -27,22 +34,477 @@ impl MessageCodec {
     }
 
     pub fn decode_client(data: &[u8]) -> Result<ClientMessage, CodecError> {
+        if let Some(&got) = data.first()
+            && got != PROTOCOL_VERSION_BYTE
+        {
+            return Err(CodecError::VersionMismatch {
+                expected: PROTOCOL_VERSION_BYTE,
+                got,
+            });
+        }
+
         ClientEnvelope::from_bytes(data)
             .map(|envelope| envelope.message)
             .map_err(|e| CodecError::DecodeError(e.to_string()))
     }
+
+    /// Pack messages into one `Batch`. Each travels as a frame of its own,
+    /// so decoding a batch never decodes further batches inside it.
+    #[allow(dead_code)]
+    pub fn batch_client(messages: &[ClientMessage]) -> ClientMessage {
+        ClientMessage::Batch {
+            frames: messages.iter().map(Self::encode_client).collect(),
+        }
+    }
+
+    /// Unpack the frames of a client `Batch`
+    pub fn unbatch_client(frames: &[Vec<u8>]) -> Result<Vec<ClientMessage>, CodecError> {
+        frames
+            .iter()
+            .map(|frame| Self::decode_client(frame))
+            .collect()
+    }
+
+    /// Pack messages into one `Batch`, each as a frame of its own
+    pub fn batch_server(messages: &[ServerMessage]) -> ServerMessage {
+        ServerMessage::Batch {
+            frames: messages.iter().map(Self::encode_server).collect(),
+        }
+    }
+
+    /// Unpack the frames of a server `Batch`
+    #[allow(dead_code)]
+    pub fn unbatch_server(frames: &[Vec<u8>]) -> Result<Vec<ServerMessage>, CodecError> {
+        frames
+            .iter()
+            .map(|frame| Self::decode_server(frame))
+            .collect()
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum CodecError {
     #[error("Failed to decode message: {0}")]
     DecodeError(String),
+    #[error("Protocol version mismatch: gateway speaks 0x{expected:02X}, client sent 0x{got:02X}")]
+    VersionMismatch { expected: u8, got: u8 },
+    #[error("Compression failed: {0}")]
+    Compression(String),
+    #[error("Decompressed data exceeds limit of {limit} bytes")]
+    DecompressedTooLarge { limit: usize },
 }
 
-pub mod error_codes {
-    pub const UNAUTHORIZED: u32 = 401;
-    pub const FORBIDDEN: u32 = 403;
-    pub const NOT_FOUND: u32 = 404;
-    pub const INTERNAL_ERROR: u32 = 500;
-    pub const INVALID_MESSAGE: u32 = 400;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_roundtrip_stream_request() {
+        let msg = ClientMessage::Request {
+            subject: "api.list".to_string(),
+            payload: vec![1, 2, 3],
+            timeout_ms: 1000,
+            request_id: 5,
+            stream: true,
+        };
+        let decoded = MessageCodec::decode_client(&MessageCodec::encode_client(&msg)).unwrap();
+        assert_eq!(decoded, msg);
+
+        let cancel = ClientMessage::CancelRequest { request_id: 5 };
+        let decoded = MessageCodec::decode_client(&MessageCodec::encode_client(&cancel)).unwrap();
+        assert_eq!(decoded, cancel);
+    }
+
+    #[test]
+    fn test_decode_client_version_mismatch() {
+        let mut data = MessageCodec::encode_client(&ClientMessage::Ping);
+        data[0] = PROTOCOL_VERSION_BYTE.wrapping_add(1);
+
+        match MessageCodec::decode_client(&data) {
+            Err(CodecError::VersionMismatch { expected, got }) => {
+                assert_eq!(expected, PROTOCOL_VERSION_BYTE);
+                assert_eq!(got, data[0]);
+            }
+            other => panic!("Expected VersionMismatch, got: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_handshake() {
+        let hello = ClientMessage::Hello {
+            client_name: "test-client".to_string(),
+            client_version: "1.0.0".to_string(),
+            schema_fingerprint: SCHEMA_FINGERPRINT.to_string(),
+            capabilities: vec![Capability::Batching, Capability::Compression],
+            compression: vec![CompressionAlgorithm::Zstd, CompressionAlgorithm::Gzip],
+        };
+        let decoded = MessageCodec::decode_client(&MessageCodec::encode_client(&hello)).unwrap();
+        assert_eq!(decoded, hello);
+
+        let rejected = ServerMessage::HelloError {
+            code: ErrorCode::SchemaMismatch,
+            message: "mismatch".to_string(),
+        };
+        let decoded = MessageCodec::decode_server(&MessageCodec::encode_server(&rejected)).unwrap();
+        assert_eq!(decoded, rejected);
+    }
+
+    #[test]
+    fn test_server_roundtrip_error_codes() {
+        let errors = vec![
+            ServerMessage::AuthError {
+                code: ErrorCode::Unauthorized,
+                reason: "Token expired".to_string(),
+            },
+            ServerMessage::SubscribeError {
+                id: 1,
+                code: ErrorCode::PermissionDenied,
+                reason: "Permission denied".to_string(),
+            },
+            ServerMessage::RequestError {
+                request_id: 2,
+                code: ErrorCode::Timeout,
+                reason: "Request timed out".to_string(),
+            },
+            ServerMessage::Error {
+                code: ErrorCode::Internal,
+                message: "boom".to_string(),
+            },
+        ];
+
+        for msg in errors {
+            let decoded = MessageCodec::decode_server(&MessageCodec::encode_server(&msg)).unwrap();
+            assert_eq!(decoded, msg);
+        }
+    }
+
+    #[test]
+    fn test_server_roundtrip_go_away() {
+        for alternate_url in [None, Some("wss://other.example.com/ws".to_string())] {
+            let msg = ServerMessage::GoAway {
+                reason: "Gateway is shutting down".to_string(),
+                reconnect_after_ms: 1000,
+                alternate_url,
+            };
+            let decoded = MessageCodec::decode_server(&MessageCodec::encode_server(&msg)).unwrap();
+            assert_eq!(decoded, msg);
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_batches() {
+        let messages = vec![ClientMessage::Ping, ClientMessage::Unsubscribe { id: 3 }];
+        let batch = MessageCodec::batch_client(&messages);
+        let decoded = MessageCodec::decode_client(&MessageCodec::encode_client(&batch)).unwrap();
+        let ClientMessage::Batch { frames } = decoded else {
+            panic!("Expected Batch, got: {:?}", decoded);
+        };
+        assert_eq!(MessageCodec::unbatch_client(&frames).unwrap(), messages);
+
+        let messages = vec![
+            ServerMessage::Pong,
+            ServerMessage::Message {
+                subscription_id: 1,
+                subject: "ticks".to_string(),
+                payload: vec![1],
+                seq: 1,
+                dropped: 0,
+                origin: MessageOrigin::Live,
+            },
+        ];
+        let batch = MessageCodec::batch_server(&messages);
+        let decoded = MessageCodec::decode_server(&MessageCodec::encode_server(&batch)).unwrap();
+        let ServerMessage::Batch { frames } = decoded else {
+            panic!("Expected Batch, got: {:?}", decoded);
+        };
+        assert_eq!(MessageCodec::unbatch_server(&frames).unwrap(), messages);
+    }
+
+    #[test]
+    fn test_nested_batch_stays_packed() {
+        // Decoding the outer batch leaves the inner one as a frame
+        let inner = MessageCodec::batch_client(&[ClientMessage::Ping]);
+        let outer = MessageCodec::batch_client(std::slice::from_ref(&inner));
+        let decoded = MessageCodec::decode_client(&MessageCodec::encode_client(&outer)).unwrap();
+        let ClientMessage::Batch { frames } = decoded else {
+            panic!("Expected Batch, got: {:?}", decoded);
+        };
+        assert_eq!(MessageCodec::unbatch_client(&frames).unwrap(), vec![inner]);
+    }
+
+    #[test]
+    fn test_decode_rejects_overlong_length() {
+        // A batch claiming u32::MAX frames in a few bytes
+        let mut data = MessageCodec::encode_client(&ClientMessage::Batch { frames: Vec::new() });
+        let len = data.len();
+        data[len - 4..].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(MessageCodec::decode_client(&data).is_err());
+
+        let mut data = MessageCodec::encode_client(&ClientMessage::Unsubscribe { id: 1 });
+        data.truncate(data.len() - 1);
+        assert!(MessageCodec::decode_client(&data).is_err());
+    }
+
+    #[test]
+    fn test_client_roundtrip_credits() {
+        let subscribe = ClientMessage::Subscribe {
+            subject: "ticks".to_string(),
+            id: 2,
+            credits: Some(16),
+            max_msgs: None,
+            replay: None,
+            slow_consumer: None,
+        };
+        let decoded =
+            MessageCodec::decode_client(&MessageCodec::encode_client(&subscribe)).unwrap();
+        assert_eq!(decoded, subscribe);
+
+        let grant = ClientMessage::GrantCredit { id: 2, credits: 8 };
+        let decoded = MessageCodec::decode_client(&MessageCodec::encode_client(&grant)).unwrap();
+        assert_eq!(decoded, grant);
+    }
+
+    #[test]
+    fn test_roundtrip_subscription_lifecycle() {
+        let subscribe = ClientMessage::Subscribe {
+            subject: "ticks".to_string(),
+            id: 3,
+            credits: None,
+            max_msgs: Some(5),
+            replay: None,
+            slow_consumer: None,
+        };
+        let decoded =
+            MessageCodec::decode_client(&MessageCodec::encode_client(&subscribe)).unwrap();
+        assert_eq!(decoded, subscribe);
+
+        for msg in [
+            ServerMessage::UnsubscribeOk {
+                id: 3,
+                total_dropped: 12,
+            },
+            ServerMessage::UnsubscribeError {
+                id: 4,
+                code: ErrorCode::NotFound,
+                reason: "No subscription with id 4".to_string(),
+            },
+            ServerMessage::SubscriptionEnded {
+                id: 3,
+                reason: SubscriptionEndReason::LimitReached,
+                total_dropped: 0,
+            },
+        ] {
+            let decoded = MessageCodec::decode_server(&MessageCodec::encode_server(&msg)).unwrap();
+            assert_eq!(decoded, msg);
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_replay() {
+        for replay in [
+            ReplayFrom::Last { count: 10 },
+            ReplayFrom::Since {
+                unix_ms: 1_700_000_000_000,
+            },
+        ] {
+            let subscribe = ClientMessage::Subscribe {
+                subject: "ticks".to_string(),
+                id: 4,
+                credits: None,
+                max_msgs: None,
+                replay: Some(replay),
+                slow_consumer: None,
+            };
+            let decoded =
+                MessageCodec::decode_client(&MessageCodec::encode_client(&subscribe)).unwrap();
+            assert_eq!(decoded, subscribe);
+        }
+
+        let msg = ServerMessage::Message {
+            subscription_id: 4,
+            subject: "ticks".to_string(),
+            payload: vec![1],
+            seq: 1,
+            dropped: 0,
+            origin: MessageOrigin::History,
+        };
+        let decoded = MessageCodec::decode_server(&MessageCodec::encode_server(&msg)).unwrap();
+        assert_eq!(decoded, msg);
+    }
+
+    #[test]
+    fn test_roundtrip_slow_consumer_policy() {
+        for policy in [
+            SlowConsumerPolicy::Block,
+            SlowConsumerPolicy::DropOldest,
+            SlowConsumerPolicy::DropNewest,
+            SlowConsumerPolicy::Conflate,
+            SlowConsumerPolicy::Disconnect,
+        ] {
+            let subscribe = ClientMessage::Subscribe {
+                subject: "prices.>".to_string(),
+                id: 6,
+                credits: None,
+                max_msgs: None,
+                replay: None,
+                slow_consumer: Some(policy),
+            };
+            let decoded =
+                MessageCodec::decode_client(&MessageCodec::encode_client(&subscribe)).unwrap();
+            assert_eq!(decoded, subscribe);
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_consumer() {
+        let subscribe = ClientMessage::SubscribeConsumer {
+            id: 5,
+            stream: "ORDERS".to_string(),
+            durable: "ui".to_string(),
+            filter_subject: "orders.>".to_string(),
+            deliver: DeliverPolicy::ByStartSequence { start_seq: 100 },
+        };
+        let decoded =
+            MessageCodec::decode_client(&MessageCodec::encode_client(&subscribe)).unwrap();
+        assert_eq!(decoded, subscribe);
+
+        for kind in [AckKind::Ack, AckKind::Nak, AckKind::InProgress] {
+            let ack = ClientMessage::Ack {
+                id: 5,
+                stream_seq: 101,
+                kind,
+            };
+            let decoded = MessageCodec::decode_client(&MessageCodec::encode_client(&ack)).unwrap();
+            assert_eq!(decoded, ack);
+        }
+
+        let msg = ServerMessage::ConsumerMessage {
+            subscription_id: 5,
+            subject: "orders.created".to_string(),
+            payload: vec![1],
+            stream_seq: 101,
+            delivered: 2,
+        };
+        let decoded = MessageCodec::decode_server(&MessageCodec::encode_server(&msg)).unwrap();
+        assert_eq!(decoded, msg);
+    }
+
+    #[test]
+    fn test_roundtrip_jetstream_publish() {
+        let publish = ClientMessage::JetStreamPublish {
+            request_id: 6,
+            subject: "orders.created".to_string(),
+            payload: vec![1, 2],
+            msg_id: Some("order-17".to_string()),
+        };
+        let decoded = MessageCodec::decode_client(&MessageCodec::encode_client(&publish)).unwrap();
+        assert_eq!(decoded, publish);
+
+        let ack = ServerMessage::PubAck {
+            request_id: 6,
+            stream: "ORDERS".to_string(),
+            seq: 17,
+            duplicate: true,
+        };
+        let decoded = MessageCodec::decode_server(&MessageCodec::encode_server(&ack)).unwrap();
+        assert_eq!(decoded, ack);
+    }
+
+    #[test]
+    fn test_roundtrip_kv() {
+        let put = ClientMessage::KvPut {
+            request_id: 7,
+            bucket: "settings".to_string(),
+            key: "users.alice.theme".to_string(),
+            value: b"dark".to_vec(),
+        };
+        let decoded = MessageCodec::decode_client(&MessageCodec::encode_client(&put)).unwrap();
+        assert_eq!(decoded, put);
+
+        let entry = ServerMessage::KvEntry {
+            request_id: 8,
+            key: "users.alice.theme".to_string(),
+            value: None,
+            revision: 0,
+        };
+        let decoded = MessageCodec::decode_server(&MessageCodec::encode_server(&entry)).unwrap();
+        assert_eq!(decoded, entry);
+
+        for operation in [KvOperation::Put, KvOperation::Delete, KvOperation::Purge] {
+            let update = ServerMessage::KvUpdate {
+                id: 9,
+                key: "users.alice.theme".to_string(),
+                value: vec![],
+                revision: 3,
+                operation,
+            };
+            let decoded =
+                MessageCodec::decode_server(&MessageCodec::encode_server(&update)).unwrap();
+            assert_eq!(decoded, update);
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_objects() {
+        let start = ClientMessage::ObjectUploadStart {
+            request_id: 1,
+            bucket: "uploads".to_string(),
+            name: "alice.photo.jpg".to_string(),
+            size: 1 << 40,
+            sha256: vec![0xab; 32],
+        };
+        let decoded = MessageCodec::decode_client(&MessageCodec::encode_client(&start)).unwrap();
+        assert_eq!(decoded, start);
+
+        let chunk = ServerMessage::ObjectChunk {
+            request_id: 2,
+            offset: 65536,
+            data: vec![1, 2, 3],
+        };
+        let decoded = MessageCodec::decode_server(&MessageCodec::encode_server(&chunk)).unwrap();
+        assert_eq!(decoded, chunk);
+    }
+
+    #[test]
+    fn test_roundtrip_backend_status() {
+        for available in [false, true] {
+            let msg = ServerMessage::BackendStatus { available };
+            let decoded = MessageCodec::decode_server(&MessageCodec::encode_server(&msg)).unwrap();
+            assert_eq!(decoded, msg);
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_compressed_frames() {
+        let inner = MessageCodec::encode_client(&ClientMessage::Ping);
+        let frame = compression::compress(&CompressionAlgorithm::Gzip, &inner).unwrap();
+        let msg = ClientMessage::Compressed {
+            algorithm: CompressionAlgorithm::Gzip,
+            frame,
+        };
+        let decoded = MessageCodec::decode_client(&MessageCodec::encode_client(&msg)).unwrap();
+        assert_eq!(decoded, msg);
+
+        let msg = ServerMessage::Compressed {
+            algorithm: CompressionAlgorithm::Zstd,
+            frame: vec![1, 2, 3],
+        };
+        let decoded = MessageCodec::decode_server(&MessageCodec::encode_server(&msg)).unwrap();
+        assert_eq!(decoded, msg);
+    }
+
+    #[test]
+    fn test_server_roundtrip_stream_reply() {
+        let chunk = ServerMessage::ResponseChunk {
+            request_id: 5,
+            seq: 3,
+            payload: vec![4, 5],
+        };
+        let decoded = MessageCodec::decode_server(&MessageCodec::encode_server(&chunk)).unwrap();
+        assert_eq!(decoded, chunk);
+
+        let end = ServerMessage::ResponseEnd { request_id: 5 };
+        let decoded = MessageCodec::decode_server(&MessageCodec::encode_server(&end)).unwrap();
+        assert_eq!(decoded, end);
+    }
 }
@@ -0,0 +1,179 @@
+//! Durable consumer subscriptions and their acknowledgements.
+
+use std::collections::HashMap;
+
+use tracing::{debug, error, warn};
+
+use super::flow::FlowControl;
+use super::handler::{ConnectionHandler, is_valid_name};
+use super::mapping;
+use super::subscribe::{ActiveSubscription, client_subject, invalid_subject};
+use crate::auth::{Permission, PermissionChecker};
+use crate::bridge::ConsumerDelivery;
+use crate::protocol::{AckKind, DeliverPolicy, ErrorCode, ServerMessage};
+
+impl ConnectionHandler {
+    pub(super) async fn handle_subscribe_consumer(
+        &mut self,
+        id: u64,
+        stream: &str,
+        durable: &str,
+        filter_subject: String,
+        deliver: &DeliverPolicy,
+    ) -> Option<ServerMessage> {
+        let invalid = invalid_subject(&filter_subject).or_else(|| {
+            (!is_valid_name(stream) || !is_valid_name(durable)).then(|| {
+                (
+                    ErrorCode::InvalidMessage,
+                    format!(
+                        "Invalid stream or durable name: {:?}, {:?}",
+                        stream, durable
+                    ),
+                )
+            })
+        });
+        // The filter subject decides what the consumer delivers, so it is
+        // what the client needs permission for
+        if let Err(refused) = self.check_subscribe(id, invalid, |claims| {
+            PermissionChecker::can_perform(claims, Permission::Subscribe, &filter_subject)
+        }) {
+            return Some(refused);
+        }
+        let session = self.session.as_mut()?;
+
+        let (nats_filter, mapping) =
+            match mapping::map_to_nats(&self.config.subject_mappings, &filter_subject) {
+                Some((rule, mapped)) => (mapped, Some(rule.clone())),
+                None => (filter_subject.clone(), None),
+            };
+
+        match self
+            .broker
+            .subscribe_consumer(
+                stream,
+                &session.user_id,
+                durable,
+                nats_filter,
+                deliver,
+                id,
+                self.nats_tx.clone(),
+            )
+            .await
+        {
+            Ok(handle) => {
+                self.broker.presence().join(session, &filter_subject);
+                session.add_subscription(id, filter_subject.clone());
+                let flow =
+                    FlowControl::new(None, self.config.credit_buffer, self.config.overflow_policy);
+                self.subscriptions.insert(
+                    id,
+                    ActiveSubscription {
+                        handle: Some(handle),
+                        flow,
+                        remaining: None,
+                        last_seq: 0,
+                        delivered_seq: 0,
+                        unacked: HashMap::new(),
+                        mapping,
+                        mailbox: None,
+                    },
+                );
+                debug!(
+                    "User {} consuming {} from stream {} as {} (id={})",
+                    session.user_id, filter_subject, stream, durable, id
+                );
+                Some(ServerMessage::SubscribeOk { id })
+            }
+            Err(e) => {
+                error!("Failed to consume stream {}: {}", stream, e);
+                Some(ServerMessage::SubscribeError {
+                    id,
+                    code: e.code(),
+                    reason: e.to_string(),
+                })
+            }
+        }
+    }
+
+    pub(super) async fn handle_ack(
+        &mut self,
+        id: u64,
+        stream_seq: u64,
+        kind: &AckKind,
+    ) -> Option<ServerMessage> {
+        let Some(subscription) = self.subscriptions.get_mut(&id) else {
+            return Some(ServerMessage::Error {
+                code: ErrorCode::NotFound,
+                message: format!("No subscription with id {}", id),
+            });
+        };
+
+        // Work in progress stays unacknowledged; anything else settles the
+        // delivery until the server redelivers it
+        let handle = match kind {
+            AckKind::InProgress => subscription.unacked.get(&stream_seq).cloned(),
+            AckKind::Ack | AckKind::Nak => subscription.unacked.remove(&stream_seq),
+        };
+        let Some(handle) = handle else {
+            return Some(ServerMessage::Error {
+                code: ErrorCode::NotFound,
+                message: format!(
+                    "Message {} of subscription {} is not awaiting acknowledgement",
+                    stream_seq, id
+                ),
+            });
+        };
+
+        match self.broker.ack(&handle, kind).await {
+            Ok(()) => None, // No response needed for acknowledgements
+            Err(e) => {
+                error!("Failed to acknowledge message {}: {}", stream_seq, e);
+                Some(ServerMessage::Error {
+                    code: e.code(),
+                    message: e.to_string(),
+                })
+            }
+        }
+    }
+
+    /// Convert a durable consumer message to a delivery and remember how to
+    /// acknowledge it. Consumer deliveries bypass credits: the server stops
+    /// sending once too many are awaiting acknowledgement.
+    pub(super) fn admit_consumer_delivery(
+        &mut self,
+        delivery: ConsumerDelivery,
+    ) -> Option<ServerMessage> {
+        let id = delivery.subscription_id;
+        let subscription = self.subscriptions.get_mut(&id)?;
+        let subject = client_subject(subscription.mapping.as_ref(), delivery.subject);
+
+        // A wildcard filter can match subjects the client is denied. Those
+        // deliveries are terminated so the server stops redelivering them.
+        let session = self.session.as_ref()?;
+        if !PermissionChecker::is_subject_allowed(&session.claims, &subject) {
+            debug!(
+                "Terminating message {} on {} denied to user {}",
+                delivery.stream_seq, subject, session.user_id
+            );
+            let broker = self.broker.clone();
+            tokio::spawn(async move {
+                if let Err(e) = broker.term(&delivery.ack).await {
+                    warn!("Failed to terminate message {}: {}", delivery.stream_seq, e);
+                }
+            });
+            return None;
+        }
+
+        // A redelivery replaces the acknowledgement subject of the earlier one
+        subscription
+            .unacked
+            .insert(delivery.stream_seq, delivery.ack);
+        Some(ServerMessage::ConsumerMessage {
+            subscription_id: id,
+            subject,
+            payload: delivery.payload,
+            stream_seq: delivery.stream_seq,
+            delivered: delivery.delivered,
+        })
+    }
+}
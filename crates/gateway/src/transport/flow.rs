@@ -0,0 +1,183 @@
+use std::collections::VecDeque;
+
+use crate::config::CreditOverflowPolicy;
+use crate::protocol::ServerMessage;
+
+/// The buffer of a credit-limited subscription filled up under
+/// [`CreditOverflowPolicy::Disconnect`]
+#[derive(Debug, PartialEq)]
+pub struct BufferOverflow;
+
+/// What became of a delivery offered to [`FlowControl`]
+#[derive(Debug, PartialEq)]
+pub enum Admission {
+    /// Send the delivery now
+    Send(ServerMessage),
+    /// Held back until the client grants credit
+    Buffered,
+    /// Held back in place of the oldest buffered delivery, which was dropped
+    Replaced,
+    /// Dropped by the overflow policy
+    Dropped,
+}
+
+/// Credit-based flow control for one subscription.
+///
+/// A subscription created without credits is unlimited and every delivery
+/// passes straight through. Otherwise each delivery consumes one credit;
+/// once credit runs out deliveries are buffered until the client grants more.
+#[derive(Debug)]
+pub struct FlowControl {
+    /// Remaining credit, or None when the subscription is not flow controlled
+    credits: Option<u32>,
+    /// Deliveries held back while out of credit
+    pending: VecDeque<ServerMessage>,
+    /// Most deliveries held back before the overflow policy applies
+    limit: usize,
+    policy: CreditOverflowPolicy,
+    /// Deliveries dropped by the overflow policy so far
+    dropped: u64,
+}
+
+impl FlowControl {
+    pub fn new(credits: Option<u32>, limit: usize, policy: CreditOverflowPolicy) -> Self {
+        Self {
+            credits,
+            pending: VecDeque::new(),
+            limit,
+            policy,
+            dropped: 0,
+        }
+    }
+
+    /// Offer a delivery and report whether it may be sent now, was buffered
+    /// or was dropped by the overflow policy
+    pub fn offer(&mut self, msg: ServerMessage) -> Result<Admission, BufferOverflow> {
+        let Some(credits) = self.credits.as_mut() else {
+            return Ok(Admission::Send(msg));
+        };
+
+        if *credits > 0 && self.pending.is_empty() {
+            *credits -= 1;
+            return Ok(Admission::Send(msg));
+        }
+
+        let mut admission = Admission::Buffered;
+        if self.pending.len() >= self.limit {
+            match self.policy {
+                CreditOverflowPolicy::DropOldest => {
+                    self.pending.pop_front();
+                    self.dropped += 1;
+                    admission = Admission::Replaced;
+                }
+                CreditOverflowPolicy::DropNewest => {
+                    self.dropped += 1;
+                    return Ok(Admission::Dropped);
+                }
+                CreditOverflowPolicy::Disconnect => return Err(BufferOverflow),
+            }
+        }
+
+        self.pending.push_back(msg);
+        Ok(admission)
+    }
+
+    /// Add credit and return the buffered deliveries it releases, oldest first
+    pub fn grant(&mut self, credits: u32) -> Vec<ServerMessage> {
+        let Some(available) = self.credits.as_mut() else {
+            return Vec::new();
+        };
+
+        *available = available.saturating_add(credits);
+        let released = (*available as usize).min(self.pending.len());
+        *available -= released as u32;
+        self.pending.drain(..released).collect()
+    }
+
+    /// Whether deliveries are still held back waiting for credit
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Deliveries dropped by the overflow policy over the subscription's
+    /// lifetime
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::MessageOrigin;
+
+    fn delivery(n: u8) -> ServerMessage {
+        ServerMessage::Message {
+            subscription_id: 1,
+            subject: "ticks".to_string(),
+            payload: vec![n],
+            seq: u64::from(n) + 1,
+            dropped: 0,
+            origin: MessageOrigin::Live,
+        }
+    }
+
+    #[test]
+    fn test_unlimited_passes_through() {
+        let mut flow = FlowControl::new(None, 1, CreditOverflowPolicy::Disconnect);
+        for n in 0..10 {
+            assert_eq!(flow.offer(delivery(n)), Ok(Admission::Send(delivery(n))));
+        }
+        assert!(flow.grant(5).is_empty());
+    }
+
+    #[test]
+    fn test_credits_limit_deliveries() {
+        let mut flow = FlowControl::new(Some(2), 10, CreditOverflowPolicy::DropOldest);
+        assert_eq!(flow.offer(delivery(0)), Ok(Admission::Send(delivery(0))));
+        assert_eq!(flow.offer(delivery(1)), Ok(Admission::Send(delivery(1))));
+        assert_eq!(flow.offer(delivery(2)), Ok(Admission::Buffered));
+        assert_eq!(flow.offer(delivery(3)), Ok(Admission::Buffered));
+    }
+
+    #[test]
+    fn test_grant_releases_in_order() {
+        let mut flow = FlowControl::new(Some(0), 10, CreditOverflowPolicy::DropOldest);
+        for n in 0..3 {
+            assert_eq!(flow.offer(delivery(n)), Ok(Admission::Buffered));
+        }
+
+        assert_eq!(flow.grant(2), vec![delivery(0), delivery(1)]);
+        assert_eq!(flow.grant(5), vec![delivery(2)]);
+
+        // Leftover credit is used by later deliveries
+        assert_eq!(flow.offer(delivery(3)), Ok(Admission::Send(delivery(3))));
+    }
+
+    #[test]
+    fn test_overflow_drop_oldest() {
+        let mut flow = FlowControl::new(Some(0), 2, CreditOverflowPolicy::DropOldest);
+        assert_eq!(flow.offer(delivery(0)), Ok(Admission::Buffered));
+        assert_eq!(flow.offer(delivery(1)), Ok(Admission::Buffered));
+        assert_eq!(flow.offer(delivery(2)), Ok(Admission::Replaced));
+        assert_eq!(flow.grant(10), vec![delivery(1), delivery(2)]);
+        assert_eq!(flow.dropped(), 1);
+    }
+
+    #[test]
+    fn test_overflow_drop_newest() {
+        let mut flow = FlowControl::new(Some(0), 2, CreditOverflowPolicy::DropNewest);
+        assert_eq!(flow.offer(delivery(0)), Ok(Admission::Buffered));
+        assert_eq!(flow.offer(delivery(1)), Ok(Admission::Buffered));
+        assert_eq!(flow.offer(delivery(2)), Ok(Admission::Dropped));
+        assert_eq!(flow.grant(10), vec![delivery(0), delivery(1)]);
+        assert_eq!(flow.dropped(), 1);
+    }
+
+    #[test]
+    fn test_overflow_disconnect() {
+        let mut flow = FlowControl::new(Some(0), 1, CreditOverflowPolicy::Disconnect);
+        assert_eq!(flow.offer(delivery(0)), Ok(Admission::Buffered));
+        assert_eq!(flow.offer(delivery(1)), Err(BufferOverflow));
+    }
+}
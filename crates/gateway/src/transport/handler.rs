@@ -1,42 +1,164 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
 use std::sync::Arc;
 use std::time::Duration;
 
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, watch};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
 use tracing::{debug, error, info, warn};
 
+use super::mapping;
+use super::resume::{self, Detached, ResumeRegistry};
+use super::subscribe::{ActiveSubscription, invalid_subject};
 use crate::auth::{JwtValidator, Permission, PermissionChecker, Session};
-use crate::bridge::{NatsBridge, NatsMessage, SubscriptionHandle};
-use crate::protocol::{ClientMessage, MessageCodec, ServerMessage, error_codes};
+use crate::bridge::{
+    Broker, Connectivity, Headers, Identity, IdentitySigner, ObjectUpload, PresenceChange,
+    PresenceWatch, ResponseStream, StreamChunk, SubscriptionEvent,
+};
+use crate::config::GatewayConfig;
+use crate::protocol::compression;
+use crate::protocol::{
+    Capability, ClientMessage, CodecError, CompressionAlgorithm, ErrorCode, MessageCodec,
+    SCHEMA_FINGERPRINT, ServerMessage, SubscriptionEndReason,
+};
+
+/// Optional protocol features this gateway can provide when a client asks for them
+const SUPPORTED_CAPABILITIES: &[Capability] = &[Capability::Batching, Capability::Compression];
+
+/// Frame compression algorithms this gateway can use
+const SUPPORTED_COMPRESSION: &[CompressionAlgorithm] =
+    &[CompressionAlgorithm::Zstd, CompressionAlgorithm::Gzip];
 
 fn client_message_requires_auth(msg: &ClientMessage) -> bool {
-    !matches!(msg, ClientMessage::Auth { .. } | ClientMessage::Ping)
+    // Messages inside a batch are checked one by one
+    !matches!(
+        msg,
+        ClientMessage::Auth { .. }
+            | ClientMessage::Ping
+            | ClientMessage::Hello { .. }
+            | ClientMessage::Batch { .. }
+    )
 }
 
 /// Handles the logic for a single client connection
 /// This is transport-agnostic - works for both WebSocket and WebTransport
 pub struct ConnectionHandler {
+    pub(super) config: Arc<GatewayConfig>,
     jwt_validator: Arc<JwtValidator>,
-    nats_bridge: Arc<NatsBridge>,
-    session: Option<Session>,
-    subscriptions: HashMap<u64, SubscriptionHandle>,
+    pub(super) broker: Arc<dyn Broker>,
+    /// Where the session is parked if the connection drops
+    resume: Arc<ResumeRegistry>,
+    /// Address the client connected from, if the transport knows it
+    client_ip: Option<IpAddr>,
+    /// Signs the identity headers of relayed messages, if configured
+    identity_signer: Option<IdentitySigner>,
+    pub(super) session: Option<Session>,
+    /// Token a new connection can present to take over the session
+    resume_token: Option<String>,
+    /// Capabilities agreed in the Hello/Welcome handshake (None until Hello)
+    capabilities: Option<Vec<Capability>>,
+    /// Frame compression agreed in the handshake
+    compression: CompressionAlgorithm,
+    /// Set once the gateway has sent GoAway; no new subscriptions are accepted
+    pub(super) draining: bool,
+    pub(super) subscriptions: HashMap<u64, ActiveSubscription>,
+    /// Presence subscriptions, sharing ids with `subscriptions`
+    pub(super) presence_watches: HashMap<u64, PresenceWatch>,
+    /// Deliveries released by credit grants and subscription notifications,
+    /// sent before anything else
+    pub(super) released: VecDeque<ServerMessage>,
+    /// Retained and history messages to replay to new subscriptions, ahead
+    /// of live ones
+    pub(super) replay: VecDeque<SubscriptionEvent>,
+    /// Set when a subscription overflowed under the Disconnect policy
+    pub(super) closing: bool,
     /// Channel for receiving NATS messages
-    nats_rx: mpsc::Receiver<NatsMessage>,
+    nats_rx: mpsc::Receiver<SubscriptionEvent>,
     /// Sender for NATS messages (given to subscription tasks)
-    nats_tx: mpsc::Sender<NatsMessage>,
+    pub(super) nats_tx: mpsc::Sender<SubscriptionEvent>,
+    /// Ids of subscriptions with something waiting in their mailbox
+    ready_rx: mpsc::UnboundedReceiver<u64>,
+    /// Sender for mailbox announcements (given to each mailbox)
+    pub(super) ready_tx: mpsc::UnboundedSender<u64>,
+    /// Channel for presence changes on the client's presence subscriptions.
+    /// Bounded: changes for a client that falls behind are dropped.
+    presence_rx: mpsc::Receiver<PresenceChange>,
+    /// Sender for presence changes (given to the presence registry)
+    presence_tx: mpsc::Sender<PresenceChange>,
+    /// State of the gateway's connection to NATS
+    backend: watch::Receiver<Connectivity>,
+    /// Backend availability last reported to the client
+    backend_available: bool,
+    /// In-flight streamed requests and object downloads: request_id ->
+    /// forwarding task
+    pub(super) streams: HashMap<u64, JoinHandle<()>>,
+    /// Object uploads in progress, by request_id. Unlike downloads they
+    /// survive session resumption, so the client can carry on where it
+    /// stopped.
+    pub(super) uploads: HashMap<u64, ObjectUpload>,
+    /// Channel for server messages produced outside of `handle_message`
+    outbound_rx: mpsc::Receiver<ServerMessage>,
+    /// Sender for server messages (given to stream forwarding tasks)
+    pub(super) outbound_tx: mpsc::Sender<ServerMessage>,
+    /// Outbound messages being coalesced into the next Batch frame
+    batch: Vec<ServerMessage>,
+    /// Approximate encoded size of `batch`
+    batch_bytes: usize,
+    /// When the pending batch must be flushed (set by its first message)
+    batch_deadline: Option<Instant>,
 }
 
 impl ConnectionHandler {
-    pub fn new(jwt_validator: Arc<JwtValidator>, nats_bridge: Arc<NatsBridge>) -> Self {
+    pub fn new(
+        config: Arc<GatewayConfig>,
+        jwt_validator: Arc<JwtValidator>,
+        broker: Arc<dyn Broker>,
+        resume: Arc<ResumeRegistry>,
+        client_ip: Option<IpAddr>,
+    ) -> Self {
         let (nats_tx, nats_rx) = mpsc::channel(256);
+        let (ready_tx, ready_rx) = mpsc::unbounded_channel();
+        let (outbound_tx, outbound_rx) = mpsc::channel(256);
+        let (presence_tx, presence_rx) = mpsc::channel(256);
+        let backend = broker.watch_connectivity();
+        let identity_signer = config
+            .identity_signing_key
+            .as_ref()
+            .map(|key| IdentitySigner::new(key.as_bytes()));
 
         Self {
+            config,
             jwt_validator,
-            nats_bridge,
+            broker,
+            resume,
+            client_ip,
+            identity_signer,
             session: None,
+            resume_token: None,
+            capabilities: None,
+            compression: CompressionAlgorithm::None,
+            draining: false,
             subscriptions: HashMap::new(),
+            presence_watches: HashMap::new(),
+            released: VecDeque::new(),
+            replay: VecDeque::new(),
+            closing: false,
             nats_rx,
             nats_tx,
+            ready_rx,
+            ready_tx,
+            presence_rx,
+            presence_tx,
+            backend,
+            backend_available: true,
+            streams: HashMap::new(),
+            uploads: HashMap::new(),
+            outbound_rx,
+            outbound_tx,
+            batch: Vec::new(),
+            batch_bytes: 0,
+            batch_deadline: None,
         }
     }
 
@@ -51,168 +173,691 @@ impl ConnectionHandler {
         self.session.as_ref().map(|s| s.id.as_str())
     }
 
+    /// Whether the connection must be closed once the last frame is sent
+    pub fn should_close(&self) -> bool {
+        self.closing
+    }
+
+    /// Whether the gateway has asked this connection to go away
+    pub fn is_draining(&self) -> bool {
+        self.draining
+    }
+
+    /// Start draining the connection and build the GoAway to send.
+    ///
+    /// The connection keeps delivering messages until the transport closes it
+    /// after [`drain_grace`](Self::drain_grace).
+    pub fn begin_drain(&mut self) -> ServerMessage {
+        self.draining = true;
+        ServerMessage::GoAway {
+            reason: "Gateway is shutting down".to_string(),
+            reconnect_after_ms: self.config.reconnect_after_ms,
+            alternate_url: self.config.alternate_url.clone(),
+        }
+    }
+
+    /// How long a draining connection stays open
+    pub fn drain_grace(&self) -> Duration {
+        Duration::from_millis(self.config.drain_grace_ms)
+    }
+
+    /// Whether the client negotiated a capability in its Hello
+    fn has_capability(&self, capability: &Capability) -> bool {
+        self.capabilities
+            .as_ref()
+            .is_some_and(|capabilities| capabilities.contains(capability))
+    }
+
     /// Process an incoming message and return a response
     pub async fn handle_message(&mut self, data: &[u8]) -> Option<ServerMessage> {
-        let msg = match MessageCodec::decode_client(data) {
-            Ok(m) => m,
+        let response = match self.decode_frame(data) {
+            Ok(ClientMessage::Batch { frames }) => self.handle_batch(&frames).await,
+            Ok(msg) => self.dispatch(msg).await,
+            Err(error) => Some(error),
+        };
+
+        response.map(|response| self.compress_outbound(response))
+    }
+
+    /// Decode a client frame, unwrapping it first if it arrived compressed
+    fn decode_frame(&self, data: &[u8]) -> Result<ClientMessage, ServerMessage> {
+        let msg = MessageCodec::decode_client(data).map_err(decode_error)?;
+
+        let ClientMessage::Compressed { algorithm, frame } = msg else {
+            return Ok(msg);
+        };
+
+        if !self.has_capability(&Capability::Compression) {
+            return Err(ServerMessage::Error {
+                code: ErrorCode::InvalidMessage,
+                message: "Compression was not negotiated".to_string(),
+            });
+        }
+
+        // A compressed frame may not unpack to more than the payload limit
+        let limit = self.config.max_payload_bytes as usize;
+        let frame = compression::decompress(&algorithm, &frame, limit).map_err(decode_error)?;
+
+        match MessageCodec::decode_client(&frame).map_err(decode_error)? {
+            ClientMessage::Compressed { .. } => Err(ServerMessage::Error {
+                code: ErrorCode::InvalidMessage,
+                message: "Compressed frames cannot be nested".to_string(),
+            }),
+            msg => Ok(msg),
+        }
+    }
+
+    /// Wrap a frame in a Compressed envelope if the client negotiated
+    /// compression and the frame is at least `compression_threshold_bytes`
+    fn compress_outbound(&self, msg: ServerMessage) -> ServerMessage {
+        if self.compression == CompressionAlgorithm::None
+            || encoded_size_hint(&msg) < self.config.compression_threshold_bytes as usize
+        {
+            return msg;
+        }
+
+        let encoded = MessageCodec::encode_server(&msg);
+        match compression::compress(&self.compression, &encoded) {
+            // Only worth it if the frame actually shrinks
+            Ok(frame) if frame.len() < encoded.len() => ServerMessage::Compressed {
+                algorithm: self.compression.clone(),
+                frame,
+            },
+            Ok(_) => msg,
             Err(e) => {
-                warn!("Failed to decode client message: {}", e);
-                return Some(ServerMessage::Error {
-                    code: error_codes::INVALID_MESSAGE,
-                    message: "Invalid message format".to_string(),
-                });
+                warn!("Failed to compress outbound frame: {}", e);
+                msg
             }
-        };
+        }
+    }
 
+    /// Check authentication for a single message and route it to its handler
+    async fn dispatch(&mut self, msg: ClientMessage) -> Option<ServerMessage> {
         // Check authentication for messages that require it
         if client_message_requires_auth(&msg) && !self.is_authenticated() {
             return Some(ServerMessage::Error {
-                code: error_codes::UNAUTHORIZED,
+                code: ErrorCode::Unauthorized,
                 message: "Not authenticated".to_string(),
             });
         }
 
         match msg {
-            ClientMessage::Auth { token } => self.handle_auth(&token).await,
-            ClientMessage::Subscribe { subject, id } => self.handle_subscribe(subject, id).await,
+            ClientMessage::Auth {
+                token,
+                resume_token,
+            } => self.handle_auth(&token, resume_token).await,
+            ClientMessage::Subscribe {
+                subject,
+                id,
+                credits,
+                max_msgs,
+                replay,
+                slow_consumer,
+            } => {
+                self.handle_subscribe(subject, id, credits, max_msgs, replay, slow_consumer)
+                    .await
+            }
             ClientMessage::Unsubscribe { id } => self.handle_unsubscribe(id).await,
             ClientMessage::Publish { subject, payload } => {
                 self.handle_publish(&subject, payload).await
             }
+            ClientMessage::JetStreamPublish {
+                request_id,
+                subject,
+                payload,
+                msg_id,
+            } => {
+                self.handle_jetstream_publish(request_id, &subject, payload, msg_id)
+                    .await
+            }
             ClientMessage::Request {
                 subject,
                 payload,
                 timeout_ms,
                 request_id,
+                stream,
             } => {
-                self.handle_request(&subject, payload, timeout_ms, request_id)
-                    .await
+                if stream {
+                    self.handle_stream_request(&subject, payload, timeout_ms, request_id)
+                        .await
+                } else {
+                    self.handle_request(&subject, payload, timeout_ms, request_id)
+                        .await
+                }
             }
             ClientMessage::Ping => Some(ServerMessage::Pong),
+            ClientMessage::CancelRequest { request_id } => {
+                self.handle_cancel_request(request_id).await
+            }
+            ClientMessage::Hello {
+                client_name,
+                client_version,
+                schema_fingerprint,
+                capabilities,
+                compression,
+            } => self.handle_hello(
+                &client_name,
+                &client_version,
+                &schema_fingerprint,
+                capabilities,
+                &compression,
+            ),
+            ClientMessage::GrantCredit { id, credits } => self.handle_grant_credit(id, credits),
+            ClientMessage::SubscribePresence { id, pattern } => {
+                self.handle_subscribe_presence(id, pattern)
+            }
+            ClientMessage::QueryPresence {
+                request_id,
+                pattern,
+            } => self.handle_query_presence(request_id, &pattern),
+            ClientMessage::SubscribeConsumer {
+                id,
+                stream,
+                durable,
+                filter_subject,
+                deliver,
+            } => {
+                self.handle_subscribe_consumer(id, &stream, &durable, filter_subject, &deliver)
+                    .await
+            }
+            ClientMessage::Ack {
+                id,
+                stream_seq,
+                kind,
+            } => self.handle_ack(id, stream_seq, &kind).await,
+            ClientMessage::KvGet {
+                request_id,
+                bucket,
+                key,
+            } => self.handle_kv_get(request_id, &bucket, &key).await,
+            ClientMessage::KvPut {
+                request_id,
+                bucket,
+                key,
+                value,
+            } => self.handle_kv_put(request_id, &bucket, &key, value).await,
+            ClientMessage::KvDelete {
+                request_id,
+                bucket,
+                key,
+            } => self.handle_kv_delete(request_id, &bucket, &key).await,
+            ClientMessage::KvWatch { id, bucket, key } => {
+                self.handle_kv_watch(id, &bucket, &key).await
+            }
+            ClientMessage::ObjectUploadStart {
+                request_id,
+                bucket,
+                name,
+                size,
+                sha256,
+            } => {
+                self.handle_object_upload_start(request_id, &bucket, &name, size, sha256)
+                    .await
+            }
+            ClientMessage::ObjectUploadChunk {
+                request_id,
+                offset,
+                data,
+            } => {
+                self.handle_object_upload_chunk(request_id, offset, data)
+                    .await
+            }
+            ClientMessage::ObjectDownload {
+                request_id,
+                bucket,
+                name,
+                offset,
+            } => {
+                self.handle_object_download(request_id, &bucket, &name, offset)
+                    .await
+            }
+            ClientMessage::Batch { .. } => Some(ServerMessage::Error {
+                code: ErrorCode::InvalidMessage,
+                message: "Batches cannot be nested".to_string(),
+            }),
+            ClientMessage::Compressed { .. } => Some(ServerMessage::Error {
+                code: ErrorCode::InvalidMessage,
+                message: "Compressed frames cannot be nested".to_string(),
+            }),
+        }
+    }
+
+    /// Process every message of a client batch in order, answering with a
+    /// batch of the responses
+    async fn handle_batch(&mut self, frames: &[Vec<u8>]) -> Option<ServerMessage> {
+        if !self.has_capability(&Capability::Batching) {
+            return Some(ServerMessage::Error {
+                code: ErrorCode::InvalidMessage,
+                message: "Batching was not negotiated".to_string(),
+            });
+        }
+
+        // A batch inside is decoded but not unpacked, and dispatch rejects it
+        let messages = match MessageCodec::unbatch_client(frames) {
+            Ok(messages) => messages,
+            Err(e) => return Some(decode_error(e)),
+        };
+
+        let mut responses = Vec::new();
+        for msg in messages {
+            if let Some(response) = self.dispatch(msg).await {
+                responses.push(response);
+            }
+        }
+
+        match responses.len() {
+            0 => None,
+            1 => responses.pop(),
+            _ => Some(MessageCodec::batch_server(&responses)),
         }
     }
 
     /// Try to receive a NATS message (non-blocking)
     #[allow(dead_code)]
-    pub fn try_recv_nats(&mut self) -> Option<NatsMessage> {
+    pub fn try_recv_nats(&mut self) -> Option<SubscriptionEvent> {
         self.nats_rx.try_recv().ok()
     }
 
-    /// Get a reference to the NATS receiver for select!
-    pub fn nats_receiver(&mut self) -> &mut mpsc::Receiver<NatsMessage> {
-        &mut self.nats_rx
+    /// Wait for the next frame to push to the client: either a NATS
+    /// delivery or the output of a background task (e.g. a streamed reply).
+    ///
+    /// When the client negotiated batching, messages that arrive within
+    /// `batch_max_delay_ms` of each other are coalesced into one `Batch`,
+    /// up to `batch_max_bytes`. Cancel-safe, so it can be used as a select!
+    /// branch: a partially built batch is kept until the next call.
+    pub async fn next_outbound(&mut self) -> Option<ServerMessage> {
+        let msg = self.next_frame().await?;
+        Some(self.compress_outbound(msg))
     }
 
-    /// Convert a NATS message to a ServerMessage
-    pub fn nats_to_server_message(&self, nats_msg: NatsMessage) -> Option<ServerMessage> {
-        let session = self.session.as_ref()?;
+    /// Next outbound frame, batched if negotiated. Cancel-safe.
+    async fn next_frame(&mut self) -> Option<ServerMessage> {
+        if !self.has_capability(&Capability::Batching) {
+            return self.recv_outbound().await;
+        }
 
-        // Find the subscription ID for this subject
-        for (sub_id, subject) in &session.subscriptions {
-            if *subject == nats_msg.subject {
-                return Some(ServerMessage::Message {
-                    subscription_id: *sub_id,
-                    subject: nats_msg.subject,
-                    payload: nats_msg.payload,
-                });
+        loop {
+            let Some(deadline) = self.batch_deadline else {
+                let msg = self.recv_outbound().await?;
+                self.push_batched(msg);
+                continue;
+            };
+
+            // A closing connection flushes at once so its last error goes out
+            if self.closing || self.batch_bytes >= self.config.batch_max_bytes as usize {
+                return self.take_batch();
             }
-        }
 
-        // Subject might match via wildcard - find any matching subscription
-        for (sub_id, pattern) in &session.subscriptions {
-            if subject_matches_pattern(pattern, &nats_msg.subject) {
-                return Some(ServerMessage::Message {
-                    subscription_id: *sub_id,
-                    subject: nats_msg.subject,
-                    payload: nats_msg.payload,
-                });
+            tokio::select! {
+                biased;
+                msg = self.recv_outbound() => match msg {
+                    Some(msg) => self.push_batched(msg),
+                    None => return self.take_batch(),
+                },
+                _ = tokio::time::sleep_until(deadline) => return self.take_batch(),
             }
         }
+    }
+
+    fn push_batched(&mut self, msg: ServerMessage) {
+        if self.batch.is_empty() {
+            let delay = Duration::from_millis(self.config.batch_max_delay_ms);
+            self.batch_deadline = Some(Instant::now() + delay);
+        }
+        self.batch_bytes += encoded_size_hint(&msg);
+        self.batch.push(msg);
+    }
 
-        None
+    /// Flush the pending batch; a lone message is sent unwrapped
+    fn take_batch(&mut self) -> Option<ServerMessage> {
+        self.batch_deadline = None;
+        self.batch_bytes = 0;
+        let mut messages = std::mem::take(&mut self.batch);
+        match messages.len() {
+            0 => None,
+            1 => messages.pop(),
+            _ => Some(MessageCodec::batch_server(&messages)),
+        }
     }
 
-    async fn handle_auth(&mut self, token: &str) -> Option<ServerMessage> {
-        match self.jwt_validator.validate(token) {
-            Ok(claims) => {
-                let session = Session::new(claims);
-                let session_id = session.id.clone();
-                info!(
-                    "User {} authenticated, session {}",
-                    session.user_id, session_id
-                );
-                self.session = Some(session);
-                Some(ServerMessage::AuthOk { session_id })
+    /// Receive the next outbound message, unbatched. Cancel-safe.
+    pub(super) async fn recv_outbound(&mut self) -> Option<ServerMessage> {
+        loop {
+            if let Some(server_msg) = self.released.pop_front() {
+                return Some(server_msg);
             }
-            Err(e) => {
-                warn!("Authentication failed: {}", e);
-                Some(ServerMessage::AuthError {
-                    reason: e.to_string(),
-                })
+
+            if let Some(event) = self.replay.pop_front() {
+                if let Some(server_msg) = self.admit_delivery(event) {
+                    return Some(server_msg);
+                }
+                continue;
+            }
+
+            tokio::select! {
+                Some(id) = self.ready_rx.recv() => {
+                    if let Some(server_msg) = self.take_from_mailbox(id) {
+                        return Some(server_msg);
+                    }
+                }
+                Some(nats_msg) = self.nats_rx.recv() => {
+                    if let Some(server_msg) = self.admit_delivery(nats_msg) {
+                        return Some(server_msg);
+                    }
+                }
+                Some(server_msg) = self.outbound_rx.recv() => return Some(server_msg),
+                Some(change) = self.presence_rx.recv() => {
+                    // Changes queued before a watch ended are dropped with it
+                    if self.presence_watches.contains_key(&change.id) {
+                        return Some(ServerMessage::Presence {
+                            id: change.id,
+                            action: change.action,
+                            member: change.member,
+                        });
+                    }
+                }
+                Ok(()) = self.backend.changed(), if self.is_authenticated() => {
+                    if let Some(server_msg) = self.backend_status() {
+                        return Some(server_msg);
+                    }
+                }
+                else => return None,
             }
         }
     }
 
-    async fn handle_subscribe(&mut self, subject: String, id: u64) -> Option<ServerMessage> {
-        let session = self.session.as_mut()?;
+    /// Report the backend's availability if it changed since the client
+    /// last heard about it
+    fn backend_status(&mut self) -> Option<ServerMessage> {
+        let available = *self.backend.borrow_and_update() == Connectivity::Connected;
+        if available == self.backend_available {
+            return None;
+        }
+        self.backend_available = available;
+        if available {
+            info!("Backend available again, notifying client");
+        } else {
+            warn!("Backend unavailable, notifying client");
+        }
+        Some(ServerMessage::BackendStatus { available })
+    }
 
-        // Check permission
-        if !PermissionChecker::can_perform(&session.claims, Permission::Subscribe, &subject) {
-            return Some(ServerMessage::SubscribeError {
-                id,
-                reason: "Permission denied".to_string(),
+    fn handle_hello(
+        &mut self,
+        client_name: &str,
+        client_version: &str,
+        schema_fingerprint: &str,
+        requested: Vec<Capability>,
+        offered_compression: &[CompressionAlgorithm],
+    ) -> Option<ServerMessage> {
+        if self.capabilities.is_some() {
+            return Some(ServerMessage::HelloError {
+                code: ErrorCode::AlreadyExists,
+                message: "Handshake already completed".to_string(),
             });
         }
 
-        // Create NATS subscription
-        match self
-            .nats_bridge
-            .subscribe(subject.clone(), self.nats_tx.clone())
-            .await
-        {
-            Ok(handle) => {
-                session.add_subscription(id, subject.clone());
-                self.subscriptions.insert(id, handle);
+        if schema_fingerprint != SCHEMA_FINGERPRINT {
+            warn!(
+                "Client {} {} uses schema {}, gateway uses {}",
+                client_name, client_version, schema_fingerprint, SCHEMA_FINGERPRINT
+            );
+            return Some(ServerMessage::HelloError {
+                code: ErrorCode::SchemaMismatch,
+                message: format!(
+                    "Client schema {} does not match gateway schema {}",
+                    schema_fingerprint, SCHEMA_FINGERPRINT
+                ),
+            });
+        }
+
+        let mut capabilities = negotiate_capabilities(&requested);
+        let compression = if capabilities.contains(&Capability::Compression) {
+            negotiate_compression(offered_compression)
+        } else {
+            CompressionAlgorithm::None
+        };
+        if compression == CompressionAlgorithm::None {
+            // Nothing in common, so compression is off for this connection
+            capabilities.retain(|capability| *capability != Capability::Compression);
+        }
+        debug!(
+            "Client {} {} negotiated {:?} with {:?} compression",
+            client_name, client_version, capabilities, compression
+        );
+        self.capabilities = Some(capabilities.clone());
+        self.compression = compression.clone();
+
+        Some(ServerMessage::Welcome {
+            gateway_version: env!("CARGO_PKG_VERSION").to_string(),
+            schema_fingerprint: SCHEMA_FINGERPRINT.to_string(),
+            capabilities,
+            max_payload_bytes: self.config.max_payload_bytes,
+            compression,
+        })
+    }
+
+    /// Describe why a payload is rejected, if it exceeds the configured limit
+    pub(super) fn check_payload_size(&self, payload: &[u8]) -> Option<String> {
+        let limit = self.config.max_payload_bytes as usize;
+        (payload.len() > limit).then(|| {
+            format!(
+                "Payload of {} bytes exceeds limit of {} bytes",
+                payload.len(),
+                limit
+            )
+        })
+    }
+
+    async fn handle_auth(
+        &mut self,
+        token: &str,
+        resume_token: Option<String>,
+    ) -> Option<ServerMessage> {
+        let claims = match self.jwt_validator.validate(token) {
+            Ok(claims) => claims,
+            Err(e) => {
+                warn!("Authentication failed: {}", e);
+                return Some(ServerMessage::AuthError {
+                    code: ErrorCode::Unauthorized,
+                    reason: e.to_string(),
+                });
+            }
+        };
+
+        // Only a fresh connection can take over a parked session
+        let resumed = match resume_token {
+            Some(resume_token) if self.session.is_none() => {
+                self.resume_session(&resume_token, &claims.sub).await
+            }
+            _ => false,
+        };
+
+        let mut session = Session::new(claims);
+        if resumed && let Some(old) = &self.session {
+            session.id = old.id.clone();
+        }
+        let session_id = session.id.clone();
+        info!(
+            "User {} authenticated, session {}{}",
+            session.user_id,
+            session_id,
+            if resumed { " (resumed)" } else { "" }
+        );
+
+        // On re-authentication (or resumption), keep the subscriptions and
+        // presence watches the new token still allows and end the rest.
+        // Presence follows the kept subscriptions over to the new session.
+        let presence = self.broker.presence();
+
+        // Watches go first, so revoked ones miss the leaves below. Kept ones
+        // are renewed to filter members by the new claims.
+        for (id, watch) in std::mem::take(&mut self.presence_watches) {
+            let pattern = watch.pattern().to_string();
+            drop(watch);
+            if PermissionChecker::can_perform(&session.claims, Permission::Subscribe, &pattern) {
+                let watch = presence.watch(
+                    id,
+                    pattern,
+                    session.claims.clone(),
+                    self.presence_tx.clone(),
+                );
+                self.presence_watches.insert(id, watch);
+            } else {
                 debug!(
-                    "User {} subscribed to {} (id={})",
-                    session.user_id, subject, id
+                    "Presence id={} ended: {:?}",
+                    id,
+                    SubscriptionEndReason::PermissionRevoked
                 );
-                Some(ServerMessage::SubscribeOk { id })
-            }
-            Err(e) => {
-                error!("Failed to subscribe to {}: {}", subject, e);
-                Some(ServerMessage::SubscribeError {
+                self.released.push_back(ServerMessage::SubscriptionEnded {
                     id,
-                    reason: e.to_string(),
-                })
+                    reason: SubscriptionEndReason::PermissionRevoked,
+                    total_dropped: 0,
+                });
             }
         }
+
+        let previous = self.session.take();
+        let mut revoked = Vec::new();
+        if let Some(mut old) = previous {
+            let moved = old.id != session.id;
+            for (id, subject) in std::mem::take(&mut old.subscriptions) {
+                if PermissionChecker::can_perform(&session.claims, Permission::Subscribe, &subject)
+                {
+                    if moved {
+                        presence.leave(&old, &subject);
+                        presence.join(&session, &subject);
+                    }
+                    session.add_subscription(id, subject);
+                } else {
+                    presence.leave(&old, &subject);
+                    revoked.push(id);
+                }
+            }
+        }
+        self.session = Some(session);
+        for id in revoked {
+            self.end_subscription(id, SubscriptionEndReason::PermissionRevoked);
+        }
+
+        self.resume_token = (self.config.resume_grace_ms > 0).then(resume::resume_token);
+
+        // A client arriving while NATS is down learns so straight away
+        if let Some(status) = self.backend_status() {
+            self.released.push_back(status);
+        }
+
+        Some(ServerMessage::AuthOk {
+            session_id,
+            resume_token: self.resume_token.clone(),
+            resumed,
+        })
     }
 
-    async fn handle_unsubscribe(&mut self, id: u64) -> Option<ServerMessage> {
-        let session = self.session.as_mut()?;
+    /// Take over the session parked under `resume_token`, with the messages
+    /// buffered for it. Returns false if there is none for this user.
+    async fn resume_session(&mut self, resume_token: &str, user_id: &str) -> bool {
+        let Some(Detached {
+            handler: old,
+            buffered,
+        }) = self.resume.claim(resume_token, user_id).await
+        else {
+            return false;
+        };
 
-        if let Some(handle) = self.subscriptions.remove(&id) {
-            handle.unsubscribe().await;
-            session.remove_subscription(id);
-            debug!("User {} unsubscribed from id={}", session.user_id, id);
+        self.session = old.session;
+        self.subscriptions = old.subscriptions;
+        self.presence_watches = old.presence_watches;
+        self.nats_rx = old.nats_rx;
+        self.nats_tx = old.nats_tx;
+        self.ready_rx = old.ready_rx;
+        self.ready_tx = old.ready_tx;
+        self.presence_rx = old.presence_rx;
+        self.presence_tx = old.presence_tx;
+        self.released = buffered;
+        self.released.extend(old.released);
+        self.replay = old.replay;
+        self.uploads = old.uploads;
+        true
+    }
+
+    fn handle_subscribe_presence(&mut self, id: u64, pattern: String) -> Option<ServerMessage> {
+        // Seeing who is on a subject takes the same permission as listening to it
+        if let Err(refused) = self.check_subscribe(id, invalid_subject(&pattern), |claims| {
+            PermissionChecker::can_perform(claims, Permission::Subscribe, &pattern)
+        }) {
+            return Some(refused);
         }
+        let session = self.session.as_ref()?;
 
-        None // No response needed for unsubscribe
+        debug!(
+            "User {} watching presence on {} (id={})",
+            session.user_id, pattern, id
+        );
+        let watch = self.broker.presence().watch(
+            id,
+            pattern,
+            session.claims.clone(),
+            self.presence_tx.clone(),
+        );
+        self.presence_watches.insert(id, watch);
+
+        Some(ServerMessage::SubscribeOk { id })
+    }
+
+    fn handle_query_presence(&self, request_id: u64, pattern: &str) -> Option<ServerMessage> {
+        let session = self.session.as_ref()?;
+
+        if !is_valid_subject(pattern, true) {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: ErrorCode::InvalidSubject,
+                reason: format!("Invalid subject: {:?}", pattern),
+            });
+        }
+
+        if !PermissionChecker::can_perform(&session.claims, Permission::Subscribe, pattern) {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: ErrorCode::PermissionDenied,
+                reason: "Permission denied".to_string(),
+            });
+        }
+
+        Some(ServerMessage::PresenceList {
+            request_id,
+            members: self.broker.presence().members(pattern, &session.claims),
+        })
     }
 
     async fn handle_publish(&mut self, subject: &str, payload: Vec<u8>) -> Option<ServerMessage> {
         let session = self.session.as_ref()?;
 
+        if !is_valid_subject(subject, false) {
+            return Some(ServerMessage::Error {
+                code: ErrorCode::InvalidSubject,
+                message: format!("Invalid subject: {:?}", subject),
+            });
+        }
+
         // Check permission
         if !PermissionChecker::can_perform(&session.claims, Permission::Publish, subject) {
             return Some(ServerMessage::Error {
-                code: error_codes::FORBIDDEN,
+                code: ErrorCode::PermissionDenied,
                 message: "Permission denied".to_string(),
             });
         }
 
-        match self.nats_bridge.publish(subject, payload).await {
+        if let Some(reason) = self.check_payload_size(&payload) {
+            return Some(ServerMessage::Error {
+                code: ErrorCode::PayloadTooLarge,
+                message: reason,
+            });
+        }
+
+        let nats_subject = self.nats_subject(subject);
+        let headers = self.identity_headers(session, &nats_subject, &payload);
+        match self.broker.publish(&nats_subject, &headers, payload).await {
             Ok(_) => {
                 debug!("User {} published to {}", session.user_id, subject);
                 None // No response needed for publish
@@ -220,13 +865,99 @@ impl ConnectionHandler {
             Err(e) => {
                 error!("Failed to publish to {}: {}", subject, e);
                 Some(ServerMessage::Error {
-                    code: error_codes::INTERNAL_ERROR,
+                    code: e.code(),
                     message: e.to_string(),
                 })
             }
         }
     }
 
+    /// Where a client publish or request to `subject` goes in NATS
+    fn nats_subject(&self, subject: &str) -> String {
+        match mapping::map_to_nats(&self.config.subject_mappings, subject) {
+            Some((_, mapped)) => mapped,
+            None => subject.to_string(),
+        }
+    }
+
+    /// Headers telling backend services who sent a message on `subject`
+    fn identity_headers(&self, session: &Session, subject: &str, payload: &[u8]) -> Headers {
+        Identity {
+            user_id: session.user_id.clone(),
+            session_id: session.id.clone(),
+            tenant: session.claims.tenant.clone(),
+            client_ip: self.client_ip,
+            gateway: self.broker.presence().instance_id().to_string(),
+        }
+        .headers(subject, payload, self.identity_signer.as_ref())
+    }
+
+    async fn handle_jetstream_publish(
+        &mut self,
+        request_id: u64,
+        subject: &str,
+        payload: Vec<u8>,
+        msg_id: Option<String>,
+    ) -> Option<ServerMessage> {
+        let session = self.session.as_ref()?;
+
+        if !is_valid_subject(subject, false) {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: ErrorCode::InvalidSubject,
+                reason: format!("Invalid subject: {:?}", subject),
+            });
+        }
+
+        if !PermissionChecker::can_perform(&session.claims, Permission::Publish, subject) {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: ErrorCode::PermissionDenied,
+                reason: "Permission denied".to_string(),
+            });
+        }
+
+        if let Some(reason) = self.check_payload_size(&payload) {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: ErrorCode::PayloadTooLarge,
+                reason,
+            });
+        }
+
+        let nats_subject = self.nats_subject(subject);
+        let headers = self.identity_headers(session, &nats_subject, &payload);
+        match self
+            .broker
+            .publish_durable(&nats_subject, &headers, payload, msg_id)
+            .await
+        {
+            Ok(ack) => {
+                debug!(
+                    "User {} stored message {} in stream {}{}",
+                    session.user_id,
+                    ack.seq,
+                    ack.stream,
+                    if ack.duplicate { " (duplicate)" } else { "" }
+                );
+                Some(ServerMessage::PubAck {
+                    request_id,
+                    stream: ack.stream,
+                    seq: ack.seq,
+                    duplicate: ack.duplicate,
+                })
+            }
+            Err(e) => {
+                error!("Failed to publish to stream for {}: {}", subject, e);
+                Some(ServerMessage::RequestError {
+                    request_id,
+                    code: e.code(),
+                    reason: e.to_string(),
+                })
+            }
+        }
+    }
+
     async fn handle_request(
         &mut self,
         subject: &str,
@@ -236,33 +967,197 @@ impl ConnectionHandler {
     ) -> Option<ServerMessage> {
         let session = self.session.as_ref()?;
 
+        if !is_valid_subject(subject, false) {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: ErrorCode::InvalidSubject,
+                reason: format!("Invalid subject: {:?}", subject),
+            });
+        }
+
         // Check permission
         if !PermissionChecker::can_perform(&session.claims, Permission::Request, subject) {
             return Some(ServerMessage::RequestError {
                 request_id,
+                code: ErrorCode::PermissionDenied,
                 reason: "Permission denied".to_string(),
             });
         }
 
+        if let Some(reason) = self.check_payload_size(&payload) {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: ErrorCode::PayloadTooLarge,
+                reason,
+            });
+        }
+
+        let nats_subject = self.nats_subject(subject);
+        let headers = self.identity_headers(session, &nats_subject, &payload);
         let timeout = Duration::from_millis(timeout_ms as u64);
-        match self.nats_bridge.request(subject, payload, timeout).await {
+        match self
+            .broker
+            .request(&nats_subject, &headers, payload, timeout)
+            .await
+        {
             Ok(response) => Some(ServerMessage::Response {
                 request_id,
                 payload: response,
             }),
             Err(e) => Some(ServerMessage::RequestError {
                 request_id,
+                code: e.code(),
                 reason: e.to_string(),
             }),
         }
     }
 
+    async fn handle_stream_request(
+        &mut self,
+        subject: &str,
+        payload: Vec<u8>,
+        timeout_ms: u32,
+        request_id: u64,
+    ) -> Option<ServerMessage> {
+        let session = self.session.as_ref()?;
+
+        if !is_valid_subject(subject, false) {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: ErrorCode::InvalidSubject,
+                reason: format!("Invalid subject: {:?}", subject),
+            });
+        }
+
+        // Check permission
+        if !PermissionChecker::can_perform(&session.claims, Permission::Request, subject) {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: ErrorCode::PermissionDenied,
+                reason: "Permission denied".to_string(),
+            });
+        }
+
+        if let Some(reason) = self.check_payload_size(&payload) {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: ErrorCode::PayloadTooLarge,
+                reason,
+            });
+        }
+
+        // Forget streams whose forwarding task already finished
+        self.streams.retain(|_, task| !task.is_finished());
+
+        if self.streams.contains_key(&request_id) {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: ErrorCode::AlreadyExists,
+                reason: "Request ID already in use".to_string(),
+            });
+        }
+
+        let nats_subject = self.nats_subject(subject);
+        let headers = self.identity_headers(session, &nats_subject, &payload);
+        let stream = match self
+            .broker
+            .request_stream(&nats_subject, &headers, payload)
+            .await
+        {
+            Ok(stream) => stream,
+            Err(e) => {
+                return Some(ServerMessage::RequestError {
+                    request_id,
+                    code: e.code(),
+                    reason: e.to_string(),
+                });
+            }
+        };
+
+        debug!(
+            "User {} started streamed request {} to {}",
+            session.user_id, request_id, subject
+        );
+
+        let timeout = Duration::from_millis(timeout_ms as u64);
+        let task = tokio::spawn(forward_stream(
+            stream,
+            request_id,
+            timeout,
+            self.outbound_tx.clone(),
+        ));
+        self.streams.insert(request_id, task);
+
+        None // Replies arrive through the outbound channel
+    }
+
+    async fn handle_cancel_request(&mut self, request_id: u64) -> Option<ServerMessage> {
+        if let Some(task) = self.streams.remove(&request_id) {
+            task.abort();
+            debug!("Streamed request {} cancelled by client", request_id);
+        }
+        if self.uploads.remove(&request_id).is_some() {
+            debug!("Object upload {} cancelled by client", request_id);
+        }
+
+        None // No response needed for cancellation
+    }
+
+    /// Called when the connection drops without closing cleanly: park the
+    /// session so the client can resume it, or clean up if it can't
+    pub async fn detach(mut self) {
+        let grace = Duration::from_millis(self.config.resume_grace_ms);
+        let resumable = !grace.is_zero() && !self.draining && !self.closing;
+        let (Some(token), Some(session)) = (self.resume_token.take(), &self.session) else {
+            self.cleanup().await;
+            return;
+        };
+        if !resumable {
+            self.cleanup().await;
+            return;
+        }
+
+        info!("Session {} detached, resumable for {:?}", session.id, grace);
+        let user_id = session.user_id.clone();
+
+        // Streamed replies belong to the connection, but a batch that was
+        // still being put together is delivered on resumption
+        for (_, task) in self.streams.drain() {
+            task.abort();
+        }
+        for msg in self.batch.drain(..).rev() {
+            self.released.push_front(msg);
+        }
+        self.batch_bytes = 0;
+        self.batch_deadline = None;
+
+        let registry = self.resume.clone();
+        let limit = self.config.resume_buffer;
+        registry.park(token, user_id, self, grace, limit);
+    }
+
     /// Cleanup when connection closes
     pub async fn cleanup(&mut self) {
         // Unsubscribe from all NATS subscriptions
-        for (_, handle) in self.subscriptions.drain() {
-            handle.unsubscribe().await;
+        for (_, subscription) in self.subscriptions.drain() {
+            if let Some(handle) = subscription.handle {
+                handle.unsubscribe().await;
+            }
         }
+        self.presence_watches.clear();
+
+        if let Some(session) = &self.session {
+            let presence = self.broker.presence();
+            for subject in session.subscriptions.values() {
+                presence.leave(session, subject);
+            }
+        }
+
+        // Abandon any streamed requests and transfers still in flight
+        for (_, task) in self.streams.drain() {
+            task.abort();
+        }
+        self.uploads.clear();
 
         if let Some(session) = &self.session {
             info!("Session {} cleaned up", session.id);
@@ -270,86 +1165,163 @@ impl ConnectionHandler {
     }
 }
 
-/// Check if a subject matches a NATS-style pattern
-fn subject_matches_pattern(pattern: &str, subject: &str) -> bool {
-    let pattern_parts: Vec<&str> = pattern.split('.').collect();
-    let subject_parts: Vec<&str> = subject.split('.').collect();
+/// Rough encoded size of a server message, used for the batch size budget
+fn encoded_size_hint(msg: &ServerMessage) -> usize {
+    // Tag, ids and length prefixes
+    const OVERHEAD: usize = 16;
+    match msg {
+        ServerMessage::Message {
+            subject, payload, ..
+        }
+        | ServerMessage::ConsumerMessage {
+            subject, payload, ..
+        } => OVERHEAD + subject.len() + payload.len(),
+        ServerMessage::KvUpdate { key, value, .. } => OVERHEAD + key.len() + value.len(),
+        ServerMessage::ObjectChunk { data, .. } => OVERHEAD + data.len(),
+        ServerMessage::Response { payload, .. } | ServerMessage::ResponseChunk { payload, .. } => {
+            OVERHEAD + payload.len()
+        }
+        ServerMessage::Batch { frames } => OVERHEAD + frames.iter().map(Vec::len).sum::<usize>(),
+        _ => OVERHEAD,
+    }
+}
 
-    let mut pi = 0;
-    let mut si = 0;
+/// Keep the requested capabilities this gateway supports, in the client's order
+fn negotiate_capabilities(requested: &[Capability]) -> Vec<Capability> {
+    let mut agreed: Vec<Capability> = Vec::new();
+    for capability in requested {
+        if SUPPORTED_CAPABILITIES.contains(capability) && !agreed.contains(capability) {
+            agreed.push(capability.clone());
+        }
+    }
+    agreed
+}
 
-    while pi < pattern_parts.len() && si < subject_parts.len() {
-        let p = pattern_parts[pi];
+/// Pick the first algorithm in the client's preference order that the
+/// gateway supports
+fn negotiate_compression(offered: &[CompressionAlgorithm]) -> CompressionAlgorithm {
+    offered
+        .iter()
+        .find(|algorithm| SUPPORTED_COMPRESSION.contains(algorithm))
+        .cloned()
+        .unwrap_or(CompressionAlgorithm::None)
+}
 
-        if p == ">" {
-            return true;
-        } else if p == "*" || p == subject_parts[si] {
-            pi += 1;
-            si += 1;
-        } else {
-            return false;
+/// Map a frame that could not be decoded to the error sent to the client
+fn decode_error(e: CodecError) -> ServerMessage {
+    warn!("Failed to decode client message: {}", e);
+    let (code, message) = match e {
+        CodecError::VersionMismatch { .. } => (ErrorCode::VersionMismatch, e.to_string()),
+        CodecError::DecompressedTooLarge { .. } => (ErrorCode::PayloadTooLarge, e.to_string()),
+        CodecError::Compression(_) => (ErrorCode::InvalidMessage, e.to_string()),
+        CodecError::DecodeError(_) => (
+            ErrorCode::InvalidMessage,
+            "Invalid message format".to_string(),
+        ),
+    };
+    ServerMessage::Error { code, message }
+}
+
+/// Forward the parts of a streamed reply to the client until it ends
+async fn forward_stream(
+    mut stream: ResponseStream,
+    request_id: u64,
+    timeout: Duration,
+    sender: mpsc::Sender<ServerMessage>,
+) {
+    let mut seq = 0u32;
+
+    loop {
+        let msg = match stream.next_chunk(timeout).await {
+            Ok(StreamChunk::Data(payload)) => {
+                let chunk = ServerMessage::ResponseChunk {
+                    request_id,
+                    seq,
+                    payload,
+                };
+                seq = seq.wrapping_add(1);
+                chunk
+            }
+            Ok(StreamChunk::End) => ServerMessage::ResponseEnd { request_id },
+            Err(e) => ServerMessage::RequestError {
+                request_id,
+                code: e.code(),
+                reason: e.to_string(),
+            },
+        };
+
+        let done = !matches!(msg, ServerMessage::ResponseChunk { .. });
+        if sender.send(msg).await.is_err() || done {
+            break;
         }
     }
+}
+
+/// Check that a subject is well formed: non-empty dot-separated tokens
+/// without whitespace. Wildcards (`*`, and `>` as the last token) are only
+/// accepted when `allow_wildcards` is set.
+pub(super) fn is_valid_subject(subject: &str, allow_wildcards: bool) -> bool {
+    let tokens: Vec<&str> = subject.split('.').collect();
+    let last = tokens.len() - 1;
+
+    tokens.iter().enumerate().all(|(i, token)| match *token {
+        "" => false,
+        "*" => allow_wildcards,
+        ">" => allow_wildcards && i == last,
+        _ => !token
+            .chars()
+            .any(|c| c.is_whitespace() || c == '*' || c == '>'),
+    })
+}
 
-    pi == pattern_parts.len() && si == subject_parts.len()
+/// Check that a stream or consumer name is one NATS accepts: non-empty,
+/// without whitespace, dots, wildcards or path separators
+pub(super) fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, '.' | '*' | '>' | '/' | '\\'))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::protocol::MessageOrigin;
 
-    // ============ subject_matches_pattern Tests ============
-
-    #[test]
-    fn test_exact_match() {
-        assert!(subject_matches_pattern("foo.bar.baz", "foo.bar.baz"));
-        assert!(!subject_matches_pattern("foo.bar.baz", "foo.bar.qux"));
-        assert!(!subject_matches_pattern("foo.bar", "foo.bar.baz"));
-    }
-
-    #[test]
-    fn test_single_wildcard() {
-        assert!(subject_matches_pattern("foo.*.baz", "foo.bar.baz"));
-        assert!(subject_matches_pattern("foo.*.baz", "foo.qux.baz"));
-        assert!(!subject_matches_pattern("foo.*.baz", "foo.bar.qux"));
-        assert!(!subject_matches_pattern("foo.*.baz", "foo.bar.baz.extra"));
-    }
-
-    #[test]
-    fn test_multi_wildcard() {
-        assert!(subject_matches_pattern("foo.>", "foo.bar"));
-        assert!(subject_matches_pattern("foo.>", "foo.bar.baz"));
-        assert!(subject_matches_pattern("foo.>", "foo.bar.baz.qux"));
-        assert!(!subject_matches_pattern("foo.>", "bar.baz"));
-    }
+    // ============ is_valid_subject Tests ============
 
     #[test]
-    fn test_full_wildcard() {
-        assert!(subject_matches_pattern(">", "foo"));
-        assert!(subject_matches_pattern(">", "foo.bar"));
-        assert!(subject_matches_pattern(">", "foo.bar.baz"));
+    fn test_valid_subjects() {
+        assert!(is_valid_subject("foo", false));
+        assert!(is_valid_subject("foo.bar.baz", false));
+        assert!(is_valid_subject("foo.*.baz", true));
+        assert!(is_valid_subject("foo.>", true));
     }
 
     #[test]
-    fn test_mixed_wildcards() {
-        assert!(subject_matches_pattern("*.bar.>", "foo.bar.baz"));
-        assert!(subject_matches_pattern("*.bar.>", "qux.bar.baz.extra"));
-        assert!(!subject_matches_pattern("*.bar.>", "foo.qux.baz"));
+    fn test_invalid_subjects() {
+        assert!(!is_valid_subject("", true));
+        assert!(!is_valid_subject("foo..bar", true));
+        assert!(!is_valid_subject("foo.", true));
+        assert!(!is_valid_subject("foo bar", true));
+        assert!(!is_valid_subject("foo.>.bar", true));
+        assert!(!is_valid_subject("foo*", true));
     }
 
     #[test]
-    fn test_empty_patterns() {
-        assert!(subject_matches_pattern("", ""));
-        assert!(!subject_matches_pattern("foo", ""));
-        assert!(!subject_matches_pattern("", "foo"));
+    fn test_wildcards_rejected_when_not_allowed() {
+        assert!(!is_valid_subject("foo.*", false));
+        assert!(!is_valid_subject("foo.>", false));
     }
 
     #[test]
-    fn test_single_token() {
-        assert!(subject_matches_pattern("foo", "foo"));
-        assert!(!subject_matches_pattern("foo", "bar"));
-        assert!(subject_matches_pattern("*", "foo"));
-        assert!(subject_matches_pattern(">", "foo"));
+    fn test_valid_names() {
+        assert!(is_valid_name("ORDERS"));
+        assert!(is_valid_name("order-ui_1"));
+        assert!(!is_valid_name(""));
+        assert!(!is_valid_name("orders.eu"));
+        assert!(!is_valid_name("orders ui"));
+        assert!(!is_valid_name("orders>"));
     }
 
     // ============ ClientMessage Tests ============
@@ -358,6 +1330,7 @@ mod tests {
     fn test_requires_auth_auth_message() {
         let msg = ClientMessage::Auth {
             token: "test".to_string(),
+            resume_token: None,
         };
         assert!(!client_message_requires_auth(&msg));
     }
@@ -373,10 +1346,20 @@ mod tests {
         let msg = ClientMessage::Subscribe {
             subject: "test".to_string(),
             id: 1,
+            credits: None,
+            max_msgs: None,
+            replay: None,
+            slow_consumer: None,
         };
         assert!(client_message_requires_auth(&msg));
     }
 
+    #[test]
+    fn test_requires_auth_grant_credit() {
+        let msg = ClientMessage::GrantCredit { id: 1, credits: 10 };
+        assert!(client_message_requires_auth(&msg));
+    }
+
     #[test]
     fn test_requires_auth_publish() {
         let msg = ClientMessage::Publish {
@@ -386,6 +1369,17 @@ mod tests {
         assert!(client_message_requires_auth(&msg));
     }
 
+    #[test]
+    fn test_requires_auth_jetstream_publish() {
+        let msg = ClientMessage::JetStreamPublish {
+            request_id: 1,
+            subject: "test".to_string(),
+            payload: vec![],
+            msg_id: None,
+        };
+        assert!(client_message_requires_auth(&msg));
+    }
+
     #[test]
     fn test_requires_auth_request() {
         let msg = ClientMessage::Request {
@@ -393,7 +1387,100 @@ mod tests {
             payload: vec![],
             timeout_ms: 1000,
             request_id: 1,
+            stream: false,
+        };
+        assert!(client_message_requires_auth(&msg));
+    }
+
+    #[test]
+    fn test_requires_auth_hello() {
+        let msg = ClientMessage::Hello {
+            client_name: "test".to_string(),
+            client_version: "1.0.0".to_string(),
+            schema_fingerprint: SCHEMA_FINGERPRINT.to_string(),
+            capabilities: vec![],
+            compression: vec![],
+        };
+        assert!(!client_message_requires_auth(&msg));
+    }
+
+    // ============ negotiate_capabilities Tests ============
+
+    #[test]
+    fn test_negotiate_drops_unsupported() {
+        let requested = vec![Capability::Compression, Capability::Headers];
+        let agreed = negotiate_capabilities(&requested);
+        assert!(
+            agreed
+                .iter()
+                .all(|capability| SUPPORTED_CAPABILITIES.contains(capability))
+        );
+    }
+
+    #[test]
+    fn test_negotiate_empty_request() {
+        assert!(negotiate_capabilities(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_negotiate_compression_follows_client_order() {
+        let offered = vec![CompressionAlgorithm::Gzip, CompressionAlgorithm::Zstd];
+        assert_eq!(negotiate_compression(&offered), CompressionAlgorithm::Gzip);
+    }
+
+    #[test]
+    fn test_negotiate_compression_without_common_algorithm() {
+        assert_eq!(negotiate_compression(&[]), CompressionAlgorithm::None);
+        assert_eq!(
+            negotiate_compression(&[CompressionAlgorithm::None]),
+            CompressionAlgorithm::None
+        );
+    }
+
+    #[test]
+    fn test_requires_auth_batch() {
+        // The messages inside are checked individually
+        let msg = MessageCodec::batch_client(&[ClientMessage::Ping]);
+        assert!(!client_message_requires_auth(&msg));
+    }
+
+    // ============ encoded_size_hint Tests ============
+
+    #[test]
+    fn test_size_hint_counts_payload() {
+        let small = ServerMessage::Message {
+            subscription_id: 1,
+            subject: "a".to_string(),
+            payload: vec![],
+            seq: 1,
+            dropped: 0,
+            origin: MessageOrigin::Live,
+        };
+        let large = ServerMessage::Message {
+            subscription_id: 1,
+            subject: "a".to_string(),
+            payload: vec![0; 1000],
+            seq: 2,
+            dropped: 0,
+            origin: MessageOrigin::Live,
+        };
+        assert_eq!(encoded_size_hint(&large) - encoded_size_hint(&small), 1000);
+    }
+
+    #[test]
+    fn test_size_hint_batch_sums_frames() {
+        let msg = ServerMessage::ResponseChunk {
+            request_id: 1,
+            seq: 0,
+            payload: vec![0; 100],
         };
+        let batch = MessageCodec::batch_server(&[msg.clone(), msg.clone()]);
+        assert!(encoded_size_hint(&batch) > 2 * 100);
+    }
+
+    #[test]
+    fn test_requires_auth_cancel_request() {
+        let msg = ClientMessage::CancelRequest { request_id: 1 };
         assert!(client_message_requires_auth(&msg));
     }
 
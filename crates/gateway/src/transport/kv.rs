@@ -0,0 +1,218 @@
+//! Key-value bucket reads, writes and watches.
+
+use std::collections::HashMap;
+
+use tracing::{debug, error};
+
+use super::flow::FlowControl;
+use super::handler::{ConnectionHandler, is_valid_name, is_valid_subject};
+use super::subscribe::ActiveSubscription;
+use crate::auth::{Permission, PermissionChecker};
+use crate::bridge::KvChange;
+use crate::protocol::{ErrorCode, ServerMessage};
+
+impl ConnectionHandler {
+    /// Check a key-value operation before it reaches the bridge, returning
+    /// the error to report if it is not allowed
+    fn check_kv_access(
+        &self,
+        permission: Permission,
+        bucket: &str,
+        key: &str,
+    ) -> Option<(ErrorCode, String)> {
+        let session = self.session.as_ref()?;
+
+        if !is_valid_name(bucket) || !is_valid_subject(key, false) {
+            return Some((
+                ErrorCode::InvalidMessage,
+                format!("Invalid bucket or key: {:?}, {:?}", bucket, key),
+            ));
+        }
+
+        if !PermissionChecker::can_access_kv(&session.claims, permission, bucket, key) {
+            return Some((ErrorCode::PermissionDenied, "Permission denied".to_string()));
+        }
+
+        None
+    }
+
+    pub(super) async fn handle_kv_get(
+        &mut self,
+        request_id: u64,
+        bucket: &str,
+        key: &str,
+    ) -> Option<ServerMessage> {
+        if let Some((code, reason)) = self.check_kv_access(Permission::KvRead, bucket, key) {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code,
+                reason,
+            });
+        }
+
+        match self.broker.kv_get(bucket, key).await {
+            Ok(entry) => Some(ServerMessage::KvEntry {
+                request_id,
+                key: key.to_string(),
+                value: entry.value,
+                revision: entry.revision,
+            }),
+            Err(e) => {
+                error!("Failed to read {} from bucket {}: {}", key, bucket, e);
+                Some(ServerMessage::RequestError {
+                    request_id,
+                    code: e.code(),
+                    reason: e.to_string(),
+                })
+            }
+        }
+    }
+
+    pub(super) async fn handle_kv_put(
+        &mut self,
+        request_id: u64,
+        bucket: &str,
+        key: &str,
+        value: Vec<u8>,
+    ) -> Option<ServerMessage> {
+        if let Some((code, reason)) = self.check_kv_access(Permission::KvWrite, bucket, key) {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code,
+                reason,
+            });
+        }
+
+        if let Some(reason) = self.check_payload_size(&value) {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: ErrorCode::PayloadTooLarge,
+                reason,
+            });
+        }
+
+        match self.broker.kv_put(bucket, key, value).await {
+            Ok(revision) => Some(ServerMessage::KvPutOk {
+                request_id,
+                revision,
+            }),
+            Err(e) => {
+                error!("Failed to write {} to bucket {}: {}", key, bucket, e);
+                Some(ServerMessage::RequestError {
+                    request_id,
+                    code: e.code(),
+                    reason: e.to_string(),
+                })
+            }
+        }
+    }
+
+    pub(super) async fn handle_kv_delete(
+        &mut self,
+        request_id: u64,
+        bucket: &str,
+        key: &str,
+    ) -> Option<ServerMessage> {
+        if let Some((code, reason)) = self.check_kv_access(Permission::KvWrite, bucket, key) {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code,
+                reason,
+            });
+        }
+
+        match self.broker.kv_delete(bucket, key).await {
+            Ok(()) => Some(ServerMessage::KvDeleteOk { request_id }),
+            Err(e) => {
+                error!("Failed to delete {} from bucket {}: {}", key, bucket, e);
+                Some(ServerMessage::RequestError {
+                    request_id,
+                    code: e.code(),
+                    reason: e.to_string(),
+                })
+            }
+        }
+    }
+
+    pub(super) async fn handle_kv_watch(
+        &mut self,
+        id: u64,
+        bucket: &str,
+        key: &str,
+    ) -> Option<ServerMessage> {
+        let invalid = (!is_valid_name(bucket) || !is_valid_subject(key, true)).then(|| {
+            (
+                ErrorCode::InvalidMessage,
+                format!("Invalid bucket or key: {:?}, {:?}", bucket, key),
+            )
+        });
+        if let Err(refused) = self.check_subscribe(id, invalid, |claims| {
+            PermissionChecker::can_access_kv(claims, Permission::KvRead, bucket, key)
+        }) {
+            return Some(refused);
+        }
+        let session = self.session.as_ref()?;
+
+        match self
+            .broker
+            .kv_watch(bucket, key, id, self.nats_tx.clone())
+            .await
+        {
+            Ok(handle) => {
+                let flow =
+                    FlowControl::new(None, self.config.credit_buffer, self.config.overflow_policy);
+                self.subscriptions.insert(
+                    id,
+                    ActiveSubscription {
+                        handle: Some(handle),
+                        flow,
+                        remaining: None,
+                        last_seq: 0,
+                        delivered_seq: 0,
+                        unacked: HashMap::new(),
+                        mapping: None,
+                        mailbox: None,
+                    },
+                );
+                debug!(
+                    "User {} watching {} in bucket {} (id={})",
+                    session.user_id, key, bucket, id
+                );
+                Some(ServerMessage::SubscribeOk { id })
+            }
+            Err(e) => {
+                error!("Failed to watch bucket {}: {}", bucket, e);
+                Some(ServerMessage::SubscribeError {
+                    id,
+                    code: e.code(),
+                    reason: e.to_string(),
+                })
+            }
+        }
+    }
+
+    /// Convert a change of a watched key to a delivery. Watches bypass
+    /// credits, and changes to keys the client may not read are skipped, so
+    /// a wildcard watch only reports what the token allows.
+    pub(super) fn admit_kv_change(&mut self, change: KvChange) -> Option<ServerMessage> {
+        let session = self.session.as_ref()?;
+        if !self.subscriptions.contains_key(&change.subscription_id)
+            || !PermissionChecker::can_access_kv(
+                &session.claims,
+                Permission::KvRead,
+                &change.bucket,
+                &change.key,
+            )
+        {
+            return None;
+        }
+
+        Some(ServerMessage::KvUpdate {
+            id: change.subscription_id,
+            key: change.key,
+            value: change.value,
+            revision: change.revision,
+            operation: change.operation,
+        })
+    }
+}
@@ -0,0 +1,250 @@
+//! Subject mapping between the client and NATS namespaces.
+//!
+//! A rule pairs a client-facing template with a NATS template, such as
+//! `chat.{room}` and `svc.chat.v2.rooms.{room}.msgs`. `{name}` captures one
+//! token and a trailing `>` carries all remaining tokens; both templates
+//! must use the same captures. Publishes, requests, subscriptions and
+//! consumer filters are rewritten with the first rule whose client template
+//! covers the subject, and deliveries are rewritten back with the rule their
+//! subscription used.
+//! Wildcards in a subscription map through captures, so `chat.*` becomes
+//! `svc.chat.v2.rooms.*.msgs`; a subscription no rule covers entirely, such
+//! as `chat.>` under the rule above, is left as it is.
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Literal(String),
+    Capture(String),
+    /// A trailing `>`: the remaining tokens, at least one
+    Rest,
+}
+
+/// One client-to-NATS subject rewrite rule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubjectMapping {
+    client: Vec<Token>,
+    nats: Vec<Token>,
+}
+
+impl SubjectMapping {
+    /// Parse a `client=nats` rule. Returns None if either template is
+    /// malformed or the two do not use the same captures.
+    pub fn parse(rule: &str) -> Option<Self> {
+        let (client, nats) = rule.split_once('=')?;
+        let client = parse_template(client.trim())?;
+        let nats = parse_template(nats.trim())?;
+
+        let mut client_captures = captures(&client);
+        let mut nats_captures = captures(&nats);
+        client_captures.sort_unstable();
+        nats_captures.sort_unstable();
+        let repeated = client_captures.windows(2).any(|pair| pair[0] == pair[1]);
+        let rest = |template: &[Token]| template.last() == Some(&Token::Rest);
+        if repeated || client_captures != nats_captures || rest(&client) != rest(&nats) {
+            return None;
+        }
+        Some(Self { client, nats })
+    }
+
+    /// Parse a comma-separated list of `client=nats` rules
+    pub fn parse_list(s: &str) -> Option<Vec<Self>> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+            .map(Self::parse)
+            .collect()
+    }
+
+    /// The NATS subject for a client subject, if this rule covers it
+    pub fn to_nats(&self, subject: &str) -> Option<String> {
+        rewrite(&self.client, &self.nats, subject)
+    }
+
+    /// The client subject for a NATS subject, if this rule covers it
+    pub fn to_client(&self, subject: &str) -> Option<String> {
+        rewrite(&self.nats, &self.client, subject)
+    }
+}
+
+/// The first rule covering a client subject and the subject it maps to
+pub fn map_to_nats<'a>(
+    rules: &'a [SubjectMapping],
+    subject: &str,
+) -> Option<(&'a SubjectMapping, String)> {
+    rules
+        .iter()
+        .find_map(|rule| rule.to_nats(subject).map(|mapped| (rule, mapped)))
+}
+
+fn parse_template(template: &str) -> Option<Vec<Token>> {
+    let tokens: Vec<&str> = template.split('.').collect();
+    let last = tokens.len() - 1;
+    tokens
+        .iter()
+        .enumerate()
+        .map(|(i, token)| match *token {
+            ">" if i == last => Some(Token::Rest),
+            "" | "*" | ">" => None,
+            token => match token.strip_prefix('{').and_then(|t| t.strip_suffix('}')) {
+                Some(name) if !name.is_empty() => Some(Token::Capture(name.to_string())),
+                Some(_) => None,
+                None if token.contains(['{', '}']) => None,
+                None => Some(Token::Literal(token.to_string())),
+            },
+        })
+        .collect()
+}
+
+fn captures(template: &[Token]) -> Vec<&str> {
+    template
+        .iter()
+        .filter_map(|token| match token {
+            Token::Capture(name) => Some(name.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Match `subject` against `from` and fill `to` with what it captured.
+/// Wildcards in the subject may only fall on captures.
+fn rewrite(from: &[Token], to: &[Token], subject: &str) -> Option<String> {
+    let tokens: Vec<&str> = subject.split('.').collect();
+    let mut captured = HashMap::new();
+    let mut rest = None;
+
+    for (i, token) in from.iter().enumerate() {
+        match token {
+            Token::Literal(literal) => {
+                if tokens.get(i)? != literal {
+                    return None;
+                }
+            }
+            Token::Capture(name) => {
+                let value = *tokens.get(i)?;
+                // A capture is one token; `>` may span several
+                if value == ">" {
+                    return None;
+                }
+                captured.insert(name.as_str(), value);
+            }
+            Token::Rest => {
+                if i >= tokens.len() {
+                    return None;
+                }
+                rest = Some(tokens[i..].join("."));
+            }
+        }
+    }
+    if rest.is_none() && tokens.len() != from.len() {
+        return None;
+    }
+
+    let mapped: Vec<&str> = to
+        .iter()
+        .map(|token| match token {
+            Token::Literal(literal) => literal.as_str(),
+            Token::Capture(name) => captured[name.as_str()],
+            Token::Rest => rest.as_deref().unwrap_or_default(),
+        })
+        .collect();
+    Some(mapped.join("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat() -> SubjectMapping {
+        SubjectMapping::parse("chat.{room}=svc.chat.v2.rooms.{room}.msgs").unwrap()
+    }
+
+    #[test]
+    fn test_parse_rejects_mismatched_templates() {
+        assert!(SubjectMapping::parse("chat.{room}=svc.chat").is_none());
+        assert!(SubjectMapping::parse("chat.{room}=svc.{user}").is_none());
+        assert!(SubjectMapping::parse("chat.>=svc.chat").is_none());
+        assert!(SubjectMapping::parse("chat.*=svc.chat.*").is_none());
+        assert!(SubjectMapping::parse("chat.{}=svc.{}").is_none());
+        assert!(SubjectMapping::parse("chat..{room}=svc.{room}").is_none());
+        assert!(SubjectMapping::parse("chat.{room}").is_none());
+        assert!(SubjectMapping::parse("a.{x}.{x}=b.{x}.{x}").is_none());
+        assert_eq!(
+            SubjectMapping::parse_list("a.{x}=b.{x}, c.>=d.>").map(|rules| rules.len()),
+            Some(2)
+        );
+        assert!(SubjectMapping::parse_list("a.{x}=b.{x},broken").is_none());
+    }
+
+    #[test]
+    fn test_maps_both_ways() {
+        let rule = chat();
+        assert_eq!(
+            rule.to_nats("chat.lobby").as_deref(),
+            Some("svc.chat.v2.rooms.lobby.msgs")
+        );
+        assert_eq!(
+            rule.to_client("svc.chat.v2.rooms.lobby.msgs").as_deref(),
+            Some("chat.lobby")
+        );
+        assert_eq!(rule.to_nats("chat"), None);
+        assert_eq!(rule.to_nats("chat.lobby.extra"), None);
+        assert_eq!(rule.to_nats("news.lobby"), None);
+        assert_eq!(rule.to_client("svc.chat.v2.rooms.lobby"), None);
+    }
+
+    #[test]
+    fn test_captures_may_be_reordered() {
+        let rule = SubjectMapping::parse("orders.{region}.{id}=svc.{id}.in.{region}").unwrap();
+        assert_eq!(
+            rule.to_nats("orders.eu.42").as_deref(),
+            Some("svc.42.in.eu")
+        );
+        assert_eq!(
+            rule.to_client("svc.42.in.eu").as_deref(),
+            Some("orders.eu.42")
+        );
+    }
+
+    #[test]
+    fn test_trailing_wildcard_carries_the_rest() {
+        let rule = SubjectMapping::parse("metrics.{host}.>=telemetry.v1.{host}.>").unwrap();
+        assert_eq!(
+            rule.to_nats("metrics.web1.cpu.load").as_deref(),
+            Some("telemetry.v1.web1.cpu.load")
+        );
+        assert_eq!(rule.to_nats("metrics.web1"), None);
+        assert_eq!(
+            rule.to_nats("metrics.*.>").as_deref(),
+            Some("telemetry.v1.*.>")
+        );
+    }
+
+    #[test]
+    fn test_subscription_wildcards_map_through_captures() {
+        let rule = chat();
+        assert_eq!(
+            rule.to_nats("chat.*").as_deref(),
+            Some("svc.chat.v2.rooms.*.msgs")
+        );
+        // Nothing can stand for several tokens in the middle of the NATS subject
+        assert_eq!(rule.to_nats("chat.>"), None);
+        assert_eq!(rule.to_nats("*.lobby"), None);
+    }
+
+    #[test]
+    fn test_first_covering_rule_wins() {
+        let rules = SubjectMapping::parse_list(
+            "chat.admin=svc.chat.admin,chat.{room}=svc.chat.v2.rooms.{room}.msgs",
+        )
+        .unwrap();
+        let (rule, mapped) = map_to_nats(&rules, "chat.admin").unwrap();
+        assert_eq!(rule, &rules[0]);
+        assert_eq!(mapped, "svc.chat.admin");
+        let (rule, mapped) = map_to_nats(&rules, "chat.lobby").unwrap();
+        assert_eq!(rule, &rules[1]);
+        assert_eq!(mapped, "svc.chat.v2.rooms.lobby.msgs");
+        assert!(map_to_nats(&rules, "news").is_none());
+    }
+}
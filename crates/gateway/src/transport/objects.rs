@@ -0,0 +1,311 @@
+//! Chunked object uploads and downloads.
+
+use tokio::sync::mpsc;
+use tracing::{debug, error};
+
+use super::handler::{ConnectionHandler, is_valid_name};
+use crate::auth::{Permission, PermissionChecker};
+use crate::bridge::{ObjectDownload, ObjectUpload, STAGING_PREFIX};
+use crate::protocol::{ErrorCode, ServerMessage};
+
+/// Largest chunk of an object sent in one download message
+const OBJECT_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Length of a SHA-256 digest
+const SHA256_BYTES: usize = 32;
+
+/// Uploads a connection may have in progress at once
+const MAX_UPLOADS: usize = 8;
+
+impl ConnectionHandler {
+    /// Check an object transfer before it reaches the bridge, returning the
+    /// error to report if it is not allowed
+    fn check_object_access(
+        &self,
+        permission: Permission,
+        bucket: &str,
+        name: &str,
+    ) -> Option<(ErrorCode, String)> {
+        let session = self.session.as_ref()?;
+
+        // Staged uploads are the gateway's own until their digest checks out
+        if !is_valid_name(bucket) || name.is_empty() || name.starts_with(STAGING_PREFIX) {
+            return Some((
+                ErrorCode::InvalidMessage,
+                format!("Invalid bucket or object name: {:?}, {:?}", bucket, name),
+            ));
+        }
+
+        if !PermissionChecker::can_access_object(&session.claims, permission, bucket, name) {
+            return Some((ErrorCode::PermissionDenied, "Permission denied".to_string()));
+        }
+
+        None
+    }
+
+    pub(super) async fn handle_object_upload_start(
+        &mut self,
+        request_id: u64,
+        bucket: &str,
+        name: &str,
+        size: u64,
+        sha256: Vec<u8>,
+    ) -> Option<ServerMessage> {
+        if let Some((code, reason)) =
+            self.check_object_access(Permission::ObjectWrite, bucket, name)
+        {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code,
+                reason,
+            });
+        }
+
+        if sha256.len() != SHA256_BYTES {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: ErrorCode::InvalidMessage,
+                reason: format!("SHA-256 digest must be {} bytes", SHA256_BYTES),
+            });
+        }
+
+        // An upload cut short by a dropped connection carries on where it
+        // stopped, under the new request id
+        let previous = self
+            .uploads
+            .iter()
+            .find(|(_, upload)| upload.matches(bucket, name, size, &sha256))
+            .map(|(id, _)| *id);
+        if let Some(previous) = previous
+            && let Some(upload) = self.uploads.remove(&previous)
+        {
+            let offset = upload.offset();
+            self.uploads.insert(request_id, upload);
+            debug!("Resuming upload of {} at offset {}", name, offset);
+            return Some(ServerMessage::ObjectUploadProgress { request_id, offset });
+        }
+
+        if self.uploads.contains_key(&request_id) {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: ErrorCode::AlreadyExists,
+                reason: "Request ID already in use".to_string(),
+            });
+        }
+
+        // Each upload holds a staging object and a task feeding it
+        if self.uploads.len() >= MAX_UPLOADS {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: ErrorCode::QuotaExceeded,
+                reason: format!("At most {} uploads at a time", MAX_UPLOADS),
+            });
+        }
+
+        match self.broker.upload_object(bucket, name, size, sha256).await {
+            Ok(upload) if upload.is_complete() => Some(finish_upload(request_id, upload).await),
+            Ok(upload) => {
+                self.uploads.insert(request_id, upload);
+                Some(ServerMessage::ObjectUploadProgress {
+                    request_id,
+                    offset: 0,
+                })
+            }
+            Err(e) => {
+                error!("Failed to start upload of {} to {}: {}", name, bucket, e);
+                Some(ServerMessage::RequestError {
+                    request_id,
+                    code: e.code(),
+                    reason: e.to_string(),
+                })
+            }
+        }
+    }
+
+    pub(super) async fn handle_object_upload_chunk(
+        &mut self,
+        request_id: u64,
+        offset: u64,
+        data: Vec<u8>,
+    ) -> Option<ServerMessage> {
+        if let Some(reason) = self.check_payload_size(&data) {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: ErrorCode::PayloadTooLarge,
+                reason,
+            });
+        }
+
+        let Some(upload) = self.uploads.get_mut(&request_id) else {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: ErrorCode::NotFound,
+                reason: format!("No upload with request id {}", request_id),
+            });
+        };
+
+        // A chunk out of place is refused, leaving the upload as it was
+        if offset != upload.offset() {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: ErrorCode::InvalidMessage,
+                reason: format!("Expected a chunk at offset {}", upload.offset()),
+            });
+        }
+
+        if let Err(e) = upload.write(&data).await {
+            self.uploads.remove(&request_id);
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: e.code(),
+                reason: e.to_string(),
+            });
+        }
+
+        if !upload.is_complete() {
+            return Some(ServerMessage::ObjectUploadProgress {
+                request_id,
+                offset: upload.offset(),
+            });
+        }
+        let upload = self.uploads.remove(&request_id)?;
+        Some(finish_upload(request_id, upload).await)
+    }
+
+    pub(super) async fn handle_object_download(
+        &mut self,
+        request_id: u64,
+        bucket: &str,
+        name: &str,
+        offset: u64,
+    ) -> Option<ServerMessage> {
+        if let Some((code, reason)) = self.check_object_access(Permission::ObjectRead, bucket, name)
+        {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code,
+                reason,
+            });
+        }
+
+        // Forget streams whose forwarding task already finished
+        self.streams.retain(|_, task| !task.is_finished());
+
+        if self.streams.contains_key(&request_id) {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: ErrorCode::AlreadyExists,
+                reason: "Request ID already in use".to_string(),
+            });
+        }
+
+        let download = match self.broker.download_object(bucket, name).await {
+            Ok(download) => download,
+            Err(e) => {
+                return Some(ServerMessage::RequestError {
+                    request_id,
+                    code: e.code(),
+                    reason: e.to_string(),
+                });
+            }
+        };
+
+        if offset > download.size {
+            return Some(ServerMessage::RequestError {
+                request_id,
+                code: ErrorCode::InvalidMessage,
+                reason: format!(
+                    "Offset {} is past the end of {:?} ({} bytes)",
+                    offset, name, download.size
+                ),
+            });
+        }
+
+        let size = download.size;
+        let chunk_bytes = OBJECT_CHUNK_BYTES.min(self.config.max_payload_bytes as usize);
+        let task = tokio::spawn(forward_download(
+            download,
+            request_id,
+            offset,
+            chunk_bytes,
+            self.outbound_tx.clone(),
+        ));
+        self.streams.insert(request_id, task);
+
+        // Chunks follow through the outbound channel
+        Some(ServerMessage::ObjectMeta {
+            request_id,
+            name: name.to_string(),
+            size,
+        })
+    }
+}
+
+/// Wait for a fully received upload to be stored and report the outcome
+async fn finish_upload(request_id: u64, upload: ObjectUpload) -> ServerMessage {
+    match upload.finish().await {
+        Ok(size) => ServerMessage::ObjectUploadOk { request_id, size },
+        Err(e) => {
+            error!("Object upload {} failed: {}", request_id, e);
+            ServerMessage::RequestError {
+                request_id,
+                code: e.code(),
+                reason: e.to_string(),
+            }
+        }
+    }
+}
+
+/// Send an object to the client in chunks, starting at `offset`, then the
+/// digest of the whole object
+async fn forward_download(
+    mut download: ObjectDownload,
+    request_id: u64,
+    offset: u64,
+    chunk_bytes: usize,
+    sender: mpsc::Sender<ServerMessage>,
+) {
+    let mut position = 0u64;
+
+    loop {
+        let mut data = match download.read_chunk(chunk_bytes).await {
+            Ok(data) => data,
+            Err(e) => {
+                let _ = sender
+                    .send(ServerMessage::RequestError {
+                        request_id,
+                        code: e.code(),
+                        reason: e.to_string(),
+                    })
+                    .await;
+                break;
+            }
+        };
+        if data.is_empty() {
+            let end = ServerMessage::ObjectDownloadEnd {
+                request_id,
+                sha256: download.sha256(),
+            };
+            let _ = sender.send(end).await;
+            break;
+        }
+
+        let start = position;
+        position += data.len() as u64;
+        // Bytes before the offset are only read to hash them
+        if position <= offset {
+            continue;
+        }
+        let skip = offset.saturating_sub(start) as usize;
+        data.drain(..skip);
+
+        let chunk = ServerMessage::ObjectChunk {
+            request_id,
+            offset: start + skip as u64,
+            data,
+        };
+        if sender.send(chunk).await.is_err() {
+            break;
+        }
+    }
+}
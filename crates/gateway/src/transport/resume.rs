@@ -0,0 +1,159 @@
+//! Resumption of sessions whose connection dropped.
+//!
+//! When a connection goes away without closing cleanly, its handler is
+//! parked here instead of being cleaned up: NATS subscriptions stay open and
+//! deliveries are buffered. A new connection that authenticates as the same
+//! user and presents the resume token from `AuthOk` takes the session over,
+//! along with everything buffered. Sessions not resumed within the grace
+//! period, or whose buffer fills up, are cleaned up.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use ring::rand::{SecureRandom, SystemRandom};
+use tokio::sync::oneshot;
+use tracing::{debug, info, warn};
+
+use super::handler::ConnectionHandler;
+use crate::protocol::ServerMessage;
+
+/// A parked session, handed over to the connection resuming it
+pub(super) struct Detached {
+    pub(super) handler: ConnectionHandler,
+    /// Messages produced while no connection was attached, oldest first
+    pub(super) buffered: VecDeque<ServerMessage>,
+}
+
+struct Parked {
+    user_id: String,
+    /// Asks the task holding the session to hand it over
+    claim: oneshot::Sender<oneshot::Sender<Detached>>,
+}
+
+/// Sessions waiting for their client to reconnect, by resume token
+#[derive(Default)]
+pub struct ResumeRegistry {
+    parked: Mutex<HashMap<String, Parked>>,
+}
+
+impl ResumeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hold on to a session whose connection dropped for `grace`, buffering
+    /// up to `limit` outbound messages
+    pub(super) fn park(
+        self: &Arc<Self>,
+        token: String,
+        user_id: String,
+        handler: ConnectionHandler,
+        grace: Duration,
+        limit: usize,
+    ) {
+        let (claim_tx, claim_rx) = oneshot::channel();
+        self.parked.lock().unwrap().insert(
+            token.clone(),
+            Parked {
+                user_id,
+                claim: claim_tx,
+            },
+        );
+        tokio::spawn(hold(self.clone(), token, handler, claim_rx, grace, limit));
+    }
+
+    /// Take over the session parked under `token`, if there is one and it
+    /// belongs to `user_id`
+    pub(super) async fn claim(&self, token: &str, user_id: &str) -> Option<Detached> {
+        let parked = {
+            let mut parked = self.parked.lock().unwrap();
+            if parked.get(token)?.user_id != user_id {
+                warn!("Resume token presented by another user, ignoring it");
+                return None;
+            }
+            parked.remove(token)?
+        };
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        parked.claim.send(reply_tx).ok()?;
+        reply_rx.await.ok()
+    }
+
+    fn forget(&self, token: &str) {
+        self.parked.lock().unwrap().remove(token);
+    }
+}
+
+/// Keep a parked session running until it is claimed or given up
+async fn hold(
+    registry: Arc<ResumeRegistry>,
+    token: String,
+    mut handler: ConnectionHandler,
+    mut claim_rx: oneshot::Receiver<oneshot::Sender<Detached>>,
+    grace: Duration,
+    limit: usize,
+) {
+    let expiry = tokio::time::sleep(grace);
+    tokio::pin!(expiry);
+    let mut buffered = VecDeque::new();
+
+    let claimed = loop {
+        tokio::select! {
+            claim = &mut claim_rx => break claim.ok(),
+            _ = &mut expiry => {
+                debug!("Detached session was not resumed within {:?}", grace);
+                break None;
+            }
+            msg = handler.recv_outbound() => {
+                let Some(msg) = msg else { break None };
+                if buffered.len() == limit || handler.should_close() {
+                    warn!("Detached session overflowed its buffer of {} messages", limit);
+                    break None;
+                }
+                buffered.push_back(msg);
+            }
+        }
+    };
+
+    if let Some(reply) = claimed {
+        match reply.send(Detached { handler, buffered }) {
+            Ok(()) => {
+                info!("Detached session resumed");
+                return;
+            }
+            // The resuming connection went away in the meantime
+            Err(detached) => handler = detached.handler,
+        }
+    }
+
+    registry.forget(&token);
+    handler.cleanup().await;
+}
+
+/// Generate an unguessable resume token
+pub(super) fn resume_token() -> String {
+    let mut bytes = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("system random number generator failed");
+    bytes
+        .iter()
+        .fold(String::with_capacity(32), |mut token, b| {
+            let _ = write!(token, "{:02x}", b);
+            token
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resume_tokens_are_unique() {
+        let token = resume_token();
+        assert_eq!(token.len(), 32);
+        assert_ne!(token, resume_token());
+    }
+}
@@ -0,0 +1,446 @@
+//! Core subscriptions: subscribing and unsubscribing, and admitting the
+//! messages waiting in their mailboxes under flow control and message
+//! limits.
+
+use std::collections::HashMap;
+
+use tracing::{debug, error, warn};
+
+use super::flow::{Admission, BufferOverflow, FlowControl};
+use super::handler::{ConnectionHandler, is_valid_subject};
+use super::mapping::{self, SubjectMapping};
+use crate::auth::{Claims, Permission, PermissionChecker};
+use crate::bridge::{
+    AckHandle, Mailbox, MailboxEvent, SubscriptionEvent, SubscriptionHandle, mailbox,
+};
+use crate::protocol::{
+    ErrorCode, ReplayFrom, ServerMessage, SlowConsumerPolicy, SubscriptionEndReason,
+};
+
+impl ConnectionHandler {
+    /// Checks shared by every kind of subscription, in order: the gateway
+    /// is not draining, the target is valid (`invalid` says why not), the
+    /// id is free and the client is `permitted`. Returns the
+    /// SubscribeError to send otherwise.
+    pub(super) fn check_subscribe(
+        &self,
+        id: u64,
+        invalid: Option<(ErrorCode, String)>,
+        permitted: impl FnOnce(&Claims) -> bool,
+    ) -> Result<(), ServerMessage> {
+        let refuse = |code, reason| Err(ServerMessage::SubscribeError { id, code, reason });
+
+        if self.draining {
+            return refuse(ErrorCode::Draining, "Gateway is shutting down".to_string());
+        }
+        if let Some((code, reason)) = invalid {
+            return refuse(code, reason);
+        }
+        if self.subscription_id_in_use(id) {
+            return refuse(
+                ErrorCode::AlreadyExists,
+                "Subscription ID already in use".to_string(),
+            );
+        }
+        if !self
+            .session
+            .as_ref()
+            .is_some_and(|session| permitted(&session.claims))
+        {
+            return refuse(ErrorCode::PermissionDenied, "Permission denied".to_string());
+        }
+        Ok(())
+    }
+
+    pub(super) async fn handle_subscribe(
+        &mut self,
+        subject: String,
+        id: u64,
+        credits: Option<u32>,
+        max_msgs: Option<u32>,
+        replay: Option<ReplayFrom>,
+        slow_consumer: Option<SlowConsumerPolicy>,
+    ) -> Option<ServerMessage> {
+        if let Err(refused) = self.check_subscribe(id, invalid_subject(&subject), |claims| {
+            PermissionChecker::can_perform(claims, Permission::Subscribe, &subject)
+        }) {
+            return Some(refused);
+        }
+
+        if max_msgs == Some(0) {
+            return Some(ServerMessage::SubscribeError {
+                id,
+                code: ErrorCode::InvalidMessage,
+                reason: "max_msgs must be at least 1".to_string(),
+            });
+        }
+        let session = self.session.as_mut()?;
+
+        // Permissions apply to the client's subject, NATS sees the mapped one
+        let (nats_subject, mapping) =
+            match mapping::map_to_nats(&self.config.subject_mappings, &subject) {
+                Some((rule, mapped)) => (mapped, Some(rule.clone())),
+                None => (subject.clone(), None),
+            };
+
+        // The client's choice, else the first configured rule covering the
+        // subject. Blocking costs an upstream subscription of its own, so
+        // only the configuration can ask for it.
+        let configured = self
+            .config
+            .subject_slow_consumer
+            .iter()
+            .find(|rule| PermissionChecker::matches_pattern(&rule.pattern, &subject))
+            .map_or(&self.config.slow_consumer_policy, |rule| &rule.policy)
+            .clone();
+        if matches!(slow_consumer, Some(SlowConsumerPolicy::Block))
+            && !matches!(configured, SlowConsumerPolicy::Block)
+        {
+            return Some(ServerMessage::SubscribeError {
+                id,
+                code: ErrorCode::InvalidMessage,
+                reason: "Block slow-consumer policy is not configured for this subject".to_string(),
+            });
+        }
+        let policy = slow_consumer.unwrap_or(configured);
+        let (sender, mailbox) = mailbox(
+            id,
+            self.config.subscription_buffer,
+            policy,
+            self.ready_tx.clone(),
+        );
+
+        // Create NATS subscription
+        match self.broker.subscribe(nats_subject.clone(), sender).await {
+            Ok(handle) => {
+                self.broker.presence().join(session, &subject);
+                session.add_subscription(id, subject.clone());
+                let flow = FlowControl::new(
+                    credits,
+                    self.config.credit_buffer,
+                    self.config.overflow_policy,
+                );
+                self.subscriptions.insert(
+                    id,
+                    ActiveSubscription {
+                        handle: Some(handle),
+                        flow,
+                        remaining: max_msgs,
+                        last_seq: 0,
+                        delivered_seq: 0,
+                        unacked: HashMap::new(),
+                        mapping: mapping.clone(),
+                        mailbox: Some(mailbox),
+                    },
+                );
+                // Requested history takes the place of retained messages;
+                // subjects the client may not see are left out
+                let backlog = match &replay {
+                    Some(from) => self.broker.history(&nats_subject, id, from),
+                    None => self.broker.retained(&nats_subject, id),
+                };
+                self.replay.extend(
+                    backlog
+                        .into_iter()
+                        .filter(|msg| {
+                            let subject = client_subject(mapping.as_ref(), msg.subject.clone());
+                            PermissionChecker::is_subject_allowed(&session.claims, &subject)
+                        })
+                        .map(SubscriptionEvent::Message),
+                );
+                debug!(
+                    "User {} subscribed to {} (id={})",
+                    session.user_id, subject, id
+                );
+                Some(ServerMessage::SubscribeOk { id })
+            }
+            Err(e) => {
+                error!("Failed to subscribe to {}: {}", subject, e);
+                Some(ServerMessage::SubscribeError {
+                    id,
+                    code: e.code(),
+                    reason: e.to_string(),
+                })
+            }
+        }
+    }
+
+    pub(super) async fn handle_unsubscribe(&mut self, id: u64) -> Option<ServerMessage> {
+        let session = self.session.as_mut()?;
+
+        // Dropping the watch ends the presence subscription
+        if self.presence_watches.remove(&id).is_some() {
+            debug!("User {} left presence id={}", session.user_id, id);
+            return Some(ServerMessage::UnsubscribeOk {
+                id,
+                total_dropped: 0,
+            });
+        }
+
+        let Some(subscription) = self.subscriptions.remove(&id) else {
+            return Some(ServerMessage::UnsubscribeError {
+                id,
+                code: ErrorCode::NotFound,
+                reason: format!("No subscription with id {}", id),
+            });
+        };
+
+        let total_dropped = subscription.total_dropped();
+        if let Some(handle) = subscription.handle {
+            handle.unsubscribe().await;
+        }
+        if let Some(subject) = session.remove_subscription(id) {
+            self.broker.presence().leave(session, &subject);
+        }
+        debug!("User {} unsubscribed from id={}", session.user_id, id);
+
+        Some(ServerMessage::UnsubscribeOk { id, total_dropped })
+    }
+
+    pub(super) fn subscription_id_in_use(&self, id: u64) -> bool {
+        self.subscriptions.contains_key(&id) || self.presence_watches.contains_key(&id)
+    }
+
+    pub(super) fn handle_grant_credit(&mut self, id: u64, credits: u32) -> Option<ServerMessage> {
+        if let Some(subscription) = self.subscriptions.get_mut(&id) {
+            let released = subscription.flow.grant(credits);
+            self.released.extend(
+                released
+                    .into_iter()
+                    .map(|delivery| subscription.stamp_dropped(delivery)),
+            );
+
+            // A subscription at its limit ends once its buffer is drained
+            if subscription.remaining == Some(0) && !subscription.flow.has_pending() {
+                self.end_subscription(id, SubscriptionEndReason::LimitReached);
+            }
+        }
+
+        None // Released deliveries go out through next_outbound
+    }
+
+    /// Take the next message from a subscription's mailbox and admit it.
+    /// Messages the mailbox discarded count towards the sequence, so the
+    /// delivery reports them as dropped.
+    pub(super) fn take_from_mailbox(&mut self, id: u64) -> Option<ServerMessage> {
+        // Announcements may outlive their subscription
+        let subscription = self.subscriptions.get_mut(&id)?;
+        match subscription.mailbox.as_ref()?.take()? {
+            MailboxEvent::Message { message, dropped } => {
+                subscription.last_seq += dropped;
+                self.admit_delivery(SubscriptionEvent::Message(message))
+            }
+            MailboxEvent::Closed => {
+                self.end_subscription(id, SubscriptionEndReason::BackendClosed);
+                None
+            }
+            MailboxEvent::Overflowed => {
+                warn!(
+                    "Subscription {} fell {} messages behind, disconnecting client",
+                    id, self.config.subscription_buffer
+                );
+                self.closing = true;
+                Some(ServerMessage::Error {
+                    code: ErrorCode::QuotaExceeded,
+                    message: format!(
+                        "Subscription {} fell more than {} messages behind",
+                        id, self.config.subscription_buffer
+                    ),
+                })
+            }
+        }
+    }
+
+    /// Convert a NATS message to a delivery, subject to its subscription's
+    /// flow control and message limit. Returns None if the delivery is held
+    /// back or dropped.
+    pub(super) fn admit_delivery(&mut self, event: SubscriptionEvent) -> Option<ServerMessage> {
+        let nats_msg = match event {
+            SubscriptionEvent::Message(nats_msg) => nats_msg,
+            SubscriptionEvent::Consumer(delivery) => return self.admit_consumer_delivery(delivery),
+            SubscriptionEvent::KvChange(change) => return self.admit_kv_change(change),
+            SubscriptionEvent::Closed { subscription_id } => {
+                self.end_subscription(subscription_id, SubscriptionEndReason::BackendClosed);
+                return None;
+            }
+        };
+        let id = nats_msg.subscription_id;
+
+        // Messages still in flight for a removed subscription are discarded
+        let subscription = self.subscriptions.get_mut(&id)?;
+
+        // As are those that arrive after the limit was reached
+        if subscription.remaining == Some(0) {
+            return None;
+        }
+
+        subscription.last_seq += 1;
+        let delivery = ServerMessage::Message {
+            subscription_id: id,
+            subject: client_subject(subscription.mapping.as_ref(), nats_msg.subject),
+            payload: nats_msg.payload,
+            seq: subscription.last_seq,
+            dropped: 0,
+            origin: nats_msg.origin,
+        };
+
+        let admitted = subscription
+            .flow
+            .offer(delivery)
+            .map(|admission| match admission {
+                Admission::Send(delivery) => Admission::Send(subscription.stamp_dropped(delivery)),
+                other => other,
+            });
+
+        // Only deliveries the client will receive count towards the limit
+        if let Ok(Admission::Send(_) | Admission::Buffered) = admitted
+            && let Some(remaining) = subscription.remaining.as_mut()
+        {
+            *remaining -= 1;
+            if *remaining == 0 {
+                // Stop receiving; buffered deliveries still go out on grant
+                subscription.handle = None;
+                if !subscription.flow.has_pending() {
+                    self.end_subscription(id, SubscriptionEndReason::LimitReached);
+                }
+            }
+        }
+
+        match admitted {
+            Ok(Admission::Send(delivery)) => Some(delivery),
+            Ok(_) => None,
+            Err(BufferOverflow) => {
+                warn!(
+                    "Subscription {} overflowed its buffer, disconnecting client",
+                    id
+                );
+                self.closing = true;
+                Some(ServerMessage::Error {
+                    code: ErrorCode::QuotaExceeded,
+                    message: format!(
+                        "Subscription {} exceeded its buffer of {} messages",
+                        id, self.config.credit_buffer
+                    ),
+                })
+            }
+        }
+    }
+
+    /// Drop a subscription the client did not unsubscribe from and queue a
+    /// notification telling it why
+    pub(super) fn end_subscription(&mut self, id: u64, reason: SubscriptionEndReason) {
+        // Dropping the handle cancels the NATS subscription
+        let Some(subscription) = self.subscriptions.remove(&id) else {
+            return;
+        };
+        if let Some(session) = self.session.as_mut()
+            && let Some(subject) = session.remove_subscription(id)
+        {
+            self.broker.presence().leave(session, &subject);
+        }
+        debug!("Subscription id={} ended: {:?}", id, reason);
+        self.released.push_back(ServerMessage::SubscriptionEnded {
+            id,
+            reason,
+            total_dropped: subscription.total_dropped(),
+        });
+    }
+}
+
+/// A NATS subscription held for the client, with its flow control state
+pub(super) struct ActiveSubscription {
+    /// None once the message limit was reached
+    pub(super) handle: Option<SubscriptionHandle>,
+    pub(super) flow: FlowControl,
+    /// Messages left before the subscription ends, if it has a limit
+    pub(super) remaining: Option<u32>,
+    /// Sequence number given to the last message received from NATS
+    pub(super) last_seq: u64,
+    /// Sequence number of the last message sent to the client
+    pub(super) delivered_seq: u64,
+    /// Durable consumer deliveries awaiting acknowledgement, by stream sequence
+    pub(super) unacked: HashMap<u64, AckHandle>,
+    /// Rule the subject was mapped to NATS with, reversed on delivery
+    pub(super) mapping: Option<SubjectMapping>,
+    /// Queue of live messages for a core subscription, under its
+    /// slow-consumer policy
+    pub(super) mailbox: Option<Mailbox>,
+}
+
+impl ActiveSubscription {
+    /// Record a delivery as sent, filling in how many messages the gateway
+    /// discarded since the previous one
+    fn stamp_dropped(&mut self, mut delivery: ServerMessage) -> ServerMessage {
+        if let ServerMessage::Message { seq, dropped, .. } = &mut delivery {
+            let gap = *seq - self.delivered_seq - 1;
+            *dropped = u32::try_from(gap).unwrap_or(u32::MAX);
+            self.delivered_seq = *seq;
+        }
+        delivery
+    }
+
+    /// Messages the gateway discarded over the subscription's lifetime,
+    /// by its slow-consumer and overflow policies
+    fn total_dropped(&self) -> u64 {
+        self.flow.dropped() + self.mailbox.as_ref().map_or(0, Mailbox::total_dropped)
+    }
+}
+
+/// Why `subject` cannot be subscribed to, if it is not a valid pattern
+pub(super) fn invalid_subject(subject: &str) -> Option<(ErrorCode, String)> {
+    (!is_valid_subject(subject, true)).then(|| {
+        (
+            ErrorCode::InvalidSubject,
+            format!("Invalid subject: {:?}", subject),
+        )
+    })
+}
+
+/// The subject a message received from NATS on `subject` has for the client
+pub(super) fn client_subject(mapping: Option<&SubjectMapping>, subject: String) -> String {
+    match mapping {
+        Some(rule) => rule.to_client(&subject).unwrap_or(subject),
+        None => subject,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CreditOverflowPolicy;
+    use crate::protocol::MessageOrigin;
+
+    fn delivery(seq: u64) -> ServerMessage {
+        ServerMessage::Message {
+            subscription_id: 1,
+            subject: "ticks".to_string(),
+            payload: vec![],
+            seq,
+            dropped: 0,
+            origin: MessageOrigin::Live,
+        }
+    }
+
+    #[test]
+    fn test_stamp_dropped_counts_gap() {
+        let mut subscription = ActiveSubscription {
+            handle: None,
+            flow: FlowControl::new(None, 1, CreditOverflowPolicy::DropOldest),
+            remaining: None,
+            last_seq: 5,
+            delivered_seq: 0,
+            unacked: HashMap::new(),
+            mapping: None,
+            mailbox: None,
+        };
+
+        let stamped = |msg: ServerMessage| match msg {
+            ServerMessage::Message { dropped, .. } => dropped,
+            other => panic!("Expected Message, got: {:?}", other),
+        };
+
+        assert_eq!(stamped(subscription.stamp_dropped(delivery(1))), 0);
+        assert_eq!(stamped(subscription.stamp_dropped(delivery(4))), 2);
+        assert_eq!(stamped(subscription.stamp_dropped(delivery(5))), 0);
+    }
+}
@@ -5,41 +5,58 @@ use axum::{
     Router,
     extract::{
         ConnectInfo, State, WebSocketUpgrade,
-        ws::{Message, WebSocket},
+        ws::{CloseFrame, Message, WebSocket, close_code},
     },
+    http::StatusCode,
     response::IntoResponse,
     routing::get,
 };
 use futures::{SinkExt, StreamExt};
+use tokio::sync::watch;
 use tokio::task::JoinHandle;
+use tokio::time::Instant;
 use tower_http::cors::{Any, CorsLayer};
 use tracing::{debug, info, warn};
 
 use crate::auth::JwtValidator;
-use crate::bridge::NatsBridge;
+use crate::bridge::Broker;
+use crate::config::GatewayConfig;
 use crate::protocol::MessageCodec;
 
+use super::drain_requested;
 use super::handler::ConnectionHandler;
+use super::resume::ResumeRegistry;
 
 /// Shared state for WebSocket handlers
 #[derive(Clone)]
 struct AppState {
+    config: Arc<GatewayConfig>,
     jwt_validator: Arc<JwtValidator>,
-    nats_bridge: Arc<NatsBridge>,
+    broker: Arc<dyn Broker>,
+    /// Sessions of dropped connections, waiting to be resumed
+    resume: Arc<ResumeRegistry>,
+    /// Flips to true when the gateway starts draining
+    drain: watch::Receiver<bool>,
 }
 
 /// Run the WebSocket server
-/// Returns the actual bound port and a handle to the server task
+/// Returns the actual bound port and a handle to the server task.
+/// Once `drain` becomes true the server stops accepting connections and
+/// every open connection is sent a GoAway.
 pub async fn run_server(
-    host: String,
-    port: u16,
+    config: Arc<GatewayConfig>,
     jwt_validator: Arc<JwtValidator>,
-    nats_bridge: Arc<NatsBridge>,
+    broker: Arc<dyn Broker>,
+    drain: watch::Receiver<bool>,
 ) -> Result<(u16, JoinHandle<Result<(), std::io::Error>>), Box<dyn std::error::Error + Send + Sync>>
 {
+    let addr = format!("{}:{}", config.host, config.ws_port);
     let state = AppState {
+        config,
         jwt_validator,
-        nats_bridge,
+        broker,
+        resume: Arc::new(ResumeRegistry::new()),
+        drain: drain.clone(),
     };
 
     let cors = CorsLayer::new()
@@ -53,7 +70,6 @@ pub async fn run_server(
         .with_state(state)
         .layer(cors);
 
-    let addr = format!("{}:{}", host, port);
     let listener = tokio::net::TcpListener::bind(&addr).await?;
     let actual_addr = listener.local_addr()?;
     let actual_port = actual_addr.port();
@@ -61,18 +77,31 @@ pub async fn run_server(
     info!("WebSocket server listening on {}", actual_addr);
 
     let handle = tokio::spawn(async move {
+        let mut drain = drain;
         axum::serve(
             listener,
             app.into_make_service_with_connect_info::<SocketAddr>(),
         )
+        .with_graceful_shutdown(async move {
+            drain_requested(&mut drain).await;
+        })
         .await
     });
 
     Ok((actual_port, handle))
 }
 
-async fn health_handler() -> &'static str {
-    "OK"
+/// Reports degraded while the gateway cannot reach NATS, so load balancers
+/// can steer new clients elsewhere
+async fn health_handler(State(state): State<AppState>) -> (StatusCode, &'static str) {
+    if state.broker.is_connected() {
+        (StatusCode::OK, "OK")
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "DEGRADED: NATS unavailable",
+        )
+    }
 }
 
 async fn ws_handler(
@@ -85,9 +114,19 @@ async fn ws_handler(
 }
 
 async fn handle_socket(socket: WebSocket, state: AppState, addr: SocketAddr) {
-    let mut handler = ConnectionHandler::new(state.jwt_validator, state.nats_bridge);
+    let mut handler = ConnectionHandler::new(
+        state.config,
+        state.jwt_validator,
+        state.broker,
+        state.resume,
+        Some(addr.ip()),
+    );
+    let mut drain = state.drain;
+    let mut drain_deadline: Option<Instant> = None;
 
     let (mut sender, mut receiver) = socket.split();
+    // Set when the connection ends on purpose rather than dropping
+    let mut closed_cleanly = false;
 
     loop {
         tokio::select! {
@@ -104,6 +143,7 @@ async fn handle_socket(socket: WebSocket, state: AppState, addr: SocketAddr) {
                     }
                     Some(Ok(Message::Close(_))) => {
                         debug!("WebSocket closed by client {}", addr);
+                        closed_cleanly = true;
                         break;
                     }
                     Some(Ok(Message::Ping(data))) => {
@@ -125,20 +165,55 @@ async fn handle_socket(socket: WebSocket, state: AppState, addr: SocketAddr) {
                 }
             }
 
-            // Handle NATS messages to forward to client
-            nats_msg = handler.nats_receiver().recv() => {
-                if let Some(nats_msg) = nats_msg
-                    && let Some(server_msg) = handler.nats_to_server_message(nats_msg)
-                {
+            // Handle NATS messages and background task output to forward to client
+            server_msg = handler.next_outbound() => {
+                if let Some(server_msg) = server_msg {
                     let encoded = MessageCodec::encode_server(&server_msg);
                     if sender.send(Message::Binary(encoded.into())).await.is_err() {
                         break;
                     }
                 }
+                if handler.should_close() {
+                    debug!("Closing slow consumer WebSocket connection for {}", addr);
+                    let _ = sender
+                        .send(Message::Close(Some(CloseFrame {
+                            code: close_code::POLICY,
+                            reason: "Slow consumer".into(),
+                        })))
+                        .await;
+                    break;
+                }
+            }
+
+            // Gateway is shutting down (or gone): tell the client to go away
+            _ = drain_requested(&mut drain), if !handler.is_draining() => {
+                drain_deadline = Some(Instant::now() + handler.drain_grace());
+                let encoded = MessageCodec::encode_server(&handler.begin_drain());
+                if sender.send(Message::Binary(encoded.into())).await.is_err() {
+                    break;
+                }
+            }
+
+            // Grace period over: close the connection
+            _ = tokio::time::sleep_until(drain_deadline.unwrap_or_else(Instant::now)),
+                if drain_deadline.is_some() =>
+            {
+                debug!("Closing drained WebSocket connection for {}", addr);
+                let _ = sender
+                    .send(Message::Close(Some(CloseFrame {
+                        code: close_code::AWAY,
+                        reason: "Gateway is shutting down".into(),
+                    })))
+                    .await;
+                break;
             }
         }
     }
 
-    handler.cleanup().await;
+    if closed_cleanly {
+        handler.cleanup().await;
+    } else {
+        handler.detach().await;
+    }
     info!("WebSocket connection closed for {}", addr);
 }
@@ -0,0 +1,169 @@
+use std::sync::Arc;
+
+use futures::StreamExt;
+use mottomesh_gateway::GatewayConfig;
+use mottomesh_gateway::bridge::{
+    Broker, Headers, Mailbox, MailboxEvent, MemoryBroker, Responder, SubscriptionHandle, mailbox,
+};
+use mottomesh_gateway::protocol::SlowConsumerPolicy;
+use tokio::sync::mpsc;
+
+use super::jwt::TEST_JWT_SECRET;
+use super::nats::{TestNats, get_nats};
+
+/// Broker a gateway under test relays through, so the same test body can
+/// run against NATS and against the in-memory broker
+pub enum Backend {
+    Nats(TestNats),
+    Memory(Arc<MemoryBroker>),
+}
+
+impl Backend {
+    pub async fn nats() -> Self {
+        Self::Nats(get_nats().await)
+    }
+
+    pub fn memory() -> Self {
+        Self::Memory(Arc::new(MemoryBroker::new()))
+    }
+
+    /// A test config for a gateway on this backend
+    pub fn config(&self) -> GatewayConfig {
+        let nats_url = match self {
+            Self::Nats(nats) => nats.url(),
+            Self::Memory(_) => "",
+        };
+        GatewayConfig::for_test(0, nats_url, TEST_JWT_SECRET)
+    }
+
+    /// Publish straight to the backend, as a service would
+    pub async fn publish(&self, subject: &str, payload: &[u8]) {
+        match self {
+            Self::Nats(nats) => nats.publish(subject, payload).await,
+            Self::Memory(broker) => broker
+                .publish(subject, &Headers::default(), payload.to_vec())
+                .await
+                .expect("Failed to publish"),
+        }
+    }
+
+    /// Subscribe straight to the backend
+    pub async fn subscribe(&self, subject: &str) -> BackendSubscriber {
+        match self {
+            Self::Nats(nats) => BackendSubscriber::Nats(nats.subscribe(subject).await),
+            Self::Memory(broker) => {
+                let (ready_tx, ready) = mpsc::unbounded_channel();
+                let (sender, mailbox) = mailbox(0, 64, SlowConsumerPolicy::DropOldest, ready_tx);
+                let handle = broker
+                    .subscribe(subject.to_string(), sender)
+                    .await
+                    .expect("Failed to subscribe");
+                BackendSubscriber::Memory {
+                    _handle: handle,
+                    mailbox,
+                    ready,
+                }
+            }
+        }
+    }
+
+    /// Take requests on `subject` without answering them
+    pub async fn listen(&self, subject: &str) -> Listener {
+        match self {
+            Self::Nats(nats) => Listener::Nats(nats.subscribe(subject).await),
+            Self::Memory(broker) => Listener::Memory(broker.serve(subject)),
+        }
+    }
+
+    /// Answer the first request on `subject` with `reply` applied to its
+    /// payload
+    pub async fn respond_once(
+        &self,
+        subject: &str,
+        reply: impl FnOnce(&[u8]) -> Vec<u8> + Send + 'static,
+    ) {
+        match self.listen(subject).await {
+            Listener::Nats(mut requests) => {
+                let Self::Nats(nats) = self else {
+                    unreachable!()
+                };
+                let client = nats.client().clone();
+                tokio::spawn(async move {
+                    if let Some(msg) = requests.next().await
+                        && let Some(inbox) = msg.reply
+                    {
+                        client
+                            .publish(inbox, reply(&msg.payload).into())
+                            .await
+                            .expect("Failed to send reply");
+                    }
+                });
+            }
+            Listener::Memory(mut requests) => {
+                tokio::spawn(async move {
+                    if let Some(request) = requests.next().await {
+                        let payload = reply(&request.payload);
+                        request.respond(payload).await;
+                    }
+                });
+            }
+        }
+    }
+}
+
+/// A subscription made directly on the backend
+pub enum BackendSubscriber {
+    Nats(async_nats::Subscriber),
+    Memory {
+        _handle: SubscriptionHandle,
+        mailbox: Mailbox,
+        ready: mpsc::UnboundedReceiver<u64>,
+    },
+}
+
+impl BackendSubscriber {
+    /// Wait for the payload of the next message
+    pub async fn next_payload(&mut self) -> Option<Vec<u8>> {
+        match self {
+            Self::Nats(subscriber) => subscriber.next().await.map(|msg| msg.payload.to_vec()),
+            Self::Memory { mailbox, ready, .. } => loop {
+                ready.recv().await?;
+                match mailbox.take() {
+                    Some(MailboxEvent::Message { message, .. }) => return Some(message.payload),
+                    Some(MailboxEvent::Closed) => return None,
+                    _ => {}
+                }
+            },
+        }
+    }
+}
+
+/// Requests taken on a subject and left unanswered while held
+pub enum Listener {
+    Nats(async_nats::Subscriber),
+    Memory(Responder),
+}
+
+/// Generate a NATS and an in-memory variant of each listed test. Each body
+/// is an `async fn(Backend)`; the variants are named `<test>::nats` and
+/// `<test>::memory`.
+#[macro_export]
+macro_rules! backend_tests {
+    ($($test:ident),* $(,)?) => {
+        $(
+            mod $test {
+                use $crate::common::backend::Backend;
+
+                #[tokio::test]
+                async fn nats() {
+                    super::$test(Backend::nats().await).await;
+                }
+
+                #[tokio::test]
+                async fn memory() {
+                    super::$test(Backend::memory()).await;
+                }
+            }
+        )*
+    };
+}
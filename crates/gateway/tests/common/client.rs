@@ -6,6 +6,14 @@ use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungsten
 
 use mottomesh_gateway::protocol::{ClientMessage, MessageCodec, ServerMessage};
 
+/// Outcome of a successful authentication
+#[derive(Debug)]
+pub struct AuthResult {
+    pub session_id: String,
+    pub resume_token: Option<String>,
+    pub resumed: bool,
+}
+
 /// WebSocket test client
 pub struct TestClient {
     ws: WebSocketStream<MaybeTlsStream<TcpStream>>,
@@ -30,6 +38,14 @@ impl TestClient {
             .expect("Failed to send message");
     }
 
+    /// Send raw bytes, bypassing the codec
+    pub async fn send_raw(&mut self, data: Vec<u8>) {
+        self.ws
+            .send(Message::Binary(data.into()))
+            .await
+            .expect("Failed to send message");
+    }
+
     /// Receive a server message with timeout
     pub async fn recv(&mut self) -> Option<ServerMessage> {
         self.recv_timeout(Duration::from_secs(5)).await
@@ -58,14 +74,34 @@ impl TestClient {
 
     /// Authenticate with the gateway
     pub async fn auth(&mut self, token: &str) -> Result<String, String> {
+        self.auth_resuming(token, None)
+            .await
+            .map(|auth| auth.session_id)
+    }
+
+    /// Authenticate, asking to resume the session parked under `resume_token`
+    pub async fn auth_resuming(
+        &mut self,
+        token: &str,
+        resume_token: Option<String>,
+    ) -> Result<AuthResult, String> {
         self.send(ClientMessage::Auth {
             token: token.to_string(),
+            resume_token,
         })
         .await;
 
         match self.recv().await {
-            Some(ServerMessage::AuthOk { session_id }) => Ok(session_id),
-            Some(ServerMessage::AuthError { reason }) => Err(reason),
+            Some(ServerMessage::AuthOk {
+                session_id,
+                resume_token,
+                resumed,
+            }) => Ok(AuthResult {
+                session_id,
+                resume_token,
+                resumed,
+            }),
+            Some(ServerMessage::AuthError { reason, .. }) => Err(reason),
             Some(other) => Err(format!("Unexpected response: {:?}", other)),
             None => Err("No response received".to_string()),
         }
@@ -76,12 +112,28 @@ impl TestClient {
         self.send(ClientMessage::Subscribe {
             subject: subject.to_string(),
             id,
+            credits: None,
+            max_msgs: None,
+            replay: None,
+            slow_consumer: None,
         })
         .await;
 
         match self.recv().await {
             Some(ServerMessage::SubscribeOk { id }) => Ok(id),
-            Some(ServerMessage::SubscribeError { id: _, reason }) => Err(reason),
+            Some(ServerMessage::SubscribeError { reason, .. }) => Err(reason),
+            Some(other) => Err(format!("Unexpected response: {:?}", other)),
+            None => Err("No response received".to_string()),
+        }
+    }
+
+    /// Unsubscribe from a subscription
+    pub async fn unsubscribe(&mut self, id: u64) -> Result<u64, String> {
+        self.send(ClientMessage::Unsubscribe { id }).await;
+
+        match self.recv().await {
+            Some(ServerMessage::UnsubscribeOk { id, .. }) => Ok(id),
+            Some(ServerMessage::UnsubscribeError { reason, .. }) => Err(reason),
             Some(other) => Err(format!("Unexpected response: {:?}", other)),
             None => Err("No response received".to_string()),
         }
@@ -1,13 +1,20 @@
 use std::sync::Arc;
+use std::time::Duration;
 
-use mottomesh_gateway::{auth::JwtValidator, bridge::NatsBridge, transport};
+use mottomesh_gateway::bridge::{Broker, MemoryBroker, NatsBridge};
+use mottomesh_gateway::{GatewayConfig, auth::JwtValidator, transport};
+use tokio::sync::watch;
 use tokio::task::JoinHandle;
 
+use super::backend::Backend;
+use super::http;
 use super::jwt::TEST_JWT_SECRET;
 
 /// Test gateway wrapper with shutdown capability
-pub struct TestGateway {
+pub struct TestGateway<B: ?Sized = NatsBridge> {
     pub port: u16,
+    pub broker: Arc<B>,
+    drain_tx: watch::Sender<bool>,
     _server_handle: JoinHandle<Result<(), std::io::Error>>,
 }
 
@@ -19,30 +26,96 @@ impl TestGateway {
 
     /// Start with a custom JWT secret
     pub async fn start_with_secret(nats_url: &str, jwt_secret: &str) -> Self {
-        let jwt_validator =
-            Arc::new(JwtValidator::new(jwt_secret).expect("Failed to create JWT validator"));
-        let nats_bridge = Arc::new(
-            NatsBridge::connect(nats_url)
-                .await
-                .expect("Failed to connect to NATS"),
-        );
+        // Port 0 lets the OS assign a free port
+        Self::start_with_config(GatewayConfig::for_test(0, nats_url, jwt_secret)).await
+    }
 
-        // Start WebSocket server on port 0 (OS assigns free port)
-        let (port, server_handle) = transport::websocket::run_server(
-            "127.0.0.1".to_string(),
-            0,
-            jwt_validator,
-            nats_bridge,
+    /// Start with a custom config
+    pub async fn start_with_config(config: GatewayConfig) -> Self {
+        let broker = Arc::new(connect_nats(&config).await);
+        Self::serve(config, broker.clone(), broker).await
+    }
+}
+
+/// Connect to NATS with the bridge features the config asks for
+async fn connect_nats(config: &GatewayConfig) -> NatsBridge {
+    NatsBridge::connect(&config.nats)
+        .await
+        .expect("Failed to connect to NATS")
+        .with_compression(config.subject_compression.clone(), config.max_payload_bytes)
+        .with_consumer_limits(
+            Duration::from_millis(config.consumer_inactive_threshold_ms),
+            config.consumers_per_user,
+        )
+        .with_retained(
+            config.retained_subjects.clone(),
+            config.retained_max_subjects,
+        )
+        .await
+        .expect("Failed to subscribe to retained subjects")
+        .with_history(
+            config.history_subjects.clone(),
+            config.history_length,
+            config.history_max_subjects,
         )
         .await
-        .expect("Failed to start WebSocket server");
+        .expect("Failed to subscribe to history subjects")
+}
+
+impl TestGateway<MemoryBroker> {
+    /// Start a gateway relaying through an in-memory broker, needing no NATS
+    pub async fn start_in_memory() -> Self {
+        Self::start_in_memory_with_config(GatewayConfig::for_test(0, "", TEST_JWT_SECRET)).await
+    }
+
+    /// Start an in-memory gateway with a custom config
+    pub async fn start_in_memory_with_config(config: GatewayConfig) -> Self {
+        let broker = Arc::new(MemoryBroker::new());
+        Self::serve(config, broker.clone(), broker).await
+    }
+}
+
+impl TestGateway<dyn Broker> {
+    /// Start a gateway relaying through `backend`
+    pub async fn start_on(backend: &Backend, config: GatewayConfig) -> Self {
+        let broker: Arc<dyn Broker> = match backend {
+            Backend::Nats(_) => Arc::new(connect_nats(&config).await),
+            Backend::Memory(broker) => broker.clone(),
+        };
+        Self::serve(config, broker.clone(), broker).await
+    }
+}
+
+impl<B: ?Sized> TestGateway<B> {
+    /// Serve `relay`, keeping `broker` as its concrete type for the test
+    async fn serve(config: GatewayConfig, broker: Arc<B>, relay: Arc<dyn Broker>) -> Self {
+        let jwt_validator = Arc::new(
+            JwtValidator::new(&config.jwt_secret).expect("Failed to create JWT validator"),
+        );
+        let (drain_tx, drain_rx) = watch::channel(false);
+        let (port, server_handle) =
+            transport::websocket::run_server(Arc::new(config), jwt_validator, relay, drain_rx)
+                .await
+                .expect("Failed to start WebSocket server");
 
         Self {
             port,
+            broker,
+            drain_tx,
             _server_handle: server_handle,
         }
     }
 
+    /// Send GoAway to every connection, as the gateway does on shutdown
+    pub fn drain(&self) {
+        self.drain_tx.send_replace(true);
+    }
+
+    /// Query the health endpoint, returning the status code and body
+    pub async fn health(&self) -> (u16, String) {
+        http::get(self.port, "/health").await
+    }
+
     /// Get the WebSocket URL
     pub fn ws_url(&self) -> String {
         format!("ws://127.0.0.1:{}/ws", self.port)
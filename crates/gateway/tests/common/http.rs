@@ -0,0 +1,31 @@
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Send a plain HTTP GET to a local port, returning the status code and body.
+/// HTTP/1.0 keeps the body unchunked and the connection closing after it.
+pub async fn get(port: u16, path: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(("127.0.0.1", port))
+        .await
+        .expect("Failed to connect");
+    let request = format!("GET {} HTTP/1.0\r\nHost: localhost\r\n\r\n", path);
+    stream
+        .write_all(request.as_bytes())
+        .await
+        .expect("Failed to send request");
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .await
+        .expect("Failed to read response");
+
+    let status = response
+        .split(' ')
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .expect("Malformed status line");
+    let body = response
+        .split_once("\r\n\r\n")
+        .map(|(_, body)| body.to_string())
+        .unwrap_or_default();
+    (status, body)
+}
@@ -34,6 +34,74 @@ pub fn create_token(
         permissions,
         allowed_subjects,
         deny_subjects: vec![],
+        kv_allowed: vec![],
+        kv_deny: vec![],
+        object_allowed: vec![],
+        object_deny: vec![],
+        tenant: None,
+    };
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(TEST_JWT_SECRET.as_bytes()),
+    )
+    .expect("Failed to create JWT token")
+}
+
+/// Create a token allowed everything except the `deny_subjects` patterns
+pub fn create_token_with_deny(subject: &str, deny_subjects: Vec<String>) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as usize;
+
+    let claims = Claims {
+        sub: subject.to_string(),
+        exp: now + 3600,
+        iat: now,
+        permissions: vec!["publish".into(), "subscribe".into()],
+        allowed_subjects: vec![">".into()],
+        deny_subjects,
+        kv_allowed: vec![],
+        kv_deny: vec![],
+        object_allowed: vec![],
+        object_deny: vec![],
+        tenant: None,
+    };
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(TEST_JWT_SECRET.as_bytes()),
+    )
+    .expect("Failed to create JWT token")
+}
+
+/// Create a token with the given key-value permissions and patterns
+pub fn create_kv_token(
+    subject: &str,
+    permissions: Vec<String>,
+    kv_allowed: Vec<String>,
+    kv_deny: Vec<String>,
+) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as usize;
+
+    let claims = Claims {
+        sub: subject.to_string(),
+        exp: now + 3600,
+        iat: now,
+        permissions,
+        allowed_subjects: vec![">".into()],
+        deny_subjects: vec![],
+        kv_allowed,
+        kv_deny,
+        object_allowed: vec![],
+        object_deny: vec![],
+        tenant: None,
     };
 
     encode(
@@ -58,6 +126,11 @@ pub fn create_expired_token(subject: &str) -> String {
         permissions: vec!["publish".into(), "subscribe".into()],
         allowed_subjects: vec!["*".into()],
         deny_subjects: vec![],
+        kv_allowed: vec![],
+        kv_deny: vec![],
+        object_allowed: vec![],
+        object_deny: vec![],
+        tenant: None,
     };
 
     encode(
@@ -77,3 +150,66 @@ pub fn create_limited_token(subject: &str, allowed_subjects: Vec<String>) -> Str
         allowed_subjects,
     )
 }
+
+/// Create a token with the given object permissions and patterns
+pub fn create_object_token(
+    subject: &str,
+    permissions: Vec<String>,
+    object_allowed: Vec<String>,
+    object_deny: Vec<String>,
+) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as usize;
+
+    let claims = Claims {
+        sub: subject.to_string(),
+        exp: now + 3600,
+        iat: now,
+        permissions,
+        allowed_subjects: vec![">".into()],
+        deny_subjects: vec![],
+        kv_allowed: vec![],
+        kv_deny: vec![],
+        object_allowed,
+        object_deny,
+        tenant: None,
+    };
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(TEST_JWT_SECRET.as_bytes()),
+    )
+    .expect("Failed to create JWT token")
+}
+
+/// Create a token like `create_valid_token` for a user of `tenant`
+pub fn create_tenant_token(subject: &str, tenant: &str) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as usize;
+
+    let claims = Claims {
+        sub: subject.to_string(),
+        exp: now + 3600,
+        iat: now,
+        permissions: vec!["publish".into(), "subscribe".into(), "request".into()],
+        allowed_subjects: vec![">".into()],
+        deny_subjects: vec![],
+        kv_allowed: vec![],
+        kv_deny: vec![],
+        object_allowed: vec![],
+        object_deny: vec![],
+        tenant: Some(tenant.to_string()),
+    };
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(TEST_JWT_SECRET.as_bytes()),
+    )
+    .expect("Failed to create JWT token")
+}
@@ -1,4 +1,6 @@
+pub mod backend;
 pub mod client;
 pub mod gateway;
+pub mod http;
 pub mod jwt;
 pub mod nats;
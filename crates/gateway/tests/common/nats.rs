@@ -1,7 +1,10 @@
-use testcontainers::{ContainerAsync, runners::AsyncRunner};
-use testcontainers_modules::nats::Nats;
+use testcontainers::core::IntoContainerPort;
+use testcontainers::{ContainerAsync, ImageExt, runners::AsyncRunner};
+use testcontainers_modules::nats::{Nats, NatsServerCmd};
 use tokio::sync::OnceCell;
 
+use super::http;
+
 /// Shared NATS container for all tests
 static NATS_CONTAINER: OnceCell<NatsContainer> = OnceCell::const_new();
 
@@ -15,6 +18,7 @@ impl NatsContainer {
     /// Start a new NATS container
     async fn start() -> Self {
         let container = Nats::default()
+            .with_cmd(&NatsServerCmd::default().with_jetstream())
             .start()
             .await
             .expect("Failed to start NATS container");
@@ -38,6 +42,76 @@ impl NatsContainer {
     }
 }
 
+/// A NATS server of its own, for tests that need particular server settings
+pub struct DedicatedNats {
+    container: ContainerAsync<Nats>,
+    url: String,
+    /// Host port of the monitoring endpoint, served if started with `-m 8222`
+    monitor_port: u16,
+}
+
+impl DedicatedNats {
+    /// Start a server with `args`, copying `files` (path, contents) into the
+    /// container first. The host port is fixed, so the server can be
+    /// restarted at the same address.
+    pub async fn start(args: &[&str], files: Vec<(&str, Vec<u8>)>) -> Self {
+        let port = portpicker::pick_unused_port().expect("No free port");
+        let monitor_port = portpicker::pick_unused_port().expect("No free port");
+        let mut request = Nats::default()
+            .with_cmd(args.iter().copied())
+            .with_mapped_port(port, 4222.tcp())
+            .with_mapped_port(monitor_port, 8222.tcp());
+        for (path, contents) in files {
+            request = request.with_copy_to(path, contents);
+        }
+        let container = request
+            .start()
+            .await
+            .expect("Failed to start NATS container");
+
+        let host = container.get_host().await.expect("Failed to get host");
+
+        Self {
+            container,
+            url: format!("{}:{}", host, port),
+            monitor_port,
+        }
+    }
+
+    /// Get the NATS URL
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Subscriptions the server holds across all connections, as reported
+    /// by its monitoring endpoint
+    pub async fn subscription_count(&self) -> u64 {
+        let (status, body) = http::get(self.monitor_port, "/subsz").await;
+        assert_eq!(status, 200, "Monitoring endpoint failed: {}", body);
+        let subsz: serde_json::Value =
+            serde_json::from_str(&body).expect("Malformed /subsz response");
+        subsz["num_subscriptions"]
+            .as_u64()
+            .expect("Missing num_subscriptions")
+    }
+
+    /// Stop the server, dropping every connection
+    pub async fn stop(&self) {
+        self.container
+            .stop()
+            .await
+            .expect("Failed to stop NATS container");
+    }
+
+    /// Start the server again after [`DedicatedNats::stop`]
+    pub async fn restart(&self) {
+        self.container
+            .start()
+            .await
+            .expect("Failed to restart NATS container");
+    }
+}
+
 /// NATS test helper with fresh client
 pub struct TestNats {
     url: String,
@@ -78,6 +152,41 @@ impl TestNats {
         self.client.flush().await.expect("Failed to flush NATS");
     }
 
+    /// Create a JetStream stream capturing `subjects`
+    pub async fn create_stream(&self, name: &str, subjects: Vec<String>) {
+        async_nats::jetstream::new(self.client.clone())
+            .create_stream(async_nats::jetstream::stream::Config {
+                name: name.to_string(),
+                subjects,
+                ..Default::default()
+            })
+            .await
+            .expect("Failed to create stream");
+    }
+
+    /// Create a key-value bucket
+    pub async fn create_bucket(&self, bucket: &str) {
+        async_nats::jetstream::new(self.client.clone())
+            .create_key_value(async_nats::jetstream::kv::Config {
+                bucket: bucket.to_string(),
+                history: 5,
+                ..Default::default()
+            })
+            .await
+            .expect("Failed to create bucket");
+    }
+
+    /// Create an object store for tests that transfer objects
+    pub async fn create_object_store(&self, bucket: &str) {
+        async_nats::jetstream::new(self.client.clone())
+            .create_object_store(async_nats::jetstream::object_store::Config {
+                bucket: bucket.to_string(),
+                ..Default::default()
+            })
+            .await
+            .expect("Failed to create object store");
+    }
+
     /// Subscribe to a NATS subject
     pub async fn subscribe(&self, subject: &str) -> async_nats::Subscriber {
         self.client
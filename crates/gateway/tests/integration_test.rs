@@ -1,28 +1,49 @@
 //! Integration tests for the Mottomesh Gateway
 //!
 //! These tests spin up a real NATS container and test the full WebSocket flow.
+//! The core flows are listed in `backend_tests!` and also run against the
+//! in-memory broker, as `<test>::memory`.
 
 mod common;
 
+use std::sync::Arc;
 use std::time::Duration;
 
 use common::{
+    backend::Backend,
     client::TestClient,
     gateway::TestGateway,
-    jwt::{create_expired_token, create_limited_token, create_valid_token},
-    nats::{get_nats, test_subject},
+    jwt::{
+        TEST_JWT_SECRET, create_expired_token, create_kv_token, create_limited_token,
+        create_object_token, create_tenant_token, create_token, create_token_with_deny,
+        create_valid_token,
+    },
+    nats::{DedicatedNats, TestNats, get_nats, test_subject, test_subject_prefix},
 };
 use futures::StreamExt;
-use mottomesh_gateway::protocol::{ClientMessage, ServerMessage, error_codes};
+use mottomesh_gateway::auth::JwtValidator;
+use mottomesh_gateway::bridge::{
+    Broker, CLIENT_IP_HEADER, GATEWAY_HEADER, Headers, IdentitySigner, MemoryBroker, NatsBridge,
+    SESSION_HEADER, SIGNATURE_HEADER, STREAM_END_HEADER, TENANT_HEADER, TIMESTAMP_HEADER,
+    USER_HEADER,
+};
+use mottomesh_gateway::config::{NatsAuth, NatsConfig, SubjectCompression, SubjectSlowConsumer};
+use mottomesh_gateway::protocol::compression;
+use mottomesh_gateway::protocol::{
+    AckKind, Capability, ClientMessage, CompressionAlgorithm, DeliverPolicy, ErrorCode,
+    KvOperation, MessageCodec, MessageOrigin, PresenceAction, ReplayFrom, SCHEMA_FINGERPRINT,
+    ServerMessage, SlowConsumerPolicy, SubscriptionEndReason,
+};
+use mottomesh_gateway::transport::SubjectMapping;
+use mottomesh_gateway::{CreditOverflowPolicy, Gateway, GatewayConfig};
+use tokio::sync::oneshot;
 
 // ============================================================================
 // Auth Flow Tests
 // ============================================================================
 
-#[tokio::test]
-async fn test_auth_success() {
-    let nats = get_nats().await;
-    let gateway = TestGateway::start(nats.url()).await;
+async fn test_auth_success(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
     let mut client = TestClient::connect(&gateway.ws_url()).await;
 
     let token = create_valid_token("user-1");
@@ -35,10 +56,8 @@ async fn test_auth_success() {
     client.close().await;
 }
 
-#[tokio::test]
-async fn test_auth_invalid_token() {
-    let nats = get_nats().await;
-    let gateway = TestGateway::start(nats.url()).await;
+async fn test_auth_invalid_token(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
     let mut client = TestClient::connect(&gateway.ws_url()).await;
 
     let result = client.auth("invalid-token-12345").await;
@@ -54,10 +73,8 @@ async fn test_auth_invalid_token() {
     client.close().await;
 }
 
-#[tokio::test]
-async fn test_auth_expired_token() {
-    let nats = get_nats().await;
-    let gateway = TestGateway::start(nats.url()).await;
+async fn test_auth_expired_token(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
     let mut client = TestClient::connect(&gateway.ws_url()).await;
 
     let token = create_expired_token("user-expired");
@@ -74,10 +91,8 @@ async fn test_auth_expired_token() {
     client.close().await;
 }
 
-#[tokio::test]
-async fn test_unauthenticated_subscribe() {
-    let nats = get_nats().await;
-    let gateway = TestGateway::start(nats.url()).await;
+async fn test_unauthenticated_subscribe(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
     let mut client = TestClient::connect(&gateway.ws_url()).await;
 
     // Try to subscribe without authenticating first
@@ -85,6 +100,10 @@ async fn test_unauthenticated_subscribe() {
         .send(ClientMessage::Subscribe {
             subject: "test.topic".to_string(),
             id: 1,
+            credits: None,
+            max_msgs: None,
+            replay: None,
+            slow_consumer: None,
         })
         .await;
 
@@ -94,7 +113,7 @@ async fn test_unauthenticated_subscribe() {
         Some(ServerMessage::Error { code, .. }) => {
             assert_eq!(
                 code,
-                error_codes::UNAUTHORIZED,
+                ErrorCode::Unauthorized,
                 "Should get unauthorized error"
             );
         }
@@ -112,14 +131,19 @@ async fn test_unauthenticated_subscribe() {
     client.close().await;
 }
 
+backend_tests!(
+    test_auth_success,
+    test_auth_invalid_token,
+    test_auth_expired_token,
+    test_unauthenticated_subscribe,
+);
+
 // ============================================================================
 // Subscribe/Publish Flow Tests
 // ============================================================================
 
-#[tokio::test]
-async fn test_subscribe_success() {
-    let nats = get_nats().await;
-    let gateway = TestGateway::start(nats.url()).await;
+async fn test_subscribe_success(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
     let mut client = TestClient::connect(&gateway.ws_url()).await;
 
     // Authenticate first
@@ -136,10 +160,8 @@ async fn test_subscribe_success() {
     client.close().await;
 }
 
-#[tokio::test]
-async fn test_subscribe_receive_message() {
-    let nats = get_nats().await;
-    let gateway = TestGateway::start(nats.url()).await;
+async fn test_subscribe_receive_message(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
     let mut client = TestClient::connect(&gateway.ws_url()).await;
 
     // Authenticate and subscribe
@@ -157,7 +179,7 @@ async fn test_subscribe_receive_message() {
 
     // Publish from NATS directly
     let payload = b"Hello from NATS!";
-    nats.publish(&subject, payload).await;
+    backend.publish(&subject, payload).await;
 
     // Client should receive the message
     let response = client.recv().await;
@@ -167,10 +189,16 @@ async fn test_subscribe_receive_message() {
             subscription_id,
             subject: msg_subject,
             payload: msg_payload,
+            seq,
+            dropped,
+            origin,
         }) => {
             assert_eq!(subscription_id, 42, "Subscription ID should match");
             assert_eq!(msg_subject, subject, "Subject should match");
             assert_eq!(msg_payload, payload, "Payload should match");
+            assert_eq!(seq, 1, "First message should have seq 1");
+            assert_eq!(dropped, 0, "Nothing should be dropped");
+            assert_eq!(origin, MessageOrigin::Live, "Message should be live");
         }
         other => panic!("Expected Message, got: {:?}", other),
     }
@@ -178,10 +206,8 @@ async fn test_subscribe_receive_message() {
     client.close().await;
 }
 
-#[tokio::test]
-async fn test_subscribe_wildcard() {
-    let nats = get_nats().await;
-    let gateway = TestGateway::start(nats.url()).await;
+async fn test_subscribe_wildcard(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
     let mut client = TestClient::connect(&gateway.ws_url()).await;
 
     // Authenticate
@@ -199,7 +225,7 @@ async fn test_subscribe_wildcard() {
 
     // Publish to a specific subject that matches the wildcard
     let specific_subject = test_subject("test_wildcard", "events");
-    nats.publish(&specific_subject, b"Wildcard test").await;
+    backend.publish(&specific_subject, b"Wildcard test").await;
 
     // Should receive the message
     let response = client.recv().await;
@@ -209,6 +235,7 @@ async fn test_subscribe_wildcard() {
             subscription_id,
             subject,
             payload,
+            ..
         }) => {
             assert_eq!(subscription_id, 1);
             assert_eq!(subject, specific_subject);
@@ -220,10 +247,8 @@ async fn test_subscribe_wildcard() {
     client.close().await;
 }
 
-#[tokio::test]
-async fn test_unsubscribe() {
-    let nats = get_nats().await;
-    let gateway = TestGateway::start(nats.url()).await;
+async fn test_unsubscribe(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
     let mut client = TestClient::connect(&gateway.ws_url()).await;
 
     // Authenticate and subscribe
@@ -239,13 +264,13 @@ async fn test_unsubscribe() {
     tokio::time::sleep(Duration::from_millis(100)).await;
 
     // Unsubscribe
-    client.send(ClientMessage::Unsubscribe { id: 1 }).await;
-
-    // Give time for unsubscribe to process
-    tokio::time::sleep(Duration::from_millis(100)).await;
+    client
+        .unsubscribe(1)
+        .await
+        .expect("Unsubscribe should succeed");
 
     // Publish a message - client should NOT receive it
-    nats.publish(&subject, b"Should not receive").await;
+    backend.publish(&subject, b"Should not receive").await;
 
     // Use a short timeout since we expect no message
     let response = client.recv_timeout(Duration::from_millis(500)).await;
@@ -259,43 +284,77 @@ async fn test_unsubscribe() {
     client.close().await;
 }
 
-#[tokio::test]
-async fn test_publish_to_nats() {
-    let nats = get_nats().await;
-    let gateway = TestGateway::start(nats.url()).await;
+async fn test_publish_to_backend(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
     let mut client = TestClient::connect(&gateway.ws_url()).await;
 
     // Authenticate
     let token = create_valid_token("user-pub");
     client.auth(&token).await.expect("Auth should succeed");
 
-    // Subscribe on NATS side
+    // Subscribe on the backend side
     let subject = test_subject("test_publish", "outgoing");
-    let mut nats_sub = nats.subscribe(&subject).await;
+    let mut backend_sub = backend.subscribe(&subject).await;
 
     // Publish from client
     let payload = b"Hello from client!";
     client.publish(&subject, payload).await;
 
-    // NATS should receive the message
-    let msg = tokio::time::timeout(Duration::from_secs(5), nats_sub.next())
+    // The backend should receive the message
+    let received = tokio::time::timeout(Duration::from_secs(5), backend_sub.next_payload())
         .await
-        .expect("Timeout waiting for NATS message")
-        .expect("Should receive message on NATS");
+        .expect("Timeout waiting for backend message")
+        .expect("Should receive message on the backend");
+
+    assert_eq!(received, payload);
+
+    client.close().await;
+}
+
+async fn test_subscribe_invalid_subject(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    let token = create_valid_token("user-invalid-subject");
+    client.auth(&token).await.expect("Auth should succeed");
+
+    client
+        .send(ClientMessage::Subscribe {
+            subject: "events..broken".to_string(),
+            id: 1,
+            credits: None,
+            max_msgs: None,
+            replay: None,
+            slow_consumer: None,
+        })
+        .await;
 
-    assert_eq!(msg.payload.as_ref(), payload);
+    match client.recv().await {
+        Some(ServerMessage::SubscribeError { id, code, .. }) => {
+            assert_eq!(id, 1);
+            assert_eq!(code, ErrorCode::InvalidSubject);
+        }
+        other => panic!("Expected SubscribeError, got: {:?}", other),
+    }
 
     client.close().await;
 }
 
+backend_tests!(
+    test_subscribe_success,
+    test_subscribe_receive_message,
+    test_subscribe_wildcard,
+    test_unsubscribe,
+    test_publish_to_backend,
+    test_subscribe_invalid_subject,
+);
+
 // ============================================================================
 // Request/Reply Tests
 // ============================================================================
 
-#[tokio::test]
-async fn test_request_reply() {
-    let nats = get_nats().await;
-    let gateway = TestGateway::start(nats.url()).await;
+async fn test_request_reply(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
     let mut client = TestClient::connect(&gateway.ws_url()).await;
 
     // Authenticate
@@ -304,21 +363,12 @@ async fn test_request_reply() {
 
     let subject = test_subject("test_request_reply", "rpc");
 
-    // Set up a responder on NATS
-    let mut responder = nats.subscribe(&subject).await;
-    let nats_client = nats.client().clone();
-
-    tokio::spawn(async move {
-        if let Some(msg) = responder.next().await
-            && let Some(reply) = msg.reply
-        {
-            let response = format!("Echo: {}", String::from_utf8_lossy(&msg.payload));
-            nats_client
-                .publish(reply, response.into_bytes().into())
-                .await
-                .expect("Failed to send reply");
-        }
-    });
+    // Set up a responder on the backend
+    backend
+        .respond_once(&subject, |payload| {
+            format!("Echo: {}", String::from_utf8_lossy(payload)).into_bytes()
+        })
+        .await;
 
     // Give responder time to subscribe
     tokio::time::sleep(Duration::from_millis(100)).await;
@@ -330,6 +380,7 @@ async fn test_request_reply() {
             payload: b"Hello".to_vec(),
             timeout_ms: 5000,
             request_id: 123,
+            stream: false,
         })
         .await;
 
@@ -354,18 +405,18 @@ async fn test_request_reply() {
     client.close().await;
 }
 
-#[tokio::test]
-async fn test_request_timeout() {
-    let nats = get_nats().await;
-    let gateway = TestGateway::start(nats.url()).await;
+async fn test_request_timeout(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
     let mut client = TestClient::connect(&gateway.ws_url()).await;
 
     // Authenticate
     let token = create_valid_token("user-timeout");
     client.auth(&token).await.expect("Auth should succeed");
 
-    // Request to a subject with no responder
-    let subject = test_subject("test_request_timeout", "no-responder");
+    // A responder that receives the request but never answers
+    let subject = test_subject("test_request_timeout", "slow");
+    let _responder = backend.listen(&subject).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
 
     client
         .send(ClientMessage::Request {
@@ -373,6 +424,7 @@ async fn test_request_timeout() {
             payload: b"Hello?".to_vec(),
             timeout_ms: 500, // Short timeout
             request_id: 456,
+            stream: false,
         })
         .await;
 
@@ -380,15 +432,11 @@ async fn test_request_timeout() {
     let response = client.recv_timeout(Duration::from_secs(2)).await;
 
     match response {
-        Some(ServerMessage::RequestError { request_id, reason }) => {
+        Some(ServerMessage::RequestError {
+            request_id, code, ..
+        }) => {
             assert_eq!(request_id, 456, "Request ID should match");
-            assert!(
-                reason.to_lowercase().contains("timeout")
-                    || reason.to_lowercase().contains("no response")
-                    || reason.to_lowercase().contains("no responders"),
-                "Error should mention timeout or no responders: {}",
-                reason
-            );
+            assert_eq!(code, ErrorCode::Timeout);
         }
         other => panic!("Expected RequestError, got: {:?}", other),
     }
@@ -396,158 +444,4241 @@ async fn test_request_timeout() {
     client.close().await;
 }
 
-// ============================================================================
-// Connection Tests
-// ============================================================================
-
-#[tokio::test]
-async fn test_ping_pong() {
-    let nats = get_nats().await;
-    let gateway = TestGateway::start(nats.url()).await;
+async fn test_request_no_responders(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
     let mut client = TestClient::connect(&gateway.ws_url()).await;
 
-    // Ping should work without authentication
-    client.ping().await;
+    let token = create_valid_token("user-no-responders");
+    client.auth(&token).await.expect("Auth should succeed");
 
-    let response = client.recv().await;
+    // Nothing listens on this subject
+    let subject = test_subject("test_request_no_responders", "rpc");
 
-    match response {
-        Some(ServerMessage::Pong) => {
-            // Success!
+    for (request_id, stream) in [(457, false), (458, true)] {
+        client
+            .send(ClientMessage::Request {
+                subject: subject.clone(),
+                payload: b"Hello?".to_vec(),
+                timeout_ms: 10_000,
+                request_id,
+                stream,
+            })
+            .await;
+
+        // Reported well before the request would time out
+        match client.recv_timeout(Duration::from_secs(2)).await {
+            Some(ServerMessage::RequestError {
+                request_id: id,
+                code,
+                ..
+            }) => {
+                assert_eq!(id, request_id, "Request ID should match");
+                assert_eq!(code, ErrorCode::NoResponders);
+            }
+            other => panic!("Expected RequestError, got: {:?}", other),
         }
-        other => panic!("Expected Pong, got: {:?}", other),
     }
 
     client.close().await;
 }
 
+backend_tests!(
+    test_request_reply,
+    test_request_timeout,
+    test_request_no_responders,
+);
+
 #[tokio::test]
-async fn test_multiple_clients() {
+async fn test_request_outlasts_nats_default_timeout() {
     let nats = get_nats().await;
     let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_valid_token("user-slow-request"))
+        .await
+        .expect("Auth should succeed");
 
-    // Connect multiple clients
-    let mut client1 = TestClient::connect(&gateway.ws_url()).await;
-    let mut client2 = TestClient::connect(&gateway.ws_url()).await;
-    let mut client3 = TestClient::connect(&gateway.ws_url()).await;
+    // Answers after the NATS client's own 10 second request timeout
+    let subject = test_subject("test_request_long_timeout", "rpc");
+    let mut responder = nats.subscribe(&subject).await;
+    let nats_client = nats.client().clone();
+    tokio::spawn(async move {
+        if let Some(msg) = responder.next().await
+            && let Some(inbox) = msg.reply
+        {
+            tokio::time::sleep(Duration::from_secs(11)).await;
+            nats_client
+                .publish(inbox, b"late".as_slice().into())
+                .await
+                .expect("Failed to send reply");
+        }
+    });
 
-    // All should be able to authenticate
-    let token1 = create_valid_token("user-1");
-    let token2 = create_valid_token("user-2");
-    let token3 = create_valid_token("user-3");
+    client
+        .send(ClientMessage::Request {
+            subject,
+            payload: b"Hello?".to_vec(),
+            timeout_ms: 15_000,
+            request_id: 459,
+            stream: false,
+        })
+        .await;
 
-    let (r1, r2, r3) = tokio::join!(
-        client1.auth(&token1),
-        client2.auth(&token2),
-        client3.auth(&token3)
-    );
+    match client.recv_timeout(Duration::from_secs(15)).await {
+        Some(ServerMessage::Response {
+            request_id,
+            payload,
+        }) => {
+            assert_eq!(request_id, 459);
+            assert_eq!(payload, b"late");
+        }
+        other => panic!("Expected Response, got: {:?}", other),
+    }
 
-    assert!(r1.is_ok(), "Client 1 auth failed: {:?}", r1);
-    assert!(r2.is_ok(), "Client 2 auth failed: {:?}", r2);
-    assert!(r3.is_ok(), "Client 3 auth failed: {:?}", r3);
+    client.close().await;
+}
 
-    // All should have different session IDs
-    let s1 = r1.unwrap();
-    let s2 = r2.unwrap();
-    let s3 = r3.unwrap();
+// ============================================================================
+// Streamed Request Tests
+// ============================================================================
 
-    assert_ne!(s1, s2, "Session IDs should be unique");
-    assert_ne!(s2, s3, "Session IDs should be unique");
-    assert_ne!(s1, s3, "Session IDs should be unique");
+/// Reply to the first request on `subject` with `parts` chunks, optionally
+/// followed by an end-of-stream marker
+async fn spawn_stream_responder(nats: &TestNats, subject: &str, parts: usize, end: bool) {
+    let mut responder = nats.subscribe(subject).await;
+    let nats_client = nats.client().clone();
 
-    client1.close().await;
-    client2.close().await;
-    client3.close().await;
+    tokio::spawn(async move {
+        if let Some(msg) = responder.next().await
+            && let Some(reply) = msg.reply
+        {
+            for i in 0..parts {
+                nats_client
+                    .publish(reply.clone(), format!("part-{}", i).into_bytes().into())
+                    .await
+                    .expect("Failed to send chunk");
+            }
+            if end {
+                let mut headers = async_nats::HeaderMap::new();
+                headers.insert(STREAM_END_HEADER, "true");
+                nats_client
+                    .publish_with_headers(reply, headers, Vec::new().into())
+                    .await
+                    .expect("Failed to send end marker");
+            }
+            nats_client.flush().await.expect("Failed to flush NATS");
+        }
+    });
+
+    // Give responder time to subscribe
+    tokio::time::sleep(Duration::from_millis(100)).await;
 }
 
 #[tokio::test]
-async fn test_client_disconnect_cleanup() {
+async fn test_stream_request_ordering() {
     let nats = get_nats().await;
     let gateway = TestGateway::start(nats.url()).await;
     let mut client = TestClient::connect(&gateway.ws_url()).await;
 
-    // Authenticate and subscribe
-    let token = create_valid_token("user-disconnect");
+    let token = create_valid_token("user-stream");
     client.auth(&token).await.expect("Auth should succeed");
 
-    let subject = test_subject("test_disconnect", "events");
-    client
-        .subscribe(&subject, 1)
-        .await
-        .expect("Subscribe should succeed");
-
-    tokio::time::sleep(Duration::from_millis(100)).await;
+    let subject = test_subject("test_stream_ordering", "rpc");
+    spawn_stream_responder(&nats, &subject, 5, true).await;
 
-    // Close the client
-    client.close().await;
+    client
+        .send(ClientMessage::Request {
+            subject: subject.clone(),
+            payload: b"list".to_vec(),
+            timeout_ms: 5000,
+            request_id: 7,
+            stream: true,
+        })
+        .await;
 
-    // Give time for cleanup
-    tokio::time::sleep(Duration::from_millis(200)).await;
+    for expected_seq in 0..5u32 {
+        match client.recv().await {
+            Some(ServerMessage::ResponseChunk {
+                request_id,
+                seq,
+                payload,
+            }) => {
+                assert_eq!(request_id, 7, "Request ID should match");
+                assert_eq!(seq, expected_seq, "Chunks should arrive in order");
+                assert_eq!(payload, format!("part-{}", expected_seq).into_bytes());
+            }
+            other => panic!("Expected ResponseChunk, got: {:?}", other),
+        }
+    }
 
-    // Publish a message - should not cause any errors
-    // (subscription should be cleaned up on disconnect)
-    nats.publish(&subject, b"After disconnect").await;
+    match client.recv().await {
+        Some(ServerMessage::ResponseEnd { request_id }) => assert_eq!(request_id, 7),
+        other => panic!("Expected ResponseEnd, got: {:?}", other),
+    }
 
-    // If we get here without panicking, cleanup worked
-    // The gateway should have removed the subscription when the client disconnected
+    client.close().await;
 }
 
-// ============================================================================
-// Permission Tests
-// ============================================================================
-
 #[tokio::test]
-async fn test_subscribe_with_limited_permissions() {
+async fn test_stream_request_idle_timeout() {
     let nats = get_nats().await;
     let gateway = TestGateway::start(nats.url()).await;
     let mut client = TestClient::connect(&gateway.ws_url()).await;
 
-    // Create token that only allows specific subjects
-    let allowed_subject = test_subject("test_limited", "allowed");
-    let token = create_limited_token("user-limited", vec![allowed_subject.clone()]);
+    let token = create_valid_token("user-stream-timeout");
     client.auth(&token).await.expect("Auth should succeed");
 
-    // Subscribe to allowed subject should work
-    let result = client.subscribe(&allowed_subject, 1).await;
-    assert!(
-        result.is_ok(),
-        "Subscribe to allowed subject should succeed: {:?}",
-        result
-    );
+    // Responder sends two chunks but never marks the end of the stream
+    let subject = test_subject("test_stream_timeout", "rpc");
+    spawn_stream_responder(&nats, &subject, 2, false).await;
 
-    // Subscribe to disallowed subject should fail
-    let denied_subject = test_subject("test_limited", "denied");
     client
-        .send(ClientMessage::Subscribe {
-            subject: denied_subject.clone(),
-            id: 2,
+        .send(ClientMessage::Request {
+            subject: subject.clone(),
+            payload: b"list".to_vec(),
+            timeout_ms: 500,
+            request_id: 8,
+            stream: true,
         })
         .await;
 
-    let response = client.recv().await;
+    for _ in 0..2 {
+        match client.recv().await {
+            Some(ServerMessage::ResponseChunk { request_id, .. }) => assert_eq!(request_id, 8),
+            other => panic!("Expected ResponseChunk, got: {:?}", other),
+        }
+    }
 
-    match response {
-        Some(ServerMessage::SubscribeError { id, reason }) => {
-            assert_eq!(id, 2);
+    match client.recv_timeout(Duration::from_secs(2)).await {
+        Some(ServerMessage::RequestError {
+            request_id,
+            code,
+            reason,
+        }) => {
+            assert_eq!(request_id, 8);
+            assert_eq!(code, ErrorCode::Timeout);
             assert!(
-                reason.to_lowercase().contains("permission")
-                    || reason.to_lowercase().contains("denied")
-                    || reason.to_lowercase().contains("forbidden")
-                    || reason.to_lowercase().contains("allowed"),
-                "Error should mention permissions: {}",
+                reason.to_lowercase().contains("timed out"),
+                "Error should mention timeout: {}",
                 reason
             );
         }
-        Some(ServerMessage::Error { code, message }) => {
-            assert_eq!(
-                code,
-                error_codes::FORBIDDEN,
-                "Should get forbidden error: {}",
-                message
-            );
-        }
-        other => panic!("Expected error for denied subject, got: {:?}", other),
+        other => panic!("Expected RequestError, got: {:?}", other),
     }
 
     client.close().await;
 }
+
+#[tokio::test]
+async fn test_stream_request_cancel() {
+    let nats = get_nats().await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    let token = create_valid_token("user-stream-cancel");
+    client.auth(&token).await.expect("Auth should succeed");
+
+    let subject = test_subject("test_stream_cancel", "rpc");
+    let mut responder = nats.subscribe(&subject).await;
+    let nats_client = nats.client().clone();
+    let (reply_tx, reply_rx) = tokio::sync::oneshot::channel();
+
+    tokio::spawn(async move {
+        if let Some(msg) = responder.next().await
+            && let Some(reply) = msg.reply
+        {
+            nats_client
+                .publish(reply.clone(), b"first".to_vec().into())
+                .await
+                .expect("Failed to send chunk");
+            nats_client.flush().await.expect("Failed to flush NATS");
+            let _ = reply_tx.send((nats_client, reply));
+        }
+    });
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    client
+        .send(ClientMessage::Request {
+            subject: subject.clone(),
+            payload: b"list".to_vec(),
+            timeout_ms: 5000,
+            request_id: 9,
+            stream: true,
+        })
+        .await;
+
+    match client.recv().await {
+        Some(ServerMessage::ResponseChunk {
+            request_id, seq, ..
+        }) => {
+            assert_eq!(request_id, 9);
+            assert_eq!(seq, 0);
+        }
+        other => panic!("Expected ResponseChunk, got: {:?}", other),
+    }
+
+    client
+        .send(ClientMessage::CancelRequest { request_id: 9 })
+        .await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Chunks published after cancellation must not reach the client
+    let (nats_client, reply) = reply_rx.await.expect("Responder should hand over reply");
+    nats_client
+        .publish(reply, b"second".to_vec().into())
+        .await
+        .expect("Failed to send chunk");
+    nats_client.flush().await.expect("Failed to flush NATS");
+
+    let response = client.recv_timeout(Duration::from_millis(500)).await;
+    assert!(
+        response.is_none(),
+        "Should not receive chunks after cancel, got: {:?}",
+        response
+    );
+
+    client.close().await;
+}
+
+// ============================================================================
+// Handshake Tests
+// ============================================================================
+
+fn hello(schema_fingerprint: &str) -> ClientMessage {
+    ClientMessage::Hello {
+        client_name: "integration-test".to_string(),
+        client_version: "0.0.0".to_string(),
+        schema_fingerprint: schema_fingerprint.to_string(),
+        capabilities: vec![],
+        compression: vec![],
+    }
+}
+
+async fn test_hello_welcome(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    // Hello should work without authentication
+    client.send(hello(SCHEMA_FINGERPRINT)).await;
+
+    match client.recv().await {
+        Some(ServerMessage::Welcome {
+            gateway_version,
+            schema_fingerprint,
+            max_payload_bytes,
+            ..
+        }) => {
+            assert!(!gateway_version.is_empty());
+            assert_eq!(schema_fingerprint, SCHEMA_FINGERPRINT);
+            assert!(max_payload_bytes > 0);
+        }
+        other => panic!("Expected Welcome, got: {:?}", other),
+    }
+
+    // A second Hello is rejected
+    client.send(hello(SCHEMA_FINGERPRINT)).await;
+
+    match client.recv().await {
+        Some(ServerMessage::HelloError { code, .. }) => {
+            assert_eq!(code, ErrorCode::AlreadyExists);
+        }
+        other => panic!("Expected HelloError, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+async fn test_hello_schema_mismatch(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    client.send(hello("not-the-gateway-schema")).await;
+
+    match client.recv().await {
+        Some(ServerMessage::HelloError { code, message }) => {
+            assert_eq!(code, ErrorCode::SchemaMismatch);
+            assert!(
+                message.contains(SCHEMA_FINGERPRINT),
+                "Error should name the gateway schema: {}",
+                message
+            );
+        }
+        other => panic!("Expected HelloError, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+async fn test_protocol_version_mismatch(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    let mut encoded = MessageCodec::encode_client(&ClientMessage::Ping);
+    encoded[0] = encoded[0].wrapping_add(1);
+    client.send_raw(encoded).await;
+
+    match client.recv().await {
+        Some(ServerMessage::Error { code, message }) => {
+            assert_eq!(code, ErrorCode::VersionMismatch);
+            assert!(
+                message.contains("version"),
+                "Error should mention the version: {}",
+                message
+            );
+        }
+        other => panic!("Expected Error, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+async fn test_publish_payload_too_large(backend: Backend) {
+    let mut config = backend.config();
+    config.max_payload_bytes = 16;
+    let gateway = TestGateway::start_on(&backend, config).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    let token = create_valid_token("user-large");
+    client.auth(&token).await.expect("Auth should succeed");
+
+    let subject = test_subject("test_payload_limit", "events");
+    client.publish(&subject, &[0u8; 17]).await;
+
+    match client.recv().await {
+        Some(ServerMessage::Error { code, .. }) => {
+            assert_eq!(code, ErrorCode::PayloadTooLarge);
+        }
+        other => panic!("Expected Error, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+backend_tests!(
+    test_hello_welcome,
+    test_hello_schema_mismatch,
+    test_protocol_version_mismatch,
+    test_publish_payload_too_large,
+);
+
+// ============================================================================
+// Batching Tests
+// ============================================================================
+
+/// Complete a handshake asking for batching
+async fn negotiate_batching(client: &mut TestClient) {
+    client
+        .send(ClientMessage::Hello {
+            client_name: "integration-test".to_string(),
+            client_version: "0.0.0".to_string(),
+            schema_fingerprint: SCHEMA_FINGERPRINT.to_string(),
+            capabilities: vec![Capability::Batching],
+            compression: vec![],
+        })
+        .await;
+
+    match client.recv().await {
+        Some(ServerMessage::Welcome { capabilities, .. }) => {
+            assert_eq!(capabilities, vec![Capability::Batching]);
+        }
+        other => panic!("Expected Welcome, got: {:?}", other),
+    }
+}
+
+async fn test_batched_deliveries(backend: Backend) {
+    let mut config = backend.config();
+    config.batch_max_delay_ms = 200;
+    let gateway = TestGateway::start_on(&backend, config).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    negotiate_batching(&mut client).await;
+    let token = create_valid_token("user-batch");
+    client.auth(&token).await.expect("Auth should succeed");
+
+    let subject = test_subject("test_batch", "ticks");
+    client
+        .subscribe(&subject, 1)
+        .await
+        .expect("Subscribe should succeed");
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    for i in 0..5u8 {
+        backend.publish(&subject, &[i]).await;
+    }
+
+    // Collect deliveries, which may be split across frames
+    let mut payloads = Vec::new();
+    let mut batched = false;
+    while payloads.len() < 5 {
+        match client.recv().await {
+            Some(ServerMessage::Batch { frames }) => {
+                batched = true;
+                for msg in MessageCodec::unbatch_server(&frames).unwrap() {
+                    match msg {
+                        ServerMessage::Message { payload, .. } => payloads.push(payload),
+                        other => panic!("Expected Message in batch, got: {:?}", other),
+                    }
+                }
+            }
+            Some(ServerMessage::Message { payload, .. }) => payloads.push(payload),
+            other => panic!("Expected deliveries, got: {:?}", other),
+        }
+    }
+
+    assert!(batched, "Deliveries should be coalesced into a batch");
+    assert_eq!(
+        payloads,
+        (0..5u8).map(|i| vec![i]).collect::<Vec<_>>(),
+        "Batching must preserve order"
+    );
+
+    client.close().await;
+}
+
+async fn test_client_batch(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    let batch = MessageCodec::batch_client(&[ClientMessage::Ping, ClientMessage::Ping]);
+
+    // Rejected until batching is negotiated
+    client.send(batch.clone()).await;
+    match client.recv().await {
+        Some(ServerMessage::Error { code, .. }) => assert_eq!(code, ErrorCode::InvalidMessage),
+        other => panic!("Expected Error, got: {:?}", other),
+    }
+
+    negotiate_batching(&mut client).await;
+
+    client.send(batch).await;
+    match client.recv().await {
+        Some(ServerMessage::Batch { frames }) => {
+            assert_eq!(
+                MessageCodec::unbatch_server(&frames).unwrap(),
+                vec![ServerMessage::Pong, ServerMessage::Pong]
+            );
+        }
+        other => panic!("Expected Batch, got: {:?}", other),
+    }
+
+    // A batch inside a batch is refused rather than unpacked
+    let nested = MessageCodec::batch_client(&[MessageCodec::batch_client(&[ClientMessage::Ping])]);
+    client.send(nested).await;
+    match client.recv().await {
+        Some(ServerMessage::Error { code, .. }) => assert_eq!(code, ErrorCode::InvalidMessage),
+        other => panic!("Expected Error, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+backend_tests!(test_batched_deliveries, test_client_batch,);
+
+// ============================================================================
+// Compression Tests
+// ============================================================================
+
+/// Complete a handshake offering zstd compression
+async fn negotiate_compression(client: &mut TestClient) {
+    client
+        .send(ClientMessage::Hello {
+            client_name: "integration-test".to_string(),
+            client_version: "0.0.0".to_string(),
+            schema_fingerprint: SCHEMA_FINGERPRINT.to_string(),
+            capabilities: vec![Capability::Compression],
+            compression: vec![CompressionAlgorithm::Zstd],
+        })
+        .await;
+
+    match client.recv().await {
+        Some(ServerMessage::Welcome {
+            capabilities,
+            compression,
+            ..
+        }) => {
+            assert_eq!(capabilities, vec![Capability::Compression]);
+            assert_eq!(compression, CompressionAlgorithm::Zstd);
+        }
+        other => panic!("Expected Welcome, got: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_compressed_frames() {
+    let nats = get_nats().await;
+    let mut config = GatewayConfig::for_test(0, nats.url(), TEST_JWT_SECRET);
+    config.compression_threshold_bytes = 256;
+    let gateway = TestGateway::start_with_config(config).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    negotiate_compression(&mut client).await;
+    let token = create_valid_token("user-compress");
+    client.auth(&token).await.expect("Auth should succeed");
+
+    let subject = test_subject("test_compress", "frames");
+    client
+        .subscribe(&subject, 1)
+        .await
+        .expect("Subscribe should succeed");
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Large deliveries arrive compressed
+    let large = b"compressible ".repeat(100);
+    nats.publish(&subject, &large).await;
+
+    match client.recv().await {
+        Some(ServerMessage::Compressed { algorithm, frame }) => {
+            assert_eq!(algorithm, CompressionAlgorithm::Zstd);
+            let frame = compression::decompress(&algorithm, &frame, usize::MAX).unwrap();
+            match MessageCodec::decode_server(&frame).unwrap() {
+                ServerMessage::Message { payload, .. } => assert_eq!(payload, large),
+                other => panic!("Expected Message, got: {:?}", other),
+            }
+        }
+        other => panic!("Expected Compressed, got: {:?}", other),
+    }
+
+    // Small ones below the threshold do not
+    nats.publish(&subject, b"tiny").await;
+
+    match client.recv().await {
+        Some(ServerMessage::Message { payload, .. }) => assert_eq!(payload, b"tiny"),
+        other => panic!("Expected Message, got: {:?}", other),
+    }
+
+    // The gateway unwraps compressed frames from the client
+    let mut nats_sub = nats.subscribe(&subject).await;
+    let publish = MessageCodec::encode_client(&ClientMessage::Publish {
+        subject: subject.clone(),
+        payload: large.clone(),
+    });
+    client
+        .send(ClientMessage::Compressed {
+            algorithm: CompressionAlgorithm::Gzip,
+            frame: compression::compress(&CompressionAlgorithm::Gzip, &publish).unwrap(),
+        })
+        .await;
+
+    let msg = tokio::time::timeout(Duration::from_secs(5), nats_sub.next())
+        .await
+        .expect("Timeout waiting for NATS message")
+        .expect("Should receive message on NATS");
+    assert_eq!(msg.payload.as_ref(), large.as_slice());
+
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_compressed_frame_requires_negotiation() {
+    let nats = get_nats().await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    let ping = MessageCodec::encode_client(&ClientMessage::Ping);
+    client
+        .send(ClientMessage::Compressed {
+            algorithm: CompressionAlgorithm::Gzip,
+            frame: compression::compress(&CompressionAlgorithm::Gzip, &ping).unwrap(),
+        })
+        .await;
+
+    match client.recv().await {
+        Some(ServerMessage::Error { code, .. }) => assert_eq!(code, ErrorCode::InvalidMessage),
+        other => panic!("Expected Error, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_subject_compression_policy() {
+    let nats = get_nats().await;
+    let subject = test_subject("test_subject_compression", "data");
+    let mut config = GatewayConfig::for_test(0, nats.url(), TEST_JWT_SECRET);
+    config.subject_compression = vec![SubjectCompression {
+        pattern: subject.clone(),
+        algorithm: CompressionAlgorithm::Gzip,
+    }];
+    let gateway = TestGateway::start_with_config(config).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    let token = create_valid_token("user-policy");
+    client.auth(&token).await.expect("Auth should succeed");
+    client
+        .subscribe(&subject, 1)
+        .await
+        .expect("Subscribe should succeed");
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Backend payloads are stored gzipped and delivered plain
+    let payload = b"stored compressed".to_vec();
+    let stored = compression::compress(&CompressionAlgorithm::Gzip, &payload).unwrap();
+    nats.publish(&subject, &stored).await;
+
+    match client.recv().await {
+        Some(ServerMessage::Message {
+            payload: delivered, ..
+        }) => assert_eq!(delivered, payload),
+        other => panic!("Expected Message, got: {:?}", other),
+    }
+
+    // Client payloads are gzipped on the way into NATS
+    let mut nats_sub = nats.subscribe(&subject).await;
+    client.publish(&subject, &payload).await;
+
+    let msg = tokio::time::timeout(Duration::from_secs(5), nats_sub.next())
+        .await
+        .expect("Timeout waiting for NATS message")
+        .expect("Should receive message on NATS");
+    let unpacked =
+        compression::decompress(&CompressionAlgorithm::Gzip, &msg.payload, usize::MAX).unwrap();
+    assert_eq!(unpacked, payload);
+
+    client.close().await;
+}
+
+// ============================================================================
+// Flow Control Tests
+// ============================================================================
+
+async fn test_credit_flow_control(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    let token = create_valid_token("user-credits");
+    client.auth(&token).await.expect("Auth should succeed");
+
+    let subject = test_subject("test_credits", "ticks");
+    client
+        .send(ClientMessage::Subscribe {
+            subject: subject.clone(),
+            id: 1,
+            credits: Some(2),
+            max_msgs: None,
+            replay: None,
+            slow_consumer: None,
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::SubscribeOk { id }) => assert_eq!(id, 1),
+        other => panic!("Expected SubscribeOk, got: {:?}", other),
+    }
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    for i in 0..4u8 {
+        backend.publish(&subject, &[i]).await;
+    }
+
+    for i in 0..2u8 {
+        match client.recv().await {
+            Some(ServerMessage::Message { payload, .. }) => assert_eq!(payload, vec![i]),
+            other => panic!("Expected Message, got: {:?}", other),
+        }
+    }
+
+    // Out of credit: the rest stays buffered at the gateway
+    let held = client.recv_timeout(Duration::from_millis(300)).await;
+    assert!(held.is_none(), "Expected no delivery, got: {:?}", held);
+
+    client
+        .send(ClientMessage::GrantCredit { id: 1, credits: 2 })
+        .await;
+
+    for i in 2..4u8 {
+        match client.recv().await {
+            Some(ServerMessage::Message { payload, .. }) => assert_eq!(payload, vec![i]),
+            other => panic!("Expected Message, got: {:?}", other),
+        }
+    }
+
+    client.close().await;
+}
+
+async fn test_credit_overflow_disconnects(backend: Backend) {
+    let mut config = backend.config();
+    config.credit_buffer = 2;
+    config.overflow_policy = CreditOverflowPolicy::Disconnect;
+    let gateway = TestGateway::start_on(&backend, config).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    let token = create_valid_token("user-overflow");
+    client.auth(&token).await.expect("Auth should succeed");
+
+    let subject = test_subject("test_overflow", "ticks");
+    client
+        .send(ClientMessage::Subscribe {
+            subject: subject.clone(),
+            id: 1,
+            credits: Some(0),
+            max_msgs: None,
+            replay: None,
+            slow_consumer: None,
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::SubscribeOk { id }) => assert_eq!(id, 1),
+        other => panic!("Expected SubscribeOk, got: {:?}", other),
+    }
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    for i in 0..3u8 {
+        backend.publish(&subject, &[i]).await;
+    }
+
+    match client.recv().await {
+        Some(ServerMessage::Error { code, .. }) => assert_eq!(code, ErrorCode::QuotaExceeded),
+        other => panic!("Expected Error, got: {:?}", other),
+    }
+    assert!(
+        client.recv().await.is_none(),
+        "Slow consumer should be disconnected"
+    );
+}
+
+async fn test_dropped_messages_leave_seq_gap(backend: Backend) {
+    let mut config = backend.config();
+    config.credit_buffer = 2;
+    config.overflow_policy = CreditOverflowPolicy::DropOldest;
+    let gateway = TestGateway::start_on(&backend, config).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    let token = create_valid_token("user-seq-gap");
+    client.auth(&token).await.expect("Auth should succeed");
+
+    let subject = test_subject("test_seq_gap", "ticks");
+    client
+        .send(ClientMessage::Subscribe {
+            subject: subject.clone(),
+            id: 1,
+            credits: Some(1),
+            max_msgs: None,
+            replay: None,
+            slow_consumer: None,
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::SubscribeOk { id }) => assert_eq!(id, 1),
+        other => panic!("Expected SubscribeOk, got: {:?}", other),
+    }
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // One goes out, the buffer keeps the last two of the remaining four
+    for i in 0..5u8 {
+        backend.publish(&subject, &[i]).await;
+    }
+
+    match client.recv().await {
+        Some(ServerMessage::Message { seq, dropped, .. }) => {
+            assert_eq!((seq, dropped), (1, 0));
+        }
+        other => panic!("Expected Message, got: {:?}", other),
+    }
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    client
+        .send(ClientMessage::GrantCredit { id: 1, credits: 10 })
+        .await;
+
+    for expected in [(4, 2), (5, 0)] {
+        match client.recv().await {
+            Some(ServerMessage::Message { seq, dropped, .. }) => {
+                assert_eq!((seq, dropped), expected);
+            }
+            other => panic!("Expected Message, got: {:?}", other),
+        }
+    }
+
+    client.close().await;
+}
+
+backend_tests!(
+    test_credit_flow_control,
+    test_credit_overflow_disconnects,
+    test_dropped_messages_leave_seq_gap,
+);
+
+// ============================================================================
+// Subscription Lifecycle Tests
+// ============================================================================
+
+async fn test_subscribe_max_msgs(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    let token = create_valid_token("user-max-msgs");
+    client.auth(&token).await.expect("Auth should succeed");
+
+    let subject = test_subject("test_max_msgs", "ticks");
+    client
+        .send(ClientMessage::Subscribe {
+            subject: subject.clone(),
+            id: 1,
+            credits: None,
+            max_msgs: Some(2),
+            replay: None,
+            slow_consumer: None,
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::SubscribeOk { id }) => assert_eq!(id, 1),
+        other => panic!("Expected SubscribeOk, got: {:?}", other),
+    }
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    for i in 0..3u8 {
+        backend.publish(&subject, &[i]).await;
+    }
+
+    for i in 0..2u8 {
+        match client.recv().await {
+            Some(ServerMessage::Message { payload, .. }) => assert_eq!(payload, vec![i]),
+            other => panic!("Expected Message, got: {:?}", other),
+        }
+    }
+
+    match client.recv().await {
+        Some(ServerMessage::SubscriptionEnded { id, reason, .. }) => {
+            assert_eq!(id, 1);
+            assert_eq!(reason, SubscriptionEndReason::LimitReached);
+        }
+        other => panic!("Expected SubscriptionEnded, got: {:?}", other),
+    }
+
+    // The third message is never delivered
+    let extra = client.recv_timeout(Duration::from_millis(300)).await;
+    assert!(extra.is_none(), "Expected no delivery, got: {:?}", extra);
+
+    // The subscription is gone, so unsubscribing from it fails
+    let result = client.unsubscribe(1).await;
+    assert!(result.is_err(), "Unsubscribe should fail: {:?}", result);
+
+    client.close().await;
+}
+
+async fn test_subscribe_max_msgs_zero_rejected(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    let token = create_valid_token("user-max-zero");
+    client.auth(&token).await.expect("Auth should succeed");
+
+    client
+        .send(ClientMessage::Subscribe {
+            subject: test_subject("test_max_zero", "ticks"),
+            id: 1,
+            credits: None,
+            max_msgs: Some(0),
+            replay: None,
+            slow_consumer: None,
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::SubscribeError { id, code, .. }) => {
+            assert_eq!(id, 1);
+            assert_eq!(code, ErrorCode::InvalidMessage);
+        }
+        other => panic!("Expected SubscribeError, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+async fn test_unsubscribe_unknown_id(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    let token = create_valid_token("user-unsub-unknown");
+    client.auth(&token).await.expect("Auth should succeed");
+
+    client.send(ClientMessage::Unsubscribe { id: 42 }).await;
+    match client.recv().await {
+        Some(ServerMessage::UnsubscribeError { id, code, .. }) => {
+            assert_eq!(id, 42);
+            assert_eq!(code, ErrorCode::NotFound);
+        }
+        other => panic!("Expected UnsubscribeError, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+async fn test_reauth_revokes_subscriptions(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    let kept_subject = test_subject("test_reauth", "kept");
+    let revoked_subject = test_subject("test_reauth", "revoked");
+
+    let token = create_valid_token("user-reauth");
+    client.auth(&token).await.expect("Auth should succeed");
+    client
+        .subscribe(&kept_subject, 1)
+        .await
+        .expect("Subscribe should succeed");
+    client
+        .subscribe(&revoked_subject, 2)
+        .await
+        .expect("Subscribe should succeed");
+    client
+        .send(ClientMessage::SubscribePresence {
+            id: 3,
+            pattern: revoked_subject.clone(),
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::SubscribeOk { id }) => assert_eq!(id, 3),
+        other => panic!("Expected SubscribeOk, got: {:?}", other),
+    }
+
+    // Re-authenticate with a token that only allows one of the subjects
+    let limited = create_limited_token("user-reauth", vec![kept_subject.clone()]);
+    client.auth(&limited).await.expect("Re-auth should succeed");
+
+    // Both the subscription and the presence watch on it end
+    let mut ended = Vec::new();
+    for _ in 0..2 {
+        match client.recv().await {
+            Some(ServerMessage::SubscriptionEnded { id, reason, .. }) => {
+                assert_eq!(reason, SubscriptionEndReason::PermissionRevoked);
+                ended.push(id);
+            }
+            other => panic!("Expected SubscriptionEnded, got: {:?}", other),
+        }
+    }
+    ended.sort();
+    assert_eq!(ended, vec![2, 3]);
+
+    // The allowed subscription keeps delivering
+    backend.publish(&kept_subject, b"still here").await;
+    match client.recv().await {
+        Some(ServerMessage::Message {
+            subscription_id, ..
+        }) => assert_eq!(subscription_id, 1),
+        other => panic!("Expected Message, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+backend_tests!(
+    test_subscribe_max_msgs,
+    test_subscribe_max_msgs_zero_rejected,
+    test_unsubscribe_unknown_id,
+    test_reauth_revokes_subscriptions,
+);
+
+// ============================================================================
+// Shared Subscription Tests
+// ============================================================================
+
+/// Sessions watching the same subject
+const SHARED_SESSIONS: usize = 200;
+
+/// Wait for the gateway to notice closed connections
+async fn wait_for_sessions(gateway: &TestGateway, sessions: usize) {
+    for _ in 0..50 {
+        if gateway.broker.subscription_stats().sessions == sessions {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!(
+        "Expected {} sessions, got {:?}",
+        sessions,
+        gateway.broker.subscription_stats()
+    );
+}
+
+#[tokio::test]
+async fn test_shared_subscription_load() {
+    let nats = DedicatedNats::start(&["--jetstream", "-m", "8222"], vec![]).await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut publisher = TestClient::connect(&gateway.ws_url()).await;
+    publisher
+        .auth(&create_valid_token("user-shared-publisher"))
+        .await
+        .expect("Auth should succeed");
+    let baseline = nats.subscription_count().await;
+
+    let subject = test_subject("test_shared_load", "prices.>");
+    let mut clients = Vec::with_capacity(SHARED_SESSIONS);
+    for i in 0..SHARED_SESSIONS {
+        let mut client = TestClient::connect(&gateway.ws_url()).await;
+        client
+            .auth(&create_valid_token(&format!("user-shared-{}", i)))
+            .await
+            .expect("Auth should succeed");
+        client
+            .subscribe(&subject, i as u64 + 1)
+            .await
+            .expect("Subscribe should succeed");
+        clients.push(client);
+    }
+
+    // One subscription in NATS, however many sessions watch the subject
+    let stats = gateway.broker.subscription_stats();
+    assert_eq!(stats.upstream, 1);
+    assert_eq!(stats.sessions, SHARED_SESSIONS);
+    assert_eq!(nats.subscription_count().await, baseline + 1);
+
+    let tick = test_subject("test_shared_load", "prices.btc");
+    publisher.publish(&tick, b"42000").await;
+    for (i, client) in clients.iter_mut().enumerate() {
+        match client.recv().await {
+            Some(ServerMessage::Message {
+                subscription_id,
+                payload,
+                ..
+            }) => {
+                assert_eq!(subscription_id, i as u64 + 1);
+                assert_eq!(payload, b"42000");
+            }
+            other => panic!("Client {} expected Message, got: {:?}", i, other),
+        }
+    }
+
+    // The upstream subscription lives until the last session leaves
+    let last = clients.pop().unwrap();
+    for client in clients {
+        client.close().await;
+    }
+    wait_for_sessions(&gateway, 1).await;
+    assert_eq!(gateway.broker.subscription_stats().upstream, 1);
+
+    last.close().await;
+    wait_for_sessions(&gateway, 0).await;
+    assert_eq!(gateway.broker.subscription_stats().upstream, 0);
+    assert_eq!(nats.subscription_count().await, baseline);
+}
+
+#[tokio::test]
+async fn test_shared_subscription_unsubscribe_keeps_others() {
+    let nats = get_nats().await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut first = TestClient::connect(&gateway.ws_url()).await;
+    let mut second = TestClient::connect(&gateway.ws_url()).await;
+    first
+        .auth(&create_valid_token("user-shared-first"))
+        .await
+        .expect("Auth should succeed");
+    second
+        .auth(&create_valid_token("user-shared-second"))
+        .await
+        .expect("Auth should succeed");
+
+    let subject = test_subject("test_shared_unsubscribe", "events");
+    first
+        .subscribe(&subject, 1)
+        .await
+        .expect("Subscribe should succeed");
+    second
+        .subscribe(&subject, 5)
+        .await
+        .expect("Subscribe should succeed");
+    // The same session subscribing twice gets each delivery twice
+    second
+        .subscribe(&subject, 6)
+        .await
+        .expect("Subscribe should succeed");
+    assert_eq!(gateway.broker.subscription_stats().upstream, 1);
+
+    first
+        .unsubscribe(1)
+        .await
+        .expect("Unsubscribe should succeed");
+    first.publish(&subject, b"still here").await;
+
+    let mut ids = Vec::new();
+    for _ in 0..2 {
+        match second.recv().await {
+            Some(ServerMessage::Message {
+                subscription_id,
+                payload,
+                ..
+            }) => {
+                assert_eq!(payload, b"still here");
+                ids.push(subscription_id);
+            }
+            other => panic!("Expected Message, got: {:?}", other),
+        }
+    }
+    ids.sort();
+    assert_eq!(ids, vec![5, 6]);
+    assert!(
+        first
+            .recv_timeout(Duration::from_millis(200))
+            .await
+            .is_none(),
+        "Unsubscribed session should not receive messages"
+    );
+
+    first.close().await;
+    second.close().await;
+}
+
+// ============================================================================
+// Retained Subject Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_retained_message_replayed_to_new_subscriber() {
+    let nats = get_nats().await;
+    let mut config = GatewayConfig::for_test(0, nats.url(), TEST_JWT_SECRET);
+    config.retained_subjects = vec![test_subject("test_retained", "*")];
+    let gateway = TestGateway::start_with_config(config).await;
+    let mut publisher = TestClient::connect(&gateway.ws_url()).await;
+    let mut subscriber = TestClient::connect(&gateway.ws_url()).await;
+
+    publisher
+        .auth(&create_valid_token("user-publisher"))
+        .await
+        .expect("Auth should succeed");
+    subscriber
+        .auth(&create_valid_token("user-subscriber"))
+        .await
+        .expect("Auth should succeed");
+
+    let subject = test_subject("test_retained", "price");
+    publisher.publish(&subject, b"old").await;
+    publisher.publish(&subject, b"latest").await;
+
+    // Let the gateway's cache see both publishes
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    subscriber
+        .subscribe(&subject, 1)
+        .await
+        .expect("Subscribe should succeed");
+
+    match subscriber.recv().await {
+        Some(ServerMessage::Message {
+            payload,
+            seq,
+            origin,
+            ..
+        }) => {
+            assert_eq!(payload, b"latest");
+            assert_eq!(seq, 1);
+            assert_eq!(origin, MessageOrigin::Retained);
+        }
+        other => panic!("Expected Message, got: {:?}", other),
+    }
+
+    publisher.publish(&subject, b"live").await;
+
+    match subscriber.recv().await {
+        Some(ServerMessage::Message {
+            payload,
+            seq,
+            origin,
+            ..
+        }) => {
+            assert_eq!(payload, b"live");
+            assert_eq!(seq, 2);
+            assert_eq!(origin, MessageOrigin::Live);
+        }
+        other => panic!("Expected Message, got: {:?}", other),
+    }
+
+    publisher.close().await;
+    subscriber.close().await;
+}
+
+#[tokio::test]
+async fn test_retained_replay_respects_permissions() {
+    let nats = get_nats().await;
+    let mut config = GatewayConfig::for_test(0, nats.url(), TEST_JWT_SECRET);
+    config.retained_subjects = vec![test_subject("test_retained_perm", "*")];
+    let gateway = TestGateway::start_with_config(config).await;
+    let mut publisher = TestClient::connect(&gateway.ws_url()).await;
+    let mut subscriber = TestClient::connect(&gateway.ws_url()).await;
+
+    let public = test_subject("test_retained_perm", "public");
+    let secret = test_subject("test_retained_perm", "secret");
+
+    publisher
+        .auth(&create_valid_token("user-publisher"))
+        .await
+        .expect("Auth should succeed");
+    subscriber
+        .auth(&create_token_with_deny(
+            "user-subscriber",
+            vec![secret.clone()],
+        ))
+        .await
+        .expect("Auth should succeed");
+
+    publisher.publish(&secret, b"hidden").await;
+    publisher.publish(&public, b"visible").await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    subscriber
+        .subscribe(&test_subject("test_retained_perm", "*"), 1)
+        .await
+        .expect("Subscribe should succeed");
+
+    match subscriber.recv().await {
+        Some(ServerMessage::Message {
+            subject, origin, ..
+        }) => {
+            assert_eq!(subject, public);
+            assert_eq!(origin, MessageOrigin::Retained);
+        }
+        other => panic!("Expected Message, got: {:?}", other),
+    }
+
+    let extra = subscriber.recv_timeout(Duration::from_millis(200)).await;
+    assert!(extra.is_none(), "Denied subject was replayed: {:?}", extra);
+
+    publisher.close().await;
+    subscriber.close().await;
+}
+
+#[tokio::test]
+async fn test_empty_payload_clears_retained_message() {
+    let nats = get_nats().await;
+    let mut config = GatewayConfig::for_test(0, nats.url(), TEST_JWT_SECRET);
+    config.retained_subjects = vec![test_subject("test_retained_clear", "*")];
+    let gateway = TestGateway::start_with_config(config).await;
+    let mut publisher = TestClient::connect(&gateway.ws_url()).await;
+    let mut subscriber = TestClient::connect(&gateway.ws_url()).await;
+
+    publisher
+        .auth(&create_valid_token("user-publisher"))
+        .await
+        .expect("Auth should succeed");
+    subscriber
+        .auth(&create_valid_token("user-subscriber"))
+        .await
+        .expect("Auth should succeed");
+
+    let subject = test_subject("test_retained_clear", "status");
+    publisher.publish(&subject, b"up").await;
+    publisher.publish(&subject, b"").await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    subscriber
+        .subscribe(&subject, 1)
+        .await
+        .expect("Subscribe should succeed");
+
+    let replayed = subscriber.recv_timeout(Duration::from_millis(200)).await;
+    assert!(
+        replayed.is_none(),
+        "Cleared subject was replayed: {:?}",
+        replayed
+    );
+
+    publisher.close().await;
+    subscriber.close().await;
+}
+
+// ============================================================================
+// History Replay Tests
+// ============================================================================
+
+/// Start a gateway recording history for `pattern` and publish `payloads`
+/// to `subject` through it
+async fn gateway_with_history(
+    nats: &TestNats,
+    pattern: String,
+    subject: &str,
+    payloads: &[&[u8]],
+) -> (TestGateway, TestClient) {
+    let mut config = GatewayConfig::for_test(0, nats.url(), TEST_JWT_SECRET);
+    config.history_subjects = vec![pattern];
+    config.history_length = 3;
+    let gateway = TestGateway::start_with_config(config).await;
+    let mut publisher = TestClient::connect(&gateway.ws_url()).await;
+    publisher
+        .auth(&create_valid_token("user-publisher"))
+        .await
+        .expect("Auth should succeed");
+
+    for payload in payloads {
+        publisher.publish(subject, payload).await;
+    }
+    // Let the gateway record the publishes
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    (gateway, publisher)
+}
+
+#[tokio::test]
+async fn test_history_replays_last_messages_before_live() {
+    let nats = get_nats().await;
+    let subject = test_subject("test_history", "ticks");
+    let (gateway, mut publisher) = gateway_with_history(
+        &nats,
+        test_subject("test_history", "*"),
+        &subject,
+        &[b"1", b"2", b"3", b"4"],
+    )
+    .await;
+    let mut subscriber = TestClient::connect(&gateway.ws_url()).await;
+    subscriber
+        .auth(&create_valid_token("user-subscriber"))
+        .await
+        .expect("Auth should succeed");
+
+    subscriber
+        .send(ClientMessage::Subscribe {
+            subject: subject.clone(),
+            id: 1,
+            credits: None,
+            max_msgs: None,
+            replay: Some(ReplayFrom::Last { count: 2 }),
+            slow_consumer: None,
+        })
+        .await;
+    match subscriber.recv().await {
+        Some(ServerMessage::SubscribeOk { id }) => assert_eq!(id, 1),
+        other => panic!("Expected SubscribeOk, got: {:?}", other),
+    }
+
+    publisher.publish(&subject, b"5").await;
+
+    let expected: [(&[u8], MessageOrigin); 3] = [
+        (b"3", MessageOrigin::History),
+        (b"4", MessageOrigin::History),
+        (b"5", MessageOrigin::Live),
+    ];
+    for (seq, (payload, expected_origin)) in (1..).zip(expected) {
+        match subscriber.recv().await {
+            Some(ServerMessage::Message {
+                payload: msg_payload,
+                seq: msg_seq,
+                origin,
+                ..
+            }) => {
+                assert_eq!(msg_payload, payload);
+                assert_eq!(msg_seq, seq);
+                assert_eq!(origin, expected_origin);
+            }
+            other => panic!("Expected Message, got: {:?}", other),
+        }
+    }
+
+    publisher.close().await;
+    subscriber.close().await;
+}
+
+#[tokio::test]
+async fn test_history_replay_since_time() {
+    let nats = get_nats().await;
+    let subject = test_subject("test_history_since", "ticks");
+    let (gateway, mut publisher) = gateway_with_history(
+        &nats,
+        test_subject("test_history_since", "*"),
+        &subject,
+        &[b"old"],
+    )
+    .await;
+
+    let since = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    publisher.publish(&subject, b"new").await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut subscriber = TestClient::connect(&gateway.ws_url()).await;
+    subscriber
+        .auth(&create_valid_token("user-subscriber"))
+        .await
+        .expect("Auth should succeed");
+    subscriber
+        .send(ClientMessage::Subscribe {
+            subject: subject.clone(),
+            id: 1,
+            credits: None,
+            max_msgs: None,
+            replay: Some(ReplayFrom::Since { unix_ms: since }),
+            slow_consumer: None,
+        })
+        .await;
+    match subscriber.recv().await {
+        Some(ServerMessage::SubscribeOk { id }) => assert_eq!(id, 1),
+        other => panic!("Expected SubscribeOk, got: {:?}", other),
+    }
+
+    match subscriber.recv().await {
+        Some(ServerMessage::Message {
+            payload, origin, ..
+        }) => {
+            assert_eq!(payload, b"new");
+            assert_eq!(origin, MessageOrigin::History);
+        }
+        other => panic!("Expected Message, got: {:?}", other),
+    }
+    let extra = subscriber.recv_timeout(Duration::from_millis(200)).await;
+    assert!(extra.is_none(), "Unexpected message: {:?}", extra);
+
+    publisher.close().await;
+    subscriber.close().await;
+}
+
+#[tokio::test]
+async fn test_subscribe_without_replay_skips_history() {
+    let nats = get_nats().await;
+    let subject = test_subject("test_history_none", "ticks");
+    let (gateway, publisher) = gateway_with_history(
+        &nats,
+        test_subject("test_history_none", "*"),
+        &subject,
+        &[b"1"],
+    )
+    .await;
+    let mut subscriber = TestClient::connect(&gateway.ws_url()).await;
+    subscriber
+        .auth(&create_valid_token("user-subscriber"))
+        .await
+        .expect("Auth should succeed");
+
+    subscriber
+        .subscribe(&subject, 1)
+        .await
+        .expect("Subscribe should succeed");
+    let replayed = subscriber.recv_timeout(Duration::from_millis(200)).await;
+    assert!(replayed.is_none(), "History was replayed: {:?}", replayed);
+
+    publisher.close().await;
+    subscriber.close().await;
+}
+
+// ============================================================================
+// Durable Consumer Tests
+// ============================================================================
+
+/// Subscribe to `stream` through the durable consumer `durable`, expecting
+/// success
+async fn subscribe_consumer(client: &mut TestClient, stream: &str, durable: &str, filter: &str) {
+    client
+        .send(ClientMessage::SubscribeConsumer {
+            id: 1,
+            stream: stream.to_string(),
+            durable: durable.to_string(),
+            filter_subject: filter.to_string(),
+            deliver: DeliverPolicy::All,
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::SubscribeOk { id }) => assert_eq!(id, 1),
+        other => panic!("Expected SubscribeOk, got: {:?}", other),
+    }
+}
+
+/// Receive a consumer delivery, returning its payload, stream sequence and
+/// delivery count
+async fn recv_consumer_message(client: &mut TestClient) -> (Vec<u8>, u64, u32) {
+    match client.recv().await {
+        Some(ServerMessage::ConsumerMessage {
+            subscription_id,
+            payload,
+            stream_seq,
+            delivered,
+            ..
+        }) => {
+            assert_eq!(subscription_id, 1);
+            (payload, stream_seq, delivered)
+        }
+        other => panic!("Expected ConsumerMessage, got: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_consumer_resumes_after_acked_messages() {
+    let nats = get_nats().await;
+    let filter = test_subject("test_consumer_resume", ">");
+    let subject = test_subject("test_consumer_resume", "orders");
+    nats.create_stream("TEST_CONSUMER_RESUME", vec![filter.clone()])
+        .await;
+    nats.publish(&subject, b"first").await;
+    nats.publish(&subject, b"second").await;
+
+    let gateway = TestGateway::start(nats.url()).await;
+    let token = create_valid_token("user-orders");
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client.auth(&token).await.expect("Auth should succeed");
+    subscribe_consumer(&mut client, "TEST_CONSUMER_RESUME", "ui", &filter).await;
+
+    for expected in [b"first".as_slice(), b"second"] {
+        let (payload, stream_seq, delivered) = recv_consumer_message(&mut client).await;
+        assert_eq!(payload, expected);
+        assert_eq!(delivered, 1);
+        client
+            .send(ClientMessage::Ack {
+                id: 1,
+                stream_seq,
+                kind: AckKind::Ack,
+            })
+            .await;
+    }
+    client.close().await;
+
+    // A later connection picks up after the acknowledged messages
+    nats.publish(&subject, b"third").await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client.auth(&token).await.expect("Auth should succeed");
+    subscribe_consumer(&mut client, "TEST_CONSUMER_RESUME", "ui", &filter).await;
+
+    let (payload, stream_seq, _) = recv_consumer_message(&mut client).await;
+    assert_eq!(payload, b"third");
+    assert_eq!(stream_seq, 3);
+
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_consumer_redelivers_nak() {
+    let nats = get_nats().await;
+    let filter = test_subject("test_consumer_nak", ">");
+    nats.create_stream("TEST_CONSUMER_NAK", vec![filter.clone()])
+        .await;
+    nats.publish(&test_subject("test_consumer_nak", "orders"), b"order")
+        .await;
+
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_valid_token("user-orders"))
+        .await
+        .expect("Auth should succeed");
+    subscribe_consumer(&mut client, "TEST_CONSUMER_NAK", "ui", &filter).await;
+
+    let (_, stream_seq, delivered) = recv_consumer_message(&mut client).await;
+    assert_eq!(delivered, 1);
+    client
+        .send(ClientMessage::Ack {
+            id: 1,
+            stream_seq,
+            kind: AckKind::Nak,
+        })
+        .await;
+
+    let (payload, redelivered_seq, delivered) = recv_consumer_message(&mut client).await;
+    assert_eq!(payload, b"order");
+    assert_eq!(redelivered_seq, stream_seq);
+    assert_eq!(delivered, 2);
+
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_ack_unknown_message() {
+    let nats = get_nats().await;
+    let filter = test_subject("test_consumer_unknown", ">");
+    nats.create_stream("TEST_CONSUMER_UNKNOWN", vec![filter.clone()])
+        .await;
+
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_valid_token("user-orders"))
+        .await
+        .expect("Auth should succeed");
+    subscribe_consumer(&mut client, "TEST_CONSUMER_UNKNOWN", "ui", &filter).await;
+
+    client
+        .send(ClientMessage::Ack {
+            id: 1,
+            stream_seq: 42,
+            kind: AckKind::Ack,
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::Error { code, .. }) => assert_eq!(code, ErrorCode::NotFound),
+        other => panic!("Expected Error, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_consumer_requires_subscribe_permission() {
+    let nats = get_nats().await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_limited_token(
+            "user-limited",
+            vec!["allowed.>".to_string()],
+        ))
+        .await
+        .expect("Auth should succeed");
+
+    client
+        .send(ClientMessage::SubscribeConsumer {
+            id: 1,
+            stream: "ORDERS".to_string(),
+            durable: "ui".to_string(),
+            filter_subject: "orders.>".to_string(),
+            deliver: DeliverPolicy::New,
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::SubscribeError { code, .. }) => {
+            assert_eq!(code, ErrorCode::PermissionDenied)
+        }
+        other => panic!("Expected SubscribeError, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_consumer_terminates_denied_subjects() {
+    let nats = get_nats().await;
+    let filter = test_subject("test_consumer_deny", ">");
+    let secret = test_subject("test_consumer_deny", "secret");
+    nats.create_stream("TEST_CONSUMER_DENY", vec![filter.clone()])
+        .await;
+    nats.publish(&secret, b"hidden").await;
+    nats.publish(&test_subject("test_consumer_deny", "orders"), b"order")
+        .await;
+
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_token_with_deny("user-orders", vec![secret]))
+        .await
+        .expect("Auth should succeed");
+    subscribe_consumer(&mut client, "TEST_CONSUMER_DENY", "ui", &filter).await;
+
+    // The wildcard filter matches the denied subject, but only the allowed
+    // message arrives, and the denied one is not redelivered
+    let (payload, stream_seq, _) = recv_consumer_message(&mut client).await;
+    assert_eq!(payload, b"order");
+    assert_eq!(stream_seq, 2);
+    let extra = client.recv_timeout(Duration::from_millis(500)).await;
+    assert!(extra.is_none(), "Unexpected message: {:?}", extra);
+
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_consumers_per_user_limit() {
+    let nats = get_nats().await;
+    let filter = test_subject("test_consumer_limit", ">");
+    nats.create_stream("TEST_CONSUMER_LIMIT", vec![filter.clone()])
+        .await;
+
+    let mut config = GatewayConfig::for_test(0, nats.url(), TEST_JWT_SECRET);
+    config.consumers_per_user = 1;
+    let gateway = TestGateway::start_with_config(config).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_valid_token("user-orders"))
+        .await
+        .expect("Auth should succeed");
+    subscribe_consumer(&mut client, "TEST_CONSUMER_LIMIT", "ui", &filter).await;
+
+    client
+        .send(ClientMessage::SubscribeConsumer {
+            id: 2,
+            stream: "TEST_CONSUMER_LIMIT".to_string(),
+            durable: "reports".to_string(),
+            filter_subject: filter.clone(),
+            deliver: DeliverPolicy::All,
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::SubscribeError { id, code, .. }) => {
+            assert_eq!(id, 2);
+            assert_eq!(code, ErrorCode::QuotaExceeded);
+        }
+        other => panic!("Expected SubscribeError, got: {:?}", other),
+    }
+    client.close().await;
+
+    // Reusing a consumer the user already holds is still allowed
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_valid_token("user-orders"))
+        .await
+        .expect("Auth should succeed");
+    subscribe_consumer(&mut client, "TEST_CONSUMER_LIMIT", "ui", &filter).await;
+
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_jetstream_publish_deduplicates_by_msg_id() {
+    let nats = get_nats().await;
+    let subject = test_subject("test_jetstream_publish", "orders");
+    nats.create_stream(
+        "TEST_JETSTREAM_PUBLISH",
+        vec![test_subject("test_jetstream_publish", ">")],
+    )
+    .await;
+
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_valid_token("user-orders"))
+        .await
+        .expect("Auth should succeed");
+
+    let mut acks = Vec::new();
+    for request_id in 1..=2 {
+        client
+            .send(ClientMessage::JetStreamPublish {
+                request_id,
+                subject: subject.clone(),
+                payload: b"order".to_vec(),
+                msg_id: Some("order-1".to_string()),
+            })
+            .await;
+        match client.recv().await {
+            Some(ServerMessage::PubAck {
+                request_id: acked,
+                stream,
+                seq,
+                duplicate,
+            }) => {
+                assert_eq!(acked, request_id);
+                assert_eq!(stream, "TEST_JETSTREAM_PUBLISH");
+                acks.push((seq, duplicate));
+            }
+            other => panic!("Expected PubAck, got: {:?}", other),
+        }
+    }
+    assert_eq!(acks, vec![(1, false), (1, true)]);
+
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_jetstream_publish_without_stream() {
+    let nats = get_nats().await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_valid_token("user-orders"))
+        .await
+        .expect("Auth should succeed");
+
+    client
+        .send(ClientMessage::JetStreamPublish {
+            request_id: 1,
+            subject: test_subject("test_jetstream_no_stream", "orders"),
+            payload: b"order".to_vec(),
+            msg_id: None,
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::RequestError {
+            request_id, code, ..
+        }) => {
+            assert_eq!(request_id, 1);
+            assert_eq!(code, ErrorCode::NotFound);
+        }
+        other => panic!("Expected RequestError, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+// ============================================================================
+// Key-Value Tests
+// ============================================================================
+
+/// Token allowed to read and write every key
+fn kv_token(subject: &str) -> String {
+    create_kv_token(
+        subject,
+        vec!["kv_read".into(), "kv_write".into()],
+        vec![],
+        vec![],
+    )
+}
+
+#[tokio::test]
+async fn test_kv_put_get_delete() {
+    let nats = get_nats().await;
+    nats.create_bucket("test_kv_roundtrip").await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&kv_token("user-kv"))
+        .await
+        .expect("Auth should succeed");
+
+    client
+        .send(ClientMessage::KvPut {
+            request_id: 1,
+            bucket: "test_kv_roundtrip".to_string(),
+            key: "users.alice.theme".to_string(),
+            value: b"dark".to_vec(),
+        })
+        .await;
+    let revision = match client.recv().await {
+        Some(ServerMessage::KvPutOk {
+            request_id,
+            revision,
+        }) => {
+            assert_eq!(request_id, 1);
+            revision
+        }
+        other => panic!("Expected KvPutOk, got: {:?}", other),
+    };
+
+    client
+        .send(ClientMessage::KvGet {
+            request_id: 2,
+            bucket: "test_kv_roundtrip".to_string(),
+            key: "users.alice.theme".to_string(),
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::KvEntry {
+            request_id,
+            value,
+            revision: entry_revision,
+            ..
+        }) => {
+            assert_eq!(request_id, 2);
+            assert_eq!(value, Some(b"dark".to_vec()));
+            assert_eq!(entry_revision, revision);
+        }
+        other => panic!("Expected KvEntry, got: {:?}", other),
+    }
+
+    client
+        .send(ClientMessage::KvDelete {
+            request_id: 3,
+            bucket: "test_kv_roundtrip".to_string(),
+            key: "users.alice.theme".to_string(),
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::KvDeleteOk { request_id }) => assert_eq!(request_id, 3),
+        other => panic!("Expected KvDeleteOk, got: {:?}", other),
+    }
+
+    for (request_id, key) in [(4, "users.alice.theme"), (5, "users.bob.theme")] {
+        client
+            .send(ClientMessage::KvGet {
+                request_id,
+                bucket: "test_kv_roundtrip".to_string(),
+                key: key.to_string(),
+            })
+            .await;
+        match client.recv().await {
+            Some(ServerMessage::KvEntry { value, .. }) => assert_eq!(value, None),
+            other => panic!("Expected KvEntry, got: {:?}", other),
+        }
+    }
+
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_kv_watch_reports_current_value_and_changes() {
+    let nats = get_nats().await;
+    nats.create_bucket("test_kv_watch").await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut writer = TestClient::connect(&gateway.ws_url()).await;
+    writer
+        .auth(&kv_token("user-writer"))
+        .await
+        .expect("Auth should succeed");
+    let mut watcher = TestClient::connect(&gateway.ws_url()).await;
+    watcher
+        .auth(&create_kv_token(
+            "user-watcher",
+            vec!["kv_read".into()],
+            vec!["test_kv_watch.config.>".into()],
+            vec!["test_kv_watch.config.secrets.>".into()],
+        ))
+        .await
+        .expect("Auth should succeed");
+
+    let put = |request_id, key: &str, value: &[u8]| ClientMessage::KvPut {
+        request_id,
+        bucket: "test_kv_watch".to_string(),
+        key: key.to_string(),
+        value: value.to_vec(),
+    };
+    writer.send(put(1, "config.theme", b"light")).await;
+    assert!(matches!(
+        writer.recv().await,
+        Some(ServerMessage::KvPutOk { .. })
+    ));
+
+    watcher
+        .send(ClientMessage::KvWatch {
+            id: 1,
+            bucket: "test_kv_watch".to_string(),
+            key: "config.>".to_string(),
+        })
+        .await;
+    match watcher.recv().await {
+        Some(ServerMessage::SubscribeOk { id }) => assert_eq!(id, 1),
+        other => panic!("Expected SubscribeOk, got: {:?}", other),
+    }
+
+    // The current value comes first
+    match watcher.recv().await {
+        Some(ServerMessage::KvUpdate {
+            key,
+            value,
+            operation,
+            ..
+        }) => {
+            assert_eq!(key, "config.theme");
+            assert_eq!(value, b"light");
+            assert_eq!(operation, KvOperation::Put);
+        }
+        other => panic!("Expected KvUpdate, got: {:?}", other),
+    }
+
+    // A change to a denied key is not reported
+    writer.send(put(2, "config.secrets.db", b"hunter2")).await;
+    assert!(matches!(
+        writer.recv().await,
+        Some(ServerMessage::KvPutOk { .. })
+    ));
+    writer.send(put(3, "config.theme", b"dark")).await;
+    assert!(matches!(
+        writer.recv().await,
+        Some(ServerMessage::KvPutOk { .. })
+    ));
+    match watcher.recv().await {
+        Some(ServerMessage::KvUpdate { key, value, .. }) => {
+            assert_eq!(key, "config.theme");
+            assert_eq!(value, b"dark");
+        }
+        other => panic!("Expected KvUpdate, got: {:?}", other),
+    }
+
+    writer
+        .send(ClientMessage::KvDelete {
+            request_id: 4,
+            bucket: "test_kv_watch".to_string(),
+            key: "config.theme".to_string(),
+        })
+        .await;
+    match watcher.recv().await {
+        Some(ServerMessage::KvUpdate { key, operation, .. }) => {
+            assert_eq!(key, "config.theme");
+            assert_eq!(operation, KvOperation::Delete);
+        }
+        other => panic!("Expected KvUpdate, got: {:?}", other),
+    }
+
+    writer.close().await;
+    watcher.close().await;
+}
+
+#[tokio::test]
+async fn test_kv_permissions() {
+    let nats = get_nats().await;
+    nats.create_bucket("test_kv_permissions").await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_kv_token(
+            "user-reader",
+            vec!["kv_read".into()],
+            vec!["test_kv_permissions.public.*".into()],
+            vec![],
+        ))
+        .await
+        .expect("Auth should succeed");
+
+    // Reading is allowed, but only within the allowed keys
+    let get = |request_id, key: &str| ClientMessage::KvGet {
+        request_id,
+        bucket: "test_kv_permissions".to_string(),
+        key: key.to_string(),
+    };
+    client.send(get(1, "public.motd")).await;
+    assert!(matches!(
+        client.recv().await,
+        Some(ServerMessage::KvEntry { .. })
+    ));
+    client.send(get(2, "private.motd")).await;
+    match client.recv().await {
+        Some(ServerMessage::RequestError {
+            request_id, code, ..
+        }) => {
+            assert_eq!(request_id, 2);
+            assert_eq!(code, ErrorCode::PermissionDenied);
+        }
+        other => panic!("Expected RequestError, got: {:?}", other),
+    }
+
+    // Writing needs kv_write
+    client
+        .send(ClientMessage::KvPut {
+            request_id: 3,
+            bucket: "test_kv_permissions".to_string(),
+            key: "public.motd".to_string(),
+            value: b"hello".to_vec(),
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::RequestError {
+            request_id, code, ..
+        }) => {
+            assert_eq!(request_id, 3);
+            assert_eq!(code, ErrorCode::PermissionDenied);
+        }
+        other => panic!("Expected RequestError, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_kv_unknown_bucket() {
+    let nats = get_nats().await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&kv_token("user-kv"))
+        .await
+        .expect("Auth should succeed");
+
+    client
+        .send(ClientMessage::KvGet {
+            request_id: 1,
+            bucket: "test_kv_missing".to_string(),
+            key: "theme".to_string(),
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::RequestError {
+            request_id, code, ..
+        }) => {
+            assert_eq!(request_id, 1);
+            assert_eq!(code, ErrorCode::NotFound);
+        }
+        other => panic!("Expected RequestError, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+// ============================================================================
+// Object Transfer Tests
+// ============================================================================
+
+/// Token allowed to read and write every object
+fn object_token(subject: &str) -> String {
+    create_object_token(
+        subject,
+        vec!["object_read".into(), "object_write".into()],
+        vec![],
+        vec![],
+    )
+}
+
+fn sha256(data: &[u8]) -> Vec<u8> {
+    ring::digest::digest(&ring::digest::SHA256, data)
+        .as_ref()
+        .to_vec()
+}
+
+/// Send `data` in chunks of `chunk_size` starting at `offset`, expecting
+/// progress after each chunk but the last
+async fn upload_chunks(
+    client: &mut TestClient,
+    request_id: u64,
+    data: &[u8],
+    offset: usize,
+    chunk_size: usize,
+) -> Option<ServerMessage> {
+    let mut position = offset;
+    loop {
+        let end = (position + chunk_size).min(data.len());
+        client
+            .send(ClientMessage::ObjectUploadChunk {
+                request_id,
+                offset: position as u64,
+                data: data[position..end].to_vec(),
+            })
+            .await;
+        let reply = client.recv().await;
+        position = end;
+        if position == data.len() {
+            return reply;
+        }
+        match reply {
+            Some(ServerMessage::ObjectUploadProgress { offset, .. }) => {
+                assert_eq!(offset, position as u64)
+            }
+            other => panic!("Expected ObjectUploadProgress, got: {:?}", other),
+        }
+    }
+}
+
+/// Download an object from `offset`, returning the bytes and final digest
+async fn download(
+    client: &mut TestClient,
+    request_id: u64,
+    bucket: &str,
+    name: &str,
+    offset: u64,
+) -> (u64, Vec<u8>, Vec<u8>) {
+    client
+        .send(ClientMessage::ObjectDownload {
+            request_id,
+            bucket: bucket.to_string(),
+            name: name.to_string(),
+            offset,
+        })
+        .await;
+    let size = match client.recv().await {
+        Some(ServerMessage::ObjectMeta { size, .. }) => size,
+        other => panic!("Expected ObjectMeta, got: {:?}", other),
+    };
+
+    let mut data = Vec::new();
+    loop {
+        match client.recv().await {
+            Some(ServerMessage::ObjectChunk {
+                request_id: id,
+                offset: chunk_offset,
+                data: chunk,
+            }) => {
+                assert_eq!(id, request_id);
+                assert_eq!(chunk_offset, offset + data.len() as u64);
+                data.extend_from_slice(&chunk);
+            }
+            Some(ServerMessage::ObjectDownloadEnd { sha256, .. }) => {
+                return (size, data, sha256);
+            }
+            other => panic!("Expected ObjectChunk, got: {:?}", other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_object_upload_download_roundtrip() {
+    let nats = get_nats().await;
+    nats.create_object_store("test_objects_roundtrip").await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&object_token("user-objects"))
+        .await
+        .expect("Auth should succeed");
+
+    // Larger than one download chunk, so it comes back in pieces too
+    let data: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+    client
+        .send(ClientMessage::ObjectUploadStart {
+            request_id: 1,
+            bucket: "test_objects_roundtrip".to_string(),
+            name: "reports.2024.bin".to_string(),
+            size: data.len() as u64,
+            sha256: sha256(&data),
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::ObjectUploadProgress { request_id, offset }) => {
+            assert_eq!(request_id, 1);
+            assert_eq!(offset, 0);
+        }
+        other => panic!("Expected ObjectUploadProgress, got: {:?}", other),
+    }
+    match upload_chunks(&mut client, 1, &data, 0, 30_000).await {
+        Some(ServerMessage::ObjectUploadOk { request_id, size }) => {
+            assert_eq!(request_id, 1);
+            assert_eq!(size, data.len() as u64);
+        }
+        other => panic!("Expected ObjectUploadOk, got: {:?}", other),
+    }
+
+    let (size, downloaded, digest) = download(
+        &mut client,
+        2,
+        "test_objects_roundtrip",
+        "reports.2024.bin",
+        0,
+    )
+    .await;
+    assert_eq!(size, data.len() as u64);
+    assert_eq!(downloaded, data);
+    assert_eq!(digest, sha256(&data));
+
+    // Resuming from an offset sends only the rest, but the digest still
+    // covers the whole object
+    let (_, rest, digest) = download(
+        &mut client,
+        3,
+        "test_objects_roundtrip",
+        "reports.2024.bin",
+        150_000,
+    )
+    .await;
+    assert_eq!(rest, data[150_000..]);
+    assert_eq!(digest, sha256(&data));
+
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_object_upload_resumes_from_offset() {
+    let nats = get_nats().await;
+    nats.create_object_store("test_objects_resume").await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&object_token("user-objects"))
+        .await
+        .expect("Auth should succeed");
+
+    let data = vec![7u8; 50_000];
+    let start = |request_id| ClientMessage::ObjectUploadStart {
+        request_id,
+        bucket: "test_objects_resume".to_string(),
+        name: "blob".to_string(),
+        size: data.len() as u64,
+        sha256: sha256(&data),
+    };
+    client.send(start(1)).await;
+    assert!(matches!(
+        client.recv().await,
+        Some(ServerMessage::ObjectUploadProgress { offset: 0, .. })
+    ));
+    client
+        .send(ClientMessage::ObjectUploadChunk {
+            request_id: 1,
+            offset: 0,
+            data: data[..20_000].to_vec(),
+        })
+        .await;
+    assert!(matches!(
+        client.recv().await,
+        Some(ServerMessage::ObjectUploadProgress { offset: 20_000, .. })
+    ));
+
+    // A chunk at the wrong offset is refused without losing the upload
+    client
+        .send(ClientMessage::ObjectUploadChunk {
+            request_id: 1,
+            offset: 40_000,
+            data: data[40_000..].to_vec(),
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::RequestError { code, .. }) => {
+            assert_eq!(code, ErrorCode::InvalidMessage)
+        }
+        other => panic!("Expected RequestError, got: {:?}", other),
+    }
+
+    // Starting the same upload again picks it up where it stopped
+    client.send(start(2)).await;
+    match client.recv().await {
+        Some(ServerMessage::ObjectUploadProgress { request_id, offset }) => {
+            assert_eq!(request_id, 2);
+            assert_eq!(offset, 20_000);
+        }
+        other => panic!("Expected ObjectUploadProgress, got: {:?}", other),
+    }
+    match upload_chunks(&mut client, 2, &data, 20_000, 20_000).await {
+        Some(ServerMessage::ObjectUploadOk { size, .. }) => assert_eq!(size, 50_000),
+        other => panic!("Expected ObjectUploadOk, got: {:?}", other),
+    }
+
+    let (_, downloaded, _) = download(&mut client, 3, "test_objects_resume", "blob", 0).await;
+    assert_eq!(downloaded, data);
+
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_object_upload_digest_mismatch() {
+    let nats = get_nats().await;
+    nats.create_object_store("test_objects_digest").await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&object_token("user-objects"))
+        .await
+        .expect("Auth should succeed");
+
+    // A valid first version
+    let original = b"the first version".to_vec();
+    client
+        .send(ClientMessage::ObjectUploadStart {
+            request_id: 1,
+            bucket: "test_objects_digest".to_string(),
+            name: "tampered".to_string(),
+            size: original.len() as u64,
+            sha256: sha256(&original),
+        })
+        .await;
+    assert!(matches!(
+        client.recv().await,
+        Some(ServerMessage::ObjectUploadProgress { .. })
+    ));
+    assert!(matches!(
+        upload_chunks(&mut client, 1, &original, 0, original.len()).await,
+        Some(ServerMessage::ObjectUploadOk { .. })
+    ));
+
+    let data = b"the real contents".to_vec();
+    client
+        .send(ClientMessage::ObjectUploadStart {
+            request_id: 2,
+            bucket: "test_objects_digest".to_string(),
+            name: "tampered".to_string(),
+            size: data.len() as u64,
+            sha256: sha256(b"something else"),
+        })
+        .await;
+    assert!(matches!(
+        client.recv().await,
+        Some(ServerMessage::ObjectUploadProgress { .. })
+    ));
+    match upload_chunks(&mut client, 2, &data, 0, data.len()).await {
+        Some(ServerMessage::RequestError {
+            request_id, code, ..
+        }) => {
+            assert_eq!(request_id, 2);
+            assert_eq!(code, ErrorCode::InvalidMessage);
+        }
+        other => panic!("Expected RequestError, got: {:?}", other),
+    }
+
+    // The failed upload was not kept, and the first version survived it
+    let (_, downloaded, digest) =
+        download(&mut client, 3, "test_objects_digest", "tampered", 0).await;
+    assert_eq!(downloaded, original);
+    assert_eq!(digest, sha256(&original));
+
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_object_permissions() {
+    let nats = get_nats().await;
+    nats.create_object_store("test_objects_permissions").await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_object_token(
+            "user-reader",
+            vec!["object_read".into()],
+            vec!["test_objects_permissions.public.>".into()],
+            vec![],
+        ))
+        .await
+        .expect("Auth should succeed");
+
+    // Writing needs object_write
+    client
+        .send(ClientMessage::ObjectUploadStart {
+            request_id: 1,
+            bucket: "test_objects_permissions".to_string(),
+            name: "public.readme".to_string(),
+            size: 1,
+            sha256: sha256(b"x"),
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::RequestError {
+            request_id, code, ..
+        }) => {
+            assert_eq!(request_id, 1);
+            assert_eq!(code, ErrorCode::PermissionDenied);
+        }
+        other => panic!("Expected RequestError, got: {:?}", other),
+    }
+
+    // Reading is limited to the allowed names
+    client
+        .send(ClientMessage::ObjectDownload {
+            request_id: 2,
+            bucket: "test_objects_permissions".to_string(),
+            name: "private.keys".to_string(),
+            offset: 0,
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::RequestError {
+            request_id, code, ..
+        }) => {
+            assert_eq!(request_id, 2);
+            assert_eq!(code, ErrorCode::PermissionDenied);
+        }
+        other => panic!("Expected RequestError, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_object_staging_names_reserved() {
+    let nats = get_nats().await;
+    nats.create_object_store("test_objects_staging").await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&object_token("user-staging"))
+        .await
+        .expect("Auth should succeed");
+
+    client
+        .send(ClientMessage::ObjectUploadStart {
+            request_id: 1,
+            bucket: "test_objects_staging".to_string(),
+            name: "_upload/report.pdf".to_string(),
+            size: 1,
+            sha256: sha256(b"x"),
+        })
+        .await;
+    client
+        .send(ClientMessage::ObjectDownload {
+            request_id: 2,
+            bucket: "test_objects_staging".to_string(),
+            name: "_upload/report.pdf".to_string(),
+            offset: 0,
+        })
+        .await;
+    for expected in [1, 2] {
+        match client.recv().await {
+            Some(ServerMessage::RequestError {
+                request_id, code, ..
+            }) => {
+                assert_eq!(request_id, expected);
+                assert_eq!(code, ErrorCode::InvalidMessage);
+            }
+            other => panic!("Expected RequestError, got: {:?}", other),
+        }
+    }
+
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_object_uploads_per_connection_limited() {
+    let nats = get_nats().await;
+    nats.create_object_store("test_objects_limit").await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&object_token("user-uploads"))
+        .await
+        .expect("Auth should succeed");
+
+    // Eight uploads may be in progress at once
+    for request_id in 1..=9 {
+        client
+            .send(ClientMessage::ObjectUploadStart {
+                request_id,
+                bucket: "test_objects_limit".to_string(),
+                name: format!("file-{}", request_id),
+                size: 2,
+                sha256: sha256(b"xy"),
+            })
+            .await;
+        match client.recv().await {
+            Some(ServerMessage::ObjectUploadProgress { offset, .. }) if request_id <= 8 => {
+                assert_eq!(offset, 0)
+            }
+            Some(ServerMessage::RequestError { code, .. }) if request_id == 9 => {
+                assert_eq!(code, ErrorCode::QuotaExceeded)
+            }
+            other => panic!("Unexpected reply to upload {}: {:?}", request_id, other),
+        }
+    }
+
+    // Finishing one makes room for another
+    let reply = upload_chunks(&mut client, 1, b"xy", 0, 2).await;
+    assert!(
+        matches!(reply, Some(ServerMessage::ObjectUploadOk { .. })),
+        "Expected ObjectUploadOk, got: {:?}",
+        reply
+    );
+    client
+        .send(ClientMessage::ObjectUploadStart {
+            request_id: 9,
+            bucket: "test_objects_limit".to_string(),
+            name: "file-9".to_string(),
+            size: 2,
+            sha256: sha256(b"xy"),
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::ObjectUploadProgress { request_id, .. }) => assert_eq!(request_id, 9),
+        other => panic!("Expected ObjectUploadProgress, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+// ============================================================================
+// NATS Connection Tests
+// ============================================================================
+
+/// Start a gateway connected with `nats` and check it relays a message
+/// between its clients
+async fn assert_gateway_relays(nats: NatsConfig, test_name: &str) {
+    let mut config = GatewayConfig::for_test(0, "", TEST_JWT_SECRET);
+    config.nats = nats;
+    let gateway = TestGateway::start_with_config(config).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_valid_token("user-nats-auth"))
+        .await
+        .expect("Auth should succeed");
+
+    let subject = test_subject(test_name, "events");
+    client
+        .subscribe(&subject, 1)
+        .await
+        .expect("Subscribe should succeed");
+    client.publish(&subject, b"relayed").await;
+    match client.recv().await {
+        Some(ServerMessage::Message { payload, .. }) => assert_eq!(payload, b"relayed"),
+        other => panic!("Expected Message, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_nats_user_password_auth() {
+    let nats = DedicatedNats::start(
+        &["--jetstream", "--user", "gateway", "--pass", "s3cret"],
+        vec![],
+    )
+    .await;
+
+    let mut config = NatsConfig::new(nats.url());
+    config.auth = NatsAuth::UserPassword {
+        user: "gateway".to_string(),
+        password: "wrong".to_string(),
+    };
+    assert!(NatsBridge::connect(&config).await.is_err());
+
+    config.auth = NatsAuth::UserPassword {
+        user: "gateway".to_string(),
+        password: "s3cret".to_string(),
+    };
+    assert_gateway_relays(config, "test_nats_user_password").await;
+}
+
+#[tokio::test]
+async fn test_nats_token_auth() {
+    let nats = DedicatedNats::start(&["--jetstream", "--auth", "t0ken"], vec![]).await;
+
+    let mut config = NatsConfig::new(nats.url());
+    assert!(NatsBridge::connect(&config).await.is_err());
+
+    config.auth = NatsAuth::Token("t0ken".to_string());
+    assert_gateway_relays(config, "test_nats_token").await;
+}
+
+#[tokio::test]
+async fn test_nats_nkey_auth() {
+    let user = nkeys::KeyPair::new_user();
+    let server_config = format!(
+        "jetstream {{}}\nauthorization {{ users = [ {{ nkey: \"{}\" }} ] }}\n",
+        user.public_key()
+    );
+    let nats = DedicatedNats::start(
+        &["-c", "/etc/nats/nkey.conf"],
+        vec![("/etc/nats/nkey.conf", server_config.into_bytes())],
+    )
+    .await;
+
+    let mut config = NatsConfig::new(nats.url());
+    config.auth = NatsAuth::NKey(nkeys::KeyPair::new_user().seed().unwrap());
+    assert!(NatsBridge::connect(&config).await.is_err());
+
+    config.auth = NatsAuth::NKey(user.seed().unwrap());
+    assert_gateway_relays(config, "test_nats_nkey").await;
+}
+
+#[tokio::test]
+async fn test_nats_mutual_tls() {
+    use rcgen::{BasicConstraints, CertificateParams, ExtendedKeyUsagePurpose, IsCa, KeyPair};
+
+    let ca_key = KeyPair::generate().unwrap();
+    let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    let ca = ca_params.self_signed(&ca_key).unwrap();
+
+    let issue = |purpose: ExtendedKeyUsagePurpose| {
+        let key = KeyPair::generate().unwrap();
+        let mut params =
+            CertificateParams::new(vec!["localhost".to_string(), "127.0.0.1".to_string()]).unwrap();
+        params.extended_key_usages = vec![purpose];
+        let cert = params.signed_by(&key, &ca, &ca_key).unwrap();
+        (cert.pem(), key.serialize_pem())
+    };
+    let (server_cert, server_key) = issue(ExtendedKeyUsagePurpose::ServerAuth);
+    let (client_cert, client_key) = issue(ExtendedKeyUsagePurpose::ClientAuth);
+
+    let server_config = "jetstream {}\n\
+        tls {\n\
+          cert_file: \"/etc/nats/server.pem\"\n\
+          key_file: \"/etc/nats/server-key.pem\"\n\
+          ca_file: \"/etc/nats/ca.pem\"\n\
+          verify: true\n\
+        }\n";
+    let nats = DedicatedNats::start(
+        &["-c", "/etc/nats/tls.conf"],
+        vec![
+            ("/etc/nats/tls.conf", server_config.as_bytes().to_vec()),
+            ("/etc/nats/server.pem", server_cert.into_bytes()),
+            ("/etc/nats/server-key.pem", server_key.into_bytes()),
+            ("/etc/nats/ca.pem", ca.pem().into_bytes()),
+        ],
+    )
+    .await;
+
+    // The client side reads its certificates from files
+    let dir = std::env::temp_dir().join(format!("mottomesh-nats-tls-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("ca.pem"), ca.pem()).unwrap();
+    std::fs::write(dir.join("client.pem"), client_cert).unwrap();
+    std::fs::write(dir.join("client-key.pem"), client_key).unwrap();
+
+    let mut config = NatsConfig::new(nats.url());
+    config.tls.required = true;
+    config.tls.ca_path = Some(dir.join("ca.pem"));
+    assert!(
+        NatsBridge::connect(&config).await.is_err(),
+        "The server should refuse a client without a certificate"
+    );
+
+    config.tls.client_cert = Some((dir.join("client.pem"), dir.join("client-key.pem")));
+    assert_gateway_relays(config, "test_nats_tls").await;
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn test_nats_seed_servers() {
+    let nats = get_nats().await;
+
+    // A seed that is down is skipped in favour of the next one
+    let mut config = NatsConfig::new(&format!("127.0.0.1:1,{}", nats.url()));
+    config.name = Some("gateway-seed-test".to_string());
+    config.max_reconnects = Some(3);
+    assert_gateway_relays(config, "test_nats_seeds").await;
+}
+
+#[tokio::test]
+async fn test_nats_outage_reported_and_recovered() {
+    let nats = DedicatedNats::start(&["--jetstream"], vec![]).await;
+    let mut config = GatewayConfig::for_test(0, nats.url(), TEST_JWT_SECRET);
+    config.nats.reconnect_delay_max_ms = 200;
+    let gateway = TestGateway::start_with_config(config).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_valid_token("user-nats-outage"))
+        .await
+        .expect("Auth should succeed");
+    let subject = test_subject("test_nats_outage", "events");
+    client
+        .subscribe(&subject, 1)
+        .await
+        .expect("Subscribe should succeed");
+    assert_eq!(gateway.health().await, (200, "OK".to_string()));
+
+    nats.stop().await;
+    match client.recv_timeout(Duration::from_secs(10)).await {
+        Some(ServerMessage::BackendStatus { available }) => assert!(!available),
+        other => panic!("Expected BackendStatus, got: {:?}", other),
+    }
+    assert_eq!(gateway.health().await.0, 503);
+
+    // Publishing fails fast instead of waiting on the connection
+    client.publish(&subject, b"lost").await;
+    match client.recv().await {
+        Some(ServerMessage::Error { code, .. }) => {
+            assert_eq!(code, ErrorCode::BackendUnavailable)
+        }
+        other => panic!("Expected Error, got: {:?}", other),
+    }
+
+    // A client connecting during the outage is told right after auth
+    let mut late = TestClient::connect(&gateway.ws_url()).await;
+    late.auth(&create_valid_token("user-nats-outage-late"))
+        .await
+        .expect("Auth should succeed");
+    match late.recv().await {
+        Some(ServerMessage::BackendStatus { available }) => assert!(!available),
+        other => panic!("Expected BackendStatus, got: {:?}", other),
+    }
+
+    nats.restart().await;
+    match client.recv_timeout(Duration::from_secs(10)).await {
+        Some(ServerMessage::BackendStatus { available }) => assert!(available),
+        other => panic!("Expected BackendStatus, got: {:?}", other),
+    }
+    assert_eq!(gateway.health().await, (200, "OK".to_string()));
+
+    // The subscription carries on without the client renewing it
+    client.publish(&subject, b"back").await;
+    match client.recv().await {
+        Some(ServerMessage::Message {
+            subscription_id,
+            payload,
+            ..
+        }) => {
+            assert_eq!(subscription_id, 1);
+            assert_eq!(payload, b"back");
+        }
+        other => panic!("Expected Message, got: {:?}", other),
+    }
+
+    client.close().await;
+    late.close().await;
+}
+
+// ============================================================================
+// Session Resumption Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_resume_session_after_drop() {
+    let nats = get_nats().await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    let mut publisher = TestClient::connect(&gateway.ws_url()).await;
+    let token = create_valid_token("user-resume");
+
+    let auth = client
+        .auth_resuming(&token, None)
+        .await
+        .expect("Auth should succeed");
+    assert!(!auth.resumed);
+    let resume_token = auth.resume_token.expect("Should get a resume token");
+    publisher
+        .auth(&create_valid_token("user-publisher"))
+        .await
+        .expect("Auth should succeed");
+
+    let subject = test_subject("test_resume", "events");
+    client
+        .subscribe(&subject, 1)
+        .await
+        .expect("Subscribe should succeed");
+
+    // Drop the connection without a close frame
+    drop(client);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    publisher.publish(&subject, b"while away").await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    let resumed = client
+        .auth_resuming(&token, Some(resume_token))
+        .await
+        .expect("Auth should succeed");
+    assert!(resumed.resumed, "Session should be resumed");
+    assert_eq!(resumed.session_id, auth.session_id);
+
+    match client.recv().await {
+        Some(ServerMessage::Message {
+            subscription_id,
+            payload,
+            seq,
+            ..
+        }) => {
+            assert_eq!(subscription_id, 1);
+            assert_eq!(payload, b"while away");
+            assert_eq!(seq, 1);
+        }
+        other => panic!("Expected buffered Message, got: {:?}", other),
+    }
+
+    publisher.publish(&subject, b"back").await;
+    match client.recv().await {
+        Some(ServerMessage::Message { payload, seq, .. }) => {
+            assert_eq!(payload, b"back");
+            assert_eq!(seq, 2);
+        }
+        other => panic!("Expected Message, got: {:?}", other),
+    }
+
+    client.close().await;
+    publisher.close().await;
+}
+
+#[tokio::test]
+async fn test_resume_requires_same_user() {
+    let nats = get_nats().await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    let auth = client
+        .auth_resuming(&create_valid_token("user-owner"), None)
+        .await
+        .expect("Auth should succeed");
+    drop(client);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut other = TestClient::connect(&gateway.ws_url()).await;
+    let result = other
+        .auth_resuming(&create_valid_token("user-other"), auth.resume_token)
+        .await
+        .expect("Auth should succeed");
+    assert!(!result.resumed, "Another user must not resume the session");
+    assert_ne!(result.session_id, auth.session_id);
+
+    other.close().await;
+}
+
+#[tokio::test]
+async fn test_clean_close_ends_session() {
+    let nats = get_nats().await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    let token = create_valid_token("user-clean-close");
+
+    let auth = client
+        .auth_resuming(&token, None)
+        .await
+        .expect("Auth should succeed");
+    client.close().await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    let result = client
+        .auth_resuming(&token, auth.resume_token)
+        .await
+        .expect("Auth should succeed");
+    assert!(!result.resumed, "A cleanly closed session is not resumable");
+
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_resume_disabled() {
+    let nats = get_nats().await;
+    let mut config = GatewayConfig::for_test(0, nats.url(), TEST_JWT_SECRET);
+    config.resume_grace_ms = 0;
+    let gateway = TestGateway::start_with_config(config).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    let auth = client
+        .auth_resuming(&create_valid_token("user-no-resume"), None)
+        .await
+        .expect("Auth should succeed");
+    assert!(auth.resume_token.is_none());
+
+    client.close().await;
+}
+
+// ============================================================================
+// Presence Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_presence_join_and_leave() {
+    let nats = get_nats().await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut watcher = TestClient::connect(&gateway.ws_url()).await;
+    let mut member = TestClient::connect(&gateway.ws_url()).await;
+
+    watcher
+        .auth(&create_valid_token("user-watcher"))
+        .await
+        .expect("Auth should succeed");
+    let member_session = member
+        .auth(&create_valid_token("user-member"))
+        .await
+        .expect("Auth should succeed");
+
+    let room = test_subject("test_presence", "room");
+    watcher
+        .send(ClientMessage::SubscribePresence {
+            id: 1,
+            pattern: format!("{}.>", test_subject_prefix("test_presence")),
+        })
+        .await;
+    match watcher.recv().await {
+        Some(ServerMessage::SubscribeOk { id }) => assert_eq!(id, 1),
+        other => panic!("Expected SubscribeOk, got: {:?}", other),
+    }
+
+    member
+        .subscribe(&room, 1)
+        .await
+        .expect("Subscribe should succeed");
+
+    match watcher.recv().await {
+        Some(ServerMessage::Presence {
+            id,
+            action,
+            member: presence_member,
+        }) => {
+            assert_eq!(id, 1);
+            assert_eq!(action, PresenceAction::Join);
+            assert_eq!(presence_member.session_id, member_session);
+            assert_eq!(presence_member.user_id, "user-member");
+            assert_eq!(presence_member.subject, room);
+        }
+        other => panic!("Expected Presence, got: {:?}", other),
+    }
+
+    member
+        .unsubscribe(1)
+        .await
+        .expect("Unsubscribe should succeed");
+
+    match watcher.recv().await {
+        Some(ServerMessage::Presence { action, .. }) => {
+            assert_eq!(action, PresenceAction::Leave);
+        }
+        other => panic!("Expected Presence, got: {:?}", other),
+    }
+
+    watcher.close().await;
+    member.close().await;
+}
+
+#[tokio::test]
+async fn test_presence_leave_on_disconnect() {
+    let nats = get_nats().await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut watcher = TestClient::connect(&gateway.ws_url()).await;
+    let mut member = TestClient::connect(&gateway.ws_url()).await;
+
+    watcher
+        .auth(&create_valid_token("user-watcher"))
+        .await
+        .expect("Auth should succeed");
+    member
+        .auth(&create_valid_token("user-member"))
+        .await
+        .expect("Auth should succeed");
+
+    let room = test_subject("test_presence_disconnect", "room");
+    member
+        .subscribe(&room, 1)
+        .await
+        .expect("Subscribe should succeed");
+
+    watcher
+        .send(ClientMessage::SubscribePresence {
+            id: 1,
+            pattern: room.clone(),
+        })
+        .await;
+    match watcher.recv().await {
+        Some(ServerMessage::SubscribeOk { id }) => assert_eq!(id, 1),
+        other => panic!("Expected SubscribeOk, got: {:?}", other),
+    }
+
+    member.close().await;
+
+    match watcher.recv().await {
+        Some(ServerMessage::Presence { action, .. }) => {
+            assert_eq!(action, PresenceAction::Leave);
+        }
+        other => panic!("Expected Presence, got: {:?}", other),
+    }
+
+    watcher.close().await;
+}
+
+#[tokio::test]
+async fn test_presence_query_across_gateways() {
+    let nats = get_nats().await;
+    let gateway_a = TestGateway::start(nats.url()).await;
+    let gateway_b = TestGateway::start(nats.url()).await;
+    let mut querier = TestClient::connect(&gateway_a.ws_url()).await;
+    let mut member = TestClient::connect(&gateway_b.ws_url()).await;
+
+    querier
+        .auth(&create_valid_token("user-querier"))
+        .await
+        .expect("Auth should succeed");
+    let member_session = member
+        .auth(&create_valid_token("user-member"))
+        .await
+        .expect("Auth should succeed");
+
+    let room = test_subject("test_presence_query", "room");
+    member
+        .subscribe(&room, 1)
+        .await
+        .expect("Subscribe should succeed");
+
+    // Let the join gossip reach the other gateway
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    querier
+        .send(ClientMessage::QueryPresence {
+            request_id: 1,
+            pattern: room.clone(),
+        })
+        .await;
+    match querier.recv().await {
+        Some(ServerMessage::PresenceList {
+            request_id,
+            members,
+        }) => {
+            assert_eq!(request_id, 1);
+            assert_eq!(members.len(), 1);
+            assert_eq!(members[0].session_id, member_session);
+            assert_eq!(members[0].subject, room);
+        }
+        other => panic!("Expected PresenceList, got: {:?}", other),
+    }
+
+    querier.close().await;
+    member.close().await;
+}
+
+#[tokio::test]
+async fn test_presence_requires_subscribe_permission() {
+    let nats = get_nats().await;
+    let gateway = TestGateway::start(nats.url()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    let allowed = test_subject("test_presence_perm", "allowed");
+    let token = create_limited_token("user-presence-limited", vec![allowed]);
+    client.auth(&token).await.expect("Auth should succeed");
+
+    client
+        .send(ClientMessage::QueryPresence {
+            request_id: 1,
+            pattern: test_subject("test_presence_perm", "denied"),
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::RequestError { code, .. }) => {
+            assert_eq!(code, ErrorCode::PermissionDenied);
+        }
+        other => panic!("Expected RequestError, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+async fn test_presence_hides_denied_and_other_tenants(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
+    let mut watcher = TestClient::connect(&gateway.ws_url()).await;
+    let mut member = TestClient::connect(&gateway.ws_url()).await;
+    let mut tenant_member = TestClient::connect(&gateway.ws_url()).await;
+
+    let open = test_subject("test_presence_visibility", "open");
+    let secret = test_subject("test_presence_visibility", "secret");
+    watcher
+        .auth(&create_token_with_deny(
+            "user-watcher",
+            vec![secret.clone()],
+        ))
+        .await
+        .expect("Auth should succeed");
+    let member_session = member
+        .auth(&create_valid_token("user-member"))
+        .await
+        .expect("Auth should succeed");
+    tenant_member
+        .auth(&create_tenant_token("user-tenant", "acme"))
+        .await
+        .expect("Auth should succeed");
+
+    member
+        .subscribe(&open, 1)
+        .await
+        .expect("Subscribe should succeed");
+    member
+        .subscribe(&secret, 2)
+        .await
+        .expect("Subscribe should succeed");
+    tenant_member
+        .subscribe(&open, 1)
+        .await
+        .expect("Subscribe should succeed");
+
+    // The wildcard is allowed, but only members the watcher could
+    // subscribe alongside, in its own tenant, are listed
+    watcher
+        .send(ClientMessage::QueryPresence {
+            request_id: 1,
+            pattern: format!("{}.*", test_subject_prefix("test_presence_visibility")),
+        })
+        .await;
+    match watcher.recv().await {
+        Some(ServerMessage::PresenceList { members, .. }) => {
+            assert_eq!(members.len(), 1, "Unexpected members: {:?}", members);
+            assert_eq!(members[0].session_id, member_session);
+            assert_eq!(members[0].subject, open);
+        }
+        other => panic!("Expected PresenceList, got: {:?}", other),
+    }
+
+    watcher.close().await;
+    member.close().await;
+    tenant_member.close().await;
+}
+
+backend_tests!(test_presence_hides_denied_and_other_tenants);
+
+// ============================================================================
+// Connection Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_drain_sends_go_away() {
+    let nats = get_nats().await;
+    let mut config = GatewayConfig::for_test(0, nats.url(), TEST_JWT_SECRET);
+    config.drain_grace_ms = 500;
+    config.reconnect_after_ms = 250;
+    config.alternate_url = Some("wss://other.example.com/ws".to_string());
+    let gateway = TestGateway::start_with_config(config).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    let token = create_valid_token("user-drain");
+    client.auth(&token).await.expect("Auth should succeed");
+
+    let subject = test_subject("test_drain", "events");
+    client
+        .subscribe(&subject, 1)
+        .await
+        .expect("Subscribe should succeed");
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    gateway.drain();
+
+    match client.recv().await {
+        Some(ServerMessage::GoAway {
+            reconnect_after_ms,
+            alternate_url,
+            ..
+        }) => {
+            assert_eq!(reconnect_after_ms, 250);
+            assert_eq!(alternate_url.as_deref(), Some("wss://other.example.com/ws"));
+        }
+        other => panic!("Expected GoAway, got: {:?}", other),
+    }
+
+    // Existing subscriptions keep delivering during the grace period
+    nats.publish(&subject, b"still here").await;
+    match client.recv().await {
+        Some(ServerMessage::Message { payload, .. }) => assert_eq!(payload, b"still here"),
+        other => panic!("Expected Message, got: {:?}", other),
+    }
+
+    // New subscriptions are refused
+    match client
+        .subscribe(&test_subject("test_drain", "late"), 2)
+        .await
+    {
+        Err(reason) => assert!(reason.contains("shutting down"), "{}", reason),
+        Ok(id) => panic!("Subscribe {} should be refused while draining", id),
+    }
+
+    // The gateway closes the connection once the grace period is over
+    assert!(
+        client.recv_timeout(Duration::from_secs(2)).await.is_none(),
+        "Connection should close after the grace period"
+    );
+}
+
+#[tokio::test]
+async fn test_memory_run_returns_once_drained() {
+    let port = portpicker::pick_unused_port().expect("No free port");
+    let mut config = GatewayConfig::for_test(port, "", TEST_JWT_SECRET);
+    config.drain_grace_ms = 30_000;
+    let jwt_validator = Arc::new(JwtValidator::new(TEST_JWT_SECRET).unwrap());
+    let gateway = Gateway::with_components(config, jwt_validator, Arc::new(MemoryBroker::new()));
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+    let run = tokio::spawn(gateway.run(shutdown_rx));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let mut client = TestClient::connect(&format!("ws://127.0.0.1:{}/ws", port)).await;
+    client.ping().await;
+    assert!(matches!(client.recv().await, Some(ServerMessage::Pong)));
+
+    shutdown_tx.send(()).unwrap();
+    match client.recv().await {
+        Some(ServerMessage::GoAway { .. }) => {}
+        other => panic!("Expected GoAway, got: {:?}", other),
+    }
+
+    // The client leaving ends the drain long before the grace period
+    client.close().await;
+    let result = tokio::time::timeout(Duration::from_secs(5), run)
+        .await
+        .expect("Gateway should stop once its connections closed");
+    assert_eq!(result.unwrap().unwrap(), port);
+}
+
+async fn test_ping_pong(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    // Ping should work without authentication
+    client.ping().await;
+
+    let response = client.recv().await;
+
+    match response {
+        Some(ServerMessage::Pong) => {
+            // Success!
+        }
+        other => panic!("Expected Pong, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+async fn test_multiple_clients(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
+
+    // Connect multiple clients
+    let mut client1 = TestClient::connect(&gateway.ws_url()).await;
+    let mut client2 = TestClient::connect(&gateway.ws_url()).await;
+    let mut client3 = TestClient::connect(&gateway.ws_url()).await;
+
+    // All should be able to authenticate
+    let token1 = create_valid_token("user-1");
+    let token2 = create_valid_token("user-2");
+    let token3 = create_valid_token("user-3");
+
+    let (r1, r2, r3) = tokio::join!(
+        client1.auth(&token1),
+        client2.auth(&token2),
+        client3.auth(&token3)
+    );
+
+    assert!(r1.is_ok(), "Client 1 auth failed: {:?}", r1);
+    assert!(r2.is_ok(), "Client 2 auth failed: {:?}", r2);
+    assert!(r3.is_ok(), "Client 3 auth failed: {:?}", r3);
+
+    // All should have different session IDs
+    let s1 = r1.unwrap();
+    let s2 = r2.unwrap();
+    let s3 = r3.unwrap();
+
+    assert_ne!(s1, s2, "Session IDs should be unique");
+    assert_ne!(s2, s3, "Session IDs should be unique");
+    assert_ne!(s1, s3, "Session IDs should be unique");
+
+    client1.close().await;
+    client2.close().await;
+    client3.close().await;
+}
+
+async fn test_client_disconnect_cleanup(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    // Authenticate and subscribe
+    let token = create_valid_token("user-disconnect");
+    client.auth(&token).await.expect("Auth should succeed");
+
+    let subject = test_subject("test_disconnect", "events");
+    client
+        .subscribe(&subject, 1)
+        .await
+        .expect("Subscribe should succeed");
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    // Close the client
+    client.close().await;
+
+    // Give time for cleanup
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // Publish a message - should not cause any errors
+    // (subscription should be cleaned up on disconnect)
+    backend.publish(&subject, b"After disconnect").await;
+
+    // If we get here without panicking, cleanup worked
+    // The gateway should have removed the subscription when the client disconnected
+}
+
+backend_tests!(
+    test_ping_pong,
+    test_multiple_clients,
+    test_client_disconnect_cleanup,
+);
+
+// ============================================================================
+// Permission Tests
+// ============================================================================
+
+async fn test_subscribe_with_limited_permissions(backend: Backend) {
+    let gateway = TestGateway::start_on(&backend, backend.config()).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+
+    // Create token that only allows specific subjects
+    let allowed_subject = test_subject("test_limited", "allowed");
+    let token = create_limited_token("user-limited", vec![allowed_subject.clone()]);
+    client.auth(&token).await.expect("Auth should succeed");
+
+    // Subscribe to allowed subject should work
+    let result = client.subscribe(&allowed_subject, 1).await;
+    assert!(
+        result.is_ok(),
+        "Subscribe to allowed subject should succeed: {:?}",
+        result
+    );
+
+    // Subscribe to disallowed subject should fail
+    let denied_subject = test_subject("test_limited", "denied");
+    client
+        .send(ClientMessage::Subscribe {
+            subject: denied_subject.clone(),
+            id: 2,
+            credits: None,
+            max_msgs: None,
+            replay: None,
+            slow_consumer: None,
+        })
+        .await;
+
+    let response = client.recv().await;
+
+    match response {
+        Some(ServerMessage::SubscribeError { id, code, reason }) => {
+            assert_eq!(id, 2);
+            assert_eq!(code, ErrorCode::PermissionDenied);
+            assert!(
+                reason.to_lowercase().contains("permission")
+                    || reason.to_lowercase().contains("denied")
+                    || reason.to_lowercase().contains("forbidden")
+                    || reason.to_lowercase().contains("allowed"),
+                "Error should mention permissions: {}",
+                reason
+            );
+        }
+        Some(ServerMessage::Error { code, message }) => {
+            assert_eq!(
+                code,
+                ErrorCode::PermissionDenied,
+                "Should get forbidden error: {}",
+                message
+            );
+        }
+        other => panic!("Expected error for denied subject, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+backend_tests!(test_subscribe_with_limited_permissions);
+
+// ============================================================================
+// Subject Mapping Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_subject_mapping_through_nats() {
+    let nats = get_nats().await;
+    let prefix = test_subject_prefix("test_subject_mapping");
+    let mut config = GatewayConfig::for_test(0, nats.url(), TEST_JWT_SECRET);
+    config.subject_mappings = SubjectMapping::parse_list(&format!(
+        "{prefix}.chat.{{room}}={prefix}.svc.rooms.{{room}}.msgs"
+    ))
+    .unwrap();
+    let gateway = TestGateway::start_with_config(config).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_valid_token("user-mapping"))
+        .await
+        .expect("Auth should succeed");
+
+    // Client subscriptions are served from the mapped NATS subjects
+    client
+        .subscribe(&format!("{prefix}.chat.*"), 1)
+        .await
+        .expect("Subscribe should succeed");
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    nats.publish(&format!("{prefix}.svc.rooms.lobby.msgs"), b"from backend")
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::Message {
+            subject, payload, ..
+        }) => {
+            assert_eq!(subject, format!("{prefix}.chat.lobby"));
+            assert_eq!(payload, b"from backend");
+        }
+        other => panic!("Expected Message, got: {:?}", other),
+    }
+
+    // Client publishes land on the mapped NATS subjects
+    let mut backend = nats.subscribe(&format!("{prefix}.svc.rooms.*.msgs")).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    client
+        .publish(&format!("{prefix}.chat.kitchen"), b"from client")
+        .await;
+    let msg = tokio::time::timeout(Duration::from_secs(5), backend.next())
+        .await
+        .expect("Timed out waiting for the published message")
+        .expect("Subscription ended");
+    assert_eq!(
+        msg.subject.as_str(),
+        format!("{prefix}.svc.rooms.kitchen.msgs")
+    );
+
+    client.close().await;
+}
+
+#[tokio::test]
+async fn test_subject_mapping_for_streams() {
+    let nats = get_nats().await;
+    let prefix = test_subject_prefix("test_subject_mapping_streams");
+    nats.create_stream(
+        "TEST_SUBJECT_MAPPING_STREAMS",
+        vec![format!("{prefix}.svc.>")],
+    )
+    .await;
+    let mut config = GatewayConfig::for_test(0, nats.url(), TEST_JWT_SECRET);
+    config.subject_mappings = SubjectMapping::parse_list(&format!(
+        "{prefix}.chat.{{room}}={prefix}.svc.rooms.{{room}}.msgs"
+    ))
+    .unwrap();
+    let gateway = TestGateway::start_with_config(config).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_valid_token("user-mapping-streams"))
+        .await
+        .expect("Auth should succeed");
+
+    // Durable publishes are stored under the mapped subject
+    client
+        .send(ClientMessage::JetStreamPublish {
+            request_id: 1,
+            subject: format!("{prefix}.chat.lobby"),
+            payload: b"stored".to_vec(),
+            msg_id: None,
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::PubAck { stream, seq, .. }) => {
+            assert_eq!(stream, "TEST_SUBJECT_MAPPING_STREAMS");
+            assert_eq!(seq, 1);
+        }
+        other => panic!("Expected PubAck, got: {:?}", other),
+    }
+
+    // The consumer filter is mapped too, and deliveries map back
+    subscribe_consumer(
+        &mut client,
+        "TEST_SUBJECT_MAPPING_STREAMS",
+        "ui",
+        &format!("{prefix}.chat.*"),
+    )
+    .await;
+    match client.recv().await {
+        Some(ServerMessage::ConsumerMessage {
+            subject, payload, ..
+        }) => {
+            assert_eq!(subject, format!("{prefix}.chat.lobby"));
+            assert_eq!(payload, b"stored");
+        }
+        other => panic!("Expected ConsumerMessage, got: {:?}", other),
+    }
+
+    client.close().await;
+}
+
+// ============================================================================
+// Identity Header Tests
+// ============================================================================
+
+/// Identity headers of a message received from NATS
+fn identity_headers(msg: &async_nats::Message) -> Headers {
+    let mut headers = Headers::default();
+    let Some(received) = &msg.headers else {
+        return headers;
+    };
+    for name in [
+        USER_HEADER,
+        SESSION_HEADER,
+        TENANT_HEADER,
+        CLIENT_IP_HEADER,
+        GATEWAY_HEADER,
+        TIMESTAMP_HEADER,
+        SIGNATURE_HEADER,
+    ] {
+        if let Some(value) = received.get(name) {
+            headers.insert(name, value.as_str());
+        }
+    }
+    headers
+}
+
+#[tokio::test]
+async fn test_identity_headers_on_publish_and_request() {
+    let nats = get_nats().await;
+    let mut config = GatewayConfig::for_test(0, nats.url(), TEST_JWT_SECRET);
+    config.identity_signing_key = Some("identity-key".to_string());
+    let gateway = TestGateway::start_with_config(config).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_tenant_token("user-identity", "acme"))
+        .await
+        .expect("Auth should succeed");
+
+    let subject = test_subject("test_identity_headers", "events");
+    let mut subscriber = nats.subscribe(&format!("{}.>", subject)).await;
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    client
+        .publish(&format!("{}.published", subject), b"hello")
+        .await;
+    client
+        .send(ClientMessage::Request {
+            subject: format!("{}.requested", subject),
+            payload: b"ping".to_vec(),
+            timeout_ms: 500,
+            request_id: 1,
+            stream: false,
+        })
+        .await;
+
+    let signer = IdentitySigner::new(b"identity-key");
+    let mut sessions = Vec::new();
+    for expected in ["published", "requested"] {
+        let msg = tokio::time::timeout(Duration::from_secs(5), subscriber.next())
+            .await
+            .expect("Timed out waiting for the relayed message")
+            .expect("Subscription ended");
+        assert!(msg.subject.ends_with(expected));
+
+        let headers = identity_headers(&msg);
+        assert_eq!(headers.get(USER_HEADER), Some("user-identity"));
+        assert_eq!(headers.get(TENANT_HEADER), Some("acme"));
+        assert_eq!(headers.get(CLIENT_IP_HEADER), Some("127.0.0.1"));
+        assert!(headers.get(GATEWAY_HEADER).is_some());
+        sessions.push(headers.get(SESSION_HEADER).unwrap().to_string());
+
+        // Services holding the key can check the metadata
+        let signature = headers
+            .get(SIGNATURE_HEADER)
+            .expect("Message should be signed");
+        assert_eq!(signer.sign(&msg.subject, &headers, &msg.payload), signature);
+    }
+    assert_eq!(sessions[0], sessions[1]);
+
+    client.close().await;
+}
+
+// ============================================================================
+// In-Memory Broker Tests
+// ============================================================================
+// These run without NATS:
+// cargo test --test integration_test memory
+
+#[tokio::test]
+async fn test_memory_pub_sub_between_clients() {
+    let gateway = TestGateway::start_in_memory().await;
+    let mut publisher = TestClient::connect(&gateway.ws_url()).await;
+    let mut subscriber = TestClient::connect(&gateway.ws_url()).await;
+    publisher
+        .auth(&create_valid_token("user-memory-pub"))
+        .await
+        .expect("Auth should succeed");
+    subscriber
+        .auth(&create_valid_token("user-memory-sub"))
+        .await
+        .expect("Auth should succeed");
+
+    subscriber
+        .subscribe("memory.orders.>", 1)
+        .await
+        .expect("Subscribe should succeed");
+    publisher.publish("memory.orders.eu.42", b"placed").await;
+
+    match subscriber.recv().await {
+        Some(ServerMessage::Message {
+            subject, payload, ..
+        }) => {
+            assert_eq!(subject, "memory.orders.eu.42");
+            assert_eq!(payload, b"placed");
+        }
+        other => panic!("Expected Message, got: {:?}", other),
+    }
+
+    // Nothing is delivered once unsubscribed
+    subscriber
+        .unsubscribe(1)
+        .await
+        .expect("Unsubscribe should succeed");
+    publisher.publish("memory.orders.eu.43", b"placed").await;
+    assert!(
+        subscriber
+            .recv_timeout(Duration::from_millis(300))
+            .await
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn test_memory_request_reply() {
+    let gateway = TestGateway::start_in_memory().await;
+    let mut responder = gateway.broker.serve("memory.rpc.*");
+    tokio::spawn(async move {
+        while let Some(request) = responder.next().await {
+            let reply = format!("Echo: {}", String::from_utf8_lossy(&request.payload));
+            request.respond(reply.into_bytes()).await;
+        }
+    });
+
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_valid_token("user-memory-rpc"))
+        .await
+        .expect("Auth should succeed");
+
+    client
+        .send(ClientMessage::Request {
+            subject: "memory.rpc.echo".to_string(),
+            payload: b"Hello".to_vec(),
+            timeout_ms: 5000,
+            request_id: 1,
+            stream: false,
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::Response {
+            request_id,
+            payload,
+        }) => {
+            assert_eq!(request_id, 1);
+            assert_eq!(payload, b"Echo: Hello");
+        }
+        other => panic!("Expected Response, got: {:?}", other),
+    }
+
+    // No responder serves this subject
+    client
+        .send(ClientMessage::Request {
+            subject: "memory.other".to_string(),
+            payload: Vec::new(),
+            timeout_ms: 5000,
+            request_id: 2,
+            stream: false,
+        })
+        .await;
+    match client.recv_timeout(Duration::from_secs(2)).await {
+        Some(ServerMessage::RequestError {
+            request_id, code, ..
+        }) => {
+            assert_eq!(request_id, 2);
+            assert_eq!(code, ErrorCode::NoResponders);
+        }
+        other => panic!("Expected RequestError, got: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_memory_stream_request() {
+    let gateway = TestGateway::start_in_memory().await;
+    let mut responder = gateway.broker.serve("memory.list");
+    tokio::spawn(async move {
+        if let Some(request) = responder.next().await {
+            for i in 0..3 {
+                request.send_chunk(format!("part-{}", i).into_bytes()).await;
+            }
+            request.end().await;
+        }
+    });
+
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_valid_token("user-memory-stream"))
+        .await
+        .expect("Auth should succeed");
+    client
+        .send(ClientMessage::Request {
+            subject: "memory.list".to_string(),
+            payload: Vec::new(),
+            timeout_ms: 5000,
+            request_id: 3,
+            stream: true,
+        })
+        .await;
+
+    for expected_seq in 0..3u32 {
+        match client.recv().await {
+            Some(ServerMessage::ResponseChunk { seq, payload, .. }) => {
+                assert_eq!(seq, expected_seq);
+                assert_eq!(payload, format!("part-{}", expected_seq).into_bytes());
+            }
+            other => panic!("Expected ResponseChunk, got: {:?}", other),
+        }
+    }
+    match client.recv().await {
+        Some(ServerMessage::ResponseEnd { request_id }) => assert_eq!(request_id, 3),
+        other => panic!("Expected ResponseEnd, got: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_memory_unsupported_feature() {
+    let gateway = TestGateway::start_in_memory().await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&kv_token("user-memory-kv"))
+        .await
+        .expect("Auth should succeed");
+
+    client
+        .send(ClientMessage::KvGet {
+            request_id: 4,
+            bucket: "settings".to_string(),
+            key: "theme".to_string(),
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::RequestError {
+            request_id, code, ..
+        }) => {
+            assert_eq!(request_id, 4);
+            assert_eq!(code, ErrorCode::BackendUnavailable);
+        }
+        other => panic!("Expected RequestError, got: {:?}", other),
+    }
+
+    // The gateway reports itself ready without NATS
+    assert_eq!(gateway.health().await, (200, "OK".to_string()));
+}
+
+#[tokio::test]
+async fn test_memory_request_identity() {
+    let gateway = TestGateway::start_in_memory().await;
+    let mut responder = gateway.broker.serve("memory.whoami");
+    tokio::spawn(async move {
+        if let Some(request) = responder.next().await {
+            let reply = format!(
+                "{}/{}/{}",
+                request.headers.get(USER_HEADER).unwrap_or_default(),
+                request.headers.get(TENANT_HEADER).unwrap_or_default(),
+                request.headers.get(CLIENT_IP_HEADER).unwrap_or_default(),
+            );
+            request.respond(reply.into_bytes()).await;
+        }
+    });
+
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_tenant_token("user-memory-identity", "acme"))
+        .await
+        .expect("Auth should succeed");
+    client
+        .send(ClientMessage::Request {
+            subject: "memory.whoami".to_string(),
+            payload: Vec::new(),
+            timeout_ms: 5000,
+            request_id: 5,
+            stream: false,
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::Response { payload, .. }) => {
+            assert_eq!(payload, b"user-memory-identity/acme/127.0.0.1");
+        }
+        other => panic!("Expected Response, got: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_memory_subject_mapping() {
+    let mut config = GatewayConfig::for_test(0, "", TEST_JWT_SECRET);
+    config.subject_mappings = SubjectMapping::parse_list(
+        "chat.{room}=svc.chat.v2.rooms.{room}.msgs,rpc.{service}.>=svc.{service}.api.>",
+    )
+    .unwrap();
+    let gateway = TestGateway::start_in_memory_with_config(config).await;
+
+    // Backend services only ever see the NATS-side subjects
+    let mut responder = gateway.broker.serve("svc.*.api.>");
+    tokio::spawn(async move {
+        while let Some(request) = responder.next().await {
+            let reply = request.subject.clone().into_bytes();
+            request.respond(reply).await;
+        }
+    });
+
+    // Permissions are checked against the client-facing subjects
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_token(
+            "user-memory-mapping",
+            3600,
+            vec!["publish".into(), "subscribe".into(), "request".into()],
+            vec!["chat.*".into(), "rpc.>".into()],
+        ))
+        .await
+        .expect("Auth should succeed");
+
+    client
+        .subscribe("chat.*", 1)
+        .await
+        .expect("Subscribe should succeed");
+    client.publish("chat.lobby", b"hi").await;
+    match client.recv().await {
+        Some(ServerMessage::Message {
+            subject, payload, ..
+        }) => {
+            assert_eq!(subject, "chat.lobby");
+            assert_eq!(payload, b"hi");
+        }
+        other => panic!("Expected Message, got: {:?}", other),
+    }
+
+    client
+        .send(ClientMessage::Request {
+            subject: "rpc.billing.invoices.list".to_string(),
+            payload: Vec::new(),
+            timeout_ms: 5000,
+            request_id: 6,
+            stream: false,
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::Response { payload, .. }) => {
+            assert_eq!(payload, b"svc.billing.api.invoices.list");
+        }
+        other => panic!("Expected Response, got: {:?}", other),
+    }
+
+    // The NATS-side name of an allowed subject is not allowed itself
+    client
+        .send(ClientMessage::Subscribe {
+            subject: "svc.chat.v2.rooms.*.msgs".to_string(),
+            id: 2,
+            credits: None,
+            max_msgs: None,
+            replay: None,
+            slow_consumer: None,
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::SubscribeError { id, code, .. }) => {
+            assert_eq!(id, 2);
+            assert_eq!(code, ErrorCode::PermissionDenied);
+        }
+        other => panic!("Expected SubscribeError, got: {:?}", other),
+    }
+}
+
+/// Subscribe `client` with a slow-consumer policy of its choosing
+async fn subscribe_with_policy(
+    client: &mut TestClient,
+    subject: &str,
+    id: u64,
+    slow_consumer: Option<SlowConsumerPolicy>,
+) {
+    client
+        .send(ClientMessage::Subscribe {
+            subject: subject.to_string(),
+            id,
+            credits: None,
+            max_msgs: None,
+            replay: None,
+            slow_consumer,
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::SubscribeOk { id: ok }) => assert_eq!(ok, id),
+        other => panic!("Expected SubscribeOk, got: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_memory_max_msgs_skips_dropped_deliveries() {
+    let mut config = GatewayConfig::for_test(0, "", TEST_JWT_SECRET);
+    config.credit_buffer = 1;
+    config.overflow_policy = CreditOverflowPolicy::DropNewest;
+    let gateway = TestGateway::start_in_memory_with_config(config).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_valid_token("user-memory-limit"))
+        .await
+        .expect("Auth should succeed");
+
+    client
+        .send(ClientMessage::Subscribe {
+            subject: "limited".to_string(),
+            id: 1,
+            credits: Some(0),
+            max_msgs: Some(2),
+            replay: None,
+            slow_consumer: None,
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::SubscribeOk { id }) => assert_eq!(id, 1),
+        other => panic!("Expected SubscribeOk, got: {:?}", other),
+    }
+
+    // The first is buffered, the other two overflow and are dropped
+    for i in 1..=3u8 {
+        gateway
+            .broker
+            .publish("limited", &Headers::default(), vec![i])
+            .await
+            .unwrap();
+    }
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    client
+        .send(ClientMessage::GrantCredit { id: 1, credits: 1 })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::Message { payload, .. }) => assert_eq!(payload, vec![1]),
+        other => panic!("Expected Message, got: {:?}", other),
+    }
+
+    // Dropped deliveries did not count, so one more is still allowed
+    gateway
+        .broker
+        .publish("limited", &Headers::default(), vec![4])
+        .await
+        .unwrap();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    client
+        .send(ClientMessage::GrantCredit { id: 1, credits: 1 })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::Message {
+            payload, dropped, ..
+        }) => {
+            assert_eq!(payload, vec![4]);
+            assert_eq!(dropped, 2);
+        }
+        other => panic!("Expected Message, got: {:?}", other),
+    }
+    match client.recv().await {
+        Some(ServerMessage::SubscriptionEnded {
+            id,
+            reason,
+            total_dropped,
+        }) => {
+            assert_eq!(id, 1);
+            assert_eq!(reason, SubscriptionEndReason::LimitReached);
+            assert_eq!(total_dropped, 2);
+        }
+        other => panic!("Expected SubscriptionEnded, got: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_memory_slow_consumer_policies() {
+    let mut config = GatewayConfig::for_test(0, "", TEST_JWT_SECRET);
+    config.subscription_buffer = 4;
+    config.subject_slow_consumer = SubjectSlowConsumer::parse_list("ticks.>=drop-oldest").unwrap();
+    let gateway = TestGateway::start_in_memory_with_config(config).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_valid_token("user-memory-slow"))
+        .await
+        .expect("Auth should succeed");
+
+    // A chart feed conflates; the tick feed's policy comes from the config
+    subscribe_with_policy(
+        &mut client,
+        "prices.*",
+        1,
+        Some(SlowConsumerPolicy::Conflate),
+    )
+    .await;
+    subscribe_with_policy(&mut client, "ticks.>", 2, None).await;
+
+    // Publishing in memory never yields, so the whole burst is queued
+    // before the gateway gets to deliver any of it
+    for i in 1..=10 {
+        let payload = i.to_string().into_bytes();
+        for subject in ["prices.btc", "prices.eth", "ticks.a"] {
+            gateway
+                .broker
+                .publish(subject, &Headers::default(), payload.clone())
+                .await
+                .unwrap();
+        }
+    }
+
+    let mut prices = Vec::new();
+    let mut ticks = Vec::new();
+    while prices.len() + ticks.len() < 6 {
+        match client.recv().await {
+            Some(ServerMessage::Message {
+                subscription_id,
+                subject,
+                payload,
+                seq,
+                dropped,
+                ..
+            }) => {
+                let delivery = (subject, String::from_utf8(payload).unwrap(), seq, dropped);
+                match subscription_id {
+                    1 => prices.push(delivery),
+                    _ => ticks.push(delivery),
+                }
+            }
+            other => panic!("Expected Message, got: {:?}", other),
+        }
+    }
+
+    // Only the latest price per subject is left, and all 20 are accounted for
+    assert_eq!(
+        prices,
+        vec![
+            ("prices.btc".to_string(), "10".to_string(), 19, 18),
+            ("prices.eth".to_string(), "10".to_string(), 20, 0),
+        ]
+    );
+    // The oldest ticks made room for the newest
+    let payloads: Vec<&str> = ticks.iter().map(|(_, p, _, _)| p.as_str()).collect();
+    assert_eq!(payloads, vec!["7", "8", "9", "10"]);
+    assert_eq!((ticks[0].2, ticks[0].3), (7, 6));
+    assert_eq!((ticks[3].2, ticks[3].3), (10, 0));
+    assert!(
+        client
+            .recv_timeout(Duration::from_millis(200))
+            .await
+            .is_none()
+    );
+
+    // Unsubscribing reports the lifetime drop counts
+    for (id, expected) in [(1, 18), (2, 6)] {
+        client.send(ClientMessage::Unsubscribe { id }).await;
+        match client.recv().await {
+            Some(ServerMessage::UnsubscribeOk { total_dropped, .. }) => {
+                assert_eq!(total_dropped, expected)
+            }
+            other => panic!("Expected UnsubscribeOk, got: {:?}", other),
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_slow_consumer_block_needs_config() {
+    let nats = get_nats().await;
+    let mut config = GatewayConfig::for_test(0, nats.url(), TEST_JWT_SECRET);
+    config.subject_slow_consumer = SubjectSlowConsumer::parse_list("chat.>=block").unwrap();
+    let gateway = TestGateway::start_with_config(config).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_valid_token("user-block"))
+        .await
+        .expect("Auth should succeed");
+
+    // Blocking where the configuration does not is refused
+    client
+        .send(ClientMessage::Subscribe {
+            subject: test_subject("test_block_config", "prices"),
+            id: 1,
+            credits: None,
+            max_msgs: None,
+            replay: None,
+            slow_consumer: Some(SlowConsumerPolicy::Block),
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::SubscribeError { id, code, .. }) => {
+            assert_eq!(id, 1);
+            assert_eq!(code, ErrorCode::InvalidMessage);
+        }
+        other => panic!("Expected SubscribeError, got: {:?}", other),
+    }
+
+    subscribe_with_policy(&mut client, "chat.room", 2, Some(SlowConsumerPolicy::Block)).await;
+}
+
+#[tokio::test]
+async fn test_memory_slow_consumer_block_unsupported() {
+    let mut config = GatewayConfig::for_test(0, "", TEST_JWT_SECRET);
+    config.subject_slow_consumer = SubjectSlowConsumer::parse_list("chat.>=block").unwrap();
+    let gateway = TestGateway::start_in_memory_with_config(config).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_valid_token("user-memory-block"))
+        .await
+        .expect("Auth should succeed");
+
+    // Asking for Block outside the configured subjects is a client error
+    client
+        .send(ClientMessage::Subscribe {
+            subject: "prices.btc".to_string(),
+            id: 2,
+            credits: None,
+            max_msgs: None,
+            replay: None,
+            slow_consumer: Some(SlowConsumerPolicy::Block),
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::SubscribeError { id, code, .. }) => {
+            assert_eq!(id, 2);
+            assert_eq!(code, ErrorCode::InvalidMessage);
+        }
+        other => panic!("Expected SubscribeError, got: {:?}", other),
+    }
+
+    // The in-memory broker cannot hold a publisher back, so it refuses
+    // rather than drop messages a Block subscription must not lose
+    client
+        .send(ClientMessage::Subscribe {
+            subject: "chat.room".to_string(),
+            id: 1,
+            credits: None,
+            max_msgs: None,
+            replay: None,
+            slow_consumer: None,
+        })
+        .await;
+    match client.recv().await {
+        Some(ServerMessage::SubscribeError { id, code, reason }) => {
+            assert_eq!(id, 1);
+            assert_eq!(code, ErrorCode::BackendUnavailable);
+            assert!(reason.contains("Block"), "Unexpected reason: {}", reason);
+        }
+        other => panic!("Expected SubscribeError, got: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_memory_slow_consumer_disconnect() {
+    let mut config = GatewayConfig::for_test(0, "", TEST_JWT_SECRET);
+    config.subscription_buffer = 4;
+    let gateway = TestGateway::start_in_memory_with_config(config).await;
+    let mut client = TestClient::connect(&gateway.ws_url()).await;
+    client
+        .auth(&create_valid_token("user-memory-disconnect"))
+        .await
+        .expect("Auth should succeed");
+
+    subscribe_with_policy(
+        &mut client,
+        "alerts",
+        1,
+        Some(SlowConsumerPolicy::Disconnect),
+    )
+    .await;
+    for i in 0..5u8 {
+        gateway
+            .broker
+            .publish("alerts", &Headers::default(), vec![i])
+            .await
+            .unwrap();
+    }
+
+    match client.recv().await {
+        Some(ServerMessage::Error { code, .. }) => assert_eq!(code, ErrorCode::QuotaExceeded),
+        other => panic!("Expected Error, got: {:?}", other),
+    }
+    assert!(
+        client.recv().await.is_none(),
+        "Slow consumer should be disconnected"
+    );
+}
@@ -4,9 +4,9 @@
 // This file was generated by motto from a Rust schema definition.
 // Any changes will be overwritten on next generation.
 //
-// Protocol Version Byte: 0x6D
-// Schema Fingerprint: 6dd935d3b48ac8c177ad5995ff3060d02268379a59ca971c171bd881acb2f35a
-// Generated At: 2026-02-09T12:20:20.528556589+00:00
+// Protocol Version Byte: 0x72
+// Schema Fingerprint: 7236d81d6697036ecbb5f78f9166d388859432019e34585c2dba2a859b72c010
+// Generated At: 2026-10-18T18:30:12.417305962+00:00
 // ============================================================================
 
 //! Binary codec for encoding/decoding messages
@@ -14,6 +14,11 @@
 use super::*;
 use std::io::{Error as IoError, ErrorKind, Read, Result as IoResult, Write};
 
+/// Most elements reserved ahead of decoding a length-prefixed value. Larger
+/// values grow as their elements are read, so a short frame claiming a huge
+/// length fails once its bytes run out instead of allocating that length.
+const MAX_PREALLOC: usize = 4096;
+
 /// Trait for types that can be encoded to binary
 pub trait Encode {
     fn encode<W: Write>(&self, writer: &mut W) -> IoResult<()>;
@@ -196,8 +201,11 @@ impl Encode for String {
 impl Decode for String {
     fn decode<R: Read>(r: &mut R) -> IoResult<Self> {
         let len = u32::decode(r)? as usize;
-        let mut buf = vec![0u8; len];
-        r.read_exact(&mut buf)?;
+        let mut buf = Vec::with_capacity(len.min(MAX_PREALLOC));
+        r.take(len as u64).read_to_end(&mut buf)?;
+        if buf.len() != len {
+            return Err(IoError::new(ErrorKind::UnexpectedEof, "Truncated string"));
+        }
         String::from_utf8(buf).map_err(|e| IoError::new(ErrorKind::InvalidData, e))
     }
 }
@@ -214,7 +222,7 @@ impl<T: Encode> Encode for Vec<T> {
 impl<T: Decode> Decode for Vec<T> {
     fn decode<R: Read>(r: &mut R) -> IoResult<Self> {
         let len = u32::decode(r)? as usize;
-        let mut vec = Vec::with_capacity(len);
+        let mut vec = Vec::with_capacity(len.min(MAX_PREALLOC));
         for _ in 0..len {
             vec.push(T::decode(r)?);
         }
@@ -242,18 +250,354 @@ impl<T: Decode> Decode for Option<T> {
     }
 }
 
+impl Encode for Capability {
+    fn encode<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        match self {
+            Self::Compression => 0u8.encode(w),
+            Self::Headers => 1u8.encode(w),
+            Self::Batching => 2u8.encode(w),
+        }
+    }
+}
+
+impl Decode for Capability {
+    fn decode<R: Read>(r: &mut R) -> IoResult<Self> {
+        let tag = u8::decode(r)?;
+        match tag {
+            0 => Ok(Self::Compression),
+            1 => Ok(Self::Headers),
+            2 => Ok(Self::Batching),
+            _ => Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!("Unknown Capability tag: {}", tag),
+            )),
+        }
+    }
+}
+
+impl Encode for CompressionAlgorithm {
+    fn encode<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        match self {
+            Self::None => 0u8.encode(w),
+            Self::Gzip => 1u8.encode(w),
+            Self::Zstd => 2u8.encode(w),
+        }
+    }
+}
+
+impl Decode for CompressionAlgorithm {
+    fn decode<R: Read>(r: &mut R) -> IoResult<Self> {
+        let tag = u8::decode(r)?;
+        match tag {
+            0 => Ok(Self::None),
+            1 => Ok(Self::Gzip),
+            2 => Ok(Self::Zstd),
+            _ => Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!("Unknown CompressionAlgorithm tag: {}", tag),
+            )),
+        }
+    }
+}
+
+impl Encode for SubscriptionEndReason {
+    fn encode<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        match self {
+            Self::LimitReached => 0u8.encode(w),
+            Self::PermissionRevoked => 1u8.encode(w),
+            Self::BackendClosed => 2u8.encode(w),
+        }
+    }
+}
+
+impl Decode for SubscriptionEndReason {
+    fn decode<R: Read>(r: &mut R) -> IoResult<Self> {
+        let tag = u8::decode(r)?;
+        match tag {
+            0 => Ok(Self::LimitReached),
+            1 => Ok(Self::PermissionRevoked),
+            2 => Ok(Self::BackendClosed),
+            _ => Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!("Unknown SubscriptionEndReason tag: {}", tag),
+            )),
+        }
+    }
+}
+
+impl Encode for PresenceAction {
+    fn encode<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        match self {
+            Self::Join => 0u8.encode(w),
+            Self::Leave => 1u8.encode(w),
+        }
+    }
+}
+
+impl Decode for PresenceAction {
+    fn decode<R: Read>(r: &mut R) -> IoResult<Self> {
+        let tag = u8::decode(r)?;
+        match tag {
+            0 => Ok(Self::Join),
+            1 => Ok(Self::Leave),
+            _ => Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!("Unknown PresenceAction tag: {}", tag),
+            )),
+        }
+    }
+}
+
+impl Encode for MessageOrigin {
+    fn encode<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        match self {
+            Self::Live => 0u8.encode(w),
+            Self::Retained => 1u8.encode(w),
+            Self::History => 2u8.encode(w),
+        }
+    }
+}
+
+impl Decode for MessageOrigin {
+    fn decode<R: Read>(r: &mut R) -> IoResult<Self> {
+        let tag = u8::decode(r)?;
+        match tag {
+            0 => Ok(Self::Live),
+            1 => Ok(Self::Retained),
+            2 => Ok(Self::History),
+            _ => Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!("Unknown MessageOrigin tag: {}", tag),
+            )),
+        }
+    }
+}
+
+impl Encode for ReplayFrom {
+    fn encode<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        match self {
+            Self::Last { count } => {
+                0u8.encode(w)?;
+                count.encode(w)?;
+                Ok(())
+            }
+            Self::Since { unix_ms } => {
+                1u8.encode(w)?;
+                unix_ms.encode(w)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Decode for ReplayFrom {
+    fn decode<R: Read>(r: &mut R) -> IoResult<Self> {
+        let tag = u8::decode(r)?;
+        match tag {
+            0 => Ok(Self::Last {
+                count: Decode::decode(r)?,
+            }),
+            1 => Ok(Self::Since {
+                unix_ms: Decode::decode(r)?,
+            }),
+            _ => Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!("Unknown ReplayFrom tag: {}", tag),
+            )),
+        }
+    }
+}
+
+impl Encode for SlowConsumerPolicy {
+    fn encode<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        match self {
+            Self::Block => 0u8.encode(w),
+            Self::DropOldest => 1u8.encode(w),
+            Self::DropNewest => 2u8.encode(w),
+            Self::Conflate => 3u8.encode(w),
+            Self::Disconnect => 4u8.encode(w),
+        }
+    }
+}
+
+impl Decode for SlowConsumerPolicy {
+    fn decode<R: Read>(r: &mut R) -> IoResult<Self> {
+        let tag = u8::decode(r)?;
+        match tag {
+            0 => Ok(Self::Block),
+            1 => Ok(Self::DropOldest),
+            2 => Ok(Self::DropNewest),
+            3 => Ok(Self::Conflate),
+            4 => Ok(Self::Disconnect),
+            _ => Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!("Unknown SlowConsumerPolicy tag: {}", tag),
+            )),
+        }
+    }
+}
+
+impl Encode for DeliverPolicy {
+    fn encode<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        match self {
+            Self::All => 0u8.encode(w),
+            Self::New => 1u8.encode(w),
+            Self::Last => 2u8.encode(w),
+            Self::LastPerSubject => 3u8.encode(w),
+            Self::ByStartSequence { start_seq } => {
+                4u8.encode(w)?;
+                start_seq.encode(w)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Decode for DeliverPolicy {
+    fn decode<R: Read>(r: &mut R) -> IoResult<Self> {
+        let tag = u8::decode(r)?;
+        match tag {
+            0 => Ok(Self::All),
+            1 => Ok(Self::New),
+            2 => Ok(Self::Last),
+            3 => Ok(Self::LastPerSubject),
+            4 => Ok(Self::ByStartSequence {
+                start_seq: Decode::decode(r)?,
+            }),
+            _ => Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!("Unknown DeliverPolicy tag: {}", tag),
+            )),
+        }
+    }
+}
+
+impl Encode for AckKind {
+    fn encode<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        match self {
+            Self::Ack => 0u8.encode(w),
+            Self::Nak => 1u8.encode(w),
+            Self::InProgress => 2u8.encode(w),
+        }
+    }
+}
+
+impl Decode for AckKind {
+    fn decode<R: Read>(r: &mut R) -> IoResult<Self> {
+        let tag = u8::decode(r)?;
+        match tag {
+            0 => Ok(Self::Ack),
+            1 => Ok(Self::Nak),
+            2 => Ok(Self::InProgress),
+            _ => Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!("Unknown AckKind tag: {}", tag),
+            )),
+        }
+    }
+}
+
+impl Encode for KvOperation {
+    fn encode<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        match self {
+            Self::Put => 0u8.encode(w),
+            Self::Delete => 1u8.encode(w),
+            Self::Purge => 2u8.encode(w),
+        }
+    }
+}
+
+impl Decode for KvOperation {
+    fn decode<R: Read>(r: &mut R) -> IoResult<Self> {
+        let tag = u8::decode(r)?;
+        match tag {
+            0 => Ok(Self::Put),
+            1 => Ok(Self::Delete),
+            2 => Ok(Self::Purge),
+            _ => Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!("Unknown KvOperation tag: {}", tag),
+            )),
+        }
+    }
+}
+
+impl Encode for ErrorCode {
+    fn encode<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        match self {
+            Self::Unauthorized => 0u8.encode(w),
+            Self::PermissionDenied => 1u8.encode(w),
+            Self::InvalidMessage => 2u8.encode(w),
+            Self::InvalidSubject => 3u8.encode(w),
+            Self::PayloadTooLarge => 4u8.encode(w),
+            Self::NotFound => 5u8.encode(w),
+            Self::AlreadyExists => 6u8.encode(w),
+            Self::QuotaExceeded => 7u8.encode(w),
+            Self::NoResponders => 8u8.encode(w),
+            Self::Timeout => 9u8.encode(w),
+            Self::BackendUnavailable => 10u8.encode(w),
+            Self::VersionMismatch => 11u8.encode(w),
+            Self::SchemaMismatch => 12u8.encode(w),
+            Self::Internal => 13u8.encode(w),
+            Self::Draining => 14u8.encode(w),
+        }
+    }
+}
+
+impl Decode for ErrorCode {
+    fn decode<R: Read>(r: &mut R) -> IoResult<Self> {
+        let tag = u8::decode(r)?;
+        match tag {
+            0 => Ok(Self::Unauthorized),
+            1 => Ok(Self::PermissionDenied),
+            2 => Ok(Self::InvalidMessage),
+            3 => Ok(Self::InvalidSubject),
+            4 => Ok(Self::PayloadTooLarge),
+            5 => Ok(Self::NotFound),
+            6 => Ok(Self::AlreadyExists),
+            7 => Ok(Self::QuotaExceeded),
+            8 => Ok(Self::NoResponders),
+            9 => Ok(Self::Timeout),
+            10 => Ok(Self::BackendUnavailable),
+            11 => Ok(Self::VersionMismatch),
+            12 => Ok(Self::SchemaMismatch),
+            13 => Ok(Self::Internal),
+            14 => Ok(Self::Draining),
+            _ => Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!("Unknown ErrorCode tag: {}", tag),
+            )),
+        }
+    }
+}
+
 impl Encode for ClientMessage {
     fn encode<W: Write>(&self, w: &mut W) -> IoResult<()> {
         match self {
-            Self::Auth { token } => {
+            Self::Auth {
+                token,
+                resume_token,
+            } => {
                 0u8.encode(w)?;
                 token.encode(w)?;
+                resume_token.encode(w)?;
                 Ok(())
             }
-            Self::Subscribe { subject, id } => {
+            Self::Subscribe {
+                subject,
+                id,
+                credits,
+                max_msgs,
+                replay,
+                slow_consumer,
+            } => {
                 1u8.encode(w)?;
                 subject.encode(w)?;
                 id.encode(w)?;
+                credits.encode(w)?;
+                max_msgs.encode(w)?;
+                replay.encode(w)?;
+                slow_consumer.encode(w)?;
                 Ok(())
             }
             Self::Unsubscribe { id } => {
@@ -272,15 +616,189 @@ impl Encode for ClientMessage {
                 payload,
                 timeout_ms,
                 request_id,
+                stream,
             } => {
                 4u8.encode(w)?;
                 subject.encode(w)?;
                 payload.encode(w)?;
                 timeout_ms.encode(w)?;
                 request_id.encode(w)?;
+                stream.encode(w)?;
                 Ok(())
             }
             Self::Ping => 5u8.encode(w),
+            Self::CancelRequest { request_id } => {
+                6u8.encode(w)?;
+                request_id.encode(w)?;
+                Ok(())
+            }
+            Self::Hello {
+                client_name,
+                client_version,
+                schema_fingerprint,
+                capabilities,
+                compression,
+            } => {
+                7u8.encode(w)?;
+                client_name.encode(w)?;
+                client_version.encode(w)?;
+                schema_fingerprint.encode(w)?;
+                capabilities.encode(w)?;
+                compression.encode(w)?;
+                Ok(())
+            }
+            Self::Batch { frames } => {
+                8u8.encode(w)?;
+                frames.encode(w)?;
+                Ok(())
+            }
+            Self::GrantCredit { id, credits } => {
+                9u8.encode(w)?;
+                id.encode(w)?;
+                credits.encode(w)?;
+                Ok(())
+            }
+            Self::Compressed { algorithm, frame } => {
+                10u8.encode(w)?;
+                algorithm.encode(w)?;
+                frame.encode(w)?;
+                Ok(())
+            }
+            Self::SubscribePresence { id, pattern } => {
+                11u8.encode(w)?;
+                id.encode(w)?;
+                pattern.encode(w)?;
+                Ok(())
+            }
+            Self::QueryPresence {
+                request_id,
+                pattern,
+            } => {
+                12u8.encode(w)?;
+                request_id.encode(w)?;
+                pattern.encode(w)?;
+                Ok(())
+            }
+            Self::SubscribeConsumer {
+                id,
+                stream,
+                durable,
+                filter_subject,
+                deliver,
+            } => {
+                13u8.encode(w)?;
+                id.encode(w)?;
+                stream.encode(w)?;
+                durable.encode(w)?;
+                filter_subject.encode(w)?;
+                deliver.encode(w)?;
+                Ok(())
+            }
+            Self::Ack {
+                id,
+                stream_seq,
+                kind,
+            } => {
+                14u8.encode(w)?;
+                id.encode(w)?;
+                stream_seq.encode(w)?;
+                kind.encode(w)?;
+                Ok(())
+            }
+            Self::JetStreamPublish {
+                request_id,
+                subject,
+                payload,
+                msg_id,
+            } => {
+                15u8.encode(w)?;
+                request_id.encode(w)?;
+                subject.encode(w)?;
+                payload.encode(w)?;
+                msg_id.encode(w)?;
+                Ok(())
+            }
+            Self::KvGet {
+                request_id,
+                bucket,
+                key,
+            } => {
+                16u8.encode(w)?;
+                request_id.encode(w)?;
+                bucket.encode(w)?;
+                key.encode(w)?;
+                Ok(())
+            }
+            Self::KvPut {
+                request_id,
+                bucket,
+                key,
+                value,
+            } => {
+                17u8.encode(w)?;
+                request_id.encode(w)?;
+                bucket.encode(w)?;
+                key.encode(w)?;
+                value.encode(w)?;
+                Ok(())
+            }
+            Self::KvDelete {
+                request_id,
+                bucket,
+                key,
+            } => {
+                18u8.encode(w)?;
+                request_id.encode(w)?;
+                bucket.encode(w)?;
+                key.encode(w)?;
+                Ok(())
+            }
+            Self::KvWatch { id, bucket, key } => {
+                19u8.encode(w)?;
+                id.encode(w)?;
+                bucket.encode(w)?;
+                key.encode(w)?;
+                Ok(())
+            }
+            Self::ObjectUploadStart {
+                request_id,
+                bucket,
+                name,
+                size,
+                sha256,
+            } => {
+                20u8.encode(w)?;
+                request_id.encode(w)?;
+                bucket.encode(w)?;
+                name.encode(w)?;
+                size.encode(w)?;
+                sha256.encode(w)?;
+                Ok(())
+            }
+            Self::ObjectUploadChunk {
+                request_id,
+                offset,
+                data,
+            } => {
+                21u8.encode(w)?;
+                request_id.encode(w)?;
+                offset.encode(w)?;
+                data.encode(w)?;
+                Ok(())
+            }
+            Self::ObjectDownload {
+                request_id,
+                bucket,
+                name,
+                offset,
+            } => {
+                22u8.encode(w)?;
+                request_id.encode(w)?;
+                bucket.encode(w)?;
+                name.encode(w)?;
+                offset.encode(w)?;
+                Ok(())
+            }
         }
     }
 }
@@ -291,10 +809,15 @@ impl Decode for ClientMessage {
         match tag {
             0 => Ok(Self::Auth {
                 token: Decode::decode(r)?,
+                resume_token: Decode::decode(r)?,
             }),
             1 => Ok(Self::Subscribe {
                 subject: Decode::decode(r)?,
                 id: Decode::decode(r)?,
+                credits: Decode::decode(r)?,
+                max_msgs: Decode::decode(r)?,
+                replay: Decode::decode(r)?,
+                slow_consumer: Decode::decode(r)?,
             }),
             2 => Ok(Self::Unsubscribe {
                 id: Decode::decode(r)?,
@@ -308,8 +831,95 @@ impl Decode for ClientMessage {
                 payload: Decode::decode(r)?,
                 timeout_ms: Decode::decode(r)?,
                 request_id: Decode::decode(r)?,
+                stream: Decode::decode(r)?,
             }),
             5 => Ok(Self::Ping),
+            6 => Ok(Self::CancelRequest {
+                request_id: Decode::decode(r)?,
+            }),
+            7 => Ok(Self::Hello {
+                client_name: Decode::decode(r)?,
+                client_version: Decode::decode(r)?,
+                schema_fingerprint: Decode::decode(r)?,
+                capabilities: Decode::decode(r)?,
+                compression: Decode::decode(r)?,
+            }),
+            8 => Ok(Self::Batch {
+                frames: Decode::decode(r)?,
+            }),
+            9 => Ok(Self::GrantCredit {
+                id: Decode::decode(r)?,
+                credits: Decode::decode(r)?,
+            }),
+            10 => Ok(Self::Compressed {
+                algorithm: Decode::decode(r)?,
+                frame: Decode::decode(r)?,
+            }),
+            11 => Ok(Self::SubscribePresence {
+                id: Decode::decode(r)?,
+                pattern: Decode::decode(r)?,
+            }),
+            12 => Ok(Self::QueryPresence {
+                request_id: Decode::decode(r)?,
+                pattern: Decode::decode(r)?,
+            }),
+            13 => Ok(Self::SubscribeConsumer {
+                id: Decode::decode(r)?,
+                stream: Decode::decode(r)?,
+                durable: Decode::decode(r)?,
+                filter_subject: Decode::decode(r)?,
+                deliver: Decode::decode(r)?,
+            }),
+            14 => Ok(Self::Ack {
+                id: Decode::decode(r)?,
+                stream_seq: Decode::decode(r)?,
+                kind: Decode::decode(r)?,
+            }),
+            15 => Ok(Self::JetStreamPublish {
+                request_id: Decode::decode(r)?,
+                subject: Decode::decode(r)?,
+                payload: Decode::decode(r)?,
+                msg_id: Decode::decode(r)?,
+            }),
+            16 => Ok(Self::KvGet {
+                request_id: Decode::decode(r)?,
+                bucket: Decode::decode(r)?,
+                key: Decode::decode(r)?,
+            }),
+            17 => Ok(Self::KvPut {
+                request_id: Decode::decode(r)?,
+                bucket: Decode::decode(r)?,
+                key: Decode::decode(r)?,
+                value: Decode::decode(r)?,
+            }),
+            18 => Ok(Self::KvDelete {
+                request_id: Decode::decode(r)?,
+                bucket: Decode::decode(r)?,
+                key: Decode::decode(r)?,
+            }),
+            19 => Ok(Self::KvWatch {
+                id: Decode::decode(r)?,
+                bucket: Decode::decode(r)?,
+                key: Decode::decode(r)?,
+            }),
+            20 => Ok(Self::ObjectUploadStart {
+                request_id: Decode::decode(r)?,
+                bucket: Decode::decode(r)?,
+                name: Decode::decode(r)?,
+                size: Decode::decode(r)?,
+                sha256: Decode::decode(r)?,
+            }),
+            21 => Ok(Self::ObjectUploadChunk {
+                request_id: Decode::decode(r)?,
+                offset: Decode::decode(r)?,
+                data: Decode::decode(r)?,
+            }),
+            22 => Ok(Self::ObjectDownload {
+                request_id: Decode::decode(r)?,
+                bucket: Decode::decode(r)?,
+                name: Decode::decode(r)?,
+                offset: Decode::decode(r)?,
+            }),
             _ => Err(IoError::new(
                 ErrorKind::InvalidData,
                 format!("Unknown ClientMessage tag: {}", tag),
@@ -321,13 +931,20 @@ impl Decode for ClientMessage {
 impl Encode for ServerMessage {
     fn encode<W: Write>(&self, w: &mut W) -> IoResult<()> {
         match self {
-            Self::AuthOk { session_id } => {
+            Self::AuthOk {
+                session_id,
+                resume_token,
+                resumed,
+            } => {
                 0u8.encode(w)?;
                 session_id.encode(w)?;
+                resume_token.encode(w)?;
+                resumed.encode(w)?;
                 Ok(())
             }
-            Self::AuthError { reason } => {
+            Self::AuthError { code, reason } => {
                 1u8.encode(w)?;
+                code.encode(w)?;
                 reason.encode(w)?;
                 Ok(())
             }
@@ -336,9 +953,10 @@ impl Encode for ServerMessage {
                 id.encode(w)?;
                 Ok(())
             }
-            Self::SubscribeError { id, reason } => {
+            Self::SubscribeError { id, code, reason } => {
                 3u8.encode(w)?;
                 id.encode(w)?;
+                code.encode(w)?;
                 reason.encode(w)?;
                 Ok(())
             }
@@ -346,11 +964,17 @@ impl Encode for ServerMessage {
                 subscription_id,
                 subject,
                 payload,
+                seq,
+                dropped,
+                origin,
             } => {
                 4u8.encode(w)?;
                 subscription_id.encode(w)?;
                 subject.encode(w)?;
                 payload.encode(w)?;
+                seq.encode(w)?;
+                dropped.encode(w)?;
+                origin.encode(w)?;
                 Ok(())
             }
             Self::Response {
@@ -362,9 +986,14 @@ impl Encode for ServerMessage {
                 payload.encode(w)?;
                 Ok(())
             }
-            Self::RequestError { request_id, reason } => {
+            Self::RequestError {
+                request_id,
+                code,
+                reason,
+            } => {
                 6u8.encode(w)?;
                 request_id.encode(w)?;
+                code.encode(w)?;
                 reason.encode(w)?;
                 Ok(())
             }
@@ -375,6 +1004,220 @@ impl Encode for ServerMessage {
                 Ok(())
             }
             Self::Pong => 8u8.encode(w),
+            Self::ResponseChunk {
+                request_id,
+                seq,
+                payload,
+            } => {
+                9u8.encode(w)?;
+                request_id.encode(w)?;
+                seq.encode(w)?;
+                payload.encode(w)?;
+                Ok(())
+            }
+            Self::ResponseEnd { request_id } => {
+                10u8.encode(w)?;
+                request_id.encode(w)?;
+                Ok(())
+            }
+            Self::Welcome {
+                gateway_version,
+                schema_fingerprint,
+                capabilities,
+                max_payload_bytes,
+                compression,
+            } => {
+                11u8.encode(w)?;
+                gateway_version.encode(w)?;
+                schema_fingerprint.encode(w)?;
+                capabilities.encode(w)?;
+                max_payload_bytes.encode(w)?;
+                compression.encode(w)?;
+                Ok(())
+            }
+            Self::HelloError { code, message } => {
+                12u8.encode(w)?;
+                code.encode(w)?;
+                message.encode(w)?;
+                Ok(())
+            }
+            Self::GoAway {
+                reason,
+                reconnect_after_ms,
+                alternate_url,
+            } => {
+                13u8.encode(w)?;
+                reason.encode(w)?;
+                reconnect_after_ms.encode(w)?;
+                alternate_url.encode(w)?;
+                Ok(())
+            }
+            Self::Batch { frames } => {
+                14u8.encode(w)?;
+                frames.encode(w)?;
+                Ok(())
+            }
+            Self::Compressed { algorithm, frame } => {
+                15u8.encode(w)?;
+                algorithm.encode(w)?;
+                frame.encode(w)?;
+                Ok(())
+            }
+            Self::UnsubscribeOk { id, total_dropped } => {
+                16u8.encode(w)?;
+                id.encode(w)?;
+                total_dropped.encode(w)?;
+                Ok(())
+            }
+            Self::UnsubscribeError { id, code, reason } => {
+                17u8.encode(w)?;
+                id.encode(w)?;
+                code.encode(w)?;
+                reason.encode(w)?;
+                Ok(())
+            }
+            Self::SubscriptionEnded {
+                id,
+                reason,
+                total_dropped,
+            } => {
+                18u8.encode(w)?;
+                id.encode(w)?;
+                reason.encode(w)?;
+                total_dropped.encode(w)?;
+                Ok(())
+            }
+            Self::Presence { id, action, member } => {
+                19u8.encode(w)?;
+                id.encode(w)?;
+                action.encode(w)?;
+                member.encode(w)?;
+                Ok(())
+            }
+            Self::PresenceList {
+                request_id,
+                members,
+            } => {
+                20u8.encode(w)?;
+                request_id.encode(w)?;
+                members.encode(w)?;
+                Ok(())
+            }
+            Self::ConsumerMessage {
+                subscription_id,
+                subject,
+                payload,
+                stream_seq,
+                delivered,
+            } => {
+                21u8.encode(w)?;
+                subscription_id.encode(w)?;
+                subject.encode(w)?;
+                payload.encode(w)?;
+                stream_seq.encode(w)?;
+                delivered.encode(w)?;
+                Ok(())
+            }
+            Self::PubAck {
+                request_id,
+                stream,
+                seq,
+                duplicate,
+            } => {
+                22u8.encode(w)?;
+                request_id.encode(w)?;
+                stream.encode(w)?;
+                seq.encode(w)?;
+                duplicate.encode(w)?;
+                Ok(())
+            }
+            Self::KvEntry {
+                request_id,
+                key,
+                value,
+                revision,
+            } => {
+                23u8.encode(w)?;
+                request_id.encode(w)?;
+                key.encode(w)?;
+                value.encode(w)?;
+                revision.encode(w)?;
+                Ok(())
+            }
+            Self::KvPutOk {
+                request_id,
+                revision,
+            } => {
+                24u8.encode(w)?;
+                request_id.encode(w)?;
+                revision.encode(w)?;
+                Ok(())
+            }
+            Self::KvDeleteOk { request_id } => {
+                25u8.encode(w)?;
+                request_id.encode(w)?;
+                Ok(())
+            }
+            Self::KvUpdate {
+                id,
+                key,
+                value,
+                revision,
+                operation,
+            } => {
+                26u8.encode(w)?;
+                id.encode(w)?;
+                key.encode(w)?;
+                value.encode(w)?;
+                revision.encode(w)?;
+                operation.encode(w)?;
+                Ok(())
+            }
+            Self::ObjectUploadProgress { request_id, offset } => {
+                27u8.encode(w)?;
+                request_id.encode(w)?;
+                offset.encode(w)?;
+                Ok(())
+            }
+            Self::ObjectUploadOk { request_id, size } => {
+                28u8.encode(w)?;
+                request_id.encode(w)?;
+                size.encode(w)?;
+                Ok(())
+            }
+            Self::ObjectMeta {
+                request_id,
+                name,
+                size,
+            } => {
+                29u8.encode(w)?;
+                request_id.encode(w)?;
+                name.encode(w)?;
+                size.encode(w)?;
+                Ok(())
+            }
+            Self::ObjectChunk {
+                request_id,
+                offset,
+                data,
+            } => {
+                30u8.encode(w)?;
+                request_id.encode(w)?;
+                offset.encode(w)?;
+                data.encode(w)?;
+                Ok(())
+            }
+            Self::ObjectDownloadEnd { request_id, sha256 } => {
+                31u8.encode(w)?;
+                request_id.encode(w)?;
+                sha256.encode(w)?;
+                Ok(())
+            }
+            Self::BackendStatus { available } => {
+                32u8.encode(w)?;
+                available.encode(w)?;
+                Ok(())
+            }
         }
     }
 }
@@ -385,8 +1228,11 @@ impl Decode for ServerMessage {
         match tag {
             0 => Ok(Self::AuthOk {
                 session_id: Decode::decode(r)?,
+                resume_token: Decode::decode(r)?,
+                resumed: Decode::decode(r)?,
             }),
             1 => Ok(Self::AuthError {
+                code: Decode::decode(r)?,
                 reason: Decode::decode(r)?,
             }),
             2 => Ok(Self::SubscribeOk {
@@ -394,12 +1240,16 @@ impl Decode for ServerMessage {
             }),
             3 => Ok(Self::SubscribeError {
                 id: Decode::decode(r)?,
+                code: Decode::decode(r)?,
                 reason: Decode::decode(r)?,
             }),
             4 => Ok(Self::Message {
                 subscription_id: Decode::decode(r)?,
                 subject: Decode::decode(r)?,
                 payload: Decode::decode(r)?,
+                seq: Decode::decode(r)?,
+                dropped: Decode::decode(r)?,
+                origin: Decode::decode(r)?,
             }),
             5 => Ok(Self::Response {
                 request_id: Decode::decode(r)?,
@@ -407,6 +1257,7 @@ impl Decode for ServerMessage {
             }),
             6 => Ok(Self::RequestError {
                 request_id: Decode::decode(r)?,
+                code: Decode::decode(r)?,
                 reason: Decode::decode(r)?,
             }),
             7 => Ok(Self::Error {
@@ -414,6 +1265,118 @@ impl Decode for ServerMessage {
                 message: Decode::decode(r)?,
             }),
             8 => Ok(Self::Pong),
+            9 => Ok(Self::ResponseChunk {
+                request_id: Decode::decode(r)?,
+                seq: Decode::decode(r)?,
+                payload: Decode::decode(r)?,
+            }),
+            10 => Ok(Self::ResponseEnd {
+                request_id: Decode::decode(r)?,
+            }),
+            11 => Ok(Self::Welcome {
+                gateway_version: Decode::decode(r)?,
+                schema_fingerprint: Decode::decode(r)?,
+                capabilities: Decode::decode(r)?,
+                max_payload_bytes: Decode::decode(r)?,
+                compression: Decode::decode(r)?,
+            }),
+            12 => Ok(Self::HelloError {
+                code: Decode::decode(r)?,
+                message: Decode::decode(r)?,
+            }),
+            13 => Ok(Self::GoAway {
+                reason: Decode::decode(r)?,
+                reconnect_after_ms: Decode::decode(r)?,
+                alternate_url: Decode::decode(r)?,
+            }),
+            14 => Ok(Self::Batch {
+                frames: Decode::decode(r)?,
+            }),
+            15 => Ok(Self::Compressed {
+                algorithm: Decode::decode(r)?,
+                frame: Decode::decode(r)?,
+            }),
+            16 => Ok(Self::UnsubscribeOk {
+                id: Decode::decode(r)?,
+                total_dropped: Decode::decode(r)?,
+            }),
+            17 => Ok(Self::UnsubscribeError {
+                id: Decode::decode(r)?,
+                code: Decode::decode(r)?,
+                reason: Decode::decode(r)?,
+            }),
+            18 => Ok(Self::SubscriptionEnded {
+                id: Decode::decode(r)?,
+                reason: Decode::decode(r)?,
+                total_dropped: Decode::decode(r)?,
+            }),
+            19 => Ok(Self::Presence {
+                id: Decode::decode(r)?,
+                action: Decode::decode(r)?,
+                member: Decode::decode(r)?,
+            }),
+            20 => Ok(Self::PresenceList {
+                request_id: Decode::decode(r)?,
+                members: Decode::decode(r)?,
+            }),
+            21 => Ok(Self::ConsumerMessage {
+                subscription_id: Decode::decode(r)?,
+                subject: Decode::decode(r)?,
+                payload: Decode::decode(r)?,
+                stream_seq: Decode::decode(r)?,
+                delivered: Decode::decode(r)?,
+            }),
+            22 => Ok(Self::PubAck {
+                request_id: Decode::decode(r)?,
+                stream: Decode::decode(r)?,
+                seq: Decode::decode(r)?,
+                duplicate: Decode::decode(r)?,
+            }),
+            23 => Ok(Self::KvEntry {
+                request_id: Decode::decode(r)?,
+                key: Decode::decode(r)?,
+                value: Decode::decode(r)?,
+                revision: Decode::decode(r)?,
+            }),
+            24 => Ok(Self::KvPutOk {
+                request_id: Decode::decode(r)?,
+                revision: Decode::decode(r)?,
+            }),
+            25 => Ok(Self::KvDeleteOk {
+                request_id: Decode::decode(r)?,
+            }),
+            26 => Ok(Self::KvUpdate {
+                id: Decode::decode(r)?,
+                key: Decode::decode(r)?,
+                value: Decode::decode(r)?,
+                revision: Decode::decode(r)?,
+                operation: Decode::decode(r)?,
+            }),
+            27 => Ok(Self::ObjectUploadProgress {
+                request_id: Decode::decode(r)?,
+                offset: Decode::decode(r)?,
+            }),
+            28 => Ok(Self::ObjectUploadOk {
+                request_id: Decode::decode(r)?,
+                size: Decode::decode(r)?,
+            }),
+            29 => Ok(Self::ObjectMeta {
+                request_id: Decode::decode(r)?,
+                name: Decode::decode(r)?,
+                size: Decode::decode(r)?,
+            }),
+            30 => Ok(Self::ObjectChunk {
+                request_id: Decode::decode(r)?,
+                offset: Decode::decode(r)?,
+                data: Decode::decode(r)?,
+            }),
+            31 => Ok(Self::ObjectDownloadEnd {
+                request_id: Decode::decode(r)?,
+                sha256: Decode::decode(r)?,
+            }),
+            32 => Ok(Self::BackendStatus {
+                available: Decode::decode(r)?,
+            }),
             _ => Err(IoError::new(
                 ErrorKind::InvalidData,
                 format!("Unknown ServerMessage tag: {}", tag),
@@ -488,6 +1451,25 @@ impl Decode for ServerEnvelope {
     }
 }
 
+impl Encode for PresenceMember {
+    fn encode<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        self.session_id.encode(w)?;
+        self.user_id.encode(w)?;
+        self.subject.encode(w)?;
+        Ok(())
+    }
+}
+
+impl Decode for PresenceMember {
+    fn decode<R: Read>(r: &mut R) -> IoResult<Self> {
+        Ok(Self {
+            session_id: Decode::decode(r)?,
+            user_id: Decode::decode(r)?,
+            subject: Decode::decode(r)?,
+        })
+    }
+}
+
 impl Encode for SchemaRouter {
     fn encode<W: Write>(&self, w: &mut W) -> IoResult<()> {
         self.tag().encode(w)?;
@@ -496,6 +1478,7 @@ impl Encode for SchemaRouter {
             Self::TestData(msg) => msg.encode(w),
             Self::ClientEnvelope(msg) => msg.encode(w),
             Self::ServerEnvelope(msg) => msg.encode(w),
+            Self::PresenceMember(msg) => msg.encode(w),
         }
     }
 }
@@ -508,6 +1491,7 @@ impl Decode for SchemaRouter {
             Self::TEST_DATA_TAG => Ok(Self::TestData(Decode::decode(r)?)),
             Self::CLIENT_ENVELOPE_TAG => Ok(Self::ClientEnvelope(Decode::decode(r)?)),
             Self::SERVER_ENVELOPE_TAG => Ok(Self::ServerEnvelope(Decode::decode(r)?)),
+            Self::PRESENCE_MEMBER_TAG => Ok(Self::PresenceMember(Decode::decode(r)?)),
             _ => Err(IoError::new(
                 ErrorKind::InvalidData,
                 format!("Unknown SchemaRouter tag: {}", tag),
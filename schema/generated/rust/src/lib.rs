@@ -4,9 +4,9 @@
 // This file was generated by motto from a Rust schema definition.
 // Any changes will be overwritten on next generation.
 //
-// Protocol Version Byte: 0x6D
-// Schema Fingerprint: 6dd935d3b48ac8c177ad5995ff3060d02268379a59ca971c171bd881acb2f35a
-// Generated At: 2026-02-09T12:20:20.528556589+00:00
+// Protocol Version Byte: 0x72
+// Schema Fingerprint: 7236d81d6697036ecbb5f78f9166d388859432019e34585c2dba2a859b72c010
+// Generated At: 2026-10-18T18:30:12.417305962+00:00
 // ============================================================================
 
 #![allow(dead_code)]
@@ -38,20 +38,116 @@ pub use webtransport::WebTransportClient;
 pub use websocket::WebSocketClient;
 
 /// Protocol version byte - embedded in all packets
-pub const PROTOCOL_VERSION_BYTE: u8 = 0x6D;
+pub const PROTOCOL_VERSION_BYTE: u8 = 0x72;
 
 /// Schema fingerprint for validation
 pub const SCHEMA_FINGERPRINT: &str =
-    "6dd935d3b48ac8c177ad5995ff3060d02268379a59ca971c171bd881acb2f35a";
+    "7236d81d6697036ecbb5f78f9166d388859432019e34585c2dba2a859b72c010";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Capability {
+    Compression,
+    Headers,
+    Batching,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompressionAlgorithm {
+    None,
+    Gzip,
+    Zstd,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubscriptionEndReason {
+    LimitReached,
+    PermissionRevoked,
+    BackendClosed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PresenceAction {
+    Join,
+    Leave,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MessageOrigin {
+    Live,
+    Retained,
+    History,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplayFrom {
+    Last { count: u32 },
+    Since { unix_ms: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SlowConsumerPolicy {
+    Block,
+    DropOldest,
+    DropNewest,
+    Conflate,
+    Disconnect,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeliverPolicy {
+    All,
+    New,
+    Last,
+    LastPerSubject,
+    ByStartSequence { start_seq: u64 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AckKind {
+    Ack,
+    Nak,
+    InProgress,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KvOperation {
+    Put,
+    Delete,
+    Purge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorCode {
+    Unauthorized,
+    PermissionDenied,
+    InvalidMessage,
+    InvalidSubject,
+    PayloadTooLarge,
+    NotFound,
+    AlreadyExists,
+    QuotaExceeded,
+    NoResponders,
+    Timeout,
+    BackendUnavailable,
+    VersionMismatch,
+    SchemaMismatch,
+    Internal,
+    Draining,
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ClientMessage {
     Auth {
         token: String,
+        resume_token: Option<String>,
     },
     Subscribe {
         subject: String,
         id: u64,
+        credits: Option<u32>,
+        max_msgs: Option<u32>,
+        replay: Option<ReplayFrom>,
+        slow_consumer: Option<SlowConsumerPolicy>,
     },
     Unsubscribe {
         id: u64,
@@ -65,16 +161,106 @@ pub enum ClientMessage {
         payload: Vec<u8>,
         timeout_ms: u32,
         request_id: u64,
+        stream: bool,
     },
     Ping,
+    CancelRequest {
+        request_id: u64,
+    },
+    Hello {
+        client_name: String,
+        client_version: String,
+        schema_fingerprint: String,
+        capabilities: Vec<Capability>,
+        compression: Vec<CompressionAlgorithm>,
+    },
+    Batch {
+        frames: Vec<Vec<u8>>,
+    },
+    GrantCredit {
+        id: u64,
+        credits: u32,
+    },
+    Compressed {
+        algorithm: CompressionAlgorithm,
+        frame: Vec<u8>,
+    },
+    SubscribePresence {
+        id: u64,
+        pattern: String,
+    },
+    QueryPresence {
+        request_id: u64,
+        pattern: String,
+    },
+    SubscribeConsumer {
+        id: u64,
+        stream: String,
+        durable: String,
+        filter_subject: String,
+        deliver: DeliverPolicy,
+    },
+    Ack {
+        id: u64,
+        stream_seq: u64,
+        kind: AckKind,
+    },
+    JetStreamPublish {
+        request_id: u64,
+        subject: String,
+        payload: Vec<u8>,
+        msg_id: Option<String>,
+    },
+    KvGet {
+        request_id: u64,
+        bucket: String,
+        key: String,
+    },
+    KvPut {
+        request_id: u64,
+        bucket: String,
+        key: String,
+        value: Vec<u8>,
+    },
+    KvDelete {
+        request_id: u64,
+        bucket: String,
+        key: String,
+    },
+    KvWatch {
+        id: u64,
+        bucket: String,
+        key: String,
+    },
+    ObjectUploadStart {
+        request_id: u64,
+        bucket: String,
+        name: String,
+        size: u64,
+        sha256: Vec<u8>,
+    },
+    ObjectUploadChunk {
+        request_id: u64,
+        offset: u64,
+        data: Vec<u8>,
+    },
+    ObjectDownload {
+        request_id: u64,
+        bucket: String,
+        name: String,
+        offset: u64,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ServerMessage {
     AuthOk {
         session_id: String,
+        resume_token: Option<String>,
+        resumed: bool,
     },
     AuthError {
+        code: ErrorCode,
         reason: String,
     },
     SubscribeOk {
@@ -82,12 +268,16 @@ pub enum ServerMessage {
     },
     SubscribeError {
         id: u64,
+        code: ErrorCode,
         reason: String,
     },
     Message {
         subscription_id: u64,
         subject: String,
         payload: Vec<u8>,
+        seq: u64,
+        dropped: u32,
+        origin: MessageOrigin,
     },
     Response {
         request_id: u64,
@@ -95,13 +285,126 @@ pub enum ServerMessage {
     },
     RequestError {
         request_id: u64,
+        code: ErrorCode,
         reason: String,
     },
     Error {
-        code: u32,
+        code: ErrorCode,
         message: String,
     },
     Pong,
+    ResponseChunk {
+        request_id: u64,
+        seq: u32,
+        payload: Vec<u8>,
+    },
+    ResponseEnd {
+        request_id: u64,
+    },
+    Welcome {
+        gateway_version: String,
+        schema_fingerprint: String,
+        capabilities: Vec<Capability>,
+        max_payload_bytes: u32,
+        compression: CompressionAlgorithm,
+    },
+    HelloError {
+        code: ErrorCode,
+        message: String,
+    },
+    GoAway {
+        reason: String,
+        reconnect_after_ms: u32,
+        alternate_url: Option<String>,
+    },
+    Batch {
+        frames: Vec<Vec<u8>>,
+    },
+    Compressed {
+        algorithm: CompressionAlgorithm,
+        frame: Vec<u8>,
+    },
+    UnsubscribeOk {
+        id: u64,
+        total_dropped: u64,
+    },
+    UnsubscribeError {
+        id: u64,
+        code: ErrorCode,
+        reason: String,
+    },
+    SubscriptionEnded {
+        id: u64,
+        reason: SubscriptionEndReason,
+        total_dropped: u64,
+    },
+    Presence {
+        id: u64,
+        action: PresenceAction,
+        member: PresenceMember,
+    },
+    PresenceList {
+        request_id: u64,
+        members: Vec<PresenceMember>,
+    },
+    ConsumerMessage {
+        subscription_id: u64,
+        subject: String,
+        payload: Vec<u8>,
+        stream_seq: u64,
+        delivered: u32,
+    },
+    PubAck {
+        request_id: u64,
+        stream: String,
+        seq: u64,
+        duplicate: bool,
+    },
+    KvEntry {
+        request_id: u64,
+        key: String,
+        value: Option<Vec<u8>>,
+        revision: u64,
+    },
+    KvPutOk {
+        request_id: u64,
+        revision: u64,
+    },
+    KvDeleteOk {
+        request_id: u64,
+    },
+    KvUpdate {
+        id: u64,
+        key: String,
+        value: Vec<u8>,
+        revision: u64,
+        operation: KvOperation,
+    },
+    ObjectUploadProgress {
+        request_id: u64,
+        offset: u64,
+    },
+    ObjectUploadOk {
+        request_id: u64,
+        size: u64,
+    },
+    ObjectMeta {
+        request_id: u64,
+        name: String,
+        size: u64,
+    },
+    ObjectChunk {
+        request_id: u64,
+        offset: u64,
+        data: Vec<u8>,
+    },
+    ObjectDownloadEnd {
+        request_id: u64,
+        sha256: Vec<u8>,
+    },
+    BackendStatus {
+        available: bool,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -127,6 +430,13 @@ pub struct ServerEnvelope {
     pub message: ServerMessage,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct PresenceMember {
+    pub session_id: String,
+    pub user_id: String,
+    pub subject: String,
+}
+
 /// Auto-generated router enum for schema schema.
 ///
 /// This enum wraps all message types for type-safe routing.
@@ -136,6 +446,7 @@ pub enum SchemaRouter {
     TestData(TestData),
     ClientEnvelope(ClientEnvelope),
     ServerEnvelope(ServerEnvelope),
+    PresenceMember(PresenceMember),
 }
 
 impl SchemaRouter {
@@ -143,6 +454,7 @@ impl SchemaRouter {
     pub const TEST_DATA_TAG: u16 = 1;
     pub const CLIENT_ENVELOPE_TAG: u16 = 2;
     pub const SERVER_ENVELOPE_TAG: u16 = 3;
+    pub const PRESENCE_MEMBER_TAG: u16 = 4;
 
     /// Get the discriminant tag for this message
     pub fn tag(&self) -> u16 {
@@ -151,6 +463,7 @@ impl SchemaRouter {
             Self::TestData(_) => Self::TEST_DATA_TAG,
             Self::ClientEnvelope(_) => Self::CLIENT_ENVELOPE_TAG,
             Self::ServerEnvelope(_) => Self::SERVER_ENVELOPE_TAG,
+            Self::PresenceMember(_) => Self::PRESENCE_MEMBER_TAG,
         }
     }
 
@@ -161,6 +474,7 @@ impl SchemaRouter {
             Self::TEST_DATA_TAG => Some("TestData"),
             Self::CLIENT_ENVELOPE_TAG => Some("ClientEnvelope"),
             Self::SERVER_ENVELOPE_TAG => Some("ServerEnvelope"),
+            Self::PRESENCE_MEMBER_TAG => Some("PresenceMember"),
             _ => None,
         }
     }
@@ -174,6 +488,7 @@ pub trait SchemaRouterHandler {
     fn handle_test_data(&mut self, msg: TestData) -> Self::Output;
     fn handle_client_envelope(&mut self, msg: ClientEnvelope) -> Self::Output;
     fn handle_server_envelope(&mut self, msg: ServerEnvelope) -> Self::Output;
+    fn handle_presence_member(&mut self, msg: PresenceMember) -> Self::Output;
 }
 
 impl SchemaRouter {
@@ -184,6 +499,7 @@ impl SchemaRouter {
             Self::TestData(msg) => handler.handle_test_data(msg),
             Self::ClientEnvelope(msg) => handler.handle_client_envelope(msg),
             Self::ServerEnvelope(msg) => handler.handle_server_envelope(msg),
+            Self::PresenceMember(msg) => handler.handle_presence_member(msg),
         }
     }
 }
@@ -4,9 +4,9 @@
 // This file was generated by motto from a Rust schema definition.
 // Any changes will be overwritten on next generation.
 //
-// Protocol Version Byte: 0x6D
-// Schema Fingerprint: 6dd935d3b48ac8c177ad5995ff3060d02268379a59ca971c171bd881acb2f35a
-// Generated At: 2026-02-09T12:20:20.528556589+00:00
+// Protocol Version Byte: 0x72
+// Schema Fingerprint: 7236d81d6697036ecbb5f78f9166d388859432019e34585c2dba2a859b72c010
+// Generated At: 2026-10-18T18:30:12.417305962+00:00
 // ============================================================================
 
 //! Generated tests for encode/decode roundtrips and router functionality.
@@ -51,6 +51,15 @@ fn create_test_server_envelope() -> ServerEnvelope {
     }
 }
 
+/// Create a test instance of PresenceMember
+fn create_test_presence_member() -> PresenceMember {
+    PresenceMember {
+        session_id: "test_session_id".to_string(),
+        user_id: "test_user_id".to_string(),
+        subject: "test_subject".to_string(),
+    }
+}
+
 // ============================================================================
 // Roundtrip Tests: Encode -> Decode -> Compare
 // ============================================================================
@@ -203,6 +212,43 @@ fn test_server_envelope_encode_decode() {
     assert_eq!(original, decoded);
 }
 
+#[test]
+fn test_presence_member_roundtrip() {
+    let original = create_test_presence_member();
+
+    // Encode to bytes
+    let encoded = original.to_bytes();
+
+    // Verify version byte is present
+    assert!(!encoded.is_empty(), "Encoded bytes should not be empty");
+    assert_eq!(
+        encoded[0], PROTOCOL_VERSION_BYTE,
+        "First byte should be version byte"
+    );
+
+    // Decode back
+    let decoded = PresenceMember::from_bytes(&encoded).expect("Decode should succeed");
+
+    // Compare
+    assert_eq!(original, decoded, "Roundtrip should preserve data");
+}
+
+#[test]
+fn test_presence_member_encode_decode() {
+    let original = create_test_presence_member();
+
+    // Encode to buffer
+    let mut buffer = Vec::new();
+    original.encode(&mut buffer).expect("Encode should succeed");
+
+    // Decode from buffer
+    let mut reader = buffer.as_slice();
+    let decoded = PresenceMember::decode(&mut reader).expect("Decode should succeed");
+
+    // Compare
+    assert_eq!(original, decoded);
+}
+
 // ============================================================================
 // Enum Serialization Tests
 // ============================================================================
@@ -256,6 +302,11 @@ fn test_schema_router_tag_values() {
         3,
         "Tag for ServerEnvelope should be 3"
     );
+    assert_eq!(
+        SchemaRouter::PRESENCE_MEMBER_TAG,
+        4,
+        "Tag for PresenceMember should be 4"
+    );
 }
 
 #[test]
@@ -264,6 +315,7 @@ fn test_schema_router_type_name_from_tag() {
     assert_eq!(SchemaRouter::type_name_from_tag(1), Some("TestData"));
     assert_eq!(SchemaRouter::type_name_from_tag(2), Some("ClientEnvelope"));
     assert_eq!(SchemaRouter::type_name_from_tag(3), Some("ServerEnvelope"));
+    assert_eq!(SchemaRouter::type_name_from_tag(4), Some("PresenceMember"));
     assert_eq!(SchemaRouter::type_name_from_tag(9999), None);
 }
 
@@ -296,6 +348,13 @@ fn test_schema_router_roundtrip() {
     let encoded = msg.to_bytes();
     let decoded = SchemaRouter::from_bytes(&encoded).expect("Decode should succeed");
     assert_eq!(msg, decoded);
+
+    // Test SchemaRouter::PresenceMember
+    let msg = SchemaRouter::PresenceMember(create_test_presence_member());
+    assert_eq!(msg.tag(), SchemaRouter::PRESENCE_MEMBER_TAG);
+    let encoded = msg.to_bytes();
+    let decoded = SchemaRouter::from_bytes(&encoded).expect("Decode should succeed");
+    assert_eq!(msg, decoded);
 }
 
 /// Test handler for SchemaRouter
@@ -318,6 +377,9 @@ impl SchemaRouterHandler for TestSchemaRouterHandler {
     fn handle_server_envelope(&mut self, _msg: ServerEnvelope) -> Self::Output {
         self.calls.push("ServerEnvelope".to_string());
     }
+    fn handle_presence_member(&mut self, _msg: PresenceMember) -> Self::Output {
+        self.calls.push("PresenceMember".to_string());
+    }
 }
 
 #[test]
@@ -335,7 +397,7 @@ fn test_schema_router_handler() {
 
 #[test]
 fn test_protocol_version_byte() {
-    assert_eq!(PROTOCOL_VERSION_BYTE, 0x6D);
+    assert_eq!(PROTOCOL_VERSION_BYTE, 0x72);
 }
 
 #[test]
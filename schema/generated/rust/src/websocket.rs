@@ -4,9 +4,9 @@
 // This file was generated by motto from a Rust schema definition.
 // Any changes will be overwritten on next generation.
 //
-// Protocol Version Byte: 0x6D
-// Schema Fingerprint: 6dd935d3b48ac8c177ad5995ff3060d02268379a59ca971c171bd881acb2f35a
-// Generated At: 2026-02-09T12:20:20.528556589+00:00
+// Protocol Version Byte: 0x72
+// Schema Fingerprint: 7236d81d6697036ecbb5f78f9166d388859432019e34585c2dba2a859b72c010
+// Generated At: 2026-10-18T18:30:12.417305962+00:00
 // ============================================================================
 
 //! WebSocket client implementation.
@@ -32,11 +32,13 @@ __export(index_exports, {
   compressZstd: () => compressZstd,
   decodeClientEnvelope: () => decodeClientEnvelope,
   decodeInnerData: () => decodeInnerData,
+  decodePresenceMember: () => decodePresenceMember,
   decodeServerEnvelope: () => decodeServerEnvelope,
   decodeTestData: () => decodeTestData,
   decompressZstd: () => decompressZstd,
   encodeClientEnvelope: () => encodeClientEnvelope,
   encodeInnerData: () => encodeInnerData,
+  encodePresenceMember: () => encodePresenceMember,
   encodeServerEnvelope: () => encodeServerEnvelope,
   encodeTestData: () => encodeTestData
 });
@@ -47,12 +49,13 @@ var SchemaRouterType = {
   InnerData: 0,
   TestData: 1,
   ClientEnvelope: 2,
-  ServerEnvelope: 3
+  ServerEnvelope: 3,
+  PresenceMember: 4
 };
 
 // src/codec.ts
-var PROTOCOL_VERSION_BYTE = 109;
-var SCHEMA_FINGERPRINT = "6dd935d3b48ac8c177ad5995ff3060d02268379a59ca971c171bd881acb2f35a";
+var PROTOCOL_VERSION_BYTE = 114;
+var SCHEMA_FINGERPRINT = "7236d81d6697036ecbb5f78f9166d388859432019e34585c2dba2a859b72c010";
 var PacketView = class {
   view;
   offset = 0;
@@ -198,47 +201,605 @@ var PacketBuilder = class {
     return this.buffer.slice(0, this.offset);
   }
 };
+function encodeCapabilityFields(val, builder) {
+  switch (val.type) {
+    case "Compression":
+      builder.writeU8(0);
+      break;
+    case "Headers":
+      builder.writeU8(1);
+      break;
+    case "Batching":
+      builder.writeU8(2);
+      break;
+  }
+}
+function decodeCapabilityFields(view) {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: "Compression" };
+    case 1:
+      return { type: "Headers" };
+    case 2:
+      return { type: "Batching" };
+    default:
+      throw new Error(`Unknown Capability tag: ${tag}`);
+  }
+}
+function encodeCompressionAlgorithmFields(val, builder) {
+  switch (val.type) {
+    case "None":
+      builder.writeU8(0);
+      break;
+    case "Gzip":
+      builder.writeU8(1);
+      break;
+    case "Zstd":
+      builder.writeU8(2);
+      break;
+  }
+}
+function decodeCompressionAlgorithmFields(view) {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: "None" };
+    case 1:
+      return { type: "Gzip" };
+    case 2:
+      return { type: "Zstd" };
+    default:
+      throw new Error(`Unknown CompressionAlgorithm tag: ${tag}`);
+  }
+}
+function encodeSubscriptionEndReasonFields(val, builder) {
+  switch (val.type) {
+    case "LimitReached":
+      builder.writeU8(0);
+      break;
+    case "PermissionRevoked":
+      builder.writeU8(1);
+      break;
+    case "BackendClosed":
+      builder.writeU8(2);
+      break;
+  }
+}
+function decodeSubscriptionEndReasonFields(view) {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: "LimitReached" };
+    case 1:
+      return { type: "PermissionRevoked" };
+    case 2:
+      return { type: "BackendClosed" };
+    default:
+      throw new Error(`Unknown SubscriptionEndReason tag: ${tag}`);
+  }
+}
+function encodePresenceActionFields(val, builder) {
+  switch (val.type) {
+    case "Join":
+      builder.writeU8(0);
+      break;
+    case "Leave":
+      builder.writeU8(1);
+      break;
+  }
+}
+function decodePresenceActionFields(view) {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: "Join" };
+    case 1:
+      return { type: "Leave" };
+    default:
+      throw new Error(`Unknown PresenceAction tag: ${tag}`);
+  }
+}
+function encodeMessageOriginFields(val, builder) {
+  switch (val.type) {
+    case "Live":
+      builder.writeU8(0);
+      break;
+    case "Retained":
+      builder.writeU8(1);
+      break;
+    case "History":
+      builder.writeU8(2);
+      break;
+  }
+}
+function decodeMessageOriginFields(view) {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: "Live" };
+    case 1:
+      return { type: "Retained" };
+    case 2:
+      return { type: "History" };
+    default:
+      throw new Error(`Unknown MessageOrigin tag: ${tag}`);
+  }
+}
+function encodeReplayFromFields(val, builder) {
+  switch (val.type) {
+    case "Last":
+      builder.writeU8(0);
+      builder.writeU32(val.count);
+      break;
+    case "Since":
+      builder.writeU8(1);
+      builder.writeU64(BigInt(val.unix_ms));
+      break;
+  }
+}
+function decodeReplayFromFields(view) {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: "Last", count: view.readU32() };
+    case 1:
+      return { type: "Since", unix_ms: view.readU64() };
+    default:
+      throw new Error(`Unknown ReplayFrom tag: ${tag}`);
+  }
+}
+function encodeSlowConsumerPolicyFields(val, builder) {
+  switch (val.type) {
+    case "Block":
+      builder.writeU8(0);
+      break;
+    case "DropOldest":
+      builder.writeU8(1);
+      break;
+    case "DropNewest":
+      builder.writeU8(2);
+      break;
+    case "Conflate":
+      builder.writeU8(3);
+      break;
+    case "Disconnect":
+      builder.writeU8(4);
+      break;
+  }
+}
+function decodeSlowConsumerPolicyFields(view) {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: "Block" };
+    case 1:
+      return { type: "DropOldest" };
+    case 2:
+      return { type: "DropNewest" };
+    case 3:
+      return { type: "Conflate" };
+    case 4:
+      return { type: "Disconnect" };
+    default:
+      throw new Error(`Unknown SlowConsumerPolicy tag: ${tag}`);
+  }
+}
+function encodeDeliverPolicyFields(val, builder) {
+  switch (val.type) {
+    case "All":
+      builder.writeU8(0);
+      break;
+    case "New":
+      builder.writeU8(1);
+      break;
+    case "Last":
+      builder.writeU8(2);
+      break;
+    case "LastPerSubject":
+      builder.writeU8(3);
+      break;
+    case "ByStartSequence":
+      builder.writeU8(4);
+      builder.writeU64(BigInt(val.start_seq));
+      break;
+  }
+}
+function decodeDeliverPolicyFields(view) {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: "All" };
+    case 1:
+      return { type: "New" };
+    case 2:
+      return { type: "Last" };
+    case 3:
+      return { type: "LastPerSubject" };
+    case 4:
+      return { type: "ByStartSequence", start_seq: view.readU64() };
+    default:
+      throw new Error(`Unknown DeliverPolicy tag: ${tag}`);
+  }
+}
+function encodeAckKindFields(val, builder) {
+  switch (val.type) {
+    case "Ack":
+      builder.writeU8(0);
+      break;
+    case "Nak":
+      builder.writeU8(1);
+      break;
+    case "InProgress":
+      builder.writeU8(2);
+      break;
+  }
+}
+function decodeAckKindFields(view) {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: "Ack" };
+    case 1:
+      return { type: "Nak" };
+    case 2:
+      return { type: "InProgress" };
+    default:
+      throw new Error(`Unknown AckKind tag: ${tag}`);
+  }
+}
+function encodeKvOperationFields(val, builder) {
+  switch (val.type) {
+    case "Put":
+      builder.writeU8(0);
+      break;
+    case "Delete":
+      builder.writeU8(1);
+      break;
+    case "Purge":
+      builder.writeU8(2);
+      break;
+  }
+}
+function decodeKvOperationFields(view) {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: "Put" };
+    case 1:
+      return { type: "Delete" };
+    case 2:
+      return { type: "Purge" };
+    default:
+      throw new Error(`Unknown KvOperation tag: ${tag}`);
+  }
+}
+function encodeErrorCodeFields(val, builder) {
+  switch (val.type) {
+    case "Unauthorized":
+      builder.writeU8(0);
+      break;
+    case "PermissionDenied":
+      builder.writeU8(1);
+      break;
+    case "InvalidMessage":
+      builder.writeU8(2);
+      break;
+    case "InvalidSubject":
+      builder.writeU8(3);
+      break;
+    case "PayloadTooLarge":
+      builder.writeU8(4);
+      break;
+    case "NotFound":
+      builder.writeU8(5);
+      break;
+    case "AlreadyExists":
+      builder.writeU8(6);
+      break;
+    case "QuotaExceeded":
+      builder.writeU8(7);
+      break;
+    case "NoResponders":
+      builder.writeU8(8);
+      break;
+    case "Timeout":
+      builder.writeU8(9);
+      break;
+    case "BackendUnavailable":
+      builder.writeU8(10);
+      break;
+    case "VersionMismatch":
+      builder.writeU8(11);
+      break;
+    case "SchemaMismatch":
+      builder.writeU8(12);
+      break;
+    case "Internal":
+      builder.writeU8(13);
+      break;
+    case "Draining":
+      builder.writeU8(14);
+      break;
+  }
+}
+function decodeErrorCodeFields(view) {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: "Unauthorized" };
+    case 1:
+      return { type: "PermissionDenied" };
+    case 2:
+      return { type: "InvalidMessage" };
+    case 3:
+      return { type: "InvalidSubject" };
+    case 4:
+      return { type: "PayloadTooLarge" };
+    case 5:
+      return { type: "NotFound" };
+    case 6:
+      return { type: "AlreadyExists" };
+    case 7:
+      return { type: "QuotaExceeded" };
+    case 8:
+      return { type: "NoResponders" };
+    case 9:
+      return { type: "Timeout" };
+    case 10:
+      return { type: "BackendUnavailable" };
+    case 11:
+      return { type: "VersionMismatch" };
+    case 12:
+      return { type: "SchemaMismatch" };
+    case 13:
+      return { type: "Internal" };
+    case 14:
+      return { type: "Draining" };
+    default:
+      throw new Error(`Unknown ErrorCode tag: ${tag}`);
+  }
+}
 function encodeClientMessageFields(val, builder) {
   switch (val.type) {
     case "Auth":
       builder.writeU8(0);
       builder.writeString(val.token);
+      if (val.resume_token === null) {
+        builder.writeU8(0);
+      } else {
+        builder.writeU8(1);
+        builder.writeString(val.resume_token);
+      }
+      ;
+      break;
+    case "Subscribe":
+      builder.writeU8(1);
+      builder.writeString(val.subject);
+      builder.writeU64(BigInt(val.id));
+      if (val.credits === null) {
+        builder.writeU8(0);
+      } else {
+        builder.writeU8(1);
+        builder.writeU32(val.credits);
+      }
+      ;
+      if (val.max_msgs === null) {
+        builder.writeU8(0);
+      } else {
+        builder.writeU8(1);
+        builder.writeU32(val.max_msgs);
+      }
+      ;
+      if (val.replay === null) {
+        builder.writeU8(0);
+      } else {
+        builder.writeU8(1);
+        encodeReplayFromFields(val.replay, builder);
+      }
+      ;
+      if (val.slow_consumer === null) {
+        builder.writeU8(0);
+      } else {
+        builder.writeU8(1);
+        encodeSlowConsumerPolicyFields(val.slow_consumer, builder);
+      }
+      ;
+      break;
+    case "Unsubscribe":
+      builder.writeU8(2);
+      builder.writeU64(BigInt(val.id));
+      break;
+    case "Publish":
+      builder.writeU8(3);
+      builder.writeString(val.subject);
+      {
+        builder.writeU32(val.payload.length);
+        for (const item of val.payload) {
+          builder.writeU8(item);
+        }
+      }
+      ;
+      break;
+    case "Request":
+      builder.writeU8(4);
+      builder.writeString(val.subject);
+      {
+        builder.writeU32(val.payload.length);
+        for (const item of val.payload) {
+          builder.writeU8(item);
+        }
+      }
+      ;
+      builder.writeU32(val.timeout_ms);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeBool(val.stream);
+      break;
+    case "Ping":
+      builder.writeU8(5);
+      break;
+    case "CancelRequest":
+      builder.writeU8(6);
+      builder.writeU64(BigInt(val.request_id));
+      break;
+    case "Hello":
+      builder.writeU8(7);
+      builder.writeString(val.client_name);
+      builder.writeString(val.client_version);
+      builder.writeString(val.schema_fingerprint);
+      {
+        builder.writeU32(val.capabilities.length);
+        for (const item of val.capabilities) {
+          encodeCapabilityFields(item, builder);
+        }
+      }
+      ;
+      {
+        builder.writeU32(val.compression.length);
+        for (const item of val.compression) {
+          encodeCompressionAlgorithmFields(item, builder);
+        }
+      }
+      ;
+      break;
+    case "Batch":
+      builder.writeU8(8);
+      {
+        builder.writeU32(val.frames.length);
+        for (const item of val.frames) {
+          {
+            builder.writeU32(item.length);
+            for (const byte of item) {
+              builder.writeU8(byte);
+            }
+          }
+          ;
+        }
+      }
+      ;
+      break;
+    case "GrantCredit":
+      builder.writeU8(9);
+      builder.writeU64(BigInt(val.id));
+      builder.writeU32(val.credits);
+      break;
+    case "Compressed":
+      builder.writeU8(10);
+      encodeCompressionAlgorithmFields(val.algorithm, builder);
+      {
+        builder.writeU32(val.frame.length);
+        for (const item of val.frame) {
+          builder.writeU8(item);
+        }
+      }
+      ;
+      break;
+    case "SubscribePresence":
+      builder.writeU8(11);
+      builder.writeU64(BigInt(val.id));
+      builder.writeString(val.pattern);
+      break;
+    case "QueryPresence":
+      builder.writeU8(12);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeString(val.pattern);
+      break;
+    case "SubscribeConsumer":
+      builder.writeU8(13);
+      builder.writeU64(BigInt(val.id));
+      builder.writeString(val.stream);
+      builder.writeString(val.durable);
+      builder.writeString(val.filter_subject);
+      encodeDeliverPolicyFields(val.deliver, builder);
+      break;
+    case "Ack":
+      builder.writeU8(14);
+      builder.writeU64(BigInt(val.id));
+      builder.writeU64(BigInt(val.stream_seq));
+      encodeAckKindFields(val.kind, builder);
+      break;
+    case "JetStreamPublish":
+      builder.writeU8(15);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeString(val.subject);
+      {
+        builder.writeU32(val.payload.length);
+        for (const item of val.payload) {
+          builder.writeU8(item);
+        }
+      }
+      ;
+      if (val.msg_id === null) {
+        builder.writeU8(0);
+      } else {
+        builder.writeU8(1);
+        builder.writeString(val.msg_id);
+      }
+      ;
+      break;
+    case "KvGet":
+      builder.writeU8(16);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeString(val.bucket);
+      builder.writeString(val.key);
+      break;
+    case "KvPut":
+      builder.writeU8(17);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeString(val.bucket);
+      builder.writeString(val.key);
+      {
+        builder.writeU32(val.value.length);
+        for (const item of val.value) {
+          builder.writeU8(item);
+        }
+      }
+      ;
       break;
-    case "Subscribe":
-      builder.writeU8(1);
-      builder.writeString(val.subject);
-      builder.writeU64(BigInt(val.id));
+    case "KvDelete":
+      builder.writeU8(18);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeString(val.bucket);
+      builder.writeString(val.key);
       break;
-    case "Unsubscribe":
-      builder.writeU8(2);
+    case "KvWatch":
+      builder.writeU8(19);
       builder.writeU64(BigInt(val.id));
+      builder.writeString(val.bucket);
+      builder.writeString(val.key);
       break;
-    case "Publish":
-      builder.writeU8(3);
-      builder.writeString(val.subject);
+    case "ObjectUploadStart":
+      builder.writeU8(20);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeString(val.bucket);
+      builder.writeString(val.name);
+      builder.writeU64(BigInt(val.size));
       {
-        builder.writeU32(val.payload.length);
-        for (const item of val.payload) {
+        builder.writeU32(val.sha256.length);
+        for (const item of val.sha256) {
           builder.writeU8(item);
         }
       }
       ;
       break;
-    case "Request":
-      builder.writeU8(4);
-      builder.writeString(val.subject);
+    case "ObjectUploadChunk":
+      builder.writeU8(21);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeU64(BigInt(val.offset));
       {
-        builder.writeU32(val.payload.length);
-        for (const item of val.payload) {
+        builder.writeU32(val.data.length);
+        for (const item of val.data) {
           builder.writeU8(item);
         }
       }
       ;
-      builder.writeU32(val.timeout_ms);
-      builder.writeU64(BigInt(val.request_id));
       break;
-    case "Ping":
-      builder.writeU8(5);
+    case "ObjectDownload":
+      builder.writeU8(22);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeString(val.bucket);
+      builder.writeString(val.name);
+      builder.writeU64(BigInt(val.offset));
       break;
   }
 }
@@ -246,9 +807,9 @@ function decodeClientMessageFields(view) {
   const tag = view.readU8();
   switch (tag) {
     case 0:
-      return { type: "Auth", token: view.readString() };
+      return { type: "Auth", token: view.readString(), resume_token: view.readU8() === 0 ? null : view.readString() };
     case 1:
-      return { type: "Subscribe", subject: view.readString(), id: view.readU64() };
+      return { type: "Subscribe", subject: view.readString(), id: view.readU64(), credits: view.readU8() === 0 ? null : view.readU32(), max_msgs: view.readU8() === 0 ? null : view.readU32(), replay: view.readU8() === 0 ? null : decodeReplayFromFields(view), slow_consumer: view.readU8() === 0 ? null : decodeSlowConsumerPolicyFields(view) };
     case 2:
       return { type: "Unsubscribe", id: view.readU64() };
     case 3:
@@ -268,9 +829,106 @@ function decodeClientMessageFields(view) {
           arr.push(view.readU8());
         }
         return arr;
-      })(), timeout_ms: view.readU32(), request_id: view.readU64() };
+      })(), timeout_ms: view.readU32(), request_id: view.readU64(), stream: view.readBool() };
     case 5:
       return { type: "Ping" };
+    case 6:
+      return { type: "CancelRequest", request_id: view.readU64() };
+    case 7:
+      return { type: "Hello", client_name: view.readString(), client_version: view.readString(), schema_fingerprint: view.readString(), capabilities: (() => {
+        const len = view.readU32();
+        const arr = [];
+        for (let i = 0; i < len; i++) {
+          arr.push(decodeCapabilityFields(view));
+        }
+        return arr;
+      })(), compression: (() => {
+        const len = view.readU32();
+        const arr = [];
+        for (let i = 0; i < len; i++) {
+          arr.push(decodeCompressionAlgorithmFields(view));
+        }
+        return arr;
+      })() };
+    case 8:
+      return { type: "Batch", frames: (() => {
+        const len = view.readU32();
+        const arr = [];
+        for (let i = 0; i < len; i++) {
+          arr.push((() => {
+            const len = view.readU32();
+            const arr = [];
+            for (let i = 0; i < len; i++) {
+              arr.push(view.readU8());
+            }
+            return arr;
+          })());
+        }
+        return arr;
+      })() };
+    case 9:
+      return { type: "GrantCredit", id: view.readU64(), credits: view.readU32() };
+    case 10:
+      return { type: "Compressed", algorithm: decodeCompressionAlgorithmFields(view), frame: (() => {
+        const len = view.readU32();
+        const arr = [];
+        for (let i = 0; i < len; i++) {
+          arr.push(view.readU8());
+        }
+        return arr;
+      })() };
+    case 11:
+      return { type: "SubscribePresence", id: view.readU64(), pattern: view.readString() };
+    case 12:
+      return { type: "QueryPresence", request_id: view.readU64(), pattern: view.readString() };
+    case 13:
+      return { type: "SubscribeConsumer", id: view.readU64(), stream: view.readString(), durable: view.readString(), filter_subject: view.readString(), deliver: decodeDeliverPolicyFields(view) };
+    case 14:
+      return { type: "Ack", id: view.readU64(), stream_seq: view.readU64(), kind: decodeAckKindFields(view) };
+    case 15:
+      return { type: "JetStreamPublish", request_id: view.readU64(), subject: view.readString(), payload: (() => {
+        const len = view.readU32();
+        const arr = [];
+        for (let i = 0; i < len; i++) {
+          arr.push(view.readU8());
+        }
+        return arr;
+      })(), msg_id: view.readU8() === 0 ? null : view.readString() };
+    case 16:
+      return { type: "KvGet", request_id: view.readU64(), bucket: view.readString(), key: view.readString() };
+    case 17:
+      return { type: "KvPut", request_id: view.readU64(), bucket: view.readString(), key: view.readString(), value: (() => {
+        const len = view.readU32();
+        const arr = [];
+        for (let i = 0; i < len; i++) {
+          arr.push(view.readU8());
+        }
+        return arr;
+      })() };
+    case 18:
+      return { type: "KvDelete", request_id: view.readU64(), bucket: view.readString(), key: view.readString() };
+    case 19:
+      return { type: "KvWatch", id: view.readU64(), bucket: view.readString(), key: view.readString() };
+    case 20:
+      return { type: "ObjectUploadStart", request_id: view.readU64(), bucket: view.readString(), name: view.readString(), size: view.readU64(), sha256: (() => {
+        const len = view.readU32();
+        const arr = [];
+        for (let i = 0; i < len; i++) {
+          arr.push(view.readU8());
+        }
+        return arr;
+      })() };
+    case 21:
+      return { type: "ObjectUploadChunk", request_id: view.readU64(), offset: view.readU64(), data: (() => {
+        const len = view.readU32();
+        const arr = [];
+        for (let i = 0; i < len; i++) {
+          arr.push(view.readU8());
+        }
+        return arr;
+      })() };
+    case 22:
+      return { type: "ObjectDownload", request_id: view.readU64(), bucket: view.readString(), name: view.readString(), offset: view.readU64() };
     default:
       throw new Error(`Unknown ClientMessage tag: ${tag}`);
   }
@@ -280,9 +938,18 @@ function encodeServerMessageFields(val, builder) {
     case "AuthOk":
       builder.writeU8(0);
       builder.writeString(val.session_id);
+      if (val.resume_token === null) {
+        builder.writeU8(0);
+      } else {
+        builder.writeU8(1);
+        builder.writeString(val.resume_token);
+      }
+      ;
+      builder.writeBool(val.resumed);
       break;
     case "AuthError":
       builder.writeU8(1);
+      encodeErrorCodeFields(val.code, builder);
       builder.writeString(val.reason);
       break;
     case "SubscribeOk":
@@ -292,6 +959,7 @@ function encodeServerMessageFields(val, builder) {
     case "SubscribeError":
       builder.writeU8(3);
       builder.writeU64(BigInt(val.id));
+      encodeErrorCodeFields(val.code, builder);
       builder.writeString(val.reason);
       break;
     case "Message":
@@ -305,6 +973,9 @@ function encodeServerMessageFields(val, builder) {
         }
       }
       ;
+      builder.writeU64(BigInt(val.seq));
+      builder.writeU32(val.dropped);
+      encodeMessageOriginFields(val.origin, builder);
       break;
     case "Response":
       builder.writeU8(5);
@@ -320,29 +991,243 @@ function encodeServerMessageFields(val, builder) {
     case "RequestError":
       builder.writeU8(6);
       builder.writeU64(BigInt(val.request_id));
+      encodeErrorCodeFields(val.code, builder);
       builder.writeString(val.reason);
       break;
     case "Error":
       builder.writeU8(7);
-      builder.writeU32(val.code);
+      encodeErrorCodeFields(val.code, builder);
       builder.writeString(val.message);
       break;
     case "Pong":
       builder.writeU8(8);
       break;
+    case "ResponseChunk":
+      builder.writeU8(9);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeU32(val.seq);
+      {
+        builder.writeU32(val.payload.length);
+        for (const item of val.payload) {
+          builder.writeU8(item);
+        }
+      }
+      ;
+      break;
+    case "ResponseEnd":
+      builder.writeU8(10);
+      builder.writeU64(BigInt(val.request_id));
+      break;
+    case "Welcome":
+      builder.writeU8(11);
+      builder.writeString(val.gateway_version);
+      builder.writeString(val.schema_fingerprint);
+      {
+        builder.writeU32(val.capabilities.length);
+        for (const item of val.capabilities) {
+          encodeCapabilityFields(item, builder);
+        }
+      }
+      ;
+      builder.writeU32(val.max_payload_bytes);
+      encodeCompressionAlgorithmFields(val.compression, builder);
+      break;
+    case "HelloError":
+      builder.writeU8(12);
+      encodeErrorCodeFields(val.code, builder);
+      builder.writeString(val.message);
+      break;
+    case "GoAway":
+      builder.writeU8(13);
+      builder.writeString(val.reason);
+      builder.writeU32(val.reconnect_after_ms);
+      if (val.alternate_url === null) {
+        builder.writeU8(0);
+      } else {
+        builder.writeU8(1);
+        builder.writeString(val.alternate_url);
+      }
+      ;
+      break;
+    case "Batch":
+      builder.writeU8(14);
+      {
+        builder.writeU32(val.frames.length);
+        for (const item of val.frames) {
+          {
+            builder.writeU32(item.length);
+            for (const byte of item) {
+              builder.writeU8(byte);
+            }
+          }
+          ;
+        }
+      }
+      ;
+      break;
+    case "Compressed":
+      builder.writeU8(15);
+      encodeCompressionAlgorithmFields(val.algorithm, builder);
+      {
+        builder.writeU32(val.frame.length);
+        for (const item of val.frame) {
+          builder.writeU8(item);
+        }
+      }
+      ;
+      break;
+    case "UnsubscribeOk":
+      builder.writeU8(16);
+      builder.writeU64(BigInt(val.id));
+      builder.writeU64(BigInt(val.total_dropped));
+      break;
+    case "UnsubscribeError":
+      builder.writeU8(17);
+      builder.writeU64(BigInt(val.id));
+      encodeErrorCodeFields(val.code, builder);
+      builder.writeString(val.reason);
+      break;
+    case "SubscriptionEnded":
+      builder.writeU8(18);
+      builder.writeU64(BigInt(val.id));
+      encodeSubscriptionEndReasonFields(val.reason, builder);
+      builder.writeU64(BigInt(val.total_dropped));
+      break;
+    case "Presence":
+      builder.writeU8(19);
+      builder.writeU64(BigInt(val.id));
+      encodePresenceActionFields(val.action, builder);
+      encodePresenceMemberFields(val.member, builder);
+      break;
+    case "PresenceList":
+      builder.writeU8(20);
+      builder.writeU64(BigInt(val.request_id));
+      {
+        builder.writeU32(val.members.length);
+        for (const item of val.members) {
+          encodePresenceMemberFields(item, builder);
+        }
+      }
+      ;
+      break;
+    case "ConsumerMessage":
+      builder.writeU8(21);
+      builder.writeU64(BigInt(val.subscription_id));
+      builder.writeString(val.subject);
+      {
+        builder.writeU32(val.payload.length);
+        for (const item of val.payload) {
+          builder.writeU8(item);
+        }
+      }
+      ;
+      builder.writeU64(BigInt(val.stream_seq));
+      builder.writeU32(val.delivered);
+      break;
+    case "PubAck":
+      builder.writeU8(22);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeString(val.stream);
+      builder.writeU64(BigInt(val.seq));
+      builder.writeBool(val.duplicate);
+      break;
+    case "KvEntry":
+      builder.writeU8(23);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeString(val.key);
+      if (val.value === null) {
+        builder.writeU8(0);
+      } else {
+        builder.writeU8(1);
+        {
+          builder.writeU32(val.value.length);
+          for (const item of val.value) {
+            builder.writeU8(item);
+          }
+        }
+      }
+      ;
+      builder.writeU64(BigInt(val.revision));
+      break;
+    case "KvPutOk":
+      builder.writeU8(24);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeU64(BigInt(val.revision));
+      break;
+    case "KvDeleteOk":
+      builder.writeU8(25);
+      builder.writeU64(BigInt(val.request_id));
+      break;
+    case "KvUpdate":
+      builder.writeU8(26);
+      builder.writeU64(BigInt(val.id));
+      builder.writeString(val.key);
+      {
+        builder.writeU32(val.value.length);
+        for (const item of val.value) {
+          builder.writeU8(item);
+        }
+      }
+      ;
+      builder.writeU64(BigInt(val.revision));
+      encodeKvOperationFields(val.operation, builder);
+      break;
+    case "ObjectUploadProgress":
+      builder.writeU8(27);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeU64(BigInt(val.offset));
+      break;
+    case "ObjectUploadOk":
+      builder.writeU8(28);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeU64(BigInt(val.size));
+      break;
+    case "ObjectMeta":
+      builder.writeU8(29);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeString(val.name);
+      builder.writeU64(BigInt(val.size));
+      break;
+    case "ObjectChunk":
+      builder.writeU8(30);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeU64(BigInt(val.offset));
+      {
+        builder.writeU32(val.data.length);
+        for (const item of val.data) {
+          builder.writeU8(item);
+        }
+      }
+      ;
+      break;
+    case "ObjectDownloadEnd":
+      builder.writeU8(31);
+      builder.writeU64(BigInt(val.request_id));
+      {
+        builder.writeU32(val.sha256.length);
+        for (const item of val.sha256) {
+          builder.writeU8(item);
+        }
+      }
+      ;
+      break;
+    case "BackendStatus":
+      builder.writeU8(32);
+      builder.writeBool(val.available);
+      break;
   }
 }
 function decodeServerMessageFields(view) {
   const tag = view.readU8();
   switch (tag) {
     case 0:
-      return { type: "AuthOk", session_id: view.readString() };
+      return { type: "AuthOk", session_id: view.readString(), resume_token: view.readU8() === 0 ? null : view.readString(), resumed: view.readBool() };
     case 1:
-      return { type: "AuthError", reason: view.readString() };
+      return { type: "AuthError", code: decodeErrorCodeFields(view), reason: view.readString() };
     case 2:
       return { type: "SubscribeOk", id: view.readU64() };
     case 3:
-      return { type: "SubscribeError", id: view.readU64(), reason: view.readString() };
+      return { type: "SubscribeError", id: view.readU64(), code: decodeErrorCodeFields(view), reason: view.readString() };
     case 4:
       return { type: "Message", subscription_id: view.readU64(), subject: view.readString(), payload: (() => {
         const len = view.readU32();
@@ -351,7 +1236,7 @@ function decodeServerMessageFields(view) {
           arr.push(view.readU8());
         }
         return arr;
-      })() };
+      })(), seq: view.readU64(), dropped: view.readU32(), origin: decodeMessageOriginFields(view) };
     case 5:
       return { type: "Response", request_id: view.readU64(), payload: (() => {
         const len = view.readU32();
@@ -362,11 +1247,136 @@ function decodeServerMessageFields(view) {
         return arr;
       })() };
     case 6:
-      return { type: "RequestError", request_id: view.readU64(), reason: view.readString() };
+      return { type: "RequestError", request_id: view.readU64(), code: decodeErrorCodeFields(view), reason: view.readString() };
     case 7:
-      return { type: "Error", code: view.readU32(), message: view.readString() };
+      return { type: "Error", code: decodeErrorCodeFields(view), message: view.readString() };
     case 8:
       return { type: "Pong" };
+    case 9:
+      return { type: "ResponseChunk", request_id: view.readU64(), seq: view.readU32(), payload: (() => {
+        const len = view.readU32();
+        const arr = [];
+        for (let i = 0; i < len; i++) {
+          arr.push(view.readU8());
+        }
+        return arr;
+      })() };
+    case 10:
+      return { type: "ResponseEnd", request_id: view.readU64() };
+    case 11:
+      return { type: "Welcome", gateway_version: view.readString(), schema_fingerprint: view.readString(), capabilities: (() => {
+        const len = view.readU32();
+        const arr = [];
+        for (let i = 0; i < len; i++) {
+          arr.push(decodeCapabilityFields(view));
+        }
+        return arr;
+      })(), max_payload_bytes: view.readU32(), compression: decodeCompressionAlgorithmFields(view) };
+    case 12:
+      return { type: "HelloError", code: decodeErrorCodeFields(view), message: view.readString() };
+    case 13:
+      return { type: "GoAway", reason: view.readString(), reconnect_after_ms: view.readU32(), alternate_url: view.readU8() === 0 ? null : view.readString() };
+    case 14:
+      return { type: "Batch", frames: (() => {
+        const len = view.readU32();
+        const arr = [];
+        for (let i = 0; i < len; i++) {
+          arr.push((() => {
+            const len = view.readU32();
+            const arr = [];
+            for (let i = 0; i < len; i++) {
+              arr.push(view.readU8());
+            }
+            return arr;
+          })());
+        }
+        return arr;
+      })() };
+    case 15:
+      return { type: "Compressed", algorithm: decodeCompressionAlgorithmFields(view), frame: (() => {
+        const len = view.readU32();
+        const arr = [];
+        for (let i = 0; i < len; i++) {
+          arr.push(view.readU8());
+        }
+        return arr;
+      })() };
+    case 16:
+      return { type: "UnsubscribeOk", id: view.readU64(), total_dropped: view.readU64() };
+    case 17:
+      return { type: "UnsubscribeError", id: view.readU64(), code: decodeErrorCodeFields(view), reason: view.readString() };
+    case 18:
+      return { type: "SubscriptionEnded", id: view.readU64(), reason: decodeSubscriptionEndReasonFields(view), total_dropped: view.readU64() };
+    case 19:
+      return { type: "Presence", id: view.readU64(), action: decodePresenceActionFields(view), member: decodePresenceMemberFields(view) };
+    case 20:
+      return { type: "PresenceList", request_id: view.readU64(), members: (() => {
+        const len = view.readU32();
+        const arr = [];
+        for (let i = 0; i < len; i++) {
+          arr.push(decodePresenceMemberFields(view));
+        }
+        return arr;
+      })() };
+    case 21:
+      return { type: "ConsumerMessage", subscription_id: view.readU64(), subject: view.readString(), payload: (() => {
+        const len = view.readU32();
+        const arr = [];
+        for (let i = 0; i < len; i++) {
+          arr.push(view.readU8());
+        }
+        return arr;
+      })(), stream_seq: view.readU64(), delivered: view.readU32() };
+    case 22:
+      return { type: "PubAck", request_id: view.readU64(), stream: view.readString(), seq: view.readU64(), duplicate: view.readBool() };
+    case 23:
+      return { type: "KvEntry", request_id: view.readU64(), key: view.readString(), value: view.readU8() === 0 ? null : (() => {
+        const len = view.readU32();
+        const arr = [];
+        for (let i = 0; i < len; i++) {
+          arr.push(view.readU8());
+        }
+        return arr;
+      })(), revision: view.readU64() };
+    case 24:
+      return { type: "KvPutOk", request_id: view.readU64(), revision: view.readU64() };
+    case 25:
+      return { type: "KvDeleteOk", request_id: view.readU64() };
+    case 26:
+      return { type: "KvUpdate", id: view.readU64(), key: view.readString(), value: (() => {
+        const len = view.readU32();
+        const arr = [];
+        for (let i = 0; i < len; i++) {
+          arr.push(view.readU8());
+        }
+        return arr;
+      })(), revision: view.readU64(), operation: decodeKvOperationFields(view) };
+    case 27:
+      return { type: "ObjectUploadProgress", request_id: view.readU64(), offset: view.readU64() };
+    case 28:
+      return { type: "ObjectUploadOk", request_id: view.readU64(), size: view.readU64() };
+    case 29:
+      return { type: "ObjectMeta", request_id: view.readU64(), name: view.readString(), size: view.readU64() };
+    case 30:
+      return { type: "ObjectChunk", request_id: view.readU64(), offset: view.readU64(), data: (() => {
+        const len = view.readU32();
+        const arr = [];
+        for (let i = 0; i < len; i++) {
+          arr.push(view.readU8());
+        }
+        return arr;
+      })() };
+    case 31:
+      return { type: "ObjectDownloadEnd", request_id: view.readU64(), sha256: (() => {
+        const len = view.readU32();
+        const arr = [];
+        for (let i = 0; i < len; i++) {
+          arr.push(view.readU8());
+        }
+        return arr;
+      })() };
+    case 32:
+      return { type: "BackendStatus", available: view.readBool() };
     default:
       throw new Error(`Unknown ServerMessage tag: ${tag}`);
   }
@@ -475,9 +1485,31 @@ function decodeServerEnvelope(data) {
   view.skip(1);
   return decodeServerEnvelopeFields(view);
 }
+function encodePresenceMemberFields(msg, builder) {
+  builder.writeString(msg.session_id);
+  builder.writeString(msg.user_id);
+  builder.writeString(msg.subject);
+}
+function encodePresenceMember(msg) {
+  const builder = new PacketBuilder();
+  encodePresenceMemberFields(msg, builder);
+  return builder.build();
+}
+function decodePresenceMemberFields(view) {
+  return {
+    session_id: view.readString(),
+    user_id: view.readString(),
+    subject: view.readString()
+  };
+}
+function decodePresenceMember(data) {
+  const view = new PacketView(data);
+  view.skip(1);
+  return decodePresenceMemberFields(view);
+}
 
 // src/runtime.ts
-var PROTOCOL_VERSION = 109;
+var PROTOCOL_VERSION = 114;
 var ConnectionState = /* @__PURE__ */ ((ConnectionState2) => {
   ConnectionState2[ConnectionState2["Disconnected"] = 0] = "Disconnected";
   ConnectionState2[ConnectionState2["Connecting"] = 1] = "Connecting";
@@ -583,11 +1615,13 @@ var MottoTransport = class {
   compressZstd,
   decodeClientEnvelope,
   decodeInnerData,
+  decodePresenceMember,
   decodeServerEnvelope,
   decodeTestData,
   decompressZstd,
   encodeClientEnvelope,
   encodeInnerData,
+  encodePresenceMember,
   encodeServerEnvelope,
   encodeTestData
 });
@@ -1,10 +1,123 @@
+type Capability = {
+    type: 'Compression';
+} | {
+    type: 'Headers';
+} | {
+    type: 'Batching';
+};
+type CompressionAlgorithm = {
+    type: 'None';
+} | {
+    type: 'Gzip';
+} | {
+    type: 'Zstd';
+};
+type SubscriptionEndReason = {
+    type: 'LimitReached';
+} | {
+    type: 'PermissionRevoked';
+} | {
+    type: 'BackendClosed';
+};
+type PresenceAction = {
+    type: 'Join';
+} | {
+    type: 'Leave';
+};
+type MessageOrigin = {
+    type: 'Live';
+} | {
+    type: 'Retained';
+} | {
+    type: 'History';
+};
+type ReplayFrom = {
+    type: 'Last';
+    count: number;
+} | {
+    type: 'Since';
+    unix_ms: bigint;
+};
+type SlowConsumerPolicy = {
+    type: 'Block';
+} | {
+    type: 'DropOldest';
+} | {
+    type: 'DropNewest';
+} | {
+    type: 'Conflate';
+} | {
+    type: 'Disconnect';
+};
+type DeliverPolicy = {
+    type: 'All';
+} | {
+    type: 'New';
+} | {
+    type: 'Last';
+} | {
+    type: 'LastPerSubject';
+} | {
+    type: 'ByStartSequence';
+    start_seq: bigint;
+};
+type AckKind = {
+    type: 'Ack';
+} | {
+    type: 'Nak';
+} | {
+    type: 'InProgress';
+};
+type KvOperation = {
+    type: 'Put';
+} | {
+    type: 'Delete';
+} | {
+    type: 'Purge';
+};
+type ErrorCode = {
+    type: 'Unauthorized';
+} | {
+    type: 'PermissionDenied';
+} | {
+    type: 'InvalidMessage';
+} | {
+    type: 'InvalidSubject';
+} | {
+    type: 'PayloadTooLarge';
+} | {
+    type: 'NotFound';
+} | {
+    type: 'AlreadyExists';
+} | {
+    type: 'QuotaExceeded';
+} | {
+    type: 'NoResponders';
+} | {
+    type: 'Timeout';
+} | {
+    type: 'BackendUnavailable';
+} | {
+    type: 'VersionMismatch';
+} | {
+    type: 'SchemaMismatch';
+} | {
+    type: 'Internal';
+} | {
+    type: 'Draining';
+};
 type ClientMessage = {
     type: 'Auth';
     token: string;
+    resume_token: string | null;
 } | {
     type: 'Subscribe';
     subject: string;
     id: bigint;
+    credits: number | null;
+    max_msgs: number | null;
+    replay: ReplayFrom | null;
+    slow_consumer: SlowConsumerPolicy | null;
 } | {
     type: 'Unsubscribe';
     id: bigint;
@@ -18,14 +131,104 @@ type ClientMessage = {
     payload: number[];
     timeout_ms: number;
     request_id: bigint;
+    stream: boolean;
 } | {
     type: 'Ping';
+} | {
+    type: 'CancelRequest';
+    request_id: bigint;
+} | {
+    type: 'Hello';
+    client_name: string;
+    client_version: string;
+    schema_fingerprint: string;
+    capabilities: Capability[];
+    compression: CompressionAlgorithm[];
+} | {
+    type: 'Batch';
+    frames: number[][];
+} | {
+    type: 'GrantCredit';
+    id: bigint;
+    credits: number;
+} | {
+    type: 'Compressed';
+    algorithm: CompressionAlgorithm;
+    frame: number[];
+} | {
+    type: 'SubscribePresence';
+    id: bigint;
+    pattern: string;
+} | {
+    type: 'QueryPresence';
+    request_id: bigint;
+    pattern: string;
+} | {
+    type: 'SubscribeConsumer';
+    id: bigint;
+    stream: string;
+    durable: string;
+    filter_subject: string;
+    deliver: DeliverPolicy;
+} | {
+    type: 'Ack';
+    id: bigint;
+    stream_seq: bigint;
+    kind: AckKind;
+} | {
+    type: 'JetStreamPublish';
+    request_id: bigint;
+    subject: string;
+    payload: number[];
+    msg_id: string | null;
+} | {
+    type: 'KvGet';
+    request_id: bigint;
+    bucket: string;
+    key: string;
+} | {
+    type: 'KvPut';
+    request_id: bigint;
+    bucket: string;
+    key: string;
+    value: number[];
+} | {
+    type: 'KvDelete';
+    request_id: bigint;
+    bucket: string;
+    key: string;
+} | {
+    type: 'KvWatch';
+    id: bigint;
+    bucket: string;
+    key: string;
+} | {
+    type: 'ObjectUploadStart';
+    request_id: bigint;
+    bucket: string;
+    name: string;
+    size: bigint;
+    sha256: number[];
+} | {
+    type: 'ObjectUploadChunk';
+    request_id: bigint;
+    offset: bigint;
+    data: number[];
+} | {
+    type: 'ObjectDownload';
+    request_id: bigint;
+    bucket: string;
+    name: string;
+    offset: bigint;
 };
 type ServerMessage = {
     type: 'AuthOk';
     session_id: string;
+    resume_token: string | null;
+    resumed: boolean;
 } | {
     type: 'AuthError';
+    code: ErrorCode;
     reason: string;
 } | {
     type: 'SubscribeOk';
@@ -33,12 +236,16 @@ type ServerMessage = {
 } | {
     type: 'SubscribeError';
     id: bigint;
+    code: ErrorCode;
     reason: string;
 } | {
     type: 'Message';
     subscription_id: bigint;
     subject: string;
     payload: number[];
+    seq: bigint;
+    dropped: number;
+    origin: MessageOrigin;
 } | {
     type: 'Response';
     request_id: bigint;
@@ -46,13 +253,126 @@ type ServerMessage = {
 } | {
     type: 'RequestError';
     request_id: bigint;
+    code: ErrorCode;
     reason: string;
 } | {
     type: 'Error';
-    code: number;
+    code: ErrorCode;
     message: string;
 } | {
     type: 'Pong';
+} | {
+    type: 'ResponseChunk';
+    request_id: bigint;
+    seq: number;
+    payload: number[];
+} | {
+    type: 'ResponseEnd';
+    request_id: bigint;
+} | {
+    type: 'Welcome';
+    gateway_version: string;
+    schema_fingerprint: string;
+    capabilities: Capability[];
+    max_payload_bytes: number;
+    compression: CompressionAlgorithm;
+} | {
+    type: 'HelloError';
+    code: ErrorCode;
+    message: string;
+} | {
+    type: 'GoAway';
+    reason: string;
+    reconnect_after_ms: number;
+    alternate_url: string | null;
+} | {
+    type: 'Batch';
+    frames: number[][];
+} | {
+    type: 'Compressed';
+    algorithm: CompressionAlgorithm;
+    frame: number[];
+} | {
+    type: 'UnsubscribeOk';
+    id: bigint;
+    total_dropped: bigint;
+} | {
+    type: 'UnsubscribeError';
+    id: bigint;
+    code: ErrorCode;
+    reason: string;
+} | {
+    type: 'SubscriptionEnded';
+    id: bigint;
+    reason: SubscriptionEndReason;
+    total_dropped: bigint;
+} | {
+    type: 'Presence';
+    id: bigint;
+    action: PresenceAction;
+    member: PresenceMember;
+} | {
+    type: 'PresenceList';
+    request_id: bigint;
+    members: PresenceMember[];
+} | {
+    type: 'ConsumerMessage';
+    subscription_id: bigint;
+    subject: string;
+    payload: number[];
+    stream_seq: bigint;
+    delivered: number;
+} | {
+    type: 'PubAck';
+    request_id: bigint;
+    stream: string;
+    seq: bigint;
+    duplicate: boolean;
+} | {
+    type: 'KvEntry';
+    request_id: bigint;
+    key: string;
+    value: number[] | null;
+    revision: bigint;
+} | {
+    type: 'KvPutOk';
+    request_id: bigint;
+    revision: bigint;
+} | {
+    type: 'KvDeleteOk';
+    request_id: bigint;
+} | {
+    type: 'KvUpdate';
+    id: bigint;
+    key: string;
+    value: number[];
+    revision: bigint;
+    operation: KvOperation;
+} | {
+    type: 'ObjectUploadProgress';
+    request_id: bigint;
+    offset: bigint;
+} | {
+    type: 'ObjectUploadOk';
+    request_id: bigint;
+    size: bigint;
+} | {
+    type: 'ObjectMeta';
+    request_id: bigint;
+    name: string;
+    size: bigint;
+} | {
+    type: 'ObjectChunk';
+    request_id: bigint;
+    offset: bigint;
+    data: number[];
+} | {
+    type: 'ObjectDownloadEnd';
+    request_id: bigint;
+    sha256: number[];
+} | {
+    type: 'BackendStatus';
+    available: boolean;
 };
 interface InnerData {
     id: number[];
@@ -69,6 +389,11 @@ interface ClientEnvelope {
 interface ServerEnvelope {
     message: ServerMessage;
 }
+interface PresenceMember {
+    session_id: string;
+    user_id: string;
+    subject: string;
+}
 /**
  * Auto-generated router enum for schema schema.
  *
@@ -86,6 +411,9 @@ type SchemaRouter = {
 } | {
     type: 'ServerEnvelope';
     data: ServerEnvelope;
+} | {
+    type: 'PresenceMember';
+    data: PresenceMember;
 };
 /** Message type discriminants for SchemaRouter */
 declare const SchemaRouterType: {
@@ -93,10 +421,11 @@ declare const SchemaRouterType: {
     readonly TestData: 1;
     readonly ClientEnvelope: 2;
     readonly ServerEnvelope: 3;
+    readonly PresenceMember: 4;
 };
 
-declare const PROTOCOL_VERSION_BYTE = 109;
-declare const SCHEMA_FINGERPRINT = "6dd935d3b48ac8c177ad5995ff3060d02268379a59ca971c171bd881acb2f35a";
+declare const PROTOCOL_VERSION_BYTE = 114;
+declare const SCHEMA_FINGERPRINT = "7236d81d6697036ecbb5f78f9166d388859432019e34585c2dba2a859b72c010";
 /** Zero-copy buffer view for packet framing */
 declare class PacketView {
     private view;
@@ -165,8 +494,12 @@ declare function decodeClientEnvelope(data: Uint8Array): ClientEnvelope;
 declare function encodeServerEnvelope(msg: ServerEnvelope): Uint8Array;
 /** Decode ServerEnvelope from binary */
 declare function decodeServerEnvelope(data: Uint8Array): ServerEnvelope;
+/** Encode PresenceMember to binary */
+declare function encodePresenceMember(msg: PresenceMember): Uint8Array;
+/** Decode PresenceMember from binary */
+declare function decodePresenceMember(data: Uint8Array): PresenceMember;
 
-declare const PROTOCOL_VERSION = 109;
+declare const PROTOCOL_VERSION = 114;
 /** Connection state machine */
 declare enum ConnectionState {
     Disconnected = 0,
@@ -205,4 +538,4 @@ declare class MottoTransport {
     close(): Promise<void>;
 }
 
-export { type ClientEnvelope, type ClientMessage, ConnectionState, DEFAULT_RETRY_CONFIG, type InnerData, MottoTransport, PROTOCOL_VERSION, PROTOCOL_VERSION_BYTE, PacketBuilder, PacketView, type RetryConfig, SCHEMA_FINGERPRINT, type SchemaRouter, SchemaRouterType, type ServerEnvelope, type ServerMessage, type TestData, calculateRetryDelay, compressZstd, decodeClientEnvelope, decodeInnerData, decodeServerEnvelope, decodeTestData, decompressZstd, encodeClientEnvelope, encodeInnerData, encodeServerEnvelope, encodeTestData };
+export { type AckKind, type Capability, type ClientEnvelope, type ClientMessage, type CompressionAlgorithm, ConnectionState, DEFAULT_RETRY_CONFIG, type DeliverPolicy, type ErrorCode, type InnerData, type KvOperation, type MessageOrigin, MottoTransport, PROTOCOL_VERSION, PROTOCOL_VERSION_BYTE, PacketBuilder, PacketView, type PresenceAction, type PresenceMember, type ReplayFrom, type RetryConfig, SCHEMA_FINGERPRINT, type SchemaRouter, SchemaRouterType, type ServerEnvelope, type ServerMessage, type SlowConsumerPolicy, type SubscriptionEndReason, type TestData, calculateRetryDelay, compressZstd, decodeClientEnvelope, decodeInnerData, decodePresenceMember, decodeServerEnvelope, decodeTestData, decompressZstd, encodeClientEnvelope, encodeInnerData, encodePresenceMember, encodeServerEnvelope, encodeTestData };
@@ -5,18 +5,18 @@
 // This file was generated by motto from a Rust schema definition.
 // Any changes will be overwritten on next generation.
 //
-// Protocol Version Byte: 0x6D
-// Schema Fingerprint: 6dd935d3b48ac8c177ad5995ff3060d02268379a59ca971c171bd881acb2f35a
-// Generated At: 2026-02-09T12:20:20.528556589+00:00
+// Protocol Version Byte: 0x72
+// Schema Fingerprint: 7236d81d6697036ecbb5f78f9166d388859432019e34585c2dba2a859b72c010
+// Generated At: 2026-10-18T18:30:12.417305962+00:00
 // ============================================================================
 
 import type * as Types from './types';
 
 // Protocol version byte - embedded in all packets
-export const PROTOCOL_VERSION_BYTE = 0x6D;
+export const PROTOCOL_VERSION_BYTE = 0x72;
 
 // Schema fingerprint for validation
-export const SCHEMA_FINGERPRINT = '6dd935d3b48ac8c177ad5995ff3060d02268379a59ca971c171bd881acb2f35a';
+export const SCHEMA_FINGERPRINT = '7236d81d6697036ecbb5f78f9166d388859432019e34585c2dba2a859b72c010';
 
 
 /** Zero-copy buffer view for packet framing */
@@ -195,17 +195,425 @@ export class PacketBuilder {
   }
 }
 
+/** Encode Capability union (for nested types) */
+function encodeCapabilityFields(val: Types.Capability, builder: PacketBuilder): void {
+  switch (val.type) {
+    case 'Compression':
+      builder.writeU8(0);
+      break;
+    case 'Headers':
+      builder.writeU8(1);
+      break;
+    case 'Batching':
+      builder.writeU8(2);
+      break;
+  }
+}
+
+/** Decode Capability union (for nested types) */
+function decodeCapabilityFields(view: PacketView): Types.Capability {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: 'Compression' } as Types.Capability;
+    case 1:
+      return { type: 'Headers' } as Types.Capability;
+    case 2:
+      return { type: 'Batching' } as Types.Capability;
+    default:
+      throw new Error(`Unknown Capability tag: ${tag}`);
+  }
+}
+
+/** Encode CompressionAlgorithm union (for nested types) */
+function encodeCompressionAlgorithmFields(val: Types.CompressionAlgorithm, builder: PacketBuilder): void {
+  switch (val.type) {
+    case 'None':
+      builder.writeU8(0);
+      break;
+    case 'Gzip':
+      builder.writeU8(1);
+      break;
+    case 'Zstd':
+      builder.writeU8(2);
+      break;
+  }
+}
+
+/** Decode CompressionAlgorithm union (for nested types) */
+function decodeCompressionAlgorithmFields(view: PacketView): Types.CompressionAlgorithm {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: 'None' } as Types.CompressionAlgorithm;
+    case 1:
+      return { type: 'Gzip' } as Types.CompressionAlgorithm;
+    case 2:
+      return { type: 'Zstd' } as Types.CompressionAlgorithm;
+    default:
+      throw new Error(`Unknown CompressionAlgorithm tag: ${tag}`);
+  }
+}
+
+/** Encode SubscriptionEndReason union (for nested types) */
+function encodeSubscriptionEndReasonFields(val: Types.SubscriptionEndReason, builder: PacketBuilder): void {
+  switch (val.type) {
+    case 'LimitReached':
+      builder.writeU8(0);
+      break;
+    case 'PermissionRevoked':
+      builder.writeU8(1);
+      break;
+    case 'BackendClosed':
+      builder.writeU8(2);
+      break;
+  }
+}
+
+/** Decode SubscriptionEndReason union (for nested types) */
+function decodeSubscriptionEndReasonFields(view: PacketView): Types.SubscriptionEndReason {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: 'LimitReached' } as Types.SubscriptionEndReason;
+    case 1:
+      return { type: 'PermissionRevoked' } as Types.SubscriptionEndReason;
+    case 2:
+      return { type: 'BackendClosed' } as Types.SubscriptionEndReason;
+    default:
+      throw new Error(`Unknown SubscriptionEndReason tag: ${tag}`);
+  }
+}
+
+/** Encode PresenceAction union (for nested types) */
+function encodePresenceActionFields(val: Types.PresenceAction, builder: PacketBuilder): void {
+  switch (val.type) {
+    case 'Join':
+      builder.writeU8(0);
+      break;
+    case 'Leave':
+      builder.writeU8(1);
+      break;
+  }
+}
+
+/** Decode PresenceAction union (for nested types) */
+function decodePresenceActionFields(view: PacketView): Types.PresenceAction {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: 'Join' } as Types.PresenceAction;
+    case 1:
+      return { type: 'Leave' } as Types.PresenceAction;
+    default:
+      throw new Error(`Unknown PresenceAction tag: ${tag}`);
+  }
+}
+
+/** Encode MessageOrigin union (for nested types) */
+function encodeMessageOriginFields(val: Types.MessageOrigin, builder: PacketBuilder): void {
+  switch (val.type) {
+    case 'Live':
+      builder.writeU8(0);
+      break;
+    case 'Retained':
+      builder.writeU8(1);
+      break;
+    case 'History':
+      builder.writeU8(2);
+      break;
+  }
+}
+
+/** Decode MessageOrigin union (for nested types) */
+function decodeMessageOriginFields(view: PacketView): Types.MessageOrigin {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: 'Live' } as Types.MessageOrigin;
+    case 1:
+      return { type: 'Retained' } as Types.MessageOrigin;
+    case 2:
+      return { type: 'History' } as Types.MessageOrigin;
+    default:
+      throw new Error(`Unknown MessageOrigin tag: ${tag}`);
+  }
+}
+
+/** Encode ReplayFrom union (for nested types) */
+function encodeReplayFromFields(val: Types.ReplayFrom, builder: PacketBuilder): void {
+  switch (val.type) {
+    case 'Last':
+      builder.writeU8(0);
+      builder.writeU32(val.count);
+      break;
+    case 'Since':
+      builder.writeU8(1);
+      builder.writeU64(BigInt(val.unix_ms));
+      break;
+  }
+}
+
+/** Decode ReplayFrom union (for nested types) */
+function decodeReplayFromFields(view: PacketView): Types.ReplayFrom {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: 'Last', count: view.readU32() } as Types.ReplayFrom;
+    case 1:
+      return { type: 'Since', unix_ms: view.readU64() } as Types.ReplayFrom;
+    default:
+      throw new Error(`Unknown ReplayFrom tag: ${tag}`);
+  }
+}
+
+/** Encode SlowConsumerPolicy union (for nested types) */
+function encodeSlowConsumerPolicyFields(val: Types.SlowConsumerPolicy, builder: PacketBuilder): void {
+  switch (val.type) {
+    case 'Block':
+      builder.writeU8(0);
+      break;
+    case 'DropOldest':
+      builder.writeU8(1);
+      break;
+    case 'DropNewest':
+      builder.writeU8(2);
+      break;
+    case 'Conflate':
+      builder.writeU8(3);
+      break;
+    case 'Disconnect':
+      builder.writeU8(4);
+      break;
+  }
+}
+
+/** Decode SlowConsumerPolicy union (for nested types) */
+function decodeSlowConsumerPolicyFields(view: PacketView): Types.SlowConsumerPolicy {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: 'Block' } as Types.SlowConsumerPolicy;
+    case 1:
+      return { type: 'DropOldest' } as Types.SlowConsumerPolicy;
+    case 2:
+      return { type: 'DropNewest' } as Types.SlowConsumerPolicy;
+    case 3:
+      return { type: 'Conflate' } as Types.SlowConsumerPolicy;
+    case 4:
+      return { type: 'Disconnect' } as Types.SlowConsumerPolicy;
+    default:
+      throw new Error(`Unknown SlowConsumerPolicy tag: ${tag}`);
+  }
+}
+
+/** Encode DeliverPolicy union (for nested types) */
+function encodeDeliverPolicyFields(val: Types.DeliverPolicy, builder: PacketBuilder): void {
+  switch (val.type) {
+    case 'All':
+      builder.writeU8(0);
+      break;
+    case 'New':
+      builder.writeU8(1);
+      break;
+    case 'Last':
+      builder.writeU8(2);
+      break;
+    case 'LastPerSubject':
+      builder.writeU8(3);
+      break;
+    case 'ByStartSequence':
+      builder.writeU8(4);
+      builder.writeU64(BigInt(val.start_seq));
+      break;
+  }
+}
+
+/** Decode DeliverPolicy union (for nested types) */
+function decodeDeliverPolicyFields(view: PacketView): Types.DeliverPolicy {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: 'All' } as Types.DeliverPolicy;
+    case 1:
+      return { type: 'New' } as Types.DeliverPolicy;
+    case 2:
+      return { type: 'Last' } as Types.DeliverPolicy;
+    case 3:
+      return { type: 'LastPerSubject' } as Types.DeliverPolicy;
+    case 4:
+      return { type: 'ByStartSequence', start_seq: view.readU64() } as Types.DeliverPolicy;
+    default:
+      throw new Error(`Unknown DeliverPolicy tag: ${tag}`);
+  }
+}
+
+/** Encode AckKind union (for nested types) */
+function encodeAckKindFields(val: Types.AckKind, builder: PacketBuilder): void {
+  switch (val.type) {
+    case 'Ack':
+      builder.writeU8(0);
+      break;
+    case 'Nak':
+      builder.writeU8(1);
+      break;
+    case 'InProgress':
+      builder.writeU8(2);
+      break;
+  }
+}
+
+/** Decode AckKind union (for nested types) */
+function decodeAckKindFields(view: PacketView): Types.AckKind {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: 'Ack' } as Types.AckKind;
+    case 1:
+      return { type: 'Nak' } as Types.AckKind;
+    case 2:
+      return { type: 'InProgress' } as Types.AckKind;
+    default:
+      throw new Error(`Unknown AckKind tag: ${tag}`);
+  }
+}
+
+/** Encode KvOperation union (for nested types) */
+function encodeKvOperationFields(val: Types.KvOperation, builder: PacketBuilder): void {
+  switch (val.type) {
+    case 'Put':
+      builder.writeU8(0);
+      break;
+    case 'Delete':
+      builder.writeU8(1);
+      break;
+    case 'Purge':
+      builder.writeU8(2);
+      break;
+  }
+}
+
+/** Decode KvOperation union (for nested types) */
+function decodeKvOperationFields(view: PacketView): Types.KvOperation {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: 'Put' } as Types.KvOperation;
+    case 1:
+      return { type: 'Delete' } as Types.KvOperation;
+    case 2:
+      return { type: 'Purge' } as Types.KvOperation;
+    default:
+      throw new Error(`Unknown KvOperation tag: ${tag}`);
+  }
+}
+
+/** Encode ErrorCode union (for nested types) */
+function encodeErrorCodeFields(val: Types.ErrorCode, builder: PacketBuilder): void {
+  switch (val.type) {
+    case 'Unauthorized':
+      builder.writeU8(0);
+      break;
+    case 'PermissionDenied':
+      builder.writeU8(1);
+      break;
+    case 'InvalidMessage':
+      builder.writeU8(2);
+      break;
+    case 'InvalidSubject':
+      builder.writeU8(3);
+      break;
+    case 'PayloadTooLarge':
+      builder.writeU8(4);
+      break;
+    case 'NotFound':
+      builder.writeU8(5);
+      break;
+    case 'AlreadyExists':
+      builder.writeU8(6);
+      break;
+    case 'QuotaExceeded':
+      builder.writeU8(7);
+      break;
+    case 'NoResponders':
+      builder.writeU8(8);
+      break;
+    case 'Timeout':
+      builder.writeU8(9);
+      break;
+    case 'BackendUnavailable':
+      builder.writeU8(10);
+      break;
+    case 'VersionMismatch':
+      builder.writeU8(11);
+      break;
+    case 'SchemaMismatch':
+      builder.writeU8(12);
+      break;
+    case 'Internal':
+      builder.writeU8(13);
+      break;
+    case 'Draining':
+      builder.writeU8(14);
+      break;
+  }
+}
+
+/** Decode ErrorCode union (for nested types) */
+function decodeErrorCodeFields(view: PacketView): Types.ErrorCode {
+  const tag = view.readU8();
+  switch (tag) {
+    case 0:
+      return { type: 'Unauthorized' } as Types.ErrorCode;
+    case 1:
+      return { type: 'PermissionDenied' } as Types.ErrorCode;
+    case 2:
+      return { type: 'InvalidMessage' } as Types.ErrorCode;
+    case 3:
+      return { type: 'InvalidSubject' } as Types.ErrorCode;
+    case 4:
+      return { type: 'PayloadTooLarge' } as Types.ErrorCode;
+    case 5:
+      return { type: 'NotFound' } as Types.ErrorCode;
+    case 6:
+      return { type: 'AlreadyExists' } as Types.ErrorCode;
+    case 7:
+      return { type: 'QuotaExceeded' } as Types.ErrorCode;
+    case 8:
+      return { type: 'NoResponders' } as Types.ErrorCode;
+    case 9:
+      return { type: 'Timeout' } as Types.ErrorCode;
+    case 10:
+      return { type: 'BackendUnavailable' } as Types.ErrorCode;
+    case 11:
+      return { type: 'VersionMismatch' } as Types.ErrorCode;
+    case 12:
+      return { type: 'SchemaMismatch' } as Types.ErrorCode;
+    case 13:
+      return { type: 'Internal' } as Types.ErrorCode;
+    case 14:
+      return { type: 'Draining' } as Types.ErrorCode;
+    default:
+      throw new Error(`Unknown ErrorCode tag: ${tag}`);
+  }
+}
+
 /** Encode ClientMessage union (for nested types) */
 function encodeClientMessageFields(val: Types.ClientMessage, builder: PacketBuilder): void {
   switch (val.type) {
     case 'Auth':
       builder.writeU8(0);
       builder.writeString(val.token);
+      if (val.resume_token === null) { builder.writeU8(0); } else { builder.writeU8(1); builder.writeString(val.resume_token); };
       break;
     case 'Subscribe':
       builder.writeU8(1);
       builder.writeString(val.subject);
       builder.writeU64(BigInt(val.id));
+      if (val.credits === null) { builder.writeU8(0); } else { builder.writeU8(1); builder.writeU32(val.credits); };
+      if (val.max_msgs === null) { builder.writeU8(0); } else { builder.writeU8(1); builder.writeU32(val.max_msgs); };
+      if (val.replay === null) { builder.writeU8(0); } else { builder.writeU8(1); encodeReplayFromFields(val.replay, builder); };
+      if (val.slow_consumer === null) { builder.writeU8(0); } else { builder.writeU8(1); encodeSlowConsumerPolicyFields(val.slow_consumer, builder); };
       break;
     case 'Unsubscribe':
       builder.writeU8(2);
@@ -222,10 +630,114 @@ function encodeClientMessageFields(val: Types.ClientMessage, builder: PacketBuil
       { builder.writeU32(val.payload.length); for (const item of val.payload) { builder.writeU8(item); } };
       builder.writeU32(val.timeout_ms);
       builder.writeU64(BigInt(val.request_id));
+      builder.writeBool(val.stream);
       break;
     case 'Ping':
       builder.writeU8(5);
       break;
+    case 'CancelRequest':
+      builder.writeU8(6);
+      builder.writeU64(BigInt(val.request_id));
+      break;
+    case 'Hello':
+      builder.writeU8(7);
+      builder.writeString(val.client_name);
+      builder.writeString(val.client_version);
+      builder.writeString(val.schema_fingerprint);
+      { builder.writeU32(val.capabilities.length); for (const item of val.capabilities) { encodeCapabilityFields(item, builder); } };
+      { builder.writeU32(val.compression.length); for (const item of val.compression) { encodeCompressionAlgorithmFields(item, builder); } };
+      break;
+    case 'Batch':
+      builder.writeU8(8);
+      { builder.writeU32(val.frames.length); for (const item of val.frames) { { builder.writeU32(item.length); for (const byte of item) { builder.writeU8(byte); } }; } };
+      break;
+    case 'GrantCredit':
+      builder.writeU8(9);
+      builder.writeU64(BigInt(val.id));
+      builder.writeU32(val.credits);
+      break;
+    case 'Compressed':
+      builder.writeU8(10);
+      encodeCompressionAlgorithmFields(val.algorithm, builder);
+      { builder.writeU32(val.frame.length); for (const item of val.frame) { builder.writeU8(item); } };
+      break;
+    case 'SubscribePresence':
+      builder.writeU8(11);
+      builder.writeU64(BigInt(val.id));
+      builder.writeString(val.pattern);
+      break;
+    case 'QueryPresence':
+      builder.writeU8(12);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeString(val.pattern);
+      break;
+    case 'SubscribeConsumer':
+      builder.writeU8(13);
+      builder.writeU64(BigInt(val.id));
+      builder.writeString(val.stream);
+      builder.writeString(val.durable);
+      builder.writeString(val.filter_subject);
+      encodeDeliverPolicyFields(val.deliver, builder);
+      break;
+    case 'Ack':
+      builder.writeU8(14);
+      builder.writeU64(BigInt(val.id));
+      builder.writeU64(BigInt(val.stream_seq));
+      encodeAckKindFields(val.kind, builder);
+      break;
+    case 'JetStreamPublish':
+      builder.writeU8(15);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeString(val.subject);
+      { builder.writeU32(val.payload.length); for (const item of val.payload) { builder.writeU8(item); } };
+      if (val.msg_id === null) { builder.writeU8(0); } else { builder.writeU8(1); builder.writeString(val.msg_id); };
+      break;
+    case 'KvGet':
+      builder.writeU8(16);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeString(val.bucket);
+      builder.writeString(val.key);
+      break;
+    case 'KvPut':
+      builder.writeU8(17);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeString(val.bucket);
+      builder.writeString(val.key);
+      { builder.writeU32(val.value.length); for (const item of val.value) { builder.writeU8(item); } };
+      break;
+    case 'KvDelete':
+      builder.writeU8(18);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeString(val.bucket);
+      builder.writeString(val.key);
+      break;
+    case 'KvWatch':
+      builder.writeU8(19);
+      builder.writeU64(BigInt(val.id));
+      builder.writeString(val.bucket);
+      builder.writeString(val.key);
+      break;
+    case 'ObjectUploadStart':
+      builder.writeU8(20);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeString(val.bucket);
+      builder.writeString(val.name);
+      builder.writeU64(BigInt(val.size));
+      { builder.writeU32(val.sha256.length); for (const item of val.sha256) { builder.writeU8(item); } };
+      break;
+    case 'ObjectUploadChunk':
+      builder.writeU8(21);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeU64(BigInt(val.offset));
+      { builder.writeU32(val.data.length); for (const item of val.data) { builder.writeU8(item); } };
+      break;
+    case 'ObjectDownload':
+      builder.writeU8(22);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeString(val.bucket);
+      builder.writeString(val.name);
+      builder.writeU64(BigInt(val.offset));
+      break;
   }
 }
 
@@ -234,17 +746,51 @@ function decodeClientMessageFields(view: PacketView): Types.ClientMessage {
   const tag = view.readU8();
   switch (tag) {
     case 0:
-      return { type: 'Auth', token: view.readString() } as Types.ClientMessage;
+      return { type: 'Auth', token: view.readString(), resume_token: view.readU8() === 0 ? null : view.readString() } as Types.ClientMessage;
     case 1:
-      return { type: 'Subscribe', subject: view.readString(), id: view.readU64() } as Types.ClientMessage;
+      return { type: 'Subscribe', subject: view.readString(), id: view.readU64(), credits: view.readU8() === 0 ? null : view.readU32(), max_msgs: view.readU8() === 0 ? null : view.readU32(), replay: view.readU8() === 0 ? null : decodeReplayFromFields(view), slow_consumer: view.readU8() === 0 ? null : decodeSlowConsumerPolicyFields(view) } as Types.ClientMessage;
     case 2:
       return { type: 'Unsubscribe', id: view.readU64() } as Types.ClientMessage;
     case 3:
       return { type: 'Publish', subject: view.readString(), payload: (() => { const len = view.readU32(); const arr: number[] = []; for (let i = 0; i < len; i++) { arr.push(view.readU8()); } return arr; })() } as Types.ClientMessage;
     case 4:
-      return { type: 'Request', subject: view.readString(), payload: (() => { const len = view.readU32(); const arr: number[] = []; for (let i = 0; i < len; i++) { arr.push(view.readU8()); } return arr; })(), timeout_ms: view.readU32(), request_id: view.readU64() } as Types.ClientMessage;
+      return { type: 'Request', subject: view.readString(), payload: (() => { const len = view.readU32(); const arr: number[] = []; for (let i = 0; i < len; i++) { arr.push(view.readU8()); } return arr; })(), timeout_ms: view.readU32(), request_id: view.readU64(), stream: view.readBool() } as Types.ClientMessage;
     case 5:
       return { type: 'Ping' } as Types.ClientMessage;
+    case 6:
+      return { type: 'CancelRequest', request_id: view.readU64() } as Types.ClientMessage;
+    case 7:
+      return { type: 'Hello', client_name: view.readString(), client_version: view.readString(), schema_fingerprint: view.readString(), capabilities: (() => { const len = view.readU32(); const arr: Types.Capability[] = []; for (let i = 0; i < len; i++) { arr.push(decodeCapabilityFields(view)); } return arr; })(), compression: (() => { const len = view.readU32(); const arr: Types.CompressionAlgorithm[] = []; for (let i = 0; i < len; i++) { arr.push(decodeCompressionAlgorithmFields(view)); } return arr; })() } as Types.ClientMessage;
+    case 8:
+      return { type: 'Batch', frames: (() => { const len = view.readU32(); const arr: number[][] = []; for (let i = 0; i < len; i++) { arr.push((() => { const len = view.readU32(); const arr: number[] = []; for (let i = 0; i < len; i++) { arr.push(view.readU8()); } return arr; })()); } return arr; })() } as Types.ClientMessage;
+    case 9:
+      return { type: 'GrantCredit', id: view.readU64(), credits: view.readU32() } as Types.ClientMessage;
+    case 10:
+      return { type: 'Compressed', algorithm: decodeCompressionAlgorithmFields(view), frame: (() => { const len = view.readU32(); const arr: number[] = []; for (let i = 0; i < len; i++) { arr.push(view.readU8()); } return arr; })() } as Types.ClientMessage;
+    case 11:
+      return { type: 'SubscribePresence', id: view.readU64(), pattern: view.readString() } as Types.ClientMessage;
+    case 12:
+      return { type: 'QueryPresence', request_id: view.readU64(), pattern: view.readString() } as Types.ClientMessage;
+    case 13:
+      return { type: 'SubscribeConsumer', id: view.readU64(), stream: view.readString(), durable: view.readString(), filter_subject: view.readString(), deliver: decodeDeliverPolicyFields(view) } as Types.ClientMessage;
+    case 14:
+      return { type: 'Ack', id: view.readU64(), stream_seq: view.readU64(), kind: decodeAckKindFields(view) } as Types.ClientMessage;
+    case 15:
+      return { type: 'JetStreamPublish', request_id: view.readU64(), subject: view.readString(), payload: (() => { const len = view.readU32(); const arr: number[] = []; for (let i = 0; i < len; i++) { arr.push(view.readU8()); } return arr; })(), msg_id: view.readU8() === 0 ? null : view.readString() } as Types.ClientMessage;
+    case 16:
+      return { type: 'KvGet', request_id: view.readU64(), bucket: view.readString(), key: view.readString() } as Types.ClientMessage;
+    case 17:
+      return { type: 'KvPut', request_id: view.readU64(), bucket: view.readString(), key: view.readString(), value: (() => { const len = view.readU32(); const arr: number[] = []; for (let i = 0; i < len; i++) { arr.push(view.readU8()); } return arr; })() } as Types.ClientMessage;
+    case 18:
+      return { type: 'KvDelete', request_id: view.readU64(), bucket: view.readString(), key: view.readString() } as Types.ClientMessage;
+    case 19:
+      return { type: 'KvWatch', id: view.readU64(), bucket: view.readString(), key: view.readString() } as Types.ClientMessage;
+    case 20:
+      return { type: 'ObjectUploadStart', request_id: view.readU64(), bucket: view.readString(), name: view.readString(), size: view.readU64(), sha256: (() => { const len = view.readU32(); const arr: number[] = []; for (let i = 0; i < len; i++) { arr.push(view.readU8()); } return arr; })() } as Types.ClientMessage;
+    case 21:
+      return { type: 'ObjectUploadChunk', request_id: view.readU64(), offset: view.readU64(), data: (() => { const len = view.readU32(); const arr: number[] = []; for (let i = 0; i < len; i++) { arr.push(view.readU8()); } return arr; })() } as Types.ClientMessage;
+    case 22:
+      return { type: 'ObjectDownload', request_id: view.readU64(), bucket: view.readString(), name: view.readString(), offset: view.readU64() } as Types.ClientMessage;
     default:
       throw new Error(`Unknown ClientMessage tag: ${tag}`);
   }
@@ -256,9 +802,12 @@ function encodeServerMessageFields(val: Types.ServerMessage, builder: PacketBuil
     case 'AuthOk':
       builder.writeU8(0);
       builder.writeString(val.session_id);
+      if (val.resume_token === null) { builder.writeU8(0); } else { builder.writeU8(1); builder.writeString(val.resume_token); };
+      builder.writeBool(val.resumed);
       break;
     case 'AuthError':
       builder.writeU8(1);
+      encodeErrorCodeFields(val.code, builder);
       builder.writeString(val.reason);
       break;
     case 'SubscribeOk':
@@ -268,6 +817,7 @@ function encodeServerMessageFields(val: Types.ServerMessage, builder: PacketBuil
     case 'SubscribeError':
       builder.writeU8(3);
       builder.writeU64(BigInt(val.id));
+      encodeErrorCodeFields(val.code, builder);
       builder.writeString(val.reason);
       break;
     case 'Message':
@@ -275,6 +825,9 @@ function encodeServerMessageFields(val: Types.ServerMessage, builder: PacketBuil
       builder.writeU64(BigInt(val.subscription_id));
       builder.writeString(val.subject);
       { builder.writeU32(val.payload.length); for (const item of val.payload) { builder.writeU8(item); } };
+      builder.writeU64(BigInt(val.seq));
+      builder.writeU32(val.dropped);
+      encodeMessageOriginFields(val.origin, builder);
       break;
     case 'Response':
       builder.writeU8(5);
@@ -284,16 +837,153 @@ function encodeServerMessageFields(val: Types.ServerMessage, builder: PacketBuil
     case 'RequestError':
       builder.writeU8(6);
       builder.writeU64(BigInt(val.request_id));
+      encodeErrorCodeFields(val.code, builder);
       builder.writeString(val.reason);
       break;
     case 'Error':
       builder.writeU8(7);
-      builder.writeU32(val.code);
+      encodeErrorCodeFields(val.code, builder);
       builder.writeString(val.message);
       break;
     case 'Pong':
       builder.writeU8(8);
       break;
+    case 'ResponseChunk':
+      builder.writeU8(9);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeU32(val.seq);
+      { builder.writeU32(val.payload.length); for (const item of val.payload) { builder.writeU8(item); } };
+      break;
+    case 'ResponseEnd':
+      builder.writeU8(10);
+      builder.writeU64(BigInt(val.request_id));
+      break;
+    case 'Welcome':
+      builder.writeU8(11);
+      builder.writeString(val.gateway_version);
+      builder.writeString(val.schema_fingerprint);
+      { builder.writeU32(val.capabilities.length); for (const item of val.capabilities) { encodeCapabilityFields(item, builder); } };
+      builder.writeU32(val.max_payload_bytes);
+      encodeCompressionAlgorithmFields(val.compression, builder);
+      break;
+    case 'HelloError':
+      builder.writeU8(12);
+      encodeErrorCodeFields(val.code, builder);
+      builder.writeString(val.message);
+      break;
+    case 'GoAway':
+      builder.writeU8(13);
+      builder.writeString(val.reason);
+      builder.writeU32(val.reconnect_after_ms);
+      if (val.alternate_url === null) { builder.writeU8(0); } else { builder.writeU8(1); builder.writeString(val.alternate_url); };
+      break;
+    case 'Batch':
+      builder.writeU8(14);
+      { builder.writeU32(val.frames.length); for (const item of val.frames) { { builder.writeU32(item.length); for (const byte of item) { builder.writeU8(byte); } }; } };
+      break;
+    case 'Compressed':
+      builder.writeU8(15);
+      encodeCompressionAlgorithmFields(val.algorithm, builder);
+      { builder.writeU32(val.frame.length); for (const item of val.frame) { builder.writeU8(item); } };
+      break;
+    case 'UnsubscribeOk':
+      builder.writeU8(16);
+      builder.writeU64(BigInt(val.id));
+      builder.writeU64(BigInt(val.total_dropped));
+      break;
+    case 'UnsubscribeError':
+      builder.writeU8(17);
+      builder.writeU64(BigInt(val.id));
+      encodeErrorCodeFields(val.code, builder);
+      builder.writeString(val.reason);
+      break;
+    case 'SubscriptionEnded':
+      builder.writeU8(18);
+      builder.writeU64(BigInt(val.id));
+      encodeSubscriptionEndReasonFields(val.reason, builder);
+      builder.writeU64(BigInt(val.total_dropped));
+      break;
+    case 'Presence':
+      builder.writeU8(19);
+      builder.writeU64(BigInt(val.id));
+      encodePresenceActionFields(val.action, builder);
+      encodePresenceMemberFields(val.member, builder);
+      break;
+    case 'PresenceList':
+      builder.writeU8(20);
+      builder.writeU64(BigInt(val.request_id));
+      { builder.writeU32(val.members.length); for (const item of val.members) { encodePresenceMemberFields(item, builder); } };
+      break;
+    case 'ConsumerMessage':
+      builder.writeU8(21);
+      builder.writeU64(BigInt(val.subscription_id));
+      builder.writeString(val.subject);
+      { builder.writeU32(val.payload.length); for (const item of val.payload) { builder.writeU8(item); } };
+      builder.writeU64(BigInt(val.stream_seq));
+      builder.writeU32(val.delivered);
+      break;
+    case 'PubAck':
+      builder.writeU8(22);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeString(val.stream);
+      builder.writeU64(BigInt(val.seq));
+      builder.writeBool(val.duplicate);
+      break;
+    case 'KvEntry':
+      builder.writeU8(23);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeString(val.key);
+      if (val.value === null) { builder.writeU8(0); } else { builder.writeU8(1); { builder.writeU32(val.value.length); for (const item of val.value) { builder.writeU8(item); } } };
+      builder.writeU64(BigInt(val.revision));
+      break;
+    case 'KvPutOk':
+      builder.writeU8(24);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeU64(BigInt(val.revision));
+      break;
+    case 'KvDeleteOk':
+      builder.writeU8(25);
+      builder.writeU64(BigInt(val.request_id));
+      break;
+    case 'KvUpdate':
+      builder.writeU8(26);
+      builder.writeU64(BigInt(val.id));
+      builder.writeString(val.key);
+      { builder.writeU32(val.value.length); for (const item of val.value) { builder.writeU8(item); } };
+      builder.writeU64(BigInt(val.revision));
+      encodeKvOperationFields(val.operation, builder);
+      break;
+    case 'ObjectUploadProgress':
+      builder.writeU8(27);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeU64(BigInt(val.offset));
+      break;
+    case 'ObjectUploadOk':
+      builder.writeU8(28);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeU64(BigInt(val.size));
+      break;
+    case 'ObjectMeta':
+      builder.writeU8(29);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeString(val.name);
+      builder.writeU64(BigInt(val.size));
+      break;
+    case 'ObjectChunk':
+      builder.writeU8(30);
+      builder.writeU64(BigInt(val.request_id));
+      builder.writeU64(BigInt(val.offset));
+      { builder.writeU32(val.data.length); for (const item of val.data) { builder.writeU8(item); } };
+      break;
+    case 'ObjectDownloadEnd':
+      builder.writeU8(31);
+      builder.writeU64(BigInt(val.request_id));
+      { builder.writeU32(val.sha256.length); for (const item of val.sha256) { builder.writeU8(item); } };
+      break;
+    case 'BackendStatus':
+      builder.writeU8(32);
+      builder.writeBool(val.available);
+      break;
   }
 }
 
@@ -302,23 +992,71 @@ function decodeServerMessageFields(view: PacketView): Types.ServerMessage {
   const tag = view.readU8();
   switch (tag) {
     case 0:
-      return { type: 'AuthOk', session_id: view.readString() } as Types.ServerMessage;
+      return { type: 'AuthOk', session_id: view.readString(), resume_token: view.readU8() === 0 ? null : view.readString(), resumed: view.readBool() } as Types.ServerMessage;
     case 1:
-      return { type: 'AuthError', reason: view.readString() } as Types.ServerMessage;
+      return { type: 'AuthError', code: decodeErrorCodeFields(view), reason: view.readString() } as Types.ServerMessage;
     case 2:
       return { type: 'SubscribeOk', id: view.readU64() } as Types.ServerMessage;
     case 3:
-      return { type: 'SubscribeError', id: view.readU64(), reason: view.readString() } as Types.ServerMessage;
+      return { type: 'SubscribeError', id: view.readU64(), code: decodeErrorCodeFields(view), reason: view.readString() } as Types.ServerMessage;
     case 4:
-      return { type: 'Message', subscription_id: view.readU64(), subject: view.readString(), payload: (() => { const len = view.readU32(); const arr: number[] = []; for (let i = 0; i < len; i++) { arr.push(view.readU8()); } return arr; })() } as Types.ServerMessage;
+      return { type: 'Message', subscription_id: view.readU64(), subject: view.readString(), payload: (() => { const len = view.readU32(); const arr: number[] = []; for (let i = 0; i < len; i++) { arr.push(view.readU8()); } return arr; })(), seq: view.readU64(), dropped: view.readU32(), origin: decodeMessageOriginFields(view) } as Types.ServerMessage;
     case 5:
       return { type: 'Response', request_id: view.readU64(), payload: (() => { const len = view.readU32(); const arr: number[] = []; for (let i = 0; i < len; i++) { arr.push(view.readU8()); } return arr; })() } as Types.ServerMessage;
     case 6:
-      return { type: 'RequestError', request_id: view.readU64(), reason: view.readString() } as Types.ServerMessage;
+      return { type: 'RequestError', request_id: view.readU64(), code: decodeErrorCodeFields(view), reason: view.readString() } as Types.ServerMessage;
     case 7:
-      return { type: 'Error', code: view.readU32(), message: view.readString() } as Types.ServerMessage;
+      return { type: 'Error', code: decodeErrorCodeFields(view), message: view.readString() } as Types.ServerMessage;
     case 8:
       return { type: 'Pong' } as Types.ServerMessage;
+    case 9:
+      return { type: 'ResponseChunk', request_id: view.readU64(), seq: view.readU32(), payload: (() => { const len = view.readU32(); const arr: number[] = []; for (let i = 0; i < len; i++) { arr.push(view.readU8()); } return arr; })() } as Types.ServerMessage;
+    case 10:
+      return { type: 'ResponseEnd', request_id: view.readU64() } as Types.ServerMessage;
+    case 11:
+      return { type: 'Welcome', gateway_version: view.readString(), schema_fingerprint: view.readString(), capabilities: (() => { const len = view.readU32(); const arr: Types.Capability[] = []; for (let i = 0; i < len; i++) { arr.push(decodeCapabilityFields(view)); } return arr; })(), max_payload_bytes: view.readU32(), compression: decodeCompressionAlgorithmFields(view) } as Types.ServerMessage;
+    case 12:
+      return { type: 'HelloError', code: decodeErrorCodeFields(view), message: view.readString() } as Types.ServerMessage;
+    case 13:
+      return { type: 'GoAway', reason: view.readString(), reconnect_after_ms: view.readU32(), alternate_url: view.readU8() === 0 ? null : view.readString() } as Types.ServerMessage;
+    case 14:
+      return { type: 'Batch', frames: (() => { const len = view.readU32(); const arr: number[][] = []; for (let i = 0; i < len; i++) { arr.push((() => { const len = view.readU32(); const arr: number[] = []; for (let i = 0; i < len; i++) { arr.push(view.readU8()); } return arr; })()); } return arr; })() } as Types.ServerMessage;
+    case 15:
+      return { type: 'Compressed', algorithm: decodeCompressionAlgorithmFields(view), frame: (() => { const len = view.readU32(); const arr: number[] = []; for (let i = 0; i < len; i++) { arr.push(view.readU8()); } return arr; })() } as Types.ServerMessage;
+    case 16:
+      return { type: 'UnsubscribeOk', id: view.readU64(), total_dropped: view.readU64() } as Types.ServerMessage;
+    case 17:
+      return { type: 'UnsubscribeError', id: view.readU64(), code: decodeErrorCodeFields(view), reason: view.readString() } as Types.ServerMessage;
+    case 18:
+      return { type: 'SubscriptionEnded', id: view.readU64(), reason: decodeSubscriptionEndReasonFields(view), total_dropped: view.readU64() } as Types.ServerMessage;
+    case 19:
+      return { type: 'Presence', id: view.readU64(), action: decodePresenceActionFields(view), member: decodePresenceMemberFields(view) } as Types.ServerMessage;
+    case 20:
+      return { type: 'PresenceList', request_id: view.readU64(), members: (() => { const len = view.readU32(); const arr: Types.PresenceMember[] = []; for (let i = 0; i < len; i++) { arr.push(decodePresenceMemberFields(view)); } return arr; })() } as Types.ServerMessage;
+    case 21:
+      return { type: 'ConsumerMessage', subscription_id: view.readU64(), subject: view.readString(), payload: (() => { const len = view.readU32(); const arr: number[] = []; for (let i = 0; i < len; i++) { arr.push(view.readU8()); } return arr; })(), stream_seq: view.readU64(), delivered: view.readU32() } as Types.ServerMessage;
+    case 22:
+      return { type: 'PubAck', request_id: view.readU64(), stream: view.readString(), seq: view.readU64(), duplicate: view.readBool() } as Types.ServerMessage;
+    case 23:
+      return { type: 'KvEntry', request_id: view.readU64(), key: view.readString(), value: view.readU8() === 0 ? null : (() => { const len = view.readU32(); const arr: number[] = []; for (let i = 0; i < len; i++) { arr.push(view.readU8()); } return arr; })(), revision: view.readU64() } as Types.ServerMessage;
+    case 24:
+      return { type: 'KvPutOk', request_id: view.readU64(), revision: view.readU64() } as Types.ServerMessage;
+    case 25:
+      return { type: 'KvDeleteOk', request_id: view.readU64() } as Types.ServerMessage;
+    case 26:
+      return { type: 'KvUpdate', id: view.readU64(), key: view.readString(), value: (() => { const len = view.readU32(); const arr: number[] = []; for (let i = 0; i < len; i++) { arr.push(view.readU8()); } return arr; })(), revision: view.readU64(), operation: decodeKvOperationFields(view) } as Types.ServerMessage;
+    case 27:
+      return { type: 'ObjectUploadProgress', request_id: view.readU64(), offset: view.readU64() } as Types.ServerMessage;
+    case 28:
+      return { type: 'ObjectUploadOk', request_id: view.readU64(), size: view.readU64() } as Types.ServerMessage;
+    case 29:
+      return { type: 'ObjectMeta', request_id: view.readU64(), name: view.readString(), size: view.readU64() } as Types.ServerMessage;
+    case 30:
+      return { type: 'ObjectChunk', request_id: view.readU64(), offset: view.readU64(), data: (() => { const len = view.readU32(); const arr: number[] = []; for (let i = 0; i < len; i++) { arr.push(view.readU8()); } return arr; })() } as Types.ServerMessage;
+    case 31:
+      return { type: 'ObjectDownloadEnd', request_id: view.readU64(), sha256: (() => { const len = view.readU32(); const arr: number[] = []; for (let i = 0; i < len; i++) { arr.push(view.readU8()); } return arr; })() } as Types.ServerMessage;
+    case 32:
+      return { type: 'BackendStatus', available: view.readBool() } as Types.ServerMessage;
     default:
       throw new Error(`Unknown ServerMessage tag: ${tag}`);
   }
@@ -437,3 +1175,34 @@ export function decodeServerEnvelope(data: Uint8Array): Types.ServerEnvelope {
   view.skip(1);
   return decodeServerEnvelopeFields(view);
 }
+
+/** Encode PresenceMember fields to a PacketBuilder (for nested types) */
+function encodePresenceMemberFields(msg: Types.PresenceMember, builder: PacketBuilder): void {
+  builder.writeString(msg.session_id);
+  builder.writeString(msg.user_id);
+  builder.writeString(msg.subject);
+}
+
+/** Encode PresenceMember to binary */
+export function encodePresenceMember(msg: Types.PresenceMember): Uint8Array {
+  const builder = new PacketBuilder();
+  encodePresenceMemberFields(msg, builder);
+  return builder.build();
+}
+
+/** Decode PresenceMember fields from a PacketView (for nested types) */
+function decodePresenceMemberFields(view: PacketView): Types.PresenceMember {
+  return {
+    session_id: view.readString(),
+    user_id: view.readString(),
+    subject: view.readString(),
+  };
+}
+
+/** Decode PresenceMember from binary */
+export function decodePresenceMember(data: Uint8Array): Types.PresenceMember {
+  const view = new PacketView(data);
+  // Skip version byte
+  view.skip(1);
+  return decodePresenceMemberFields(view);
+}
@@ -5,31 +5,141 @@
 // This file was generated by motto from a Rust schema definition.
 // Any changes will be overwritten on next generation.
 //
-// Protocol Version Byte: 0x6D
-// Schema Fingerprint: 6dd935d3b48ac8c177ad5995ff3060d02268379a59ca971c171bd881acb2f35a
-// Generated At: 2026-02-09T12:20:20.528556589+00:00
+// Protocol Version Byte: 0x72
+// Schema Fingerprint: 7236d81d6697036ecbb5f78f9166d388859432019e34585c2dba2a859b72c010
+// Generated At: 2026-10-18T18:30:12.417305962+00:00
 // ============================================================================
 
 // Type Definitions
 
+export type Capability =
+  | { type: 'Compression' }
+  | { type: 'Headers' }
+  | { type: 'Batching' };
+
+export type CompressionAlgorithm =
+  | { type: 'None' }
+  | { type: 'Gzip' }
+  | { type: 'Zstd' };
+
+export type SubscriptionEndReason =
+  | { type: 'LimitReached' }
+  | { type: 'PermissionRevoked' }
+  | { type: 'BackendClosed' };
+
+export type PresenceAction =
+  | { type: 'Join' }
+  | { type: 'Leave' };
+
+export type MessageOrigin =
+  | { type: 'Live' }
+  | { type: 'Retained' }
+  | { type: 'History' };
+
+export type ReplayFrom =
+  | { type: 'Last'; count: number }
+  | { type: 'Since'; unix_ms: bigint };
+
+export type SlowConsumerPolicy =
+  | { type: 'Block' }
+  | { type: 'DropOldest' }
+  | { type: 'DropNewest' }
+  | { type: 'Conflate' }
+  | { type: 'Disconnect' };
+
+export type DeliverPolicy =
+  | { type: 'All' }
+  | { type: 'New' }
+  | { type: 'Last' }
+  | { type: 'LastPerSubject' }
+  | { type: 'ByStartSequence'; start_seq: bigint };
+
+export type AckKind =
+  | { type: 'Ack' }
+  | { type: 'Nak' }
+  | { type: 'InProgress' };
+
+export type KvOperation =
+  | { type: 'Put' }
+  | { type: 'Delete' }
+  | { type: 'Purge' };
+
+export type ErrorCode =
+  | { type: 'Unauthorized' }
+  | { type: 'PermissionDenied' }
+  | { type: 'InvalidMessage' }
+  | { type: 'InvalidSubject' }
+  | { type: 'PayloadTooLarge' }
+  | { type: 'NotFound' }
+  | { type: 'AlreadyExists' }
+  | { type: 'QuotaExceeded' }
+  | { type: 'NoResponders' }
+  | { type: 'Timeout' }
+  | { type: 'BackendUnavailable' }
+  | { type: 'VersionMismatch' }
+  | { type: 'SchemaMismatch' }
+  | { type: 'Internal' }
+  | { type: 'Draining' };
+
 export type ClientMessage =
-  | { type: 'Auth'; token: string }
-  | { type: 'Subscribe'; subject: string; id: bigint }
+  | { type: 'Auth'; token: string; resume_token: string | null }
+  | { type: 'Subscribe'; subject: string; id: bigint; credits: number | null; max_msgs: number | null; replay: ReplayFrom | null; slow_consumer: SlowConsumerPolicy | null }
   | { type: 'Unsubscribe'; id: bigint }
   | { type: 'Publish'; subject: string; payload: number[] }
-  | { type: 'Request'; subject: string; payload: number[]; timeout_ms: number; request_id: bigint }
-  | { type: 'Ping' };
+  | { type: 'Request'; subject: string; payload: number[]; timeout_ms: number; request_id: bigint; stream: boolean }
+  | { type: 'Ping' }
+  | { type: 'CancelRequest'; request_id: bigint }
+  | { type: 'Hello'; client_name: string; client_version: string; schema_fingerprint: string; capabilities: Capability[]; compression: CompressionAlgorithm[] }
+  | { type: 'Batch'; frames: number[][] }
+  | { type: 'GrantCredit'; id: bigint; credits: number }
+  | { type: 'Compressed'; algorithm: CompressionAlgorithm; frame: number[] }
+  | { type: 'SubscribePresence'; id: bigint; pattern: string }
+  | { type: 'QueryPresence'; request_id: bigint; pattern: string }
+  | { type: 'SubscribeConsumer'; id: bigint; stream: string; durable: string; filter_subject: string; deliver: DeliverPolicy }
+  | { type: 'Ack'; id: bigint; stream_seq: bigint; kind: AckKind }
+  | { type: 'JetStreamPublish'; request_id: bigint; subject: string; payload: number[]; msg_id: string | null }
+  | { type: 'KvGet'; request_id: bigint; bucket: string; key: string }
+  | { type: 'KvPut'; request_id: bigint; bucket: string; key: string; value: number[] }
+  | { type: 'KvDelete'; request_id: bigint; bucket: string; key: string }
+  | { type: 'KvWatch'; id: bigint; bucket: string; key: string }
+  | { type: 'ObjectUploadStart'; request_id: bigint; bucket: string; name: string; size: bigint; sha256: number[] }
+  | { type: 'ObjectUploadChunk'; request_id: bigint; offset: bigint; data: number[] }
+  | { type: 'ObjectDownload'; request_id: bigint; bucket: string; name: string; offset: bigint };
 
 export type ServerMessage =
-  | { type: 'AuthOk'; session_id: string }
-  | { type: 'AuthError'; reason: string }
+  | { type: 'AuthOk'; session_id: string; resume_token: string | null; resumed: boolean }
+  | { type: 'AuthError'; code: ErrorCode; reason: string }
   | { type: 'SubscribeOk'; id: bigint }
-  | { type: 'SubscribeError'; id: bigint; reason: string }
-  | { type: 'Message'; subscription_id: bigint; subject: string; payload: number[] }
+  | { type: 'SubscribeError'; id: bigint; code: ErrorCode; reason: string }
+  | { type: 'Message'; subscription_id: bigint; subject: string; payload: number[]; seq: bigint; dropped: number; origin: MessageOrigin }
   | { type: 'Response'; request_id: bigint; payload: number[] }
-  | { type: 'RequestError'; request_id: bigint; reason: string }
-  | { type: 'Error'; code: number; message: string }
-  | { type: 'Pong' };
+  | { type: 'RequestError'; request_id: bigint; code: ErrorCode; reason: string }
+  | { type: 'Error'; code: ErrorCode; message: string }
+  | { type: 'Pong' }
+  | { type: 'ResponseChunk'; request_id: bigint; seq: number; payload: number[] }
+  | { type: 'ResponseEnd'; request_id: bigint }
+  | { type: 'Welcome'; gateway_version: string; schema_fingerprint: string; capabilities: Capability[]; max_payload_bytes: number; compression: CompressionAlgorithm }
+  | { type: 'HelloError'; code: ErrorCode; message: string }
+  | { type: 'GoAway'; reason: string; reconnect_after_ms: number; alternate_url: string | null }
+  | { type: 'Batch'; frames: number[][] }
+  | { type: 'Compressed'; algorithm: CompressionAlgorithm; frame: number[] }
+  | { type: 'UnsubscribeOk'; id: bigint; total_dropped: bigint }
+  | { type: 'UnsubscribeError'; id: bigint; code: ErrorCode; reason: string }
+  | { type: 'SubscriptionEnded'; id: bigint; reason: SubscriptionEndReason; total_dropped: bigint }
+  | { type: 'Presence'; id: bigint; action: PresenceAction; member: PresenceMember }
+  | { type: 'PresenceList'; request_id: bigint; members: PresenceMember[] }
+  | { type: 'ConsumerMessage'; subscription_id: bigint; subject: string; payload: number[]; stream_seq: bigint; delivered: number }
+  | { type: 'PubAck'; request_id: bigint; stream: string; seq: bigint; duplicate: boolean }
+  | { type: 'KvEntry'; request_id: bigint; key: string; value: number[] | null; revision: bigint }
+  | { type: 'KvPutOk'; request_id: bigint; revision: bigint }
+  | { type: 'KvDeleteOk'; request_id: bigint }
+  | { type: 'KvUpdate'; id: bigint; key: string; value: number[]; revision: bigint; operation: KvOperation }
+  | { type: 'ObjectUploadProgress'; request_id: bigint; offset: bigint }
+  | { type: 'ObjectUploadOk'; request_id: bigint; size: bigint }
+  | { type: 'ObjectMeta'; request_id: bigint; name: string; size: bigint }
+  | { type: 'ObjectChunk'; request_id: bigint; offset: bigint; data: number[] }
+  | { type: 'ObjectDownloadEnd'; request_id: bigint; sha256: number[] }
+  | { type: 'BackendStatus'; available: boolean };
 
 export interface InnerData {
   id: number[];
@@ -50,6 +160,12 @@ export interface ServerEnvelope {
   message: ServerMessage;
 }
 
+export interface PresenceMember {
+  session_id: string;
+  user_id: string;
+  subject: string;
+}
+
 /**
  * Auto-generated router enum for schema schema.
  * 
@@ -59,7 +175,8 @@ export type SchemaRouter =
   | { type: 'InnerData'; data: InnerData }
   | { type: 'TestData'; data: TestData }
   | { type: 'ClientEnvelope'; data: ClientEnvelope }
-  | { type: 'ServerEnvelope'; data: ServerEnvelope };
+  | { type: 'ServerEnvelope'; data: ServerEnvelope }
+  | { type: 'PresenceMember'; data: PresenceMember };
 
 /** Message type discriminants for SchemaRouter */
 export const SchemaRouterType = {
@@ -67,5 +184,6 @@ export const SchemaRouterType = {
   TestData: 1 as const,
   ClientEnvelope: 2 as const,
   ServerEnvelope: 3 as const,
+  PresenceMember: 4 as const,
 } as const;
 
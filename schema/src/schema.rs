@@ -11,13 +11,98 @@ pub struct TestData {
     pub inner_data: InnerData,
 }
 
+pub enum Capability {
+    Compression,
+    Headers,
+    Batching,
+}
+
+pub enum CompressionAlgorithm {
+    None,
+    Gzip,
+    Zstd,
+}
+
+pub enum SubscriptionEndReason {
+    LimitReached,
+    PermissionRevoked,
+    BackendClosed,
+}
+
+pub enum PresenceAction {
+    Join,
+    Leave,
+}
+
+pub enum MessageOrigin {
+    Live,
+    Retained,
+    History,
+}
+
+pub enum ReplayFrom {
+    Last { count: u32 },
+    Since { unix_ms: u64 },
+}
+
+pub enum SlowConsumerPolicy {
+    Block,
+    DropOldest,
+    DropNewest,
+    Conflate,
+    Disconnect,
+}
+
+pub enum DeliverPolicy {
+    All,
+    New,
+    Last,
+    LastPerSubject,
+    ByStartSequence { start_seq: u64 },
+}
+
+pub enum AckKind {
+    Ack,
+    Nak,
+    InProgress,
+}
+
+pub enum KvOperation {
+    Put,
+    Delete,
+    Purge,
+}
+
+pub enum ErrorCode {
+    Unauthorized,
+    PermissionDenied,
+    InvalidMessage,
+    InvalidSubject,
+    PayloadTooLarge,
+    NotFound,
+    AlreadyExists,
+    QuotaExceeded,
+    NoResponders,
+    Timeout,
+    BackendUnavailable,
+    VersionMismatch,
+    SchemaMismatch,
+    Internal,
+    Draining,
+}
+
 pub enum ClientMessage {
     Auth {
         token: String,
+        resume_token: Option<String>,
     },
     Subscribe {
         subject: String,
         id: u64,
+        credits: Option<u32>,
+        max_msgs: Option<u32>,
+        replay: Option<ReplayFrom>,
+        slow_consumer: Option<SlowConsumerPolicy>,
     },
     Unsubscribe {
         id: u64,
@@ -31,15 +116,105 @@ pub enum ClientMessage {
         payload: Vec<u8>,
         timeout_ms: u32,
         request_id: u64,
+        stream: bool,
     },
     Ping,
+    CancelRequest {
+        request_id: u64,
+    },
+    Hello {
+        client_name: String,
+        client_version: String,
+        schema_fingerprint: String,
+        capabilities: Vec<Capability>,
+        compression: Vec<CompressionAlgorithm>,
+    },
+    Batch {
+        frames: Vec<Vec<u8>>,
+    },
+    GrantCredit {
+        id: u64,
+        credits: u32,
+    },
+    Compressed {
+        algorithm: CompressionAlgorithm,
+        frame: Vec<u8>,
+    },
+    SubscribePresence {
+        id: u64,
+        pattern: String,
+    },
+    QueryPresence {
+        request_id: u64,
+        pattern: String,
+    },
+    SubscribeConsumer {
+        id: u64,
+        stream: String,
+        durable: String,
+        filter_subject: String,
+        deliver: DeliverPolicy,
+    },
+    Ack {
+        id: u64,
+        stream_seq: u64,
+        kind: AckKind,
+    },
+    JetStreamPublish {
+        request_id: u64,
+        subject: String,
+        payload: Vec<u8>,
+        msg_id: Option<String>,
+    },
+    KvGet {
+        request_id: u64,
+        bucket: String,
+        key: String,
+    },
+    KvPut {
+        request_id: u64,
+        bucket: String,
+        key: String,
+        value: Vec<u8>,
+    },
+    KvDelete {
+        request_id: u64,
+        bucket: String,
+        key: String,
+    },
+    KvWatch {
+        id: u64,
+        bucket: String,
+        key: String,
+    },
+    ObjectUploadStart {
+        request_id: u64,
+        bucket: String,
+        name: String,
+        size: u64,
+        sha256: Vec<u8>,
+    },
+    ObjectUploadChunk {
+        request_id: u64,
+        offset: u64,
+        data: Vec<u8>,
+    },
+    ObjectDownload {
+        request_id: u64,
+        bucket: String,
+        name: String,
+        offset: u64,
+    },
 }
 
 pub enum ServerMessage {
     AuthOk {
         session_id: String,
+        resume_token: Option<String>,
+        resumed: bool,
     },
     AuthError {
+        code: ErrorCode,
         reason: String,
     },
     SubscribeOk {
@@ -47,12 +222,16 @@ pub enum ServerMessage {
     },
     SubscribeError {
         id: u64,
+        code: ErrorCode,
         reason: String,
     },
     Message {
         subscription_id: u64,
         subject: String,
         payload: Vec<u8>,
+        seq: u64,
+        dropped: u32,
+        origin: MessageOrigin,
     },
     Response {
         request_id: u64,
@@ -60,13 +239,126 @@ pub enum ServerMessage {
     },
     RequestError {
         request_id: u64,
+        code: ErrorCode,
         reason: String,
     },
     Error {
-        code: u32,
+        code: ErrorCode,
         message: String,
     },
     Pong,
+    ResponseChunk {
+        request_id: u64,
+        seq: u32,
+        payload: Vec<u8>,
+    },
+    ResponseEnd {
+        request_id: u64,
+    },
+    Welcome {
+        gateway_version: String,
+        schema_fingerprint: String,
+        capabilities: Vec<Capability>,
+        max_payload_bytes: u32,
+        compression: CompressionAlgorithm,
+    },
+    HelloError {
+        code: ErrorCode,
+        message: String,
+    },
+    GoAway {
+        reason: String,
+        reconnect_after_ms: u32,
+        alternate_url: Option<String>,
+    },
+    Batch {
+        frames: Vec<Vec<u8>>,
+    },
+    Compressed {
+        algorithm: CompressionAlgorithm,
+        frame: Vec<u8>,
+    },
+    UnsubscribeOk {
+        id: u64,
+        total_dropped: u64,
+    },
+    UnsubscribeError {
+        id: u64,
+        code: ErrorCode,
+        reason: String,
+    },
+    SubscriptionEnded {
+        id: u64,
+        reason: SubscriptionEndReason,
+        total_dropped: u64,
+    },
+    Presence {
+        id: u64,
+        action: PresenceAction,
+        member: PresenceMember,
+    },
+    PresenceList {
+        request_id: u64,
+        members: Vec<PresenceMember>,
+    },
+    ConsumerMessage {
+        subscription_id: u64,
+        subject: String,
+        payload: Vec<u8>,
+        stream_seq: u64,
+        delivered: u32,
+    },
+    PubAck {
+        request_id: u64,
+        stream: String,
+        seq: u64,
+        duplicate: bool,
+    },
+    KvEntry {
+        request_id: u64,
+        key: String,
+        value: Option<Vec<u8>>,
+        revision: u64,
+    },
+    KvPutOk {
+        request_id: u64,
+        revision: u64,
+    },
+    KvDeleteOk {
+        request_id: u64,
+    },
+    KvUpdate {
+        id: u64,
+        key: String,
+        value: Vec<u8>,
+        revision: u64,
+        operation: KvOperation,
+    },
+    ObjectUploadProgress {
+        request_id: u64,
+        offset: u64,
+    },
+    ObjectUploadOk {
+        request_id: u64,
+        size: u64,
+    },
+    ObjectMeta {
+        request_id: u64,
+        name: String,
+        size: u64,
+    },
+    ObjectChunk {
+        request_id: u64,
+        offset: u64,
+        data: Vec<u8>,
+    },
+    ObjectDownloadEnd {
+        request_id: u64,
+        sha256: Vec<u8>,
+    },
+    BackendStatus {
+        available: bool,
+    },
 }
 
 pub struct ClientEnvelope {
@@ -76,3 +368,9 @@ pub struct ClientEnvelope {
 pub struct ServerEnvelope {
     pub message: ServerMessage,
 }
+
+pub struct PresenceMember {
+    pub session_id: String,
+    pub user_id: String,
+    pub subject: String,
+}